@@ -12,6 +12,15 @@ fn main() {
         Path::new(&current_dir).join("target/debug")
     };
 
+    // Compared with the fingerprint exported by plugins, see fingerprint.rs
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=DR_RUSTC_VERSION={}", version);
+
     Command::new("rustc")
         .arg("src/test_shared.rs")
         .arg("--crate-name")
@@ -19,7 +28,23 @@ fn main() {
         .arg("--crate-type")
         .arg("dylib")
         .arg("--out-dir")
-        .arg(target)
+        .arg(&target)
+        .env("DR_RUSTC_VERSION", &version)
+        .output()
+        .unwrap_or_else(|e| panic!("failed to execute process: {}", e));
+
+    // A second build with different content, used by tests that need the library to change
+    Command::new("rustc")
+        .arg("src/test_shared.rs")
+        .arg("--crate-name")
+        .arg("test_shared_v2")
+        .arg("--crate-type")
+        .arg("dylib")
+        .arg("--cfg")
+        .arg("test_shared_v2")
+        .arg("--out-dir")
+        .arg(&target)
+        .env("DR_RUSTC_VERSION", &version)
         .output()
         .unwrap_or_else(|e| panic!("failed to execute process: {}", e));
 }
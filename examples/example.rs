@@ -32,10 +32,12 @@ impl Plugins {
             UpdateState::Before => Self::unload_plugins(self, lib.unwrap()),
             UpdateState::After => Self::reload_plugin(self, lib.unwrap()),
             UpdateState::ReloadFailed(_) => println!("Failed to reload"),
+            _ => (),
         }
     }
 }
 
+#[allow(clippy::len_zero)]
 fn main() {
     let mut plugs = Plugins {
         plugins: Vec::new(),
@@ -0,0 +1,200 @@
+use crate::{DynamicReload, Error, Lib, PlatformName, Result, UpdateState};
+use std::{ffi::c_void, sync::Arc};
+
+/// Creates the state of the application. Optional, without it the state is null.
+pub const APP_INIT: &str = "app_init";
+/// Runs one iteration of the application. Returning false stops [App::run].
+pub const APP_UPDATE: &str = "app_update";
+/// Called with the state before the library is unloaded for a reload. Optional.
+pub const APP_BEFORE_RELOAD: &str = "app_before_reload";
+/// Called with the state after a new version of the library has been loaded. Optional.
+pub const APP_AFTER_RELOAD: &str = "app_after_reload";
+/// Called with the state when the application stops. Optional.
+pub const APP_SHUTDOWN: &str = "app_shutdown";
+
+type InitFn = unsafe extern "C" fn() -> *mut c_void;
+type UpdateFn = unsafe extern "C" fn(*mut c_void) -> bool;
+type StateFn = unsafe extern "C" fn(*mut c_void);
+
+/// Runs an application that lives in a reloadable library.
+///
+/// Supports the "thin executable + app library" setup where the executable only contains a
+/// loop driving the application and all the logic lives in a cdylib that is reloaded when it's
+/// rebuilt. The library exports the following functions, where only ```app_update``` is
+/// required. The state created by ```app_init``` is kept by the executable and passed to the new
+/// version of the library after a reload.
+///
+/// ```ignore
+/// #[no_mangle]
+/// pub extern "C" fn app_init() -> *mut c_void;
+/// #[no_mangle]
+/// pub extern "C" fn app_update(state: *mut c_void) -> bool;
+/// #[no_mangle]
+/// pub extern "C" fn app_before_reload(state: *mut c_void);
+/// #[no_mangle]
+/// pub extern "C" fn app_after_reload(state: *mut c_void);
+/// #[no_mangle]
+/// pub extern "C" fn app_shutdown(state: *mut c_void);
+/// ```
+///
+/// The executable is then only
+///
+/// ```ignore
+/// fn main() {
+///     let reload = DynamicReload::new(None, Some("target/debug"), Search::Default, Duration::from_millis(300));
+///     let app = unsafe { App::new(reload, "my_app", PlatformName::Yes).unwrap() };
+///     unsafe { app.run().unwrap() };
+/// }
+/// ```
+pub struct App {
+    reload: DynamicReload,
+    lib: Arc<Lib>,
+    state: *mut c_void,
+}
+
+// Passed through update to keep track of the current version of the library
+struct Swap {
+    state: *mut c_void,
+    lib: Option<Arc<Lib>>,
+    error: Option<Error>,
+}
+
+impl Swap {
+    fn update_call(&mut self, state: UpdateState, lib: Option<&Arc<Lib>>) {
+        match state {
+            UpdateState::Before => {
+                if let Some(lib) = lib {
+                    unsafe { call_state(lib, APP_BEFORE_RELOAD, self.state) };
+                }
+                self.lib = None;
+            }
+            // The previous version is loaded again after a failure
+            UpdateState::After | UpdateState::RolledBack => {
+                if let Some(lib) = lib {
+                    unsafe { call_state(lib, APP_AFTER_RELOAD, self.state) };
+                }
+                self.lib = lib.cloned();
+            }
+            UpdateState::ReloadFailed(e) => self.error = Some(e),
+            // The previous version is used again, restore it if it went through Before
+            UpdateState::HealthCheckFailed(e) => {
+                if let (None, Some(lib)) = (self.lib.as_ref(), lib) {
+                    unsafe { call_state(lib, APP_AFTER_RELOAD, self.state) };
+                    self.lib = Some(lib.clone());
+                }
+                self.error = Some(e);
+            }
+            // Keeps running the version that is loaded
+            UpdateState::Removed
+            | UpdateState::Added
+            | UpdateState::Blacklisted(_)
+            | UpdateState::StillReferenced(_)
+            | UpdateState::BuildStarted
+            | UpdateState::BuildFinished(_)
+            | UpdateState::BuildFailed(_) => (),
+        }
+    }
+}
+
+unsafe fn call_state(lib: &Lib, name: &str, state: *mut c_void) {
+    if let Ok(f) = lib.lib.get::<StateFn>(name.as_bytes()) {
+        f(state);
+    }
+}
+
+impl App {
+    /// Loads the application library and creates its state.
+    ///
+    /// # Safety
+    ///
+    /// The library must export the functions with the signatures described in [App].
+    pub unsafe fn new(
+        mut reload: DynamicReload,
+        name: &str,
+        name_format: PlatformName,
+    ) -> Result<App> {
+        let lib = reload.add_library(name, name_format)?;
+        Self::check(&lib)?;
+
+        let state = match lib.lib.get::<InitFn>(APP_INIT.as_bytes()) {
+            Ok(init) => init(),
+            Err(_) => std::ptr::null_mut(),
+        };
+
+        Ok(App { reload, lib, state })
+    }
+
+    fn check(lib: &Lib) -> Result<()> {
+        if !lib.has_symbol(APP_UPDATE) {
+            return Err(Error::MissingSymbol(
+                lib.loaded_path.clone(),
+                APP_UPDATE.to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The library currently running the application.
+    pub fn lib(&self) -> &Arc<Lib> {
+        &self.lib
+    }
+
+    /// Gives access to [DynamicReload] for configuration.
+    pub fn reload_mut(&mut self) -> &mut DynamicReload {
+        &mut self.reload
+    }
+
+    /// Reloads the library if it has changed and runs one iteration of the application.
+    /// Returns what ```app_update``` returned. A failed reload returns the error and the
+    /// previous version keeps running on the next call.
+    ///
+    /// # Safety
+    ///
+    /// See [update](struct.DynamicReload.html#method.update).
+    pub unsafe fn step(&mut self) -> Result<bool> {
+        let mut swap = Swap {
+            state: self.state,
+            lib: Some(self.lib.clone()),
+            error: None,
+        };
+
+        self.reload.update(&Swap::update_call, &mut swap);
+
+        if let Some(e) = swap.error {
+            return Err(e);
+        }
+
+        if let Some(lib) = swap.lib {
+            Self::check(&lib)?;
+            self.lib = lib;
+        }
+
+        let update = self
+            .lib
+            .lib
+            .get::<UpdateFn>(APP_UPDATE.as_bytes())
+            .map_err(Error::Load)?;
+
+        Ok(update(self.state))
+    }
+
+    /// Runs the application until ```app_update``` returns false, then calls
+    /// ```app_shutdown```. Reload failures are logged and the previous version keeps running.
+    ///
+    /// # Safety
+    ///
+    /// See [update](struct.DynamicReload.html#method.update).
+    pub unsafe fn run(mut self) -> Result<()> {
+        loop {
+            match self.step() {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) => log::error!("Unable to reload application: {}", e),
+            }
+        }
+
+        call_state(&self.lib, APP_SHUTDOWN, self.state);
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use crate::Lib;
+use std::{
+    ffi::c_void,
+    marker::PhantomData,
+    mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Arc, Weak,
+    },
+};
+
+/// A function pointer into a library that is updated when the library is reloaded, see
+/// [bind_symbol](struct.DynamicReload.html#method.bind_symbol).
+///
+/// Meant to be cached by code that calls into a library often, such as the main loop of the
+/// application, instead of looking up the symbol each time. It's cleared while the library is
+/// unloaded and set to the symbol in the new version before ```After``` is called. The pointer
+/// is only valid while the library is loaded, so a call that is running when the library is
+/// swapped out has to be protected with [LibGuard](struct.LibGuard.html) or
+/// [set_deferred_unload](struct.DynamicReload.html#method.set_deferred_unload).
+///
+/// ```ignore
+/// let update = Arc::new(unsafe { AtomicSymbol::<extern "C" fn(*mut State)>::new("app_update") });
+/// dr.bind_symbol(&lib, update.clone());
+///
+/// loop {
+///     unsafe { dr.update(&App::reload_callback, &mut app) };
+///     if let Some(update) = update.get() {
+///         update(state);
+///     }
+/// }
+/// ```
+pub struct AtomicSymbol<T> {
+    name: String,
+    ptr: AtomicPtr<c_void>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> AtomicSymbol<T> {
+    /// Creates an unbound symbol for ```name```.
+    ///
+    /// # Safety
+    ///
+    /// ```T``` has to be a function pointer type (or another pointer sized type) matching the
+    /// symbol exported by the library.
+    pub unsafe fn new(name: &str) -> AtomicSymbol<T> {
+        assert_eq!(
+            mem::size_of::<T>(),
+            mem::size_of::<*mut c_void>(),
+            "AtomicSymbol has to be a pointer"
+        );
+
+        AtomicSymbol {
+            name: name.to_owned(),
+            ptr: AtomicPtr::new(std::ptr::null_mut()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The current symbol, ```None``` if the library is unloaded or doesn't export it.
+    pub fn get(&self) -> Option<T> {
+        let ptr = self.ptr.load(Ordering::Acquire);
+        match ptr.is_null() {
+            true => None,
+            // Safety: T is pointer sized as checked in new
+            false => Some(unsafe { mem::transmute_copy::<*mut c_void, T>(&ptr) }),
+        }
+    }
+
+    /// Name of the symbol.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+// Type erased AtomicSymbol so symbols of different types can be kept together
+pub(crate) trait Rebind: Send + Sync {
+    fn bind(&self, lib: &Lib);
+    fn unbind(&self);
+}
+
+impl<T: Send + Sync> Rebind for AtomicSymbol<T> {
+    fn bind(&self, lib: &Lib) {
+        // Safety: only the address is read, it's up to the caller of new to use it correctly
+        let ptr = unsafe { lib.lib.get::<*mut c_void>(self.name.as_bytes()) }
+            .map(|symbol| *symbol)
+            .unwrap_or(std::ptr::null_mut());
+        self.ptr.store(ptr, Ordering::Release);
+    }
+
+    fn unbind(&self) {
+        self.ptr.store(std::ptr::null_mut(), Ordering::Release);
+    }
+}
+
+// The symbols bound to each library, dropped symbols are forgotten
+#[derive(Default)]
+pub(crate) struct Bindings {
+    symbols: Vec<(PathBuf, Weak<dyn Rebind>)>,
+}
+
+impl Bindings {
+    pub(crate) fn add(&mut self, lib: &Lib, symbol: Arc<dyn Rebind>) {
+        symbol.bind(lib);
+        self.symbols
+            .push((lib.source_path().to_path_buf(), Arc::downgrade(&symbol)));
+    }
+
+    // Points the symbols of the library to a new version
+    pub(crate) fn bind(&mut self, lib: &Lib) {
+        self.symbols.retain(|(_, s)| s.strong_count() > 0);
+
+        for symbol in self.of(lib.source_path()) {
+            symbol.bind(lib);
+        }
+    }
+
+    pub(crate) fn unbind(&mut self, path: &Path) {
+        for symbol in self.of(path) {
+            symbol.unbind();
+        }
+    }
+
+    fn of(&self, path: &Path) -> Vec<Arc<dyn Rebind>> {
+        self.symbols
+            .iter()
+            .filter(|(p, _)| p == path)
+            .filter_map(|(_, s)| s.upgrade())
+            .collect()
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{DynamicReload, Lib, UpdateState};
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    task::{Wake, Waker},
+    thread::{self, JoinHandle, Thread},
+    time::Duration,
+};
+
+/// Name of the thread started by [spawn_background](struct.DynamicReload.html#method.spawn_background).
+pub const RELOAD_THREAD_NAME: &str = "dynamic_reload reload";
+
+// How often to update when there are changes that don't arrive as watcher events (debounce
+// strategies, missing libraries, clusters)
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reloads libraries on a thread of its own, see
+/// [spawn_background](struct.DynamicReload.html#method.spawn_background).
+///
+/// The thread is stopped and joined when this is dropped.
+pub struct BackgroundReload {
+    reload: Arc<Mutex<DynamicReload>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+// Wakes the reload thread when the watcher has delivered an event
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+impl BackgroundReload {
+    pub(crate) fn spawn<F>(
+        reload: DynamicReload,
+        mut update_call: F,
+    ) -> io::Result<BackgroundReload>
+    where
+        F: FnMut(UpdateState, Option<&Arc<Lib>>) + Send + 'static,
+    {
+        let reload = Arc::new(Mutex::new(reload));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_reload = reload.clone();
+        let thread_stop = stop.clone();
+
+        let thread = thread::Builder::new()
+            .name(RELOAD_THREAD_NAME.to_string())
+            .spawn(move || {
+                let waker = Waker::from(Arc::new(Unpark(thread::current())));
+
+                while !thread_stop.load(Ordering::Acquire) {
+                    let needs_polling = {
+                        let mut reload = thread_reload.lock().unwrap_or_else(|e| e.into_inner());
+                        // Registered before updating so an event arriving in between isn't missed
+                        reload.set_waker(&waker);
+                        // Safety has been accepted by the caller of spawn_background
+                        unsafe { reload.update_with(&mut update_call) };
+                        reload.needs_polling()
+                    };
+
+                    if needs_polling {
+                        thread::park_timeout(POLL_INTERVAL);
+                    } else {
+                        thread::park();
+                    }
+                }
+            })?;
+
+        Ok(BackgroundReload {
+            reload,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Gives access to the [DynamicReload] to add libraries or change settings. No reloads
+    /// happen while the guard is held.
+    pub fn lock(&self) -> MutexGuard<'_, DynamicReload> {
+        let guard = self.reload.lock().unwrap_or_else(|e| e.into_inner());
+        // Have the thread pick up the changes once the guard is released
+        if let Some(thread) = self.thread() {
+            thread.unpark();
+        }
+        guard
+    }
+
+    /// The thread doing the reloads.
+    pub fn thread(&self) -> Option<&Thread> {
+        self.thread.as_ref().map(|t| t.thread())
+    }
+
+    /// Stops the thread and gives back the [DynamicReload] to be updated manually again.
+    pub fn stop(self) -> DynamicReload {
+        let reload = self.reload.clone();
+        // Joins the thread so this is the only reference left
+        drop(self);
+
+        match Arc::try_unwrap(reload) {
+            Ok(reload) => reload.into_inner().unwrap_or_else(|e| e.into_inner()),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    fn join(&mut self) {
+        self.stop.store(true, Ordering::Release);
+
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for BackgroundReload {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
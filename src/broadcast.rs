@@ -0,0 +1,119 @@
+use crate::{Lib, UpdateState};
+use std::{
+    path::PathBuf,
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+};
+
+/// Number of notifications a subscriber can fall behind before it's dropped, see
+/// [subscribe](struct.DynamicReload.html#method.subscribe).
+pub const SUBSCRIPTION_CAPACITY: usize = 256;
+
+/// What a [ReloadNotification] is about, mirrors [UpdateState](enum.UpdateState.html) without
+/// holding on to errors. Converted with ```From<&UpdateState>```.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NotificationKind {
+    /// The library is about to be reloaded.
+    Before,
+    /// The library has been reloaded.
+    After,
+    /// Reloading the library failed with the given error.
+    ReloadFailed(String),
+    /// The file of the library has been deleted.
+    Removed,
+    /// A new library has been loaded from a directory.
+    Added,
+    /// The previous version has been loaded again after a failed reload.
+    RolledBack,
+    /// The new version failed its health check with the given error.
+    HealthCheckFailed(String),
+    /// The library is no longer retried after failing to reload too many times.
+    Blacklisted,
+    /// The library is still referenced by the application the given number of times.
+    StillReferenced(usize),
+    /// A build of the library has been started after its sources changed.
+    BuildStarted,
+    /// The build succeeded with the given output of cargo.
+    BuildFinished(String),
+    /// The build failed with the given error.
+    BuildFailed(String),
+}
+
+/// A state change sent to the receivers returned by
+/// [subscribe](struct.DynamicReload.html#method.subscribe).
+///
+/// Unlike the callback it doesn't hold on to the library, so receivers that fall behind don't
+/// keep old versions loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReloadNotification {
+    /// What happened.
+    pub kind: NotificationKind,
+    /// Path of the library, the original location when loaded through a shadow directory. None
+    /// when loading a new library from a directory failed.
+    pub path: Option<PathBuf>,
+    /// [Generation](struct.Lib.html#method.generation) of the library passed to the callback.
+    pub generation: Option<u64>,
+}
+
+// The only place that maps the states, the match fails to compile when UpdateState gets a new
+// variant
+impl From<&UpdateState> for NotificationKind {
+    fn from(state: &UpdateState) -> NotificationKind {
+        match state {
+            UpdateState::Before => NotificationKind::Before,
+            UpdateState::After => NotificationKind::After,
+            UpdateState::ReloadFailed(e) => NotificationKind::ReloadFailed(e.to_string()),
+            UpdateState::Removed => NotificationKind::Removed,
+            UpdateState::Added => NotificationKind::Added,
+            UpdateState::RolledBack => NotificationKind::RolledBack,
+            UpdateState::HealthCheckFailed(e) => NotificationKind::HealthCheckFailed(e.to_string()),
+            UpdateState::Blacklisted(_) => NotificationKind::Blacklisted,
+            UpdateState::StillReferenced(count) => NotificationKind::StillReferenced(*count),
+            UpdateState::BuildStarted => NotificationKind::BuildStarted,
+            UpdateState::BuildFinished(output) => NotificationKind::BuildFinished(output.clone()),
+            UpdateState::BuildFailed(e) => NotificationKind::BuildFailed(e.to_string()),
+        }
+    }
+}
+
+impl ReloadNotification {
+    fn new(state: &UpdateState, lib: Option<&Arc<Lib>>) -> ReloadNotification {
+        let path = match state {
+            UpdateState::Blacklisted(path) => Some(path.clone()),
+            _ => lib.map(|lib| lib.source_path().to_path_buf()),
+        };
+
+        ReloadNotification {
+            kind: NotificationKind::from(state),
+            path,
+            generation: lib.map(|lib| lib.generation()),
+        }
+    }
+}
+
+// Receivers returned by DynamicReload::subscribe. Dropped receivers and the ones that have fallen
+// too far behind are removed on the next send, so a stalled subscriber can't block or grow
+// without bounds.
+#[derive(Default)]
+pub(crate) struct Subscribers(Mutex<Vec<SyncSender<ReloadNotification>>>);
+
+impl Subscribers {
+    pub(crate) fn subscribe(&self) -> Receiver<ReloadNotification> {
+        let (tx, rx) = sync_channel(SUBSCRIPTION_CAPACITY);
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(tx);
+        rx
+    }
+
+    pub(crate) fn send(&self, state: &UpdateState, lib: Option<&Arc<Lib>>) {
+        let mut subscribers = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if subscribers.is_empty() {
+            return;
+        }
+
+        let notification = ReloadNotification::new(state, lib);
+        subscribers.retain(|tx| tx.try_send(notification.clone()).is_ok());
+    }
+}
@@ -0,0 +1,458 @@
+use crate::init::HostApi;
+use crate::search;
+use crate::watcher::FileWatcher;
+use crate::winpath;
+#[cfg(feature = "signatures")]
+use crate::VerifyingKey;
+use crate::{
+    Cluster, CopyRetry, CopyVerify, DebounceStrategy, DeferredUnload, DynamicReload, Error,
+    ExternalRefs, HealthCheck, LifecycleSymbols, MatchPolicy, Preflight, Profile, RawEvent,
+    ReloadRetry, Result, Search, SelectionPolicy, SwapOrder, WatcherBackend, DEFAULT_POLL_FALLBACK,
+    SEARCH_PATH_ENV,
+};
+use std::{
+    env,
+    ffi::c_void,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Sender},
+    time::Duration,
+};
+use tempfile::TempDir;
+
+/// Sets up a [DynamicReload], created with [builder](struct.DynamicReload.html#method.builder).
+///
+/// Unlike [new](struct.DynamicReload.html#method.new), which keeps going without a shadow
+/// directory or file watcher when they can't be created, [build](DynamicReloadBuilder::build)
+/// fails with the error. Defaults to no search paths, no shadow directory, ```Search::Default```
+/// and a debounce duration of 2 seconds.
+pub struct DynamicReloadBuilder {
+    search_paths: Vec<PathBuf>,
+    search_path_env: Option<String>,
+    loader_paths: bool,
+    follow_symlinks: bool,
+    cargo_profile: Option<Profile>,
+    recursive_paths: Vec<PathBuf>,
+    shadow_dir: Option<PathBuf>,
+    search: Search,
+    match_policy: MatchPolicy,
+    selection_policy: SelectionPolicy,
+    swap_order: SwapOrder,
+    queue_reloads: bool,
+    debounce_duration: Duration,
+    debounce_strategy: Option<Box<dyn DebounceStrategy>>,
+    watcher_backend: WatcherBackend,
+    poll_fallback: Option<Duration>,
+    watch: bool,
+    ignore_patterns: Vec<String>,
+    copy_retry: CopyRetry,
+    copy_verify: CopyVerify,
+    codesign: bool,
+    copy_debug_info: bool,
+    rollback: bool,
+    preflight: Option<Preflight>,
+    health_check: Option<HealthCheck>,
+    abi_tag: Option<String>,
+    fingerprint: Option<String>,
+    reload_retry: Option<ReloadRetry>,
+    deferred_unload: DeferredUnload,
+    external_refs: ExternalRefs,
+    #[cfg(feature = "signatures")]
+    public_key: Option<VerifyingKey>,
+    #[cfg(feature = "checksums")]
+    checksum_manifest: Option<PathBuf>,
+    init_symbol: Option<String>,
+    init_timeout: Option<Duration>,
+    host_api: Option<HostApi>,
+    lifecycle: LifecycleSymbols,
+    cluster: Option<Cluster>,
+    raw_events: Option<Sender<RawEvent>>,
+}
+
+impl Default for DynamicReloadBuilder {
+    fn default() -> DynamicReloadBuilder {
+        DynamicReloadBuilder {
+            search_paths: Vec::new(),
+            search_path_env: Some(SEARCH_PATH_ENV.to_owned()),
+            loader_paths: false,
+            follow_symlinks: false,
+            cargo_profile: None,
+            recursive_paths: Vec::new(),
+            shadow_dir: None,
+            search: Search::Default,
+            match_policy: MatchPolicy::default(),
+            selection_policy: SelectionPolicy::default(),
+            swap_order: SwapOrder::default(),
+            queue_reloads: false,
+            debounce_duration: Duration::from_secs(2),
+            debounce_strategy: None,
+            watcher_backend: WatcherBackend::Recommended,
+            poll_fallback: Some(DEFAULT_POLL_FALLBACK),
+            watch: true,
+            ignore_patterns: Vec::new(),
+            copy_retry: CopyRetry::default(),
+            copy_verify: CopyVerify::default(),
+            codesign: false,
+            copy_debug_info: false,
+            rollback: false,
+            preflight: None,
+            health_check: None,
+            abi_tag: None,
+            fingerprint: None,
+            reload_retry: None,
+            deferred_unload: DeferredUnload::default(),
+            external_refs: ExternalRefs::default(),
+            #[cfg(feature = "signatures")]
+            public_key: None,
+            #[cfg(feature = "checksums")]
+            checksum_manifest: None,
+            init_symbol: None,
+            init_timeout: None,
+            host_api: None,
+            lifecycle: LifecycleSymbols::default(),
+            cluster: None,
+            raw_events: None,
+        }
+    }
+}
+
+impl DynamicReloadBuilder {
+    /// Same as [builder](struct.DynamicReload.html#method.builder).
+    pub fn new() -> DynamicReloadBuilder {
+        DynamicReloadBuilder::default()
+    }
+
+    /// Adds an extra path to search for libraries in.
+    pub fn search_path<P: AsRef<Path>>(mut self, path: P) -> DynamicReloadBuilder {
+        self.search_paths.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Environment variable with extra search paths that are added after the ones given to
+    /// [search_path](DynamicReloadBuilder::search_path), [SEARCH_PATH_ENV] by default.
+    /// ```None``` ignores the environment.
+    pub fn search_path_env(mut self, var: Option<&str>) -> DynamicReloadBuilder {
+        self.search_path_env = var.map(str::to_owned);
+        self
+    }
+
+    /// See [set_loader_paths](struct.DynamicReload.html#method.set_loader_paths).
+    pub fn loader_paths(mut self, enabled: bool) -> DynamicReloadBuilder {
+        self.loader_paths = enabled;
+        self
+    }
+
+    /// See [set_follow_symlinks](struct.DynamicReload.html#method.set_follow_symlinks).
+    pub fn follow_symlinks(mut self, enabled: bool) -> DynamicReloadBuilder {
+        self.follow_symlinks = enabled;
+        self
+    }
+
+    /// See [set_cargo_profile](struct.DynamicReload.html#method.set_cargo_profile).
+    pub fn cargo_profile(mut self, profile: Option<Profile>) -> DynamicReloadBuilder {
+        self.cargo_profile = profile;
+        self
+    }
+
+    /// Adds a root directory that is searched and watched recursively, see
+    /// [add_recursive_search_path](struct.DynamicReload.html#method.add_recursive_search_path).
+    pub fn recursive_search_path<P: AsRef<Path>>(mut self, path: P) -> DynamicReloadBuilder {
+        self.recursive_paths.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Directory in which the temporary directory for shadow copies of the libraries is
+    /// created.
+    pub fn shadow_dir<P: AsRef<Path>>(mut self, dir: P) -> DynamicReloadBuilder {
+        self.shadow_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// How libraries are searched for.
+    pub fn search(mut self, search: Search) -> DynamicReloadBuilder {
+        self.search = search;
+        self
+    }
+
+    /// See [set_match_policy](struct.DynamicReload.html#method.set_match_policy).
+    pub fn match_policy(mut self, policy: MatchPolicy) -> DynamicReloadBuilder {
+        self.match_policy = policy;
+        self
+    }
+
+    /// See [set_selection_policy](struct.DynamicReload.html#method.set_selection_policy).
+    pub fn selection_policy(mut self, policy: SelectionPolicy) -> DynamicReloadBuilder {
+        self.selection_policy = policy;
+        self
+    }
+
+    /// See [set_swap_order](struct.DynamicReload.html#method.set_swap_order).
+    pub fn swap_order(mut self, order: SwapOrder) -> DynamicReloadBuilder {
+        self.swap_order = order;
+        self
+    }
+
+    /// See [set_queue_reloads](struct.DynamicReload.html#method.set_queue_reloads).
+    pub fn queue_reloads(mut self, queue: bool) -> DynamicReloadBuilder {
+        self.queue_reloads = queue;
+        self
+    }
+
+    /// Time to wait after the last change of a library before reloading it.
+    pub fn debounce(mut self, duration: Duration) -> DynamicReloadBuilder {
+        self.debounce_duration = duration;
+        self
+    }
+
+    /// See [set_debounce_strategy](struct.DynamicReload.html#method.set_debounce_strategy).
+    pub fn debounce_strategy(
+        mut self,
+        strategy: Box<dyn DebounceStrategy>,
+    ) -> DynamicReloadBuilder {
+        self.debounce_strategy = Some(strategy);
+        self
+    }
+
+    /// See [set_watcher_backend](struct.DynamicReload.html#method.set_watcher_backend).
+    pub fn watcher_backend(mut self, backend: WatcherBackend) -> DynamicReloadBuilder {
+        self.watcher_backend = backend;
+        self
+    }
+
+    /// See [set_poll_fallback](struct.DynamicReload.html#method.set_poll_fallback).
+    pub fn poll_fallback(mut self, interval: Option<Duration>) -> DynamicReloadBuilder {
+        self.poll_fallback = interval;
+        self
+    }
+
+    /// Watch libraries for changes, true by default. Without watching libraries are only
+    /// loaded, use [start_watching](struct.DynamicReload.html#method.start_watching) to start
+    /// later on.
+    pub fn watch(mut self, watch: bool) -> DynamicReloadBuilder {
+        self.watch = watch;
+        self
+    }
+
+    /// Adds a glob pattern for file names whose changes are ignored, see
+    /// [set_ignore_patterns](struct.DynamicReload.html#method.set_ignore_patterns).
+    pub fn ignore_pattern(mut self, pattern: &str) -> DynamicReloadBuilder {
+        self.ignore_patterns.push(pattern.to_owned());
+        self
+    }
+
+    /// See [set_copy_retry](struct.DynamicReload.html#method.set_copy_retry).
+    pub fn copy_retry(mut self, retry: CopyRetry) -> DynamicReloadBuilder {
+        self.copy_retry = retry;
+        self
+    }
+
+    /// See [set_copy_verify](struct.DynamicReload.html#method.set_copy_verify).
+    pub fn copy_verify(mut self, verify: CopyVerify) -> DynamicReloadBuilder {
+        self.copy_verify = verify;
+        self
+    }
+
+    /// See [set_codesign](struct.DynamicReload.html#method.set_codesign).
+    pub fn codesign(mut self, codesign: bool) -> DynamicReloadBuilder {
+        self.codesign = codesign;
+        self
+    }
+
+    /// See [set_copy_debug_info](struct.DynamicReload.html#method.set_copy_debug_info).
+    pub fn copy_debug_info(mut self, copy: bool) -> DynamicReloadBuilder {
+        self.copy_debug_info = copy;
+        self
+    }
+
+    /// See [set_rollback](struct.DynamicReload.html#method.set_rollback).
+    pub fn rollback(mut self, rollback: bool) -> DynamicReloadBuilder {
+        self.rollback = rollback;
+        self
+    }
+
+    /// See [set_preflight](struct.DynamicReload.html#method.set_preflight).
+    pub fn preflight(mut self, preflight: Preflight) -> DynamicReloadBuilder {
+        self.preflight = Some(preflight);
+        self
+    }
+
+    /// See [set_health_check](struct.DynamicReload.html#method.set_health_check).
+    pub fn health_check(mut self, check: HealthCheck) -> DynamicReloadBuilder {
+        self.health_check = Some(check);
+        self
+    }
+
+    /// See [set_abi_tag](struct.DynamicReload.html#method.set_abi_tag).
+    pub fn abi_tag(mut self, tag: &str) -> DynamicReloadBuilder {
+        self.abi_tag = Some(tag.to_owned());
+        self
+    }
+
+    /// See [set_fingerprint](struct.DynamicReload.html#method.set_fingerprint).
+    pub fn fingerprint(mut self, interface: &str) -> DynamicReloadBuilder {
+        self.fingerprint = Some(interface.to_owned());
+        self
+    }
+
+    /// See [set_reload_retry](struct.DynamicReload.html#method.set_reload_retry).
+    pub fn reload_retry(mut self, retry: ReloadRetry) -> DynamicReloadBuilder {
+        self.reload_retry = Some(retry);
+        self
+    }
+
+    /// See [set_deferred_unload](struct.DynamicReload.html#method.set_deferred_unload).
+    pub fn deferred_unload(mut self, deferred: DeferredUnload) -> DynamicReloadBuilder {
+        self.deferred_unload = deferred;
+        self
+    }
+
+    /// See [set_external_refs](struct.DynamicReload.html#method.set_external_refs).
+    pub fn external_refs(mut self, external: ExternalRefs) -> DynamicReloadBuilder {
+        self.external_refs = external;
+        self
+    }
+
+    /// See [set_public_key](struct.DynamicReload.html#method.set_public_key).
+    #[cfg(feature = "signatures")]
+    pub fn public_key(mut self, key: VerifyingKey) -> DynamicReloadBuilder {
+        self.public_key = Some(key);
+        self
+    }
+
+    /// See [set_checksum_manifest](struct.DynamicReload.html#method.set_checksum_manifest).
+    #[cfg(feature = "checksums")]
+    pub fn checksum_manifest(mut self, path: &Path) -> DynamicReloadBuilder {
+        self.checksum_manifest = Some(path.to_path_buf());
+        self
+    }
+
+    /// See [set_init_symbol](struct.DynamicReload.html#method.set_init_symbol).
+    pub fn init_symbol(mut self, symbol: &str) -> DynamicReloadBuilder {
+        self.init_symbol = Some(symbol.to_owned());
+        self
+    }
+
+    /// See [set_init_timeout](struct.DynamicReload.html#method.set_init_timeout).
+    pub fn init_timeout(mut self, timeout: Duration) -> DynamicReloadBuilder {
+        self.init_timeout = Some(timeout);
+        self
+    }
+
+    /// See [set_host_api](struct.DynamicReload.html#method.set_host_api).
+    pub fn host_api(mut self, api: *const c_void) -> DynamicReloadBuilder {
+        self.host_api = Some(HostApi(api));
+        self
+    }
+
+    /// See [set_lifecycle_symbols](struct.DynamicReload.html#method.set_lifecycle_symbols).
+    pub fn lifecycle_symbols(mut self, symbols: LifecycleSymbols) -> DynamicReloadBuilder {
+        self.lifecycle = symbols;
+        self
+    }
+
+    /// See [set_cluster](struct.DynamicReload.html#method.set_cluster).
+    pub fn cluster(mut self, cluster: Cluster) -> DynamicReloadBuilder {
+        self.cluster = Some(cluster);
+        self
+    }
+
+    /// See [set_raw_events](struct.DynamicReload.html#method.set_raw_events).
+    pub fn raw_events(mut self, sink: Sender<RawEvent>) -> DynamicReloadBuilder {
+        self.raw_events = Some(sink);
+        self
+    }
+
+    /// Creates the DynamicReload. Fails if the shadow directory or the file watcher can't be
+    /// created, a recursive search path doesn't exist or an ignore pattern is invalid.
+    pub fn build(self) -> Result<DynamicReload> {
+        let shadow_dir = match self.shadow_dir {
+            Some(dir) => Some(
+                TempDir::new_in(winpath::extended(&dir)).map_err(|e| Error::ShadowDir(e, dir))?,
+            ),
+            None => None,
+        };
+
+        let backend = if self.watch {
+            self.watcher_backend
+        } else {
+            WatcherBackend::None
+        };
+
+        let debounce_duration = match self.debounce_strategy {
+            Some(_) => None,
+            None => Some(self.debounce_duration),
+        };
+
+        let (tx, rx) = channel();
+        let watcher = FileWatcher::new(tx, backend, debounce_duration, self.poll_fallback)
+            .map_err(Error::Watcher)?;
+
+        let env_paths = self
+            .search_path_env
+            .as_deref()
+            .map(|var| search::env_paths(var, |var| env::var_os(var)))
+            .unwrap_or_default();
+
+        let search_paths = self
+            .search_paths
+            .into_iter()
+            .chain(env_paths)
+            .map(|path| winpath::canonicalize(&path).unwrap_or(path))
+            .collect();
+
+        let mut dr = DynamicReload::with_parts(
+            watcher,
+            rx,
+            shadow_dir,
+            search_paths,
+            self.search,
+            self.debounce_duration,
+        );
+
+        dr.match_policy = self.match_policy;
+        dr.selection_policy = self.selection_policy;
+        dr.loader_paths = self.loader_paths;
+        dr.follow_symlinks = self.follow_symlinks;
+        dr.cargo_profile = self.cargo_profile;
+        dr.swap_order = self.swap_order;
+        dr.queue_reloads = self.queue_reloads;
+        dr.watcher_backend = self.watcher_backend;
+        dr.poll_fallback = self.poll_fallback;
+        dr.debounce_strategy = self.debounce_strategy;
+        dr.copy_retry = self.copy_retry;
+        dr.copy_verify = self.copy_verify;
+        dr.codesign = self.codesign;
+        dr.copy_debug_info = self.copy_debug_info;
+        dr.rollback = self.rollback;
+        dr.preflight = self.preflight;
+        dr.health_check = self.health_check;
+        dr.abi_tag = self.abi_tag;
+        dr.fingerprint = self.fingerprint;
+        dr.retries.policy = self.reload_retry;
+        dr.unloads.mode = self.deferred_unload;
+        dr.unloads.external = self.external_refs;
+        #[cfg(feature = "signatures")]
+        {
+            dr.public_key = self.public_key;
+        }
+        #[cfg(feature = "checksums")]
+        {
+            dr.checksum_manifest = self.checksum_manifest;
+        }
+        dr.init_symbol = self.init_symbol;
+        dr.init_timeout = self.init_timeout;
+        dr.host_api = self.host_api;
+        dr.lifecycle = self.lifecycle;
+        dr.raw_events = self.raw_events;
+
+        let patterns: Vec<&str> = self.ignore_patterns.iter().map(|p| p.as_str()).collect();
+        dr.set_ignore_patterns(&patterns)?;
+
+        for path in self.recursive_paths {
+            dr.add_recursive_search_path(path)?;
+        }
+
+        dr.set_cluster(self.cluster)?;
+
+        Ok(dr)
+    }
+}
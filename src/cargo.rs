@@ -0,0 +1,218 @@
+#[cfg(feature = "cargo")]
+use crate::{DynamicReload, Error, PlatformName, Result};
+#[cfg(feature = "cargo")]
+use serde_json::Value;
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Cargo profile that libraries are built with, used to find the directory in the target
+/// directory that cargo puts the artifacts in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// The ```dev``` profile, built into ```target/debug```.
+    Debug,
+    /// The ```release``` profile, built into ```target/release```.
+    Release,
+    /// A custom profile, built into the directory with the name of the profile.
+    Custom(String),
+}
+
+impl Profile {
+    /// The profile the application itself is built with, ```Debug``` when debug assertions
+    /// are on and ```Release``` otherwise.
+    pub fn current() -> Profile {
+        if cfg!(debug_assertions) {
+            Profile::Debug
+        } else {
+            Profile::Release
+        }
+    }
+
+    /// Name of the directory in the target directory with the artifacts of the profile.
+    pub fn dir_name(&self) -> &str {
+        match self {
+            Profile::Debug => "debug",
+            Profile::Release => "release",
+            // The built in profiles keep their own directory names
+            Profile::Custom(name) if name == "dev" || name == "test" => "debug",
+            Profile::Custom(name) if name == "bench" => "release",
+            Profile::Custom(name) => name,
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Profile {
+        Profile::current()
+    }
+}
+
+/// The cargo target directory of the workspace the application is run from.
+///
+/// ```CARGO_TARGET_DIR``` (or ```CARGO_BUILD_TARGET_DIR```) when set, otherwise the
+/// ```target``` directory of the workspace that contains the current directory. ```None```
+/// outside of a cargo project.
+pub fn cargo_target_dir() -> Option<PathBuf> {
+    target_dir(&env::current_dir().ok()?, |var| env::var_os(var))
+}
+
+// The target directory for current_dir with the environment variables read through lookup
+pub(crate) fn target_dir<F>(current_dir: &Path, lookup: F) -> Option<PathBuf>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    for var in ["CARGO_TARGET_DIR", "CARGO_BUILD_TARGET_DIR"] {
+        if let Some(dir) = lookup(var).filter(|dir| !dir.is_empty()) {
+            return Some(current_dir.join(dir));
+        }
+    }
+
+    workspace_root(current_dir).map(|root| root.join("target"))
+}
+
+// The directory with the Cargo.toml of the workspace, or the package if it isn't part of one
+fn workspace_root(dir: &Path) -> Option<PathBuf> {
+    let mut package = None;
+
+    for dir in dir.ancestors() {
+        let manifest = match fs::read_to_string(dir.join("Cargo.toml")) {
+            Ok(manifest) => manifest,
+            Err(_) => continue,
+        };
+
+        if manifest.lines().any(|line| line.trim() == "[workspace]") {
+            return Some(dir.to_path_buf());
+        }
+
+        if package.is_none() {
+            package = Some(dir.to_path_buf());
+        }
+    }
+
+    package
+}
+
+// cargo build of the package with the manifest
+pub(crate) fn build_command(manifest: &Path, profile: &Profile) -> Command {
+    let mut command = cargo_command();
+    command.arg("build").arg("--manifest-path").arg(manifest);
+
+    match profile {
+        Profile::Debug => (),
+        Profile::Release => {
+            command.arg("--release");
+        }
+        Profile::Custom(name) => {
+            command.arg("--profile").arg(name);
+        }
+    }
+
+    command
+}
+
+// The cargo that runs the application when there is one
+fn cargo_command() -> Command {
+    Command::new(env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+}
+
+// Builds the package and returns the path of its dynamic library
+#[cfg(feature = "cargo")]
+pub(crate) fn compile(crate_dir: &Path, profile: &Profile) -> Result<PathBuf> {
+    let manifest = crate_dir
+        .join("Cargo.toml")
+        .canonicalize()
+        .map_err(|e| Error::Cargo(format!("no Cargo.toml in {:?}: {}", crate_dir, e)))?;
+
+    // Diagnostics are rendered to stderr and the artifacts are listed as JSON on stdout
+    let output = build_command(&manifest, profile)
+        .arg("--message-format=json-render-diagnostics")
+        .output()
+        .map_err(|e| Error::Cargo(format!("unable to run cargo build: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(Error::Cargo(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|message| {
+            message["reason"] == "compiler-artifact"
+                && message["manifest_path"].as_str().map(Path::new) == Some(manifest.as_path())
+        })
+        .flat_map(|message| message["filenames"].as_array().cloned().unwrap_or_default())
+        .filter_map(|file| file.as_str().map(PathBuf::from))
+        .find(|file| {
+            file.file_name()
+                .is_some_and(|name| DynamicReload::is_dynamiclib_name(&name.to_string_lossy()))
+        })
+        .ok_or_else(|| Error::Cargo(format!("{:?} doesn't build a dynamic library", manifest)))
+}
+
+// Path of the library built for the package, found with cargo metadata
+#[cfg(feature = "cargo")]
+pub(crate) fn artifact_path(package: &str, profile: &Profile) -> Result<PathBuf> {
+    let output = cargo_command()
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .output()
+        .map_err(|e| Error::Cargo(format!("unable to run cargo metadata: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(Error::Cargo(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+
+    let metadata = serde_json::from_slice(&output.stdout)
+        .map_err(|e| Error::Cargo(format!("invalid cargo metadata: {}", e)))?;
+    let path = find_artifact(&metadata, package, profile)?;
+
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(Error::Find(package.to_owned(), vec![path]))
+    }
+}
+
+// Where the dynamic library target of the package ends up in the target directory
+#[cfg(feature = "cargo")]
+pub(crate) fn find_artifact(metadata: &Value, package: &str, profile: &Profile) -> Result<PathBuf> {
+    let target_dir = metadata["target_directory"]
+        .as_str()
+        .ok_or_else(|| Error::Cargo("no target directory in cargo metadata".to_owned()))?;
+
+    let package_metadata = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|p| p["name"] == package)
+        .ok_or_else(|| Error::Cargo(format!("no package named {} in the workspace", package)))?;
+
+    let name = package_metadata["targets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|t| {
+            t["crate_types"]
+                .as_array()
+                .is_some_and(|types| types.iter().any(|t| t == "cdylib" || t == "dylib"))
+        })
+        .find_map(|t| t["name"].as_str())
+        .ok_or_else(|| {
+            Error::Cargo(format!(
+                "package {} doesn't build a dynamic library",
+                package
+            ))
+        })?;
+
+    Ok(Path::new(target_dir)
+        .join(profile.dir_name())
+        .join(DynamicReload::get_library_name(name, PlatformName::Yes)))
+}
@@ -0,0 +1,42 @@
+use crate::{Error, Result};
+use sha2::{Digest, Sha256};
+use std::{fs, path::Path};
+
+/// Verifies the SHA-256 digest of the file at ```loaded_path``` (the shadow copy if there is
+/// one) against the entry for the file name of ```lib_path``` in the manifest.
+///
+/// The manifest uses the output format of ```sha256sum```, one ```<hex digest> <file>``` pair
+/// per line. Only the file name of each entry is compared, empty lines and lines starting with
+/// ```#``` are ignored. The manifest is read on each load so it can be updated together with
+/// the libraries.
+pub(crate) fn verify(manifest: &Path, lib_path: &Path, loaded_path: &Path) -> Result<()> {
+    let mismatch = |reason: String| Error::ChecksumMismatch(lib_path.to_path_buf(), reason);
+
+    let text = fs::read_to_string(manifest)
+        .map_err(|e| mismatch(format!("unable to read {:?}: {}", manifest, e)))?;
+    let file_name = lib_path.file_name();
+
+    let expected = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        // sha256sum marks files read in binary mode with a '*'
+        .find(|(_, file)| Path::new(file.trim().trim_start_matches('*')).file_name() == file_name)
+        .map(|(digest, _)| digest.to_ascii_lowercase())
+        .ok_or_else(|| mismatch(format!("not listed in {:?}", manifest)))?;
+
+    let data = fs::read(loaded_path).map_err(|e| mismatch(e.to_string()))?;
+    let digest: String = Sha256::digest(&data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    match digest == expected {
+        true => Ok(()),
+        false => Err(mismatch(format!(
+            "digest {}, expected {}",
+            digest, expected
+        ))),
+    }
+}
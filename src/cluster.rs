@@ -0,0 +1,243 @@
+use crate::{copy, CopyRetry, CopyVerify, Error, FileIdentity, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+const MANIFEST: &str = "manifest";
+const ACKS: &str = "acks";
+// Number of staged generations that are always kept, older ones are removed once no follower
+// is at them
+const KEEP_GENERATIONS: u64 = 3;
+
+/// The role of a process taking part in a cluster reload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClusterRole {
+    /// Watches the libraries and publishes every loaded version to the cluster directory.
+    Coordinator,
+    /// Ignores local file changes and loads whatever the coordinator has published.
+    Follower,
+}
+
+/// Coordinates reloading between several processes sharing the same plugins (for example a
+/// server with N worker processes).
+///
+/// One process is the coordinator. Each time it loads or reloads a library, the library is
+/// staged into a new generation directory inside the shared cluster directory and a manifest
+/// listing the staged libraries is written. Followers check the manifest during
+/// [update](struct.DynamicReload.html#method.update) and reload their libraries from the
+/// staged copies, so all processes run the same generation even if the original files change
+/// again while they are being reloaded. Libraries that haven't changed since the last
+/// generation are hard linked instead of copied when possible.
+///
+/// Each follower records the generation it's at in the ```acks``` directory. Generations are
+/// only removed when no follower is at them and they're not among the last three, so the
+/// libraries a follower has loaded stay in place. A follower removes its record when it's
+/// dropped, the record of a follower that has crashed has to be removed by hand.
+///
+/// ```ignore
+/// cluster/
+///     manifest        generation number followed by one staged library per line
+///     acks/1234-0     generation of a follower
+///     1/libfoo.so
+///     2/libfoo.so
+/// ```
+pub struct Cluster {
+    dir: PathBuf,
+    role: ClusterRole,
+    generation: u64,
+    // Name of the file of a follower in the acks directory
+    id: String,
+    // (loaded path, identity of the loaded file, staged path) of the libraries published last
+    published: Vec<(PathBuf, Option<FileIdentity>, PathBuf)>,
+}
+
+/// A library staged by the coordinator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Staged {
+    pub(crate) file_name: String,
+    pub(crate) path: PathBuf,
+}
+
+impl Cluster {
+    /// Creates the coordinator of the cluster using ```dir``` as the shared directory.
+    /// The directory is created if needed and previous generations are picked up.
+    pub fn coordinator<P: AsRef<Path>>(dir: P) -> Result<Cluster> {
+        Self::new(dir.as_ref(), ClusterRole::Coordinator)
+    }
+
+    /// Creates a follower reading from the shared directory ```dir```.
+    pub fn follower<P: AsRef<Path>>(dir: P) -> Result<Cluster> {
+        Self::new(dir.as_ref(), ClusterRole::Follower)
+    }
+
+    fn new(dir: &Path, role: ClusterRole) -> Result<Cluster> {
+        fs::create_dir_all(dir).map_err(|e| Error::Cluster(dir.to_path_buf(), e.to_string()))?;
+
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let mut cluster = Cluster {
+            dir: dir.to_path_buf(),
+            role,
+            generation: 0,
+            id: format!(
+                "{}-{}",
+                process::id(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            ),
+            published: Vec::new(),
+        };
+
+        // The coordinator continues from the latest generation, followers start from scratch
+        // to load the current one
+        if role == ClusterRole::Coordinator {
+            if let Some((generation, _)) = cluster.read_manifest() {
+                cluster.generation = generation;
+            }
+        }
+
+        Ok(cluster)
+    }
+
+    /// The role of this process.
+    pub fn role(&self) -> ClusterRole {
+        self.role
+    }
+
+    /// The generation this process is at. For the coordinator this is the last published
+    /// generation, for followers the last applied one.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Stages the given libraries as a new generation and publishes it to the followers.
+    /// Each library is given as the original path (for the name) and the path it was loaded
+    /// from (so the staged copy is exactly what the coordinator is running).
+    pub(crate) fn publish(&mut self, libs: &[(&Path, &Path)]) -> Result<()> {
+        let generation = self.generation + 1;
+        let gen_dir = self.dir.join(generation.to_string());
+        fs::create_dir_all(&gen_dir).map_err(|e| self.error(e))?;
+
+        let mut manifest = format!("{}\n", generation);
+        let mut published = Vec::new();
+
+        for (original, loaded) in libs {
+            let file_name = match original.file_name() {
+                Some(name) => name,
+                None => continue,
+            };
+
+            // A library loaded from the same copy as in the last generation hasn't changed. The
+            // copy keeps its name with no-timestamps so it has to be the same file too.
+            let staged = gen_dir.join(file_name);
+            let identity = FileIdentity::from_path(loaded);
+            let previous = self
+                .published
+                .iter()
+                .find(|(path, id, _)| path == loaded && id.is_some() && *id == identity);
+            let linked = previous.is_some_and(|(_, _, prev)| fs::hard_link(prev, &staged).is_ok());
+
+            if !linked {
+                copy::try_copy(loaded, &staged, &CopyRetry::default(), CopyVerify::Size)?;
+            }
+
+            manifest.push_str(&format!("{}\n", file_name.to_string_lossy()));
+            published.push((loaded.to_path_buf(), identity, staged));
+        }
+
+        // Write and rename so followers never see a partial manifest
+        let temp = self.dir.join(format!("{}.tmp", MANIFEST));
+        fs::write(&temp, manifest).map_err(|e| self.error(e))?;
+        fs::rename(&temp, self.dir.join(MANIFEST)).map_err(|e| self.error(e))?;
+
+        self.generation = generation;
+        self.published = published;
+        self.remove_old_generations();
+
+        Ok(())
+    }
+
+    // Removes the generations that aren't among the last ones and that no follower is at
+    fn remove_old_generations(&self) {
+        let followed = self.followed_generations();
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        for entry in entries.flatten() {
+            let generation = match entry.file_name().to_str().map(str::parse::<u64>) {
+                Some(Ok(generation)) => generation,
+                _ => continue,
+            };
+
+            if generation + KEEP_GENERATIONS <= self.generation && !followed.contains(&generation) {
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
+    }
+
+    // The generations the followers are at
+    fn followed_generations(&self) -> Vec<u64> {
+        let entries = match fs::read_dir(self.dir.join(ACKS)) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        entries
+            .flatten()
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok()?.trim().parse().ok())
+            .collect()
+    }
+
+    /// Returns the staged libraries if the coordinator has published a newer generation.
+    pub(crate) fn poll(&mut self) -> Option<Vec<Staged>> {
+        let (generation, staged) = self.read_manifest()?;
+
+        if generation <= self.generation {
+            return None;
+        }
+
+        // Recorded before the libraries are loaded from the generation so it's kept from then on
+        let acks = self.dir.join(ACKS);
+        let recorded = fs::create_dir_all(&acks)
+            .and_then(|_| fs::write(acks.join(&self.id), generation.to_string()));
+        if let Err(e) = recorded {
+            log::warn!("Unable to record cluster generation {}: {}", generation, e);
+        }
+
+        self.generation = generation;
+        Some(staged)
+    }
+
+    fn read_manifest(&self) -> Option<(u64, Vec<Staged>)> {
+        let manifest = fs::read_to_string(self.dir.join(MANIFEST)).ok()?;
+        let mut lines = manifest.lines();
+        let generation = lines.next()?.trim().parse::<u64>().ok()?;
+        let gen_dir = self.dir.join(generation.to_string());
+
+        let staged = lines
+            .filter(|line| !line.is_empty())
+            .map(|line| Staged {
+                file_name: line.to_owned(),
+                path: gen_dir.join(line),
+            })
+            .collect();
+
+        Some((generation, staged))
+    }
+
+    fn error(&self, e: std::io::Error) -> Error {
+        Error::Cluster(self.dir.clone(), e.to_string())
+    }
+}
+
+impl Drop for Cluster {
+    fn drop(&mut self) {
+        if self.role == ClusterRole::Follower {
+            let _ = fs::remove_file(self.dir.join(ACKS).join(&self.id));
+        }
+    }
+}
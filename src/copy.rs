@@ -0,0 +1,387 @@
+use crate::{hash, telemetry, Error, Result};
+use std::{
+    collections::hash_map::RandomState,
+    ffi::OsString,
+    fs,
+    hash::{BuildHasher, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::Duration,
+};
+
+/// Controls how copying a library to the shadow directory is retried.
+///
+/// The library file is often still being written or is locked (by the linker, antivirus, etc)
+/// when a change is detected so the copy is retried with an exponential backoff. The delay
+/// starts at ```initial_delay```, doubles for each attempt up to ```max_delay``` and is
+/// randomly shortened by up to ```jitter``` (0.0 - 1.0) of the delay.
+///
+/// The default is 10 attempts starting at 20 ms, capped at 200 ms, which gives up after about
+/// a second. Slow machines can extend this with
+/// [set_copy_retry](struct.DynamicReload.html#method.set_copy_retry).
+#[derive(Clone, Debug)]
+pub struct CopyRetry {
+    /// Number of times to try the copy before giving up.
+    pub attempts: u32,
+    /// Delay after the first failed attempt.
+    pub initial_delay: Duration,
+    /// Upper limit of the delay between two attempts.
+    pub max_delay: Duration,
+    /// Fraction of the delay that is randomized.
+    pub jitter: f32,
+}
+
+impl Default for CopyRetry {
+    fn default() -> CopyRetry {
+        CopyRetry {
+            attempts: 10,
+            initial_delay: Duration::from_millis(20),
+            max_delay: Duration::from_millis(200),
+            jitter: 0.25,
+        }
+    }
+}
+
+impl CopyRetry {
+    /// Returns the delay to wait after the given (zero based) attempt has failed.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial_delay
+            .checked_mul(1 << attempt.min(16))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+
+        let random = RandomState::new().build_hasher().finish() as f32 / u64::MAX as f32;
+        delay.mul_f32(1.0 - jitter * random)
+    }
+}
+
+/// How the shadow copy is checked against the source after copying.
+///
+/// A rebuild that starts while the library is being copied can leave a half written copy in the
+/// shadow directory. When verification fails the copy is retried according to [CopyRetry].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CopyVerify {
+    /// Don't verify the copy.
+    None,
+    /// Check that the copy has the same size as the source and that the source wasn't modified
+    /// while it was copied. This is the default.
+    #[default]
+    Size,
+    /// Same as ```Size``` but also compares a hash of the content of both files.
+    Hash,
+}
+
+// A directory of its own in the shadow directory for one load of a library, named after the
+// library. Removed when dropped, which happens once the library has been unloaded.
+#[derive(Debug)]
+pub(crate) struct LoadDir(PathBuf);
+
+impl LoadDir {
+    pub(crate) fn create(shadow_dir: &Path, full_path: &Path) -> Result<LoadDir> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        loop {
+            let mut name = full_path.file_name().unwrap_or_default().to_os_string();
+            name.push(format!(".{}", NEXT.fetch_add(1, Ordering::Relaxed)));
+            let dir = shadow_dir.join(name);
+
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok(LoadDir(dir)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+                Err(e) => return Err(Error::ShadowDir(e, dir)),
+            }
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for LoadDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// In some cases when a file has been set so that it's reloaded, it's actually not possible
+// to read from it directly so this code does some testing first to ensure we
+// can actually read from it (by using metadata which does a stat on the file).
+// If we can't read from it, we wait according to the retry policy and try again.
+// If the file was still locked at the last attempt that is reported separately as it's
+// usually caused by another process (linker, antivirus) holding on to the file.
+//
+pub(crate) fn try_copy(
+    src: &Path,
+    dest: &Path,
+    retry: &CopyRetry,
+    verify: CopyVerify,
+) -> Result<()> {
+    let mut last_error = None;
+    let mut mismatch = false;
+
+    for attempt in 0..retry.attempts {
+        if let Ok(file) = fs::metadata(src) {
+            if file.len() > 0 {
+                // don't give up on copy errors, library file might be locked by the compiler
+                match copy_file(src, dest) {
+                    Ok(bytes) => {
+                        if verify_copy(src, dest, &file, verify) {
+                            telemetry::copied(bytes);
+                            return Ok(());
+                        }
+                        mismatch = true;
+                    }
+                    // Only the last attempt is reported, a lock may follow a changing file
+                    Err(e) => {
+                        mismatch = false;
+                        last_error = Some(e);
+                    }
+                }
+            }
+        }
+
+        if attempt + 1 < retry.attempts {
+            thread::sleep(retry.delay(attempt));
+        }
+    }
+
+    match last_error {
+        _ if mismatch => Err(Error::CopyMismatch(src.to_path_buf(), dest.to_path_buf())),
+        Some(e) if is_locked(&e) => Err(Error::FileLocked(src.to_path_buf())),
+        Some(e) => Err(Error::Copy(e, src.to_path_buf(), dest.to_path_buf())),
+        None => Err(Error::CopyTimeOut(src.to_path_buf(), dest.to_path_buf())),
+    }
+}
+
+// The metadata of the source is read before the copy starts. If it's different afterwards the
+// file was written to while it was being copied and the copy can't be trusted.
+fn verify_copy(src: &Path, dest: &Path, before: &fs::Metadata, verify: CopyVerify) -> bool {
+    if verify == CopyVerify::None {
+        return true;
+    }
+
+    let (after, copy) = match (fs::metadata(src), fs::metadata(dest)) {
+        (Ok(after), Ok(copy)) => (after, copy),
+        _ => return false,
+    };
+
+    if after.len() != before.len()
+        || after.modified().ok() != before.modified().ok()
+        || copy.len() != before.len()
+    {
+        return false;
+    }
+
+    if verify == CopyVerify::Hash {
+        return match (hash::hash_file(src), hash::hash_file(dest)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        };
+    }
+
+    true
+}
+
+// Unsigned code is killed when it's loaded on Apple Silicon and copying drops the signature the
+// linker added, so sign the copy again with an ad-hoc signature.
+#[cfg(target_os = "macos")]
+pub(crate) fn codesign(path: &Path) -> Result<()> {
+    let output = std::process::Command::new("codesign")
+        .args(["--force", "--sign", "-"])
+        .arg(path)
+        .output()
+        .map_err(|e| Error::Codesign(path.to_path_buf(), e.to_string()))?;
+
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(Error::Codesign(
+            path.to_path_buf(),
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        )),
+    }
+}
+
+// Only macOS requires signed libraries
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn codesign(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+// Copies the debug info of the library next to its copy, which is in a directory of its own
+// for the load, named the way debuggers look for it: the PDB under the name recorded in the DLL
+// on Windows and the bundle named after the copy (<hash>_foo.dylib.dSYM) on macOS. Debug info
+// is only a help when debugging so failing to copy it doesn't fail the load.
+pub(crate) fn copy_debug_info(src: &Path, dest: &Path) {
+    let dsym = |path: &Path| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".dSYM");
+        PathBuf::from(name)
+    };
+
+    let pdb = match pdb_name(src) {
+        Some(name) => (src.with_file_name(&name), dest.with_file_name(&name)),
+        None => {
+            let src = src.with_extension("pdb");
+            let name = src.file_name().unwrap_or_default().to_owned();
+            (src, dest.with_file_name(name))
+        }
+    };
+    let bundle = (dsym(src), dsym(dest));
+
+    for (src, dest) in [pdb, bundle] {
+        if src.is_dir() {
+            let _ = copy_dir(&src, &dest);
+        } else if src.is_file() {
+            let _ = copy_file(&src, &dest);
+        }
+    }
+}
+
+// File name of the PDB in the CodeView record (RSDS, GUID, age, path) of a DLL, which may differ
+// from the name of the DLL. The path is the one on the machine that built it. The record is found
+// through the debug directory of the PE headers so only the headers and the record are read.
+#[cfg(windows)]
+pub(crate) fn pdb_name(dll: &Path) -> Option<OsString> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    const IMAGE_DEBUG_TYPE_CODEVIEW: u32 = 2;
+    const DEBUG_DIRECTORY_SIZE: usize = 28;
+    const SECTION_HEADER_SIZE: usize = 40;
+
+    let mut file = fs::File::open(dll).ok()?;
+    let mut read = |offset: u64, len: usize| -> Option<Vec<u8>> {
+        let mut buf = vec![0; len];
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        file.read_exact(&mut buf).ok()?;
+        Some(buf)
+    };
+    let u16_at =
+        |buf: &[u8], at: usize| Some(u16::from_le_bytes(buf.get(at..at + 2)?.try_into().ok()?));
+    let u32_at =
+        |buf: &[u8], at: usize| Some(u32::from_le_bytes(buf.get(at..at + 4)?.try_into().ok()?));
+
+    let pe = u64::from(u32_at(&read(0x3c, 4)?, 0)?);
+    let coff = read(pe, 24)?;
+    if coff.get(..4)? != b"PE\0\0" {
+        return None;
+    }
+    let sections = usize::from(u16_at(&coff, 6)?);
+    let optional_size = usize::from(u16_at(&coff, 20)?);
+    let optional = read(pe + 24, optional_size)?;
+
+    // The data directories follow the fields of the PE32 or PE32+ header, the debug directory
+    // is the 7th of them
+    let directories = match u16_at(&optional, 0)? {
+        0x10b => 96,
+        0x20b => 112,
+        _ => return None,
+    };
+    if u32_at(&optional, directories - 4)? < 7 {
+        return None;
+    }
+    let debug_rva = u64::from(u32_at(&optional, directories + 6 * 8)?);
+    let debug_size = u32_at(&optional, directories + 6 * 8 + 4)? as usize;
+
+    // The directory is given by its address when loaded, find it in the file through the
+    // section that contains it
+    let table = read(
+        pe + 24 + optional_size as u64,
+        sections * SECTION_HEADER_SIZE,
+    )?;
+    let debug_offset = table
+        .chunks_exact(SECTION_HEADER_SIZE)
+        .find_map(|section| {
+            let size = u32_at(section, 8)?.max(u32_at(section, 16)?);
+            let address = u64::from(u32_at(section, 12)?);
+            let raw = u64::from(u32_at(section, 20)?);
+            (debug_rva >= address && debug_rva - address < u64::from(size))
+                .then(|| raw + debug_rva - address)
+        })?;
+
+    let entries = read(debug_offset, debug_size.min(16 * DEBUG_DIRECTORY_SIZE))?;
+    let (record_size, record_offset) = entries
+        .chunks_exact(DEBUG_DIRECTORY_SIZE)
+        .filter(|entry| u32_at(entry, 12) == Some(IMAGE_DEBUG_TYPE_CODEVIEW))
+        .find_map(|entry| Some((u32_at(entry, 16)? as usize, u64::from(u32_at(entry, 24)?))))?;
+
+    let record = read(record_offset, record_size.min(4096))?;
+    if record.get(..4)? != b"RSDS" {
+        return None;
+    }
+    let path = record.get(24..)?;
+    let path = std::str::from_utf8(&path[..path.iter().position(|&b| b == 0)?]).ok()?;
+
+    path.rsplit(['\\', '/'])
+        .next()
+        .filter(|name| name.ends_with(".pdb"))
+        .map(OsString::from)
+}
+
+// Only DLLs have a CodeView record
+#[cfg(not(windows))]
+fn pdb_name(_dll: &Path) -> Option<OsString> {
+    None
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> io::Result<()> {
+    fs::create_dir_all(dest)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        let target = dest.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&path, &target)?;
+        } else {
+            copy_file(&path, &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+// std opens the source without FILE_SHARE_WRITE and FILE_SHARE_DELETE which fails while the
+// toolchain still has the file open. Allow sharing everything as we only read from it.
+#[cfg(windows)]
+fn copy_file(src: &Path, dest: &Path) -> io::Result<u64> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_SHARE_READ: u32 = 0x1;
+    const FILE_SHARE_WRITE: u32 = 0x2;
+    const FILE_SHARE_DELETE: u32 = 0x4;
+
+    let mut input = fs::OpenOptions::new()
+        .read(true)
+        .share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE)
+        .open(src)?;
+    let mut output = fs::File::create(dest)?;
+
+    io::copy(&mut input, &mut output)
+}
+
+#[cfg(not(windows))]
+fn copy_file(src: &Path, dest: &Path) -> io::Result<u64> {
+    fs::copy(src, dest)
+}
+
+// ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION, other permission errors are reported as is
+#[cfg(windows)]
+fn is_locked(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(32) | Some(33))
+}
+
+// Files aren't locked against copying on other platforms
+#[cfg(not(windows))]
+fn is_locked(_e: &io::Error) -> bool {
+    false
+}
@@ -0,0 +1,66 @@
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// Libraries that other libraries depend on, and the libraries that depend on them.
+#[derive(Default)]
+pub(crate) struct Dependencies {
+    // (provider, consumer)
+    edges: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Dependencies {
+    /// Forgets everything about the library.
+    pub(crate) fn remove(&mut self, path: &Path) {
+        self.edges.retain(|(p, c)| p != path && c != path);
+    }
+
+    pub(crate) fn add(&mut self, provider: &Path, consumer: &Path) -> Result<()> {
+        if provider == consumer || self.dependents(consumer).iter().any(|p| p == provider) {
+            return Err(Error::DependencyCycle(
+                provider.to_path_buf(),
+                consumer.to_path_buf(),
+            ));
+        }
+
+        if !self.edges.iter().any(|(p, c)| p == provider && c == consumer) {
+            self.edges
+                .push((provider.to_path_buf(), consumer.to_path_buf()));
+        }
+
+        Ok(())
+    }
+
+    /// All libraries that depend on ```provider```, directly or indirectly, in the order they
+    /// have to be loaded: every library comes after all the providers it uses.
+    pub(crate) fn dependents(&self, provider: &Path) -> Vec<PathBuf> {
+        // Collect everything reachable from the provider
+        let mut reachable: Vec<PathBuf> = Vec::new();
+        let mut queue = vec![provider.to_path_buf()];
+
+        while let Some(path) = queue.pop() {
+            for (_, consumer) in self.edges.iter().filter(|(p, _)| *p == path) {
+                if !reachable.contains(consumer) {
+                    reachable.push(consumer.clone());
+                    queue.push(consumer.clone());
+                }
+            }
+        }
+
+        // Then order them so that providers within the set come first
+        let mut ordered: Vec<PathBuf> = Vec::with_capacity(reachable.len());
+
+        while !reachable.is_empty() {
+            let ready = reachable.iter().position(|path| {
+                self.edges
+                    .iter()
+                    .all(|(p, c)| c != path || p == provider || !reachable.contains(p))
+            });
+
+            // Cycles are rejected when added so there is always one ready
+            let index = ready.unwrap_or(0);
+            ordered.push(reachable.remove(index));
+        }
+
+        ordered
+    }
+}
@@ -1,7 +1,10 @@
+use notify_debouncer_mini::notify;
 use std::error::Error as StdError;
 use std::fmt;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::FailPoint;
 
 /// Errors that can be return from various operatiors
 ///
@@ -13,8 +16,79 @@ pub enum Error {
     Copy(io::Error, PathBuf, PathBuf),
     /// Timeout of file copy happend.
     CopyTimeOut(PathBuf, PathBuf),
-    /// Failed to find library
-    Find(String),
+    /// File stayed locked by another process during all copy attempts
+    FileLocked(PathBuf),
+    /// The copy never matched the source, it was likely being rebuilt during the copy
+    CopyMismatch(PathBuf, PathBuf),
+    /// Failed to find library, with the paths that were checked in the order they were checked
+    Find(String, Vec<PathBuf>),
+    /// Loading or initializing the library didn't finish within the configured time
+    InitTimeout(PathBuf),
+    /// Loading the library in the pre-flight helper process failed
+    Preflight(PathBuf, String),
+    /// Reading or writing the cluster directory failed
+    Cluster(PathBuf, String),
+    /// A failure was injected with ```inject_failure``` at the given phase
+    Injected(PathBuf, FailPoint),
+    /// The library doesn't export a required symbol
+    MissingSymbol(PathBuf, String),
+    /// A function in a plugin panicked, with the name of the function and the panic message
+    Panic(String, String),
+    /// Making the second library depend on the first would create a dependency cycle
+    DependencyCycle(PathBuf, PathBuf),
+    /// Unable to create the shadow directory in the given directory
+    ShadowDir(io::Error, PathBuf),
+    /// Invalid glob pattern
+    Pattern(glob::PatternError),
+    /// Failed to create or configure the file watcher
+    Watcher(notify::Error),
+    /// Code signing the shadow copy failed, with the output of ```codesign```
+    Codesign(PathBuf, String),
+    /// The reload of a transactional group was aborted because the given member failed to load
+    GroupAborted(String, PathBuf),
+    /// The new version of the library failed its health check, with the reason
+    HealthCheck(PathBuf, String),
+    /// Reading the exported symbols of the library failed
+    Exports(PathBuf, String),
+    /// The metadata of the library doesn't match the ABI tag set with ```set_abi_tag```
+    Incompatible(PathBuf, String),
+    /// The fingerprint of the library doesn't match the compiler and interface of the application
+    Fingerprint(PathBuf, String),
+    /// The detached signature of the library is missing or doesn't verify with the public key
+    SignatureInvalid(PathBuf, String),
+    /// The library isn't listed in the checksum manifest or its digest doesn't match
+    ChecksumMismatch(PathBuf, String),
+    /// A trampoline couldn't be created for the library, with the reason
+    Trampoline(PathBuf, String),
+    /// Saved plugin state couldn't be serialized, deserialized or migrated
+    State(String),
+    /// Running cargo failed or its output didn't contain what was asked for, with the reason
+    Cargo(String),
+    /// More than one file matches the library name, with the paths of all of them
+    Ambiguous(String, Vec<PathBuf>),
+}
+
+impl Error {
+    // Path of the library the error is about, for errors that include it
+    pub(crate) fn library_path(&self) -> Option<&Path> {
+        match *self {
+            Error::Copy(_, ref path, _)
+            | Error::CopyTimeOut(ref path, _)
+            | Error::CopyMismatch(ref path, _)
+            | Error::FileLocked(ref path)
+            | Error::InitTimeout(ref path)
+            | Error::Preflight(ref path, _)
+            | Error::Injected(ref path, _)
+            | Error::MissingSymbol(ref path, _)
+            | Error::Codesign(ref path, _)
+            | Error::HealthCheck(ref path, _)
+            | Error::Incompatible(ref path, _)
+            | Error::Fingerprint(ref path, _)
+            | Error::SignatureInvalid(ref path, _)
+            | Error::ChecksumMismatch(ref path, _) => Some(path),
+            _ => None,
+        }
+    }
 }
 
 impl StdError for Error {
@@ -23,7 +97,31 @@ impl StdError for Error {
             Error::Load(_) => "Unable to load library",
             Error::Copy(_, _, _) => "Unable to copy",
             Error::CopyTimeOut(_, _) => "Unable to copy due to time out",
-            Error::Find(_) => "Unable to find",
+            Error::FileLocked(_) => "Unable to copy locked file",
+            Error::CopyMismatch(_, _) => "Copy doesn't match source",
+            Error::Find(_, _) => "Unable to find",
+            Error::InitTimeout(_) => "Timed out initializing",
+            Error::Preflight(_, _) => "Pre-flight load failed for",
+            Error::Cluster(_, _) => "Cluster directory failure in",
+            Error::Injected(_, _) => "Injected failure for",
+            Error::MissingSymbol(_, _) => "Missing symbol in",
+            Error::Panic(_, _) => "Plugin panicked in",
+            Error::DependencyCycle(_, _) => "Dependency cycle between",
+            Error::ShadowDir(_, _) => "Unable to create shadow directory in",
+            Error::Pattern(_) => "Invalid pattern",
+            Error::Watcher(_) => "Unable to watch",
+            Error::Codesign(_, _) => "Unable to code sign",
+            Error::GroupAborted(_, _) => "Reload aborted for group",
+            Error::HealthCheck(_, _) => "Health check failed for",
+            Error::Exports(_, _) => "Unable to read the exports of",
+            Error::Incompatible(_, _) => "Incompatible library",
+            Error::Fingerprint(_, _) => "Fingerprint mismatch for",
+            Error::SignatureInvalid(_, _) => "Invalid signature for",
+            Error::ChecksumMismatch(_, _) => "Checksum mismatch for",
+            Error::Trampoline(_, _) => "Unable to create trampoline for",
+            Error::State(_) => "Invalid plugin state:",
+            Error::Cargo(_) => "Cargo failed:",
+            Error::Ambiguous(_, _) => "Several files match",
         }
     }
 
@@ -33,7 +131,31 @@ impl StdError for Error {
             Error::Load(ref e) => e.cause(),
             Error::Copy(ref e, _, _) => e.cause(),
             Error::CopyTimeOut(_, _) => None,
-            Error::Find(_) => None,
+            Error::FileLocked(_) => None,
+            Error::CopyMismatch(_, _) => None,
+            Error::Find(_, _) => None,
+            Error::InitTimeout(_) => None,
+            Error::Preflight(_, _) => None,
+            Error::Cluster(_, _) => None,
+            Error::Injected(_, _) => None,
+            Error::MissingSymbol(_, _) => None,
+            Error::Panic(_, _) => None,
+            Error::DependencyCycle(_, _) => None,
+            Error::ShadowDir(ref e, _) => Some(e),
+            Error::Pattern(ref e) => Some(e),
+            Error::Watcher(ref e) => Some(e),
+            Error::Codesign(_, _) => None,
+            Error::GroupAborted(_, _) => None,
+            Error::HealthCheck(_, _) => None,
+            Error::Exports(_, _) => None,
+            Error::Incompatible(_, _) => None,
+            Error::Fingerprint(_, _) => None,
+            Error::SignatureInvalid(_, _) => None,
+            Error::ChecksumMismatch(_, _) => None,
+            Error::Trampoline(_, _) => None,
+            Error::State(_) => None,
+            Error::Cargo(_) => None,
+            Error::Ambiguous(_, _) => None,
         }
     }
 }
@@ -58,10 +180,63 @@ impl fmt::Display for Error {
                 e.description(),
                 self.cause()
             ),
-            Error::CopyTimeOut(ref src, ref dest) => {
+            Error::CopyTimeOut(ref src, ref dest) | Error::CopyMismatch(ref src, ref dest) => {
                 write!(fmt, "{} {:?} to {:?}", self.description(), src, dest)
             }
-            Error::Find(ref name) => write!(fmt, "{} {}", self.description(), name),
+            Error::DependencyCycle(ref provider, ref consumer) => {
+                write!(fmt, "{} {:?} and {:?}", self.description(), provider, consumer)
+            }
+            Error::FileLocked(ref path) | Error::InitTimeout(ref path) => {
+                write!(fmt, "{} {:?}", self.description(), path)
+            }
+            Error::Find(ref name, ref searched) => {
+                write!(fmt, "{} {}", self.description(), name)?;
+                if !searched.is_empty() {
+                    write!(fmt, ", searched {:?}", searched)?;
+                }
+                Ok(())
+            }
+            Error::Ambiguous(ref name, ref paths) => {
+                write!(fmt, "{} {}: {:?}", self.description(), name, paths)
+            }
+            Error::State(ref reason) | Error::Cargo(ref reason) => {
+                write!(fmt, "{} {}", self.description(), reason)
+            }
+            Error::Preflight(ref path, ref reason)
+            | Error::Cluster(ref path, ref reason)
+            | Error::Codesign(ref path, ref reason)
+            | Error::HealthCheck(ref path, ref reason)
+            | Error::Exports(ref path, ref reason)
+            | Error::Incompatible(ref path, ref reason)
+            | Error::Fingerprint(ref path, ref reason)
+            | Error::SignatureInvalid(ref path, ref reason)
+            | Error::ChecksumMismatch(ref path, ref reason)
+            | Error::Trampoline(ref path, ref reason) => {
+                write!(fmt, "{} {:?}: {}", self.description(), path, reason)
+            }
+            Error::Injected(ref path, at) => {
+                write!(fmt, "{} {:?} at {:?}", self.description(), path, at)
+            }
+            Error::MissingSymbol(ref path, ref symbol) => {
+                write!(fmt, "{} {:?}: {}", self.description(), path, symbol)
+            }
+            Error::Panic(ref name, ref message) => {
+                write!(fmt, "{} {}: {}", self.description(), name, message)
+            }
+            Error::ShadowDir(ref e, ref dir) => {
+                write!(fmt, "{} {:?}: {}", self.description(), dir, e)
+            }
+            Error::GroupAborted(ref group, ref path) => {
+                write!(
+                    fmt,
+                    "{} {}, {:?} failed to load",
+                    self.description(),
+                    group,
+                    path
+                )
+            }
+            Error::Pattern(ref e) => write!(fmt, "{} {}", self.description(), e),
+            Error::Watcher(ref e) => write!(fmt, "{} {}", self.description(), e),
         }
     }
 }
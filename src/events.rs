@@ -0,0 +1,173 @@
+use crate::{Error, Lib, UpdateState};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Outcome of a reload reported by [poll_events](struct.DynamicReload.html#method.poll_events).
+#[derive(Debug)]
+pub enum ReloadPhase {
+    /// The new version of the library has been loaded.
+    Reloaded,
+    /// Loading the new version failed. The library is no longer tracked unless it's rolled
+    /// back, which is reported as ```RolledBack``` right after.
+    Failed(Error),
+    /// The file of the library has been deleted, see
+    /// [UpdateState::Removed](enum.UpdateState.html#variant.Removed).
+    Removed,
+    /// A new library has been loaded from a directory, see
+    /// [add_directory](struct.DynamicReload.html#method.add_directory).
+    Added,
+    /// The version that was loaded before a failed reload has been loaded again, see
+    /// [set_rollback](struct.DynamicReload.html#method.set_rollback).
+    RolledBack,
+    /// The new version failed its health check and has been dropped, the old version is used
+    /// again, see [set_health_check](struct.DynamicReload.html#method.set_health_check).
+    HealthCheckFailed(Error),
+    /// The library has failed to reload too many times in a row and is no longer retried, see
+    /// [set_reload_retry](struct.DynamicReload.html#method.set_reload_retry).
+    Blacklisted,
+    /// The library has been replaced or removed but is still referenced by the application the
+    /// given number of times, see
+    /// [set_external_refs](struct.DynamicReload.html#method.set_external_refs).
+    StillReferenced(usize),
+}
+
+/// A library that has been reloaded, failed to, removed or added during
+/// [poll_events](struct.DynamicReload.html#method.poll_events).
+pub struct ReloadEvent {
+    /// What happened.
+    pub phase: ReloadPhase,
+    /// Path of the library, the original location when loaded through a shadow directory. Empty
+    /// if a library from a directory failed to load with an error that doesn't include its path.
+    pub path: PathBuf,
+    /// The version that was loaded before, None for added and rolled back libraries, for
+    /// libraries that are loaded again after a failed reload and for reloads with the
+    /// ```no-timestamps``` feature, where the previous version has to be unloaded before the new
+    /// copy with the same name can be loaded. It stays loaded for as long as it's kept around,
+    /// so drop it once any state has been moved over to the new version. For
+    /// blacklisted libraries it's the version in use, if any, and for still referenced libraries
+    /// the version that is referenced.
+    pub old: Option<Arc<Lib>>,
+    /// The new version, None if the reload failed or the library was removed.
+    pub new: Option<Arc<Lib>>,
+}
+
+// Pairs the Before/After callbacks of update into events
+#[derive(Default)]
+pub(crate) struct Collector {
+    // Path of each library being reloaded and the previous version, which isn't kept with the
+    // no-timestamps feature for the same reason as in DynamicReload::unload_lib
+    pending: Vec<(PathBuf, Option<Arc<Lib>>)>,
+    // Set by Before until the next callback, a failure without a library right after it is the
+    // reload of that library
+    reloading: Option<PathBuf>,
+    pub(crate) events: Vec<ReloadEvent>,
+}
+
+impl Collector {
+    pub(crate) fn update_call(&mut self, state: UpdateState, lib: Option<&Arc<Lib>>) {
+        let reloading = self.reloading.take();
+
+        match state {
+            UpdateState::Before => {
+                if let Some(lib) = lib {
+                    let old = cfg!(not(feature = "no-timestamps")).then(|| lib.clone());
+                    self.pending.push((lib.source_path().to_path_buf(), old));
+                    self.reloading = Some(lib.source_path().to_path_buf());
+                }
+            }
+            UpdateState::After => {
+                let new = match lib {
+                    Some(lib) => lib,
+                    None => return,
+                };
+
+                let path = new.source_path().to_path_buf();
+                let index = self.pending.iter().rposition(|(p, _)| *p == path);
+
+                // Retried libraries were unloaded by an earlier update
+                self.events.push(ReloadEvent {
+                    phase: ReloadPhase::Reloaded,
+                    path,
+                    old: index.and_then(|index| self.pending.remove(index).1),
+                    new: Some(new.clone()),
+                });
+            }
+            UpdateState::Removed => {
+                if let Some(lib) = lib {
+                    self.events.push(ReloadEvent {
+                        phase: ReloadPhase::Removed,
+                        path: lib.source_path().to_path_buf(),
+                        old: Some(lib.clone()),
+                        new: None,
+                    });
+                }
+            }
+            UpdateState::Added | UpdateState::RolledBack => {
+                let phase = match state {
+                    UpdateState::Added => ReloadPhase::Added,
+                    _ => ReloadPhase::RolledBack,
+                };
+
+                if let Some(lib) = lib {
+                    self.events.push(ReloadEvent {
+                        phase,
+                        path: lib.source_path().to_path_buf(),
+                        old: None,
+                        new: Some(lib.clone()),
+                    });
+                }
+            }
+            UpdateState::ReloadFailed(e) => {
+                let path = match lib {
+                    Some(lib) => Some(lib.source_path().to_path_buf()),
+                    None => reloading,
+                };
+                let path = path.or_else(|| e.library_path().map(Path::to_path_buf));
+                self.failed(ReloadPhase::Failed(e), path, lib)
+            }
+            UpdateState::HealthCheckFailed(e) => {
+                let path = lib.map(|lib| lib.source_path().to_path_buf());
+                self.failed(ReloadPhase::HealthCheckFailed(e), path, lib)
+            }
+            UpdateState::StillReferenced(count) => {
+                if let Some(lib) = lib {
+                    self.events.push(ReloadEvent {
+                        phase: ReloadPhase::StillReferenced(count),
+                        path: lib.source_path().to_path_buf(),
+                        old: Some(lib.clone()),
+                        new: None,
+                    });
+                }
+            }
+            UpdateState::Blacklisted(path) => self.events.push(ReloadEvent {
+                phase: ReloadPhase::Blacklisted,
+                path,
+                old: lib.cloned(),
+                new: None,
+            }),
+            // The reload of the built library is reported as usual
+            UpdateState::BuildStarted
+            | UpdateState::BuildFinished(_)
+            | UpdateState::BuildFailed(_) => (),
+        }
+    }
+
+    // A library from a directory that failed to load has no path unless the error includes it
+    fn failed(&mut self, phase: ReloadPhase, path: Option<PathBuf>, lib: Option<&Arc<Lib>>) {
+        // Not pending if it failed before it was unloaded (SwapOrder::LoadFirst) or if it's a new
+        // library from a directory
+        let pending = path.as_ref().and_then(|path| {
+            let index = self.pending.iter().rposition(|(p, _)| p == path);
+            index.map(|index| self.pending.remove(index).1)
+        });
+
+        self.events.push(ReloadEvent {
+            phase,
+            path: path.unwrap_or_default(),
+            old: lib.cloned().or(pending.flatten()),
+            new: None,
+        });
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{Error, Result};
+use object::{BinaryFormat, Object};
+use std::{fs, path::Path};
+
+// Reads the names of the dynamic symbols exported by the library file
+pub(crate) fn read_exports(path: &Path) -> Result<Vec<String>> {
+    let error = |e: &dyn std::fmt::Display| Error::Exports(path.to_path_buf(), e.to_string());
+
+    let data = fs::read(path).map_err(|e| error(&e))?;
+    let file = object::File::parse(&*data).map_err(|e| error(&e))?;
+    let exports = file.exports().map_err(|e| error(&e))?;
+
+    // Mach-O symbols have a leading underscore that isn't part of the name used to look them up
+    let prefix = match file.format() {
+        BinaryFormat::MachO => "_",
+        _ => "",
+    };
+
+    let mut names: Vec<String> = exports
+        .iter()
+        .map(|export| String::from_utf8_lossy(export.name()))
+        .map(|name| name.strip_prefix(prefix).unwrap_or(&name).to_owned())
+        .collect();
+
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
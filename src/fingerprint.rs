@@ -0,0 +1,76 @@
+use crate::{Error, Result};
+use libloading::Library;
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    path::Path,
+};
+
+/// Symbol exported by plugins built with [plugin_fingerprint], with the signature
+/// ```extern "C" fn() -> *const c_char```. It returns the version of rustc the plugin was built
+/// with and the interface it implements separated by a newline.
+pub const FINGERPRINT_SYMBOL: &str = "dr_fingerprint";
+
+/// Version of rustc (```rustc --version```) dynamic_reload was built with. A plugin and the
+/// application have to be built with the same compiler as Rust has no stable ABI.
+pub const RUSTC_VERSION: &str = env!("DR_RUSTC_VERSION");
+
+/// Exports the fingerprint of a plugin, checked by the application with
+/// [set_fingerprint](struct.DynamicReload.html#method.set_fingerprint). ```interface``` names
+/// (or hashes) the interface shared between the plugin and the application and has to be
+/// changed when the interface does.
+///
+/// ```ignore
+/// dynamic_reload::plugin_fingerprint!("renderer-api-3");
+/// ```
+#[macro_export]
+macro_rules! plugin_fingerprint {
+    ($interface:expr) => {
+        #[no_mangle]
+        pub extern "C" fn dr_fingerprint() -> *const ::std::os::raw::c_char {
+            static FINGERPRINT: ::std::sync::OnceLock<::std::ffi::CString> =
+                ::std::sync::OnceLock::new();
+            FINGERPRINT
+                .get_or_init(|| $crate::fingerprint($interface))
+                .as_ptr()
+        }
+    };
+}
+
+/// Fingerprint returned by ```dr_fingerprint``` of plugins implementing ```interface```.
+pub fn fingerprint(interface: &str) -> CString {
+    CString::new(format!("{}\n{}", RUSTC_VERSION, interface)).unwrap_or_default()
+}
+
+/// Fails if the library doesn't export a fingerprint or it doesn't match ```interface``` and the
+/// version of rustc dynamic_reload was built with.
+pub(crate) unsafe fn check(lib: &Library, path: &Path, interface: &str) -> Result<()> {
+    let mismatch = |reason: String| Err(Error::Fingerprint(path.to_path_buf(), reason));
+
+    let text =
+        match lib.get::<unsafe extern "C" fn() -> *const c_char>(FINGERPRINT_SYMBOL.as_bytes()) {
+            Ok(fingerprint) => fingerprint(),
+            Err(_) => return mismatch(format!("{} isn't exported", FINGERPRINT_SYMBOL)),
+        };
+
+    if text.is_null() {
+        return mismatch(format!("{} returned null", FINGERPRINT_SYMBOL));
+    }
+
+    let text = CStr::from_ptr(text).to_string_lossy();
+    let (rustc, lib_interface) = text.split_once('\n').unwrap_or((&text, ""));
+
+    if rustc != RUSTC_VERSION {
+        mismatch(format!(
+            "built with {:?}, expected {:?}",
+            rustc, RUSTC_VERSION
+        ))
+    } else if lib_interface != interface {
+        mismatch(format!(
+            "implements interface {:?}, expected {:?}",
+            lib_interface, interface
+        ))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use crate::Lib;
+use std::{ops::Deref, sync::atomic::Ordering};
+
+/// Marks a library as in use while it's alive, created with [Lib::use_guard].
+///
+/// A library with guards isn't swapped out when its file changes. The reload is put off and
+/// done by the first [update](struct.DynamicReload.html#method.update) after the last guard
+/// has been dropped. Guards can be taken on any thread, but a guard taken while a reload is
+/// already running doesn't stop it.
+pub struct LibGuard<'a> {
+    lib: &'a Lib,
+}
+
+impl<'a> LibGuard<'a> {
+    pub(crate) fn new(lib: &'a Lib) -> LibGuard<'a> {
+        lib.users.fetch_add(1, Ordering::AcqRel);
+        LibGuard { lib }
+    }
+}
+
+impl Deref for LibGuard<'_> {
+    type Target = Lib;
+
+    fn deref(&self) -> &Lib {
+        self.lib
+    }
+}
+
+impl Drop for LibGuard<'_> {
+    fn drop(&mut self) {
+        self.lib.users.fetch_sub(1, Ordering::AcqRel);
+    }
+}
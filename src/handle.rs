@@ -0,0 +1,72 @@
+use crate::{Lib, PlatformName, Result};
+use std::{
+    path::PathBuf,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+};
+
+/// Commands sent from a [DynamicReloadHandle], applied by
+/// [update](struct.DynamicReload.html#method.update).
+pub(crate) enum Command {
+    Add(String, PlatformName, Sender<Result<Arc<Lib>>>),
+    Remove(PathBuf),
+    Reload(PathBuf),
+    Pause(bool),
+}
+
+/// Controls a [DynamicReload](struct.DynamicReload.html) from other threads, created with
+/// [handle](struct.DynamicReload.html#method.handle).
+///
+/// The handle is cheap to clone and only queues commands. They are applied by the thread that
+/// owns the DynamicReload the next time it calls [update](struct.DynamicReload.html#method.update)
+/// (or any of the functions built on top of it). Functions returning a bool return false if the
+/// DynamicReload has been dropped.
+#[derive(Clone)]
+pub struct DynamicReloadHandle {
+    tx: Sender<Command>,
+}
+
+impl DynamicReloadHandle {
+    pub(crate) fn new(tx: Sender<Command>) -> DynamicReloadHandle {
+        DynamicReloadHandle { tx }
+    }
+
+    /// Requests a library to be loaded, see
+    /// [add_library](struct.DynamicReload.html#method.add_library). The result is delivered on
+    /// the returned receiver once the library has been loaded.
+    pub fn add_library(&self, name: &str, name_format: PlatformName) -> Receiver<Result<Arc<Lib>>> {
+        let (tx, rx) = channel();
+        // If the DynamicReload is gone the sender is dropped and the receiver reports it
+        let _ = self.tx.send(Command::Add(name.to_owned(), name_format, tx));
+        rx
+    }
+
+    /// Requests a library to be removed, see
+    /// [remove_library_with](struct.DynamicReload.html#method.remove_library_with). The update
+    /// callback is called with ```Before``` when it's removed.
+    pub fn remove_library(&self, lib: &Lib) -> bool {
+        self.send(Command::Remove(lib.source_path().to_path_buf()))
+    }
+
+    /// Requests a library to be reloaded, see
+    /// [reload_library](struct.DynamicReload.html#method.reload_library).
+    pub fn reload_library(&self, lib: &Lib) -> bool {
+        self.send(Command::Reload(lib.source_path().to_path_buf()))
+    }
+
+    /// Requests reloading to be paused, see [pause](struct.DynamicReload.html#method.pause).
+    pub fn pause(&self) -> bool {
+        self.send(Command::Pause(true))
+    }
+
+    /// Requests reloading to be resumed, see [resume](struct.DynamicReload.html#method.resume).
+    pub fn resume(&self) -> bool {
+        self.send(Command::Pause(false))
+    }
+
+    fn send(&self, command: Command) -> bool {
+        self.tx.send(command).is_ok()
+    }
+}
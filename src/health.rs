@@ -0,0 +1,40 @@
+use crate::{Error, Lib, Result};
+
+/// Symbol called by [HealthCheck::Symbol], with the signature ```extern "C" fn() -> bool```.
+pub const HEALTH_CHECK_SYMBOL: &str = "dr_health_check";
+
+type CheckFn = dyn Fn(&Lib) -> std::result::Result<(), String> + Send + Sync;
+
+/// Checks a new version of a library after it has been loaded during a reload, see
+/// [set_health_check](struct.DynamicReload.html#method.set_health_check).
+///
+/// A version that fails the check is dropped and the previous version stays in use, which is
+/// reported as ```UpdateState::HealthCheckFailed``` with ```Error::HealthCheck```.
+pub enum HealthCheck {
+    /// Calls ```dr_health_check``` exported by the library, which returns false if the library
+    /// isn't working. Libraries that don't export it pass.
+    Symbol,
+    /// Calls the function with the new version, returning why it isn't working on failure.
+    /// Look at the ```original_path``` or the metadata of the library to only check some of
+    /// them.
+    Custom(Box<CheckFn>),
+}
+
+impl HealthCheck {
+    pub(crate) unsafe fn run(&self, lib: &Lib) -> Result<()> {
+        let result = match self {
+            HealthCheck::Symbol => {
+                match lib
+                    .lib
+                    .get::<unsafe extern "C" fn() -> bool>(HEALTH_CHECK_SYMBOL.as_bytes())
+                {
+                    Ok(check) if !check() => Err(format!("{} returned false", HEALTH_CHECK_SYMBOL)),
+                    _ => Ok(()),
+                }
+            }
+            HealthCheck::Custom(check) => check(lib),
+        };
+
+        result.map_err(|reason| Error::HealthCheck(lib.source_path().to_path_buf(), reason))
+    }
+}
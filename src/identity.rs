@@ -0,0 +1,90 @@
+use std::{fs, path::Path, time::SystemTime};
+
+/// Identifies a file on disk at a given point in time.
+///
+/// On *nix this is the device and inode of the file together with its size and modification
+/// time. Windows doesn't expose a stable file id through std so only size and modification time
+/// are used there, which means a replaced file is reported as [FileChange::Modified].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileIdentity {
+    id: Option<(u64, u64)>,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/// How a file has changed compared to a previous [FileIdentity].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileChange {
+    /// Same file but the contents (size or modification time) has changed.
+    Modified,
+    /// The file has been replaced by a new file (for example by renaming another file over it).
+    Replaced,
+    /// Only metadata such as permissions has changed, size and modification time are the same.
+    /// A rebuild of the same size within one tick of the file system clock can look like this
+    /// too.
+    Touched,
+}
+
+impl FileIdentity {
+    /// Reads the identity of the file at ```path```. Returns None if the file can't be accessed.
+    pub fn from_path(path: &Path) -> Option<FileIdentity> {
+        let md = fs::metadata(path).ok()?;
+
+        Some(FileIdentity {
+            id: Self::file_id(&md),
+            len: md.len(),
+            modified: md.modified().ok(),
+        })
+    }
+
+    /// Size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if the file was empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Last modification time of the file, if supported by the platform.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Returns false if ```other``` is known to be another file. Without file ids (Windows)
+    /// files can't be told apart so this is always true.
+    pub(crate) fn is_same_file(&self, other: &FileIdentity) -> bool {
+        match (self.id, other.id) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        }
+    }
+
+    /// Classifies the change from ```self``` to ```current```.
+    ///
+    /// Without a file id there is no way to tell a touch from a copy that kept the modification
+    /// time so that is always reported as [FileChange::Modified].
+    pub fn classify(&self, current: &FileIdentity) -> FileChange {
+        match (self.id, current.id) {
+            (Some(old), Some(new)) if old != new => FileChange::Replaced,
+            (Some(_), Some(_))
+                if self.len == current.len && self.modified == current.modified =>
+            {
+                FileChange::Touched
+            }
+            _ => FileChange::Modified,
+        }
+    }
+
+    #[cfg(unix)]
+    fn file_id(md: &fs::Metadata) -> Option<(u64, u64)> {
+        use std::os::unix::fs::MetadataExt;
+        Some((md.dev(), md.ino()))
+    }
+
+    #[cfg(not(unix))]
+    fn file_id(_md: &fs::Metadata) -> Option<(u64, u64)> {
+        None
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{Error, Result};
+use libloading::Library;
+use std::{
+    ffi::c_void,
+    path::{Path, PathBuf},
+    sync::mpsc::channel,
+    thread,
+    time::Duration,
+};
+
+/// Called with the pointer set with
+/// [set_host_api](struct.DynamicReload.html#method.set_host_api) each time a library is loaded,
+/// as ```extern "C" fn(host_api: *const c_void)```.
+pub const HOST_API_INIT: &str = "dr_plugin_init";
+
+// The pointer set with set_host_api, which the application guarantees can be used from any
+// thread
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct HostApi(pub(crate) *const c_void);
+
+unsafe impl Send for HostApi {}
+unsafe impl Sync for HostApi {}
+
+/// How a library is opened, taken from its [LoadOptions](crate::LoadOptions).
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(not(unix), allow(dead_code))]
+pub(crate) struct OpenFlags {
+    pub(crate) global: bool,
+    pub(crate) bind_now: bool,
+    pub(crate) raw: Option<u32>,
+    // Where the DLLs the library depends on are found, Windows only
+    #[cfg_attr(unix, allow(dead_code))]
+    pub(crate) dependency_dir: Option<PathBuf>,
+}
+
+// Plugins commonly export the same names (plugin_create etc) so unless asked for, the symbols
+// of a library are kept out of the global namespace and are only reachable through its own
+// handle. RTLD_LOCAL is what libloading opens libraries with anyway, only RTLD_GLOBAL changes
+// how symbols are resolved.
+#[cfg(unix)]
+unsafe fn open_lib(path: &Path, flags: OpenFlags) -> Result<Library> {
+    use libloading::os::unix::{
+        Library as UnixLibrary, RTLD_GLOBAL, RTLD_LAZY, RTLD_LOCAL, RTLD_NOW,
+    };
+
+    let flags = match flags.raw {
+        Some(raw) => raw as std::os::raw::c_int,
+        None => {
+            let binding = if flags.bind_now { RTLD_NOW } else { RTLD_LAZY };
+            let scope = if flags.global {
+                RTLD_GLOBAL
+            } else {
+                RTLD_LOCAL
+            };
+            binding | scope
+        }
+    };
+
+    UnixLibrary::open(Some(path), flags)
+        .map(Library::from)
+        .map_err(Error::Load)
+}
+
+// Every DLL has its own namespace on Windows and imports are always resolved when loading
+#[cfg(not(unix))]
+unsafe fn open_lib(path: &Path, flags: OpenFlags) -> Result<Library> {
+    use crate::winpath;
+    use libloading::os::windows::{
+        Library as WindowsLibrary, LOAD_LIBRARY_SEARCH_DEFAULT_DIRS,
+        LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR,
+    };
+
+    let raw = match (flags.raw, flags.dependency_dir) {
+        (Some(raw), _) => Some(raw),
+        (None, Some(dir)) => {
+            // If the directory can't be added the dependencies aren't found and the load fails
+            add_dll_directory(&dir);
+            Some(LOAD_LIBRARY_SEARCH_DEFAULT_DIRS | LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR)
+        }
+        (None, None) => None,
+    };
+
+    // LoadLibrary is limited to MAX_PATH characters unless the path is in the extended form
+    let path = &winpath::extended(path);
+
+    match raw {
+        Some(raw) => WindowsLibrary::load_with_flags(path, raw)
+            .map(Library::from)
+            .map_err(Error::Load),
+        None => Library::new(path).map_err(Error::Load),
+    }
+}
+
+// Adds the directory to the ones searched with LOAD_LIBRARY_SEARCH_USER_DIRS (part of
+// LOAD_LIBRARY_SEARCH_DEFAULT_DIRS). Directories are added once and kept for the lifetime of the
+// process as libraries loaded from them may still be in use.
+#[cfg(not(unix))]
+fn add_dll_directory(dir: &Path) {
+    use std::{ffi::c_void, os::windows::ffi::OsStrExt, sync::Mutex};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AddDllDirectory(new_directory: *const u16) -> *mut c_void;
+    }
+
+    static ADDED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+    let mut added = ADDED.lock().unwrap_or_else(|e| e.into_inner());
+    if added.iter().any(|d| d == dir) {
+        return;
+    }
+
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+    if !unsafe { AddDllDirectory(wide.as_ptr()) }.is_null() {
+        added.push(dir.to_path_buf());
+    }
+}
+
+// Runs f, with a timeout on a separate thread so a library that hangs in its constructors or
+// init function can be reported instead of freezing the host. The thread is left behind in
+// that case as there is no way to stop it, and what it returns if it ever finishes is leaked
+// like the library the caller gave up on.
+fn run<F, R>(path: &Path, timeout: Option<Duration>, f: F) -> Result<R>
+where
+    F: FnOnce() -> Result<R> + Clone + Send + 'static,
+    R: Send + 'static,
+{
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return f(),
+    };
+
+    let (tx, rx) = channel();
+    let thread_f = f.clone();
+
+    let spawned = thread::Builder::new()
+        .name("dynamic_reload init".to_string())
+        .spawn(move || {
+            if let Err(late) = tx.send(thread_f()) {
+                std::mem::forget(late);
+            }
+        });
+
+    // Not being able to start a thread is no reason to fail the load
+    if spawned.is_err() {
+        return f();
+    }
+
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(_) => Err(Error::InitTimeout(PathBuf::from(path))),
+    }
+}
+
+// Loads the library, running its constructors but none of its exported init functions so it
+// can be checked before any of its code is called on purpose. If it times out the library is
+// leaked once the constructors are done.
+pub(crate) unsafe fn open(
+    path: &Path,
+    flags: OpenFlags,
+    timeout: Option<Duration>,
+) -> Result<Library> {
+    let thread_path = path.to_path_buf();
+    run(path, timeout, move || open_lib(&thread_path, flags))
+}
+
+// Calls the init symbol and the host API init (if set and exported by the library). The library
+// is leaked if they time out as they may still be running.
+pub(crate) unsafe fn init(
+    lib: Library,
+    path: &Path,
+    init_symbol: Option<&str>,
+    host_api: Option<HostApi>,
+    timeout: Option<Duration>,
+) -> Result<Library> {
+    let init = init_symbol
+        .and_then(|name| lib.get::<unsafe extern "C" fn()>(name.as_bytes()).ok())
+        .map(|init| *init);
+    let api_init = host_api.and_then(|api| {
+        let init = lib.get::<unsafe extern "C" fn(*const c_void)>(HOST_API_INIT.as_bytes());
+        init.ok().map(|init| (*init, api))
+    });
+
+    let result = run(path, timeout, move || {
+        if let Some(init) = init {
+            init();
+        }
+        if let Some((init, api)) = api_init {
+            init(api.0);
+        }
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => Ok(lib),
+        Err(e) => {
+            std::mem::forget(lib);
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// Phase of a reload at which an injected failure happens, see
+/// [inject_failure](struct.DynamicReload.html#method.inject_failure).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FailPoint {
+    /// Copying the library to the shadow directory.
+    Copy,
+    /// Validating the library before it's loaded (pre-flight).
+    Validate,
+    /// Opening the library (dlopen/LoadLibrary).
+    Load,
+    /// The health check of the new version, see
+    /// [set_health_check](struct.DynamicReload.html#method.set_health_check).
+    HealthCheck,
+}
+
+/// Failures waiting for the next reload of a library.
+#[derive(Default)]
+pub(crate) struct Injections {
+    pending: Vec<(PathBuf, FailPoint)>,
+}
+
+impl Injections {
+    pub(crate) fn add(&mut self, path: &Path, at: FailPoint) {
+        self.pending.retain(|(p, _)| p != path);
+        self.pending.push((path.to_path_buf(), at));
+    }
+
+    /// Removes and returns the failure for the library, it's only used for one reload.
+    pub(crate) fn take(&mut self, path: &Path) -> Option<FailPoint> {
+        let index = self.pending.iter().position(|(p, _)| p == path)?;
+        Some(self.pending.remove(index).1)
+    }
+}
+
+/// Fails if ```injected``` is the phase ```at```.
+pub(crate) fn check(injected: Option<FailPoint>, at: FailPoint, path: &Path) -> Result<()> {
+    if injected == Some(at) {
+        return Err(Error::Injected(path.to_path_buf(), at));
+    }
+
+    Ok(())
+}
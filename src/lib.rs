@@ -19,23 +19,159 @@
 //!
 
 use libloading::Library;
-use notify_debouncer_mini::{notify::*,new_debouncer,DebounceEventResult, Debouncer};
+use notify_debouncer_mini::notify::*;
 use std::{
-    env, fs,
+    any::Any,
+    env,
+    ffi::{c_void, OsStr, OsString},
+    fs, io,
     path::{Path, PathBuf},
     sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
         mpsc::{channel, Receiver, Sender},
-        Arc,
+        Arc, Mutex, RwLock,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
 };
 
 pub use libloading::Symbol;
-use tempfile::TempDir;
+pub use notify_debouncer_mini::notify;
+pub use notify_debouncer_mini::{DebouncedEvent, DebouncedEventKind};
+use tempfile::{NamedTempFile, TempDir, TempPath};
 
+mod app;
+mod atomic_symbol;
+mod background;
+mod broadcast;
+mod builder;
+mod cargo;
+#[cfg(feature = "checksums")]
+mod checksum;
+mod cluster;
+mod copy;
+mod deps;
 mod error;
+mod events;
+#[cfg(feature = "exports")]
+mod exports;
+mod fingerprint;
+mod guard;
+mod handle;
+mod hash;
+mod health;
+mod identity;
+mod init;
+mod inject;
+mod lifecycle;
+mod manager;
+mod metadata;
+mod naming;
+mod observer;
+mod options;
+mod preflight;
+mod query;
+mod rebuild;
+mod registry;
+mod resolve;
+mod retry;
+mod rewatch;
+mod safe;
+mod search;
+#[cfg(feature = "signatures")]
+mod signature;
+#[cfg(feature = "test-utils")]
+pub mod soak;
+#[cfg(feature = "serde")]
+mod state;
+#[cfg(feature = "stream")]
+mod stream;
+mod symbols;
+mod system;
+mod telemetry;
+#[cfg(feature = "trampoline")]
+mod trampoline;
+mod typed;
+mod typed_plugin;
+mod unload;
+mod userdata;
+mod watcher;
+mod winpath;
+pub use self::app::{
+    App, APP_AFTER_RELOAD, APP_BEFORE_RELOAD, APP_INIT, APP_SHUTDOWN, APP_UPDATE,
+};
+pub use self::atomic_symbol::AtomicSymbol;
+use self::atomic_symbol::Bindings;
+pub use self::background::{BackgroundReload, RELOAD_THREAD_NAME};
+use self::broadcast::Subscribers;
+pub use self::broadcast::{NotificationKind, ReloadNotification, SUBSCRIPTION_CAPACITY};
+pub use self::builder::DynamicReloadBuilder;
+pub use self::cargo::{cargo_target_dir, Profile};
+pub use self::cluster::{Cluster, ClusterRole};
+pub use self::copy::{CopyRetry, CopyVerify};
+use self::copy::LoadDir;
 pub use self::error::Error;
+use self::events::Collector;
+pub use self::events::{ReloadEvent, ReloadPhase};
+#[doc(hidden)]
+pub use self::fingerprint::fingerprint;
+pub use self::fingerprint::{FINGERPRINT_SYMBOL, RUSTC_VERSION};
+pub use self::guard::LibGuard;
+use self::handle::Command;
+pub use self::handle::DynamicReloadHandle;
+pub use self::health::{HealthCheck, HEALTH_CHECK_SYMBOL};
+pub use self::identity::{FileChange, FileIdentity};
+use self::init::HostApi;
+pub use self::init::HOST_API_INIT;
+pub use self::inject::FailPoint;
+use self::deps::Dependencies;
+use self::inject::Injections;
+pub use self::lifecycle::{LifecycleSymbols, AFTER_LOAD_SYMBOL, BEFORE_RELOAD_SYMBOL};
+pub use self::manager::PluginManager;
+#[doc(hidden)]
+pub use self::metadata::valid_metadata_value;
+pub use self::metadata::{Metadata, ABI_KEY, METADATA_EXTENSION, METADATA_SYMBOL};
+use self::observer::Observers;
+pub use self::observer::ReloadObserver;
+pub use self::options::LoadOptions;
+pub use self::preflight::{preflight_main, Preflight, PREFLIGHT_ENV};
+pub use self::query::{LibHandle, LibIter, GROUP_KEY};
+use self::query::FailedLib;
+use self::rebuild::Rebuilds;
+use self::registry::Registry;
+use self::resolve::Searched;
+pub use self::resolve::{Candidate, Rejected, ResolveReport, SelectionPolicy};
+pub use self::retry::ReloadRetry;
+use self::retry::Retries;
+use self::rewatch::WatchedDirs;
+#[doc(hidden)]
+pub use self::safe::panic_error;
+pub use self::safe::{Plugin, PluginApi, PluginEvent, SafeDynamicReload};
+pub use self::search::{SearchStrategy, SEARCH_PATH_ENV};
+#[cfg(feature = "signatures")]
+pub use self::signature::{VerifyingKey, SIGNATURE_EXTENSION};
+#[cfg(feature = "serde")]
+pub use self::state::{
+    load_state, save_state, PersistentState, FREE_STATE_SYMBOL, LOAD_STATE_SYMBOL,
+    SAVE_STATE_SYMBOL,
+};
+#[cfg(feature = "stream")]
+pub use self::stream::EventStream;
+pub use self::symbols::SymbolConflict;
+#[cfg(feature = "trampoline")]
+use self::trampoline::Trampoline;
+#[cfg(feature = "trampoline")]
+pub use self::trampoline::MAX_TRAMPOLINES;
+pub use self::typed::{LibApi, TypedLib};
+pub use self::typed_plugin::{PluginInstance, TypedPlugin, PLUGIN_CREATE, PLUGIN_DESTROY};
+use self::unload::Unloads;
+pub use self::unload::{DeferredUnload, ExternalRefs};
+use self::userdata::Userdata;
+pub use self::watcher::{
+    CloseWrite, DebounceStrategy, QuietPeriod, RawEvent, WatcherBackend, WatcherStatus,
+    DEFAULT_POLL_FALLBACK, WATCHER_THREAD_NAME,
+};
+use self::watcher::{FileWatcher, WatchMsg};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -48,27 +184,184 @@ pub struct Lib {
     /// Original location of the file. This is keep so dynamic_reload knows which file to look for
     /// updates in case the library has been changed.
     pub original_path: Option<PathBuf>,
+    /// Identity of the original file at the time it was loaded. Used to tell if the file has
+    /// been modified, replaced or only touched when it changes on disk.
+    pub file_identity: Option<FileIdentity>,
+    // Hash of the shadow copy
+    content_hash: Option<u64>,
+    // Read from the manifest next to the library when it was loaded
+    manifest: Metadata,
+    // Set by the host, carried over when the library is reloaded
+    metadata: RwLock<Metadata>,
+    // Shared with the previous version when the library is reloaded
+    userdata: RwLock<Userdata>,
+    // Number of LibGuards alive
+    users: AtomicUsize,
+    // Number of successful reloads before this version
+    generation: AtomicU64,
+    loaded_at: SystemTime,
+    // Directory of this load with the copies of the dependencies, declared after lib so it's
+    // removed once the library has been unloaded
+    load_dir: Option<Arc<LoadDir>>,
+}
+
+/// Information about the file of a library captured when it was loaded, see
+/// [info](struct.Lib.html#method.info).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LibInfo {
+    /// Size of the file in bytes.
+    pub file_size: Option<u64>,
+    /// Modification time of the file, if supported by the platform.
+    pub modified: Option<SystemTime>,
+    /// Hash of the content when loaded through a shadow directory.
+    pub content_hash: Option<u64>,
+    /// When this version of the library was loaded.
+    pub loaded_at: SystemTime,
+    /// Number of times the library has been reloaded, the same as
+    /// [generation](struct.Lib.html#method.generation).
+    pub reloads: u64,
+}
+
+/// A snapshot of the state of a loaded library, see
+/// [status](struct.DynamicReload.html#method.status).
+#[derive(Clone, Debug)]
+pub struct LibStatus {
+    /// Path the library was loaded from.
+    pub loaded_path: PathBuf,
+    /// Original location of the library when loaded through a shadow directory.
+    pub original_path: Option<PathBuf>,
+    /// Identity of the original file at the time it was loaded.
+    pub file_identity: Option<FileIdentity>,
+    /// All metadata of the library.
+    pub metadata: Metadata,
+    /// Number of successful reloads before this version was loaded.
+    pub generation: u64,
 }
 
 /// Contains information about loaded libraries and also tracks search paths and reloading events.
+///
+/// DynamicReload is ```Send``` and ```Sync```. It can be moved to another thread or shared
+/// behind an ```Arc<Mutex<DynamicReload>>``` or ```Arc<RwLock<DynamicReload>>```. Methods taking
+/// ```&self``` only read the state and can be called from several threads at once, everything
+/// that loads, reloads or unloads libraries takes ```&mut self``` and is serialized by the lock.
+/// A version of a library is only unloaded once its last ```Arc<Lib>``` is dropped, so threads
+/// holding on to one keep it loaded while a reload is done.
 pub struct DynamicReload {
-    libs: Vec<Arc<Lib>>,
-    watcher: Option<Debouncer<RecommendedWatcher>>,
+    libs: Registry,
+    watcher: Option<FileWatcher>,
+    // Set when new fails to create the watcher
+    watcher_error: Option<Error>,
+    watched_dirs: WatchedDirs,
     shadow_dir: Option<TempDir>,
     search_paths: Vec<PathBuf>,
-    watch_recv: Receiver<DebounceEventResult>,
+    search: Search,
+    match_policy: MatchPolicy,
+    selection_policy: SelectionPolicy,
+    swap_order: SwapOrder,
+    // Groups that are reloaded all-or-nothing
+    transactional: Vec<String>,
+    queue_reloads: bool,
+    // Changed files waiting for commit_pending
+    queued: Vec<PathBuf>,
+    // Only used through &mut, the Mutex is there to make DynamicReload Sync
+    watch_recv: Mutex<Receiver<WatchMsg>>,
+    raw_events: Option<Sender<RawEvent>>,
+    commands: Mutex<Receiver<Command>>,
+    command_tx: Sender<Command>,
+    paused: bool,
+    debounce_duration: Duration,
+    debounce_strategy: Option<Box<dyn DebounceStrategy>>,
+    missing_paths: Vec<PathBuf>,
+    // Changed libraries waiting for their guards to be dropped
+    guarded: Vec<PathBuf>,
+    // When a missing file was first seen missing, and the ones reported as removed
+    missing_since: Vec<(PathBuf, Instant)>,
+    removed: Vec<PathBuf>,
+    recursive_paths: Vec<PathBuf>,
+    loader_paths: bool,
+    // Reads the variables of the dynamic linker, replaced by tests so they don't have to change
+    // the environment of the whole process
+    env_lookup: fn(&str) -> Option<OsString>,
+    follow_symlinks: bool,
+    // Libraries loaded through a symbolic link with the file the link pointed to when watched
+    link_targets: Vec<(PathBuf, PathBuf)>,
+    cargo_profile: Option<Profile>,
+    rebuilds: Rebuilds,
+    // Directories where all libraries are loaded, with the file name filter
+    directories: Vec<(PathBuf, Option<glob::Pattern>)>,
+    // Options of the libraries added with options, by the path they are loaded from
+    options: Vec<(PathBuf, LoadOptions)>,
+    // Changes waiting for the debounce of LoadOptions: (path, last change, debounce)
+    delayed: Vec<(PathBuf, Instant, Duration)>,
+    copy_retry: CopyRetry,
+    copy_verify: CopyVerify,
+    codesign: bool,
+    copy_debug_info: bool,
+    rollback: bool,
+    preflight: Option<Preflight>,
+    health_check: Option<HealthCheck>,
+    abi_tag: Option<String>,
+    fingerprint: Option<String>,
+    #[cfg(feature = "signatures")]
+    public_key: Option<VerifyingKey>,
+    #[cfg(feature = "checksums")]
+    checksum_manifest: Option<PathBuf>,
+    init_symbol: Option<String>,
+    init_timeout: Option<Duration>,
+    host_api: Option<HostApi>,
+    lifecycle: LifecycleSymbols,
+    watcher_backend: WatcherBackend,
+    poll_fallback: Option<Duration>,
+    ignore_patterns: Vec<glob::Pattern>,
+    relocated: Vec<(PathBuf, PathBuf)>,
+    cluster: Option<Cluster>,
+    failed: Vec<FailedLib>,
+    retries: Retries,
+    unloads: Unloads,
+    bindings: Bindings,
+    observers: Observers,
+    subscribers: Subscribers,
+    #[cfg(feature = "trampoline")]
+    trampolines: Vec<Arc<Trampoline>>,
+    injections: Injections,
+    dependencies: Dependencies,
+}
+
+// State of a library kept between unloading and loading it again during a reload
+struct Unloaded {
+    // Kept until the reload is done so it can be given to the callback if it fails
+    lib: Option<Arc<Lib>>,
+    metadata: Metadata,
+    all_metadata: Metadata,
+    userdata: Userdata,
+    // Generation of the version that was unloaded
+    generation: Option<u64>,
+    injected: Option<FailPoint>,
+    start: Instant,
+    // (original path, shadow copy, content hash, load directory) of the version that was
+    // unloaded
+    previous: Option<(PathBuf, PathBuf, u64, Option<Arc<LoadDir>>)>,
 }
 
 /// Searching for a shared library can be done in current directory, but can also be allowed to
-/// search backwards.
+/// search backwards. See [add_library](struct.DynamicReload.html#method.add_library) for the
+/// order in which locations are searched.
 pub enum Search {
-    /// Search in current directory only
+    /// Search in current directory, the search paths and the directory of the executable
     Default,
     /// Allow searching in current directory and backwards of parent directories as well
     Backwards,
+    /// Same as Backwards and then also search the standard locations of the OS: ```PATH``` on
+    /// Windows, ```LD_LIBRARY_PATH``` and the directories configured for the dynamic linker on
+    /// Linux and ```DYLD_LIBRARY_PATH```/```DYLD_FALLBACK_LIBRARY_PATH``` on macOS. Useful when
+    /// reloading libraries that are installed system-wide.
+    System,
+    /// Leave all searching to a [SearchStrategy]
+    Custom(Box<dyn SearchStrategy>),
 }
 
 /// This is the states that the callback function supplied to [update](struct.DynamicReload.html#method.update) can be called with.
+#[non_exhaustive]
 pub enum UpdateState {
     /// Set when a shared library is about to be reloaded. Gives the application time to save state,
     /// do clean up, etc
@@ -76,12 +369,77 @@ pub enum UpdateState {
     /// Called when a library has been reloaded. Allows the application to restore state.
     After,
     /// In case reloading of the library failed (broken file, etc) this will be set and allow the
-    /// application to to deal with the issue.
+    /// application to to deal with the issue. The version that was loaded before is passed to
+    /// the callback so the application can keep using it, it stays loaded for as long as it's
+    /// kept around. No library is passed when loading a new library from a directory fails or
+    /// with the ```no-timestamps``` feature, where the previous version has to be unloaded
+    /// before the new copy with the same name can be loaded.
     ReloadFailed(Error),
+    /// The file of the library has been deleted. The library stays loaded until the application
+    /// lets go of it and is reloaded as usual (```Before``` and ```After```) if the file shows
+    /// up again.
+    Removed,
+    /// A new library has been loaded from a directory added with
+    /// [add_directory](struct.DynamicReload.html#method.add_directory).
+    Added,
+    /// Called after ```ReloadFailed``` when the version that was loaded before has been loaded
+    /// again, see [set_rollback](struct.DynamicReload.html#method.set_rollback). Allows the
+    /// application to restore state like after ```After```.
+    RolledBack,
+    /// The new version of the library failed its health check, see
+    /// [set_health_check](struct.DynamicReload.html#method.set_health_check). It has been
+    /// dropped and the previous version, which is passed to the callback, is used again. Allows
+    /// the application to restore state like after ```After```. No library is passed with the
+    /// ```no-timestamps``` feature where the previous version is no longer tracked.
+    HealthCheckFailed(Error),
+    /// The library at the path has failed to reload too many times in a row and is no longer
+    /// retried, see [set_reload_retry](struct.DynamicReload.html#method.set_reload_retry). The
+    /// version in use is passed to the callback if there is one.
+    Blacklisted(PathBuf),
+    /// The library has been replaced or removed but the application still holds the given
+    /// number of references to it, see
+    /// [set_external_refs](struct.DynamicReload.html#method.set_external_refs).
+    StillReferenced(usize),
+    /// The sources of the library have changed and a build has been started, see
+    /// [watch_sources](struct.DynamicReload.html#method.watch_sources).
+    BuildStarted,
+    /// The build succeeded with the given output of cargo, which has the warnings. The library
+    /// is reloaded as usual once the watcher sees the new version.
+    BuildFinished(String),
+    /// The build failed, with the output of cargo in ```Error::Cargo```. The library that is
+    /// loaded stays in use.
+    BuildFailed(Error),
+}
+
+/// Decides if a changed file is one of the loaded libraries, see
+/// [set_match_policy](struct.DynamicReload.html#method.set_match_policy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatchPolicy {
+    /// The file has to have the same path as the library was loaded from, after resolving
+    /// relative paths and symbolic links of the directory.
+    #[default]
+    FullPath,
+    /// Only the file names are compared, so a change to a file with the same name in any
+    /// watched directory reloads the library.
+    FileName,
+}
+
+/// Order of loading the new version of a library and unloading the old one during a reload, see
+/// [set_swap_order](struct.DynamicReload.html#method.set_swap_order).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SwapOrder {
+    /// Call ```Before```, unload the old version, load the new one and call ```After```. A
+    /// failed load leaves the library unloaded.
+    #[default]
+    UnloadFirst,
+    /// Load the new version, then call ```Before```, swap the versions, call ```After``` and
+    /// drop the old one. A failed load only calls ```ReloadFailed``` and the old version stays
+    /// loaded and tracked.
+    LoadFirst,
 }
 
 /// This is used to decide how the name used for [add_library](struct.DynamicReload.html#method.add_library) is to be handled.
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug)]
 pub enum PlatformName {
     /// Leave name as is and don't do any formating.
     No,
@@ -92,7 +450,42 @@ pub enum PlatformName {
     /// Linux:   foobar -> libfoobar.so
     /// Mac:     foobar -> libfoobar.dylib
     /// ```
+    ///
+    /// Names that are already formatted (```libfoobar.so```, or versioned as
+    /// ```libfoobar.so.1.2.3```) are left as is and dashes are replaced with underscores the same
+    /// way cargo does (```foo-bar``` -> ```libfoo_bar.so```).
+    ///
+    /// A library added as ```foobar``` is reloaded on changes to its versioned names in the same
+    /// directory (```libfoobar.so.1```), which link to each other in CMake builds. The reload
+    /// loads the added path again, so add the name that links to the newest version.
     Yes,
+    /// Same as ```Yes``` but for the platform of the given target triple instead of the one
+    /// the application runs on, for plugins built by another toolchain.
+    ///
+    /// ```ignore
+    /// PlatformName::Target("x86_64-pc-windows-gnu"): foobar -> foobar.dll
+    /// PlatformName::Target("aarch64-apple-darwin"):  foobar -> libfoobar.dylib
+    /// ```
+    Target(&'static str),
+    /// Format the name with the function, for naming schemes that don't follow the
+    /// conventions of the platform such as MinGW (```foobar``` -> ```libfoobar.dll```).
+    ///
+    /// ```ignore
+    /// PlatformName::Custom(|name| format!("lib{}.dll", name))
+    /// ```
+    Custom(fn(&str) -> String),
+}
+
+impl PartialEq for PlatformName {
+    fn eq(&self, other: &PlatformName) -> bool {
+        match (self, other) {
+            (PlatformName::No, PlatformName::No) | (PlatformName::Yes, PlatformName::Yes) => true,
+            (PlatformName::Target(a), PlatformName::Target(b)) => a == b,
+            // Only tells if it's the same function, the same code may be at several addresses
+            (PlatformName::Custom(a), PlatformName::Custom(b)) => *a as usize == *b as usize,
+            _ => false,
+        }
+    }
 }
 
 impl<'a> DynamicReload {
@@ -101,7 +494,8 @@ impl<'a> DynamicReload {
     ///
     /// ```search_path``` is a list of extra paths that when
     /// calling [add_library](struct.DynamicReload.html#method.add_library) the code will
-    /// also try to find the shared library within those locations.
+    /// also try to find the shared library within those locations. The paths listed in the
+    /// [SEARCH_PATH_ENV] environment variable are added after them.
     ///
     /// ```shadow_dir``` is a location where a temporary directory will be created to
     /// keep a copy of all the shared libraries and load from there. The reason is that some
@@ -117,6 +511,14 @@ impl<'a> DynamicReload {
     /// changed on disk, until it will cause a reload. (Multiple write calls could be made to the library
     /// until it is fully written.)
     ///
+    /// The native file watcher of the platform is used. To use another [WatcherBackend] from the
+    /// start, such as polling for network shares where native events never arrive, use
+    /// [builder](struct.DynamicReload.html#method.builder) instead.
+    ///
+    /// Failing to create the temporary directory or the file watcher is only logged, use
+    /// [try_new](struct.DynamicReload.html#method.try_new) to get an error instead. Paths that
+    /// aren't valid UTF-8 can be given to the builder, which takes any ```AsRef<Path>```.
+    ///
     /// # Examples
     ///
     /// ```ignore
@@ -132,653 +534,9235 @@ impl<'a> DynamicReload {
     pub fn new(
         search_paths: Option<Vec<&'a str>>,
         shadow_dir: Option<&'a str>,
-        _search: Search,
+        search: Search,
         debounce_duration: Duration,
     ) -> DynamicReload {
         let (tx, rx) = channel();
-        DynamicReload {
-            libs: Vec::new(),
-            watcher: Self::get_watcher(tx, debounce_duration),
-            shadow_dir: Self::get_temp_dir(shadow_dir),
-            watch_recv: rx,
-            search_paths: Self::get_search_paths(search_paths),
-        }
+        let (watcher, watcher_error) = match Self::get_watcher(tx, debounce_duration) {
+            Ok(watcher) => (watcher, None),
+            Err(e) => (None, Some(e)),
+        };
+
+        let mut search_paths = Self::get_search_paths(search_paths);
+        search_paths.extend(
+            search::env_paths(SEARCH_PATH_ENV, |var| env::var_os(var))
+                .into_iter()
+                .map(|path| winpath::canonicalize(&path).unwrap_or(path)),
+        );
+
+        let mut dr = Self::with_parts(
+            watcher,
+            rx,
+            Self::get_temp_dir(shadow_dir),
+            search_paths,
+            search,
+            debounce_duration,
+        );
+        dr.watcher_error = watcher_error;
+        dr
     }
 
     ///
-    /// Add a library to be loaded and to be reloaded once updated.
-    /// If PlatformName is set to Yes the input name will be formatted according
-    /// to the standard way libraries looks on that platform examples:
+    /// Same as [new](struct.DynamicReload.html#method.new) but returns an error instead of
+    /// carrying on without a shadow directory or without watching for changes.
     ///
-    /// ```ignore
-    /// Windows: foobar -> foobar.dll
-    /// Linux:   foobar -> libfoobar.so
-    /// Mac:     foobar -> libfoobar.dylib
-    /// ```
+    /// Fails with ```Error::ShadowDir``` if the temporary directory can't be created in
+    /// ```shadow_dir``` (for example when it doesn't exist) and with ```Error::Watcher``` if
+    /// the file watcher can't be created.
     ///
-    /// If set to no the given input name will be used as is. This function
-    /// will also search for the file in this priority order
+    /// # Examples
     ///
     /// ```ignore
-    /// 1. Current directory
-    /// 2. In the search paths (relative to current directory)
-    /// 3. Current directory of the executable
-    /// 4. Search backwards from executable if Backwards has been set DynamicReload::new
+    /// let mut dr = DynamicReload::try_new(
+    ///     Some(vec!["plugins"]),
+    ///     Some("target/debug"),
+    ///     Search::Default,
+    ///     Duration::from_secs(2),
+    /// )?;
     /// ```
+    ///
+    pub fn try_new(
+        search_paths: Option<Vec<&'a str>>,
+        shadow_dir: Option<&'a str>,
+        search: Search,
+        debounce_duration: Duration,
+    ) -> Result<DynamicReload> {
+        let mut builder = DynamicReload::builder()
+            .search(search)
+            .debounce(debounce_duration);
+
+        for path in search_paths.unwrap_or_default() {
+            builder = builder.search_path(path);
+        }
+
+        if let Some(dir) = shadow_dir {
+            builder = builder.shadow_dir(dir);
+        }
+
+        builder.build()
+    }
+
+    ///
+    /// Returns a [DynamicReloadBuilder] for setting up DynamicReload with failures reported as
+    /// errors instead of being ignored.
+    ///
     /// # Examples
     ///
     /// ```ignore
-    /// // Add a library named test_lib and format it according to standard platform standard.
-    /// add_library("test_lib", PlatformName::Yes)
+    /// let dr = DynamicReload::builder()
+    ///     .search_path("plugins")
+    ///     .shadow_dir("target/debug")
+    ///     .debounce(Duration::from_millis(300))
+    ///     .build()?;
     /// ```
-    /// # Safety
-    /// Note taken from libloading that is used for library loading
-    ///
-    /// When a library is loaded, initialisation routines contained within it are executed.
-    /// For the purposes of safety, the execution of these routines is conceptually the same calling an unknown foreign function and may impose arbitrary requirements on the caller for the call to be sound.
-    /// Additionally, the callers of this function must also ensure that execution of the termination routines contained within
-    /// the library is safe as well. These routines may be executed when the library is unloaded.
     ///
-    pub unsafe fn add_library(
-        &mut self,
-        name: &str,
-        name_format: PlatformName,
-    ) -> Result<Arc<Lib>> {
-        match Self::try_load_library(self, name, name_format) {
-            Ok(lib) => {
-                if let Some(w) = self.watcher.as_mut() {
-                    if let Some(path) = lib.original_path.as_ref() {
-                        let parent = path.as_path().parent().unwrap();
-                        let parent_buf = if cfg!(windows) {
-                            parent.to_path_buf().canonicalize().unwrap()
-                        } else {
-                            parent.to_path_buf()
-                        };
-
-                        let _ = w.watcher().watch(&parent_buf, RecursiveMode::NonRecursive);
-                    }
-                }
-                // Bump the ref here as we keep one around to keep track of files that needs to be reloaded
-                self.libs.push(lib.clone());
-                Ok(lib)
-            }
-            Err(e) => Err(e),
+    pub fn builder() -> DynamicReloadBuilder {
+        DynamicReloadBuilder::new()
+    }
+
+    fn with_parts(
+        watcher: Option<FileWatcher>,
+        watch_recv: Receiver<WatchMsg>,
+        shadow_dir: Option<TempDir>,
+        search_paths: Vec<PathBuf>,
+        search: Search,
+        debounce_duration: Duration,
+    ) -> DynamicReload {
+        let (command_tx, commands) = channel();
+
+        DynamicReload {
+            libs: Registry::default(),
+            watcher,
+            watcher_error: None,
+            watched_dirs: WatchedDirs::default(),
+            shadow_dir,
+            watch_recv: Mutex::new(watch_recv),
+            raw_events: None,
+            commands: Mutex::new(commands),
+            command_tx,
+            paused: false,
+            search_paths,
+            search,
+            match_policy: MatchPolicy::default(),
+            selection_policy: SelectionPolicy::default(),
+            swap_order: SwapOrder::default(),
+            transactional: Vec::new(),
+            queue_reloads: false,
+            queued: Vec::new(),
+            debounce_duration,
+            debounce_strategy: None,
+            missing_paths: Vec::new(),
+            guarded: Vec::new(),
+            missing_since: Vec::new(),
+            removed: Vec::new(),
+            recursive_paths: Vec::new(),
+            loader_paths: false,
+            env_lookup: |var| env::var_os(var),
+            follow_symlinks: false,
+            link_targets: Vec::new(),
+            cargo_profile: None,
+            rebuilds: Rebuilds::default(),
+            directories: Vec::new(),
+            options: Vec::new(),
+            delayed: Vec::new(),
+            copy_retry: CopyRetry::default(),
+            copy_verify: CopyVerify::default(),
+            codesign: false,
+            copy_debug_info: false,
+            rollback: false,
+            preflight: None,
+            health_check: None,
+            abi_tag: None,
+            fingerprint: None,
+            #[cfg(feature = "signatures")]
+            public_key: None,
+            #[cfg(feature = "checksums")]
+            checksum_manifest: None,
+            init_symbol: None,
+            init_timeout: None,
+            host_api: None,
+            lifecycle: LifecycleSymbols::default(),
+            watcher_backend: WatcherBackend::Recommended,
+            poll_fallback: Some(DEFAULT_POLL_FALLBACK),
+            ignore_patterns: Vec::new(),
+            relocated: Vec::new(),
+            cluster: None,
+            failed: Vec::new(),
+            retries: Retries::default(),
+            unloads: Unloads::default(),
+            bindings: Bindings::default(),
+            observers: Observers::default(),
+            subscribers: Subscribers::default(),
+            #[cfg(feature = "trampoline")]
+            trampolines: Vec::new(),
+            injections: Injections::default(),
+            dependencies: Dependencies::default(),
         }
     }
 
     ///
+    /// Returns a snapshot of all the currently loaded libraries, including their metadata.
     ///
-    /// Needs to be called in order to handle reloads of libraries.
+    pub fn status(&self) -> Vec<LibStatus> {
+        self.libs
+            .iter()
+            .map(|lib| LibStatus {
+                loaded_path: lib.loaded_path.clone(),
+                original_path: lib.original_path.clone(),
+                file_identity: lib.file_identity,
+                metadata: lib.all_metadata(),
+                generation: lib.generation(),
+            })
+            .collect()
+    }
+
     ///
-    /// ```update_call``` function with its data needs to be supplied to allow the application to
-    /// take appropriate action depending on what needs to be done with the loaded library.
+    /// Iterates over all managed libraries, including the ones that failed to reload.
+    ///
+    /// # Examples
     ///
     /// ```ignore
-    /// struct Plugins {
-    ///     // ...
-    /// }
+    /// let stale: Vec<LibHandle> = dr.iter_libs().in_group("render").filter_stale().collect();
+    /// let broken = dr.iter_libs().failed().count();
+    /// ```
     ///
-    /// impl Plugins {
-    ///    fn reload_callback(&mut self, state: UpdateState, lib: Option<&Arc<Lib>>) {
-    ///        match state {
-    ///            UpdateState::Before => // save state, remove from lists, etc, here
-    ///            UpdateState::After => // shared lib reloaded, re-add, restore state
-    ///            UpdateState::ReloadFailed(Error) => // shared lib failed to reload due to error
-    ///        }
-    ///    }
-    /// }
+    pub fn iter_libs(&self) -> LibIter {
+        let loaded = self.libs.iter().map(LibHandle::loaded);
+        let failed = self.failed.iter().map(LibHandle::failed);
+        LibIter::new(loaded.chain(failed).collect())
+    }
+
     ///
-    /// fn main() {
-    ///     let plugins = Plugins { ... };
-    ///     let mut dr = DynamicReload::new(None, Some("target/debug"), Search::Backwards, Duration::from_secs(2));
-    ///     dr.add_library("test_shared", Search::Backwards);
-    ///     dr.update(Plugin::reload_callback, &mut plugins);
+    /// Iterates over the currently loaded libraries. Libraries that failed to reload are left
+    /// out, see [iter_libs](struct.DynamicReload.html#method.iter_libs).
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// for lib in dr.libs() {
+    ///     println!("{:?} loaded from {:?}", lib.source_path(), lib.loaded_path);
     /// }
     /// ```
-    /// # Safety
-    /// Note taken from libloading that is used for library loading
     ///
-    /// When a library is loaded, initialisation routines contained within it are executed.
-    /// For the purposes of safety, the execution of these routines is conceptually the same calling an unknown foreign function and may impose arbitrary requirements on the caller for the call to be sound.
-    /// Additionally, the callers of this function must also ensure that execution of the termination routines contained within
-    /// the library is safe as well. These routines may be executed when the library is unloaded.
-    ///
-    pub unsafe fn update<F, T>(&mut self, update_call: &F, data: &mut T)
-    where
-        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
-    {
-        while let Ok(evt) = self.watch_recv.try_recv() {
-            match evt {
-                Ok(events) => {
-                    for event in events {
-                        Self::reload_libs(self, &event.path, update_call, data);
-                    }
-                }
-                _ => (),
-            }
-        }
+    pub fn libs(&self) -> impl Iterator<Item = &Arc<Lib>> + '_ {
+        self.libs.iter()
     }
 
-    unsafe fn reload_libs<F, T>(&mut self, file_path: &Path, update_call: &F, data: &mut T)
-    where
-        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
-    {
-        let len = self.libs.len();
-        for i in (0..len).rev() {
-            if Self::should_reload(file_path, &self.libs[i]) {
-                Self::reload_lib(self, i, file_path, update_call, data);
-            }
-        }
+    ///
+    /// Looks up a loaded library by the name and format that were passed to
+    /// [add_library](struct.DynamicReload.html#method.add_library). If libraries with the same
+    /// file name have been loaded from different directories the first one added is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// unsafe { dr.add_library("test_shared", PlatformName::Yes)? };
+    /// let lib = dr.get_by_name("test_shared", PlatformName::Yes).unwrap();
+    /// ```
+    ///
+    pub fn get_by_name(&self, name: &str, name_format: PlatformName) -> Option<&Arc<Lib>> {
+        let lib_name = Self::get_library_name(name, name_format);
+        let index = *self.libs.named(lib_name.as_ref()).first()?;
+        Some(&self.libs[index])
     }
 
-    unsafe fn reload_lib<F, T>(
-        &mut self,
-        index: usize,
-        file_path: &Path,
-        update_call: &F,
-        data: &mut T,
-    ) where
-        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
-    {
-        update_call(data, UpdateState::Before, Some(&self.libs[index]));
-        self.remove_lib(index);
+    ///
+    /// Looks up a loaded library by the path of its file, the original location when loaded
+    /// through a shadow directory.
+    ///
+    pub fn get_by_path(&self, path: &Path) -> Option<&Arc<Lib>> {
+        let index = self.libs.find(path)?;
+        Some(&self.libs[index])
+    }
 
-        match Self::load_library(self, file_path) {
-            Ok(lib) => {
-                self.libs.push(lib.clone());
-                update_call(data, UpdateState::After, Some(&lib));
-            }
+    ///
+    /// Checks the given symbol names for conflicts between the loaded libraries.
+    ///
+    /// Reported are symbols exported by more than one library and symbols where the global
+    /// namespace (```RTLD_GLOBAL```) resolves to a different definition, which means code inside
+    /// a plugin may end up calling another library than itself. See [SymbolConflict].
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// for conflict in dr.symbol_conflicts(&["plugin_create", "plugin_destroy"]) {
+    ///     if conflict.global {
+    ///         println!("Warning: {} is shadowed by a global symbol", conflict.name);
+    ///     }
+    /// }
+    /// ```
+    ///
+    pub fn symbol_conflicts(&self, names: &[&str]) -> Vec<SymbolConflict> {
+        symbols::conflicts(&self.libs, names)
+    }
 
-            Err(err) => {
-                update_call(data, UpdateState::ReloadFailed(err), None);
-                //println!("Unable to reload lib {:?} err {:?}", file_path, err); // Removed due to move in previous line
-            }
-        }
+    ///
+    /// Makes the next reload of ```lib``` fail at the given phase.
+    ///
+    /// This is intended for tests of the application so the ```ReloadFailed``` handling can be
+    /// exercised without having to produce a broken library. The failure is reported as
+    /// ```Error::Injected``` and only affects one reload.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let lib = dr.add_library("test_shared", PlatformName::Yes)?;
+    /// dr.inject_failure(&lib, FailPoint::Load);
+    /// // The next reload of test_shared reports UpdateState::ReloadFailed
+    /// ```
+    ///
+    pub fn inject_failure(&mut self, lib: &Lib, at: FailPoint) {
+        self.injections.add(lib.source_path(), at);
     }
 
-    unsafe fn try_load_library(&self, name: &str, name_format: PlatformName) -> Result<Arc<Lib>> {
-        match Self::search_dirs(self, name, name_format) {
-            Some(path) => Self::load_library(self, &path),
-            None => Err(Error::Find(name.into())),
-        }
+    ///
+    /// Replaces the time based debouncing with a custom [DebounceStrategy].
+    ///
+    /// In this mode the raw (non-debounced) events from the file watcher are given to the
+    /// strategy which decides when a changed library is ready to be reloaded. This is useful
+    /// when the build tooling makes time based debouncing unreliable. Passing ```None```
+    /// switches back to the debounce duration given to [new](struct.DynamicReload.html#method.new).
+    ///
+    /// All libraries that has already been added will keep being watched.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Reload as soon as the linker has closed the file
+    /// dr.set_debounce_strategy(Some(Box::new(CloseWrite::new())))?;
+    /// ```
+    ///
+    pub fn set_debounce_strategy(
+        &mut self,
+        strategy: Option<Box<dyn DebounceStrategy>>,
+    ) -> Result<()> {
+        self.debounce_strategy = strategy;
+        self.start_watching()
     }
 
-    unsafe fn load_library(&self, full_path: &Path) -> Result<Arc<Lib>> {
-        let path;
-        let original_path;
+    ///
+    /// Returns true if changes to libraries are currently being watched.
+    ///
+    pub fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
 
-        if let Some(sd) = self.shadow_dir.as_ref() {
-            path = Self::format_filename(sd.path(), full_path);
-            Self::try_copy(full_path, &path)?;
-            original_path = Some(full_path.to_path_buf());
-        } else {
-            original_path = None;
-            path = full_path.to_path_buf();
+    ///
+    /// Returns if changes are being watched and the error if creating the watcher failed in
+    /// [new](struct.DynamicReload.html#method.new), which carries on without reloading.
+    /// [builder](struct.DynamicReload.html#method.builder) returns the error instead.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// if let WatcherStatus::Failed(e) = dr.watcher_status() {
+    ///     ui.warn(format!("Hot reloading is disabled: {}", e));
+    /// }
+    /// ```
+    ///
+    pub fn watcher_status(&self) -> WatcherStatus<'_> {
+        match (self.watcher.as_ref(), self.watcher_error.as_ref()) {
+            (Some(watcher), _) => WatcherStatus::Active(watcher.backend()),
+            (None, Some(e)) => WatcherStatus::Failed(e),
+            (None, None) => WatcherStatus::Stopped,
         }
-
-        Self::init_library(original_path, path)
     }
 
-    unsafe fn init_library(org_path: Option<PathBuf>, path: PathBuf) -> Result<Arc<Lib>> {
-        match Library::new(&path) {
-            Ok(l) => Ok(Arc::new(Lib {
-                original_path: org_path,
-                loaded_path: path,
-                lib: l,
-            })),
-            Err(e) => Err(Error::Load(e)),
-        }
+    ///
+    /// Returns the thread that delivers file change events, named ```"dynamic_reload watcher"```.
+    /// Useful for hosts that keep track of the threads in the process. Notify also runs its own
+    /// threads (named ```"notify-rs ..."```) that are started and stopped together with this one.
+    ///
+    pub fn watcher_thread(&self) -> Option<&std::thread::Thread> {
+        self.watcher.as_ref().and_then(|w| w.thread())
     }
 
-    fn should_reload(reload_path: &Path, lib: &Lib) -> bool {
-        if let Some(p) = lib.original_path.as_ref() {
-            // Check if file names match.
-            if reload_path.file_name() == p.file_name() {
-                return true;
-            }
+    ///
+    /// Stops watching for changes. All watches are removed and the watcher threads are joined
+    /// before this returns. Libraries stay loaded but won't be reloaded until
+    /// [start_watching](struct.DynamicReload.html#method.start_watching) is called.
+    ///
+    pub fn stop_watching(&mut self) {
+        if let Some(watcher) = self.watcher.take() {
+            watcher.stop();
         }
 
-        false
+        self.watched_dirs.clear();
     }
 
-    fn search_dirs(&self, name: &str, name_format: PlatformName) -> Option<PathBuf> {
-        let lib_name = Self::get_library_name(name, name_format);
+    ///
+    /// Shuts down the file watching and discards all changes that has been detected but not
+    /// yet handled. Watches are torn down and all threads started by DynamicReload are joined
+    /// before this returns, so it's safe to exit the process right after. Loaded libraries
+    /// are kept. Use [stop_and_flush](struct.DynamicReload.html#method.stop_and_flush) to
+    /// handle pending changes instead.
+    ///
+    pub fn stop(&mut self) {
+        self.stop_watching();
 
-        // 1. Search the current directory
-        if let Some(path) = Self::search_current_dir(&lib_name) {
-            return Some(path);
-        }
+        while self.receiver().try_recv().is_ok() {}
 
-        // 2. Search the relative paths
-        if let Some(path) = Self::search_relative_paths(self, &lib_name) {
-            return Some(path);
+        if let Some(strategy) = self.debounce_strategy.as_mut() {
+            strategy.flush();
         }
 
-        // 3. Search the executable dir and then go backwards
-        Self::search_backwards_from_exe(&lib_name)
+        self.missing_paths.clear();
+        self.missing_since.clear();
+        self.removed.clear();
+        self.delayed.clear();
+        self.queued.clear();
+        self.guarded.clear();
     }
 
-    fn search_current_dir(name: &String) -> Option<PathBuf> {
-        Self::is_file(&Path::new(name).to_path_buf())
-    }
+    ///
+    /// Same as [stop](struct.DynamicReload.html#method.stop) but changes that has been detected
+    /// before the call are reloaded (calling ```update_call``` as with
+    /// [update](struct.DynamicReload.html#method.update)) instead of discarded.
+    ///
+    /// With the default time based debouncing this waits for the debounce duration before
+    /// stopping so that changes that are still being debounced are delivered.
+    ///
+    /// # Safety
+    /// See [update](struct.DynamicReload.html#method.update)
+    ///
+    pub unsafe fn stop_and_flush<F, T>(&mut self, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        if self.is_watching() && self.debounce_strategy.is_none() {
+            thread::sleep(self.debounce_duration + self.debounce_duration / 4);
+        }
 
-    fn search_relative_paths(&self, name: &String) -> Option<PathBuf> {
-        for p in self.search_paths.iter() {
-            let path = Path::new(p).join(name);
-            if let Some(file) = Self::is_file(&path) {
-                return Some(file);
-            }
+        self.stop_watching();
+
+        let mut paths = self.changed_paths();
+        if let Some(strategy) = self.debounce_strategy.as_mut() {
+            paths.extend(strategy.flush());
         }
+        paths.extend(self.delayed.drain(..).map(|(path, _, _)| path));
+        paths.extend(std::mem::take(&mut self.queued));
 
-        None
-    }
+        for path in paths {
+            Self::reload_libs(self, &path, update_call, data);
+        }
 
-    fn get_parent_dir(path: &Path) -> Option<PathBuf> {
-        path.parent().map(|p| p.to_path_buf())
+        // Files that still don't exist won't be coming back
+        self.missing_paths.clear();
+        self.missing_since.clear();
+        self.removed.clear();
     }
 
-    fn search_backwards_from_file(path: &Path, lib_name: &String) -> Option<PathBuf> {
-        match Self::get_parent_dir(path) {
-            Some(p) => {
-                let new_path = Path::new(&p).join(lib_name);
-                if Self::is_file(&new_path).is_some() {
-                    return Some(new_path);
+    ///
+    /// Returns a handle that other threads can use to add, remove and reload libraries. The
+    /// commands are applied when [update](struct.DynamicReload.html#method.update) is called.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let handle = dr.handle();
+    ///
+    /// thread::spawn(move || {
+    ///     // In the UI
+    ///     if reload_button.clicked() {
+    ///         handle.reload_library(&lib);
+    ///     }
+    /// });
+    /// ```
+    ///
+    pub fn handle(&self) -> DynamicReloadHandle {
+        DynamicReloadHandle::new(self.command_tx.clone())
+    }
+
+    ///
+    /// Stops reloading libraries until [resume](struct.DynamicReload.html#method.resume) is
+    /// called. Changes are still detected and are reloaded once resumed.
+    ///
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    ///
+    /// Resumes reloading after [pause](struct.DynamicReload.html#method.pause).
+    ///
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    ///
+    /// Returns true if reloading has been paused.
+    ///
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    ///
+    /// Switches the backend used to detect changes. Existing watches are moved over to the new
+    /// backend. ```WatcherBackend::None``` stops watching.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Libraries are on a network share where native events doesn't work
+    /// dr.set_watcher_backend(WatcherBackend::Poll { interval: Duration::from_secs(1) })?;
+    /// ```
+    ///
+    pub fn set_watcher_backend(&mut self, backend: WatcherBackend) -> Result<()> {
+        self.watcher_backend = backend;
+        self.start_watching()
+    }
+
+    ///
+    /// Sets the poll interval used when ```WatcherBackend::Recommended``` can't be used, either
+    /// because the native watcher can't be created or because it fails to watch a directory
+    /// (out of inotify watches, file systems such as some Docker bind mounts). Defaults to
+    /// [DEFAULT_POLL_FALLBACK], ```None``` turns the fallback off. Use
+    /// [active_watcher_backend](struct.DynamicReload.html#method.active_watcher_backend) to see
+    /// if the fallback is in use.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.set_poll_fallback(Some(Duration::from_millis(500)));
+    /// ```
+    ///
+    pub fn set_poll_fallback(&mut self, interval: Option<Duration>) {
+        self.poll_fallback = interval;
+    }
+
+    ///
+    /// Returns the backend that is watching for changes, None if nothing is watched. This is
+    /// ```WatcherBackend::Poll``` if the native backend has been replaced by the poll fallback.
+    ///
+    pub fn active_watcher_backend(&self) -> Option<WatcherBackend> {
+        self.watcher.as_ref().map(|w| w.backend())
+    }
+
+    ///
+    /// Sends all events from the file watcher to ```sink``` as they are received, including
+    /// errors and events for files that aren't libraries, ignored files and events that don't
+    /// cause a reload. Events are passed on when they are handled by
+    /// [update](struct.DynamicReload.html#method.update). ```None``` stops sending events,
+    /// which also happens when the receiver is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    /// dr.set_raw_events(Some(tx));
+    /// dr.update(&Plugins::reload_callback, &mut plugins);
+    /// for event in rx.try_iter() {
+    ///     println!("{:?}", event);
+    /// }
+    /// ```
+    ///
+    pub fn set_raw_events(&mut self, sink: Option<Sender<RawEvent>>) {
+        self.raw_events = sink;
+    }
+
+    ///
+    /// Sets how changed files are matched against the loaded libraries. Defaults to
+    /// ```MatchPolicy::FullPath``` so libraries with the same file name in different
+    /// directories are reloaded independently of each other.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Reload when a library with the same name changes in any of the watched directories
+    /// dr.set_match_policy(MatchPolicy::FileName);
+    /// ```
+    ///
+    pub fn set_match_policy(&mut self, policy: MatchPolicy) {
+        self.match_policy = policy;
+    }
+
+    ///
+    /// Sets which file is loaded when a library name matches files in several of the searched
+    /// locations, for example a stale copy in the current directory and a fresh build in
+    /// ```target```. Defaults to ```SelectionPolicy::FirstMatch``` which loads the first one in
+    /// the search order of [add_library](struct.DynamicReload.html#method.add_library), the
+    /// other policies search all locations. Doesn't apply to ```Search::Custom```.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Load the most recent build wherever it is
+    /// dr.set_selection_policy(SelectionPolicy::NewestModified);
+    /// ```
+    ///
+    pub fn set_selection_policy(&mut self, policy: SelectionPolicy) {
+        self.selection_policy = policy;
+    }
+
+    ///
+    /// Sets if the new version of a library is loaded before or after the old one is unloaded.
+    /// Defaults to ```SwapOrder::UnloadFirst```. With ```SwapOrder::LoadFirst``` the library is
+    /// never missing during a reload and a broken version never replaces a working one.
+    ///
+    /// Both versions are loaded at the same time for a moment so global state in the library
+    /// isn't shared between them. Libraries that other libraries depend on are always unloaded
+    /// first as the dependents have to be unloaded before them, and so are all libraries with
+    /// the ```no-timestamps``` feature where both versions would have the same path.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.set_swap_order(SwapOrder::LoadFirst);
+    /// ```
+    ///
+    pub fn set_swap_order(&mut self, order: SwapOrder) {
+        self.swap_order = order;
+    }
+
+    ///
+    /// Makes the libraries in ```group``` (see [GROUP_KEY]) reload all-or-nothing. When a member
+    /// changes all members that have changed are loaded next to the versions in use. Once all of
+    /// them have loaded ```Before``` is called for each of them, they are swapped together and
+    /// ```After``` is called for each new version. If any of them fails to load the new versions
+    /// are dropped, the versions in use stay loaded and ```ReloadFailed``` is reported for every
+    /// changed member, with ```Error::GroupAborted``` for the members that did load.
+    ///
+    /// As the versions are loaded at the same time libraries that other libraries depend on
+    /// shouldn't be in a transactional group. Has no effect with the ```no-timestamps```
+    /// feature where both versions would have the same path.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let renderer = dr.add_library("renderer", PlatformName::Yes)?;
+    /// let shaders = dr.add_library("shaders", PlatformName::Yes)?;
+    /// renderer.set_metadata(GROUP_KEY, "render");
+    /// shaders.set_metadata(GROUP_KEY, "render");
+    /// dr.set_transactional("render", true);
+    /// ```
+    ///
+    pub fn set_transactional(&mut self, group: &str, transactional: bool) {
+        self.transactional.retain(|g| g != group);
+        if transactional {
+            self.transactional.push(group.to_owned());
+        }
+    }
+
+    ///
+    /// Queues reloads instead of applying them in [update](struct.DynamicReload.html#method.update)
+    /// so the application can decide when libraries are swapped, for example at a safe point
+    /// in the frame. Changes are still detected and debounced by ```update```, they are listed
+    /// by [pending_reloads](struct.DynamicReload.html#method.pending_reloads) and applied by
+    /// [commit_pending](struct.DynamicReload.html#method.commit_pending). Reloads that are
+    /// queued when this is turned off are applied by the next ```update```. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.set_queue_reloads(true);
+    ///
+    /// loop {
+    ///     unsafe { dr.update(&Plugins::reload_callback, &mut plugins) };
+    ///     run_frame(&mut plugins);
+    ///
+    ///     if !dr.pending_reloads().is_empty() {
+    ///         unsafe { dr.commit_pending(&Plugins::reload_callback, &mut plugins) };
+    ///     }
+    /// }
+    /// ```
+    ///
+    pub fn set_queue_reloads(&mut self, queue: bool) {
+        self.queue_reloads = queue;
+    }
+
+    ///
+    /// Sets glob patterns for file names whose changes should be ignored, such as
+    /// ```"*.pdb"``` or ```"*.tmp"```. Replaces any previously set patterns.
+    ///
+    pub fn set_ignore_patterns(&mut self, patterns: &[&str]) -> Result<()> {
+        self.ignore_patterns = patterns
+            .iter()
+            .map(|p| glob::Pattern::new(p).map_err(Error::Pattern))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(())
+    }
+
+    ///
+    /// Replaces the search paths given to [new](struct.DynamicReload.html#method.new),
+    /// including the ones from the [SEARCH_PATH_ENV] environment variable.
+    ///
+    /// All libraries that are already loaded are resolved again using the new paths. A library
+    /// that now resolves to another file is reloaded from there (with the usual
+    /// ```Before```/```After``` calls) during the next
+    /// [update](struct.DynamicReload.html#method.update) and the new location is watched.
+    ///
+    pub fn set_search_paths<P: AsRef<Path>>(&mut self, search_paths: Vec<P>) {
+        self.search_paths = Self::get_search_paths(Some(search_paths));
+
+        for lib in self.libs.iter() {
+            let original = match lib.original_path.as_ref() {
+                Some(path) => path,
+                None => continue,
+            };
+
+            let file_name = original.file_name().unwrap();
+            let name = file_name.to_string_lossy();
+
+            if let Ok(path) = self.find_file(&name, file_name) {
+                if &path != original {
+                    self.relocated.push((original.clone(), path));
                 }
-                Self::search_backwards_from_file(&p, lib_name)
             }
+        }
+    }
+
+    ///
+    /// Also searches the directories listed in the environment variables of the dynamic
+    /// linker, right after the search paths: ```PATH``` on Windows, ```LD_LIBRARY_PATH``` on
+    /// Linux and ```DYLD_LIBRARY_PATH```/```DYLD_FALLBACK_LIBRARY_PATH``` on macOS. Off by
+    /// default.
+    ///
+    /// Unlike ```Search::System``` it doesn't search backwards from the executable or in the
+    /// system directories, so plugins provided by the environment are found without picking up
+    /// anything installed system-wide. Libraries found there are loaded with their absolute
+    /// path, which is what [original_path](struct.Lib.html#structfield.original_path) is set to
+    /// with a shadow directory.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // LD_LIBRARY_PATH=/opt/plugins ./host
+    /// dr.set_loader_paths(true);
+    /// let lib = unsafe { dr.add_library("physics", PlatformName::Yes)? };
+    /// ```
+    ///
+    pub fn set_loader_paths(&mut self, enabled: bool) {
+        self.loader_paths = enabled;
+    }
+
+    ///
+    /// Follows symbolic links to libraries, which custom build systems often put in
+    /// ```target/debug``` instead of the library. The directory of the file the link points to
+    /// is watched as well, so changes to it reload the library and not only changes to the link.
+    /// The library is still reported with the path of the link and
+    /// [add_library_path](struct.DynamicReload.html#method.add_library_path) keeps the link
+    /// instead of resolving it. Off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // target/debug/libphysics.so -> /build/out/physics/libphysics.so
+    /// dr.set_follow_symlinks(true);
+    /// let lib = unsafe { dr.add_library("physics", PlatformName::Yes)? };
+    /// ```
+    ///
+    pub fn set_follow_symlinks(&mut self, enabled: bool) {
+        self.follow_symlinks = enabled;
+
+        if enabled {
+            let libs: Vec<Arc<Lib>> = self.libs.iter().cloned().collect();
+            for lib in libs {
+                self.watch_lib(&lib);
+            }
+        } else {
+            let targets: Vec<(PathBuf, PathBuf)> = self.link_targets.drain(..).collect();
+            for dir in targets.iter().filter_map(|(_, target)| target.parent()) {
+                self.release_watch(dir);
+            }
+        }
+    }
+
+    ///
+    /// Also searches the directory cargo builds the artifacts of ```profile``` into, right
+    /// after the search paths. The target directory is found with [cargo_target_dir] so
+    /// ```CARGO_TARGET_DIR``` and workspaces are taken into account, which a hard coded
+    /// ```"target/debug"``` search path doesn't. ```None``` turns it off, which is the default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Finds the plugin built with the same profile as the application
+    /// dr.set_cargo_profile(Some(Profile::current()));
+    /// let lib = unsafe { dr.add_library("my_plugin", PlatformName::Yes)? };
+    /// ```
+    ///
+    pub fn set_cargo_profile(&mut self, profile: Option<Profile>) {
+        self.cargo_profile = profile;
+    }
+
+    ///
+    /// Makes this instance part of a cluster of processes that reload libraries together, see
+    /// [Cluster]. A coordinator publishes the currently loaded libraries right away and then
+    /// every time a library is added or reloaded. A follower ignores changes to its own files
+    /// and instead reloads its libraries from what the coordinator has published when
+    /// [update](struct.DynamicReload.html#method.update) is called.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let cluster = if is_main_process {
+    ///     Cluster::coordinator("target/cluster")?
+    /// } else {
+    ///     Cluster::follower("target/cluster")?
+    /// };
+    ///
+    /// dr.set_cluster(Some(cluster))?;
+    /// ```
+    ///
+    pub fn set_cluster(&mut self, cluster: Option<Cluster>) -> Result<()> {
+        self.cluster = cluster;
+        self.publish_to_cluster()
+    }
+
+    ///
+    /// Returns the cluster this instance is part of, if any.
+    ///
+    pub fn cluster(&self) -> Option<&Cluster> {
+        self.cluster.as_ref()
+    }
+
+    fn publish_to_cluster(&mut self) -> Result<()> {
+        let cluster = match self.cluster.as_mut() {
+            Some(c) if c.role() == ClusterRole::Coordinator => c,
+            _ => return Ok(()),
+        };
+
+        let libs: Vec<(&Path, &Path)> = self
+            .libs
+            .iter()
+            .filter_map(|lib| {
+                let original = lib.original_path.as_ref()?;
+                Some((original.as_path(), lib.loaded_path.as_path()))
+            })
+            .collect();
+
+        if libs.is_empty() {
+            return Ok(());
+        }
+
+        cluster.publish(&libs)
+    }
+
+    unsafe fn apply_cluster_generation<F, T>(&mut self, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        let staged = match self.cluster.as_mut() {
+            Some(c) if c.role() == ClusterRole::Follower => c.poll(),
             _ => None,
+        };
+
+        for staged in staged.unwrap_or_default() {
+            let index = self.libs.iter().position(|lib| {
+                lib.original_path
+                    .as_ref()
+                    .and_then(|p| p.file_name())
+                    .is_some_and(|name| name.to_string_lossy() == staged.file_name)
+            });
+
+            let index = match index {
+                Some(index) => index,
+                None => continue,
+            };
+
+            // Every library is staged with each generation, only reload the ones that changed
+            let hash = hash::hash_file(&staged.path).ok();
+            if hash.is_some() && hash == self.libs[index].content_hash {
+                continue;
+            }
+
+            Self::reload_lib(self, index, &staged.path, update_call, data);
+        }
+    }
+
+    ///
+    /// Starts watching for changes again after
+    /// [stop_watching](struct.DynamicReload.html#method.stop_watching). Any existing watcher is
+    /// replaced and all libraries and recursive search paths are watched again.
+    ///
+    pub fn start_watching(&mut self) -> Result<()> {
+        self.start_watcher(self.watcher_backend)
+    }
+
+    fn start_watcher(&mut self, backend: WatcherBackend) -> Result<()> {
+        self.stop_watching();
+
+        let (tx, rx) = channel();
+
+        let debounce_duration = match self.debounce_strategy {
+            Some(_) => None,
+            None => Some(self.debounce_duration),
+        };
+
+        self.watcher = FileWatcher::new(tx, backend, debounce_duration, self.poll_fallback)
+            .map_err(Error::Watcher)?;
+        self.watcher_error = None;
+        self.watch_recv = Mutex::new(rx);
+
+        for root in self.recursive_paths.clone() {
+            self.watch_recursive(&root)?;
+        }
+
+        for lib in self.libs.clone() {
+            self.watch_lib(&lib);
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Sets how copying libraries to the shadow directory is retried when the file is locked or
+    /// still being written. See [CopyRetry] for the defaults.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Slow CI machine, allow up to 30 attempts
+    /// dr.set_copy_retry(CopyRetry { attempts: 30, ..CopyRetry::default() });
+    /// ```
+    ///
+    pub fn set_copy_retry(&mut self, retry: CopyRetry) {
+        self.copy_retry = retry;
+    }
+
+    ///
+    /// Sets how the shadow copy is verified against the original library before it's loaded.
+    /// A copy that doesn't match is retried. Default is [CopyVerify::Size].
+    ///
+    pub fn set_copy_verify(&mut self, verify: CopyVerify) {
+        self.copy_verify = verify;
+    }
+
+    ///
+    /// Signs shadow copies with an ad-hoc signature (```codesign --force --sign -```) before
+    /// they are loaded. Copying a library drops the signature added by the linker and on Apple
+    /// Silicon unsigned libraries are killed by the OS when loaded. Only has an effect on macOS
+    /// and requires the ```codesign``` tool (part of the Xcode command line tools). Disabled by
+    /// default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.set_codesign(cfg!(all(target_os = "macos", target_arch = "aarch64")));
+    /// ```
+    ///
+    pub fn set_codesign(&mut self, codesign: bool) {
+        self.codesign = codesign;
+    }
+
+    ///
+    /// Copies the debug info of libraries to the shadow directory along with the library so
+    /// debuggers can find the symbols of the loaded copy. Each load gets a directory of its own
+    /// in the shadow directory that is removed when the library is unloaded. The ```.pdb```
+    /// next to a DLL is copied under the name recorded in the DLL and the ```.dSYM``` bundle
+    /// next to a dylib is named after the shadow copy. Libraries without debug info are loaded
+    /// as usual. Disabled by default.
+    ///
+    pub fn set_copy_debug_info(&mut self, copy: bool) {
+        self.copy_debug_info = copy;
+    }
+
+    ///
+    /// Loads the previous version of a library again when reloading it fails. After
+    /// ```ReloadFailed``` the update callback is called with ```RolledBack``` and the previous
+    /// version, which stays tracked and is reloaded as usual on the next change. Requires a
+    /// shadow directory as the previous version is loaded from its shadow copy. With the
+    /// ```no-timestamps``` feature each version is copied into a directory of its own so the
+    /// copy of the previous version is kept. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.set_rollback(true);
+    ///
+    /// dr.update(&|app: &mut App, state, lib| match state {
+    ///     UpdateState::ReloadFailed(e) => println!("Reload failed: {}", e),
+    ///     // The old code is running again
+    ///     UpdateState::After | UpdateState::RolledBack => app.restore(lib.unwrap()),
+    ///     _ => (),
+    /// }, &mut app);
+    /// ```
+    ///
+    pub fn set_rollback(&mut self, rollback: bool) {
+        self.rollback = rollback;
+    }
+
+    ///
+    /// Enables loading each library in a helper process before it's loaded into the host, see
+    /// [Preflight]. If the helper fails the load fails (or reports ```ReloadFailed``` on reload)
+    /// without risking the host process. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// fn main() {
+    ///     dynamic_reload::preflight_main();
+    ///
+    ///     let mut dr = DynamicReload::new(None, Some("target/debug"), Search::Default, Duration::from_secs(2));
+    ///     dr.set_preflight(Some(Preflight::current_exe()));
+    /// }
+    /// ```
+    ///
+    pub fn set_preflight(&mut self, preflight: Option<Preflight>) {
+        self.preflight = preflight;
+    }
+
+    ///
+    /// Checks the new version of a library after it has been loaded during a reload, see
+    /// [HealthCheck]. A version that fails is dropped before it's used and the previous version
+    /// is used again, reported with ```UpdateState::HealthCheckFailed```. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Plugins export dr_health_check to test themselves
+    /// dr.set_health_check(Some(HealthCheck::Symbol));
+    ///
+    /// // Or check from the application
+    /// dr.set_health_check(Some(HealthCheck::Custom(Box::new(|lib| {
+    ///     match lib.has_symbol("plugin_create") {
+    ///         true => Ok(()),
+    ///         false => Err("plugin_create is missing".to_string()),
+    ///     }
+    /// }))));
+    /// ```
+    ///
+    pub fn set_health_check(&mut self, check: Option<HealthCheck>) {
+        self.health_check = check;
+    }
+
+    ///
+    /// Only accepts libraries whose ```abi``` metadata (see [ABI_KEY]) is ```tag```, usually
+    /// embedded with [plugin_metadata]. Other libraries, including ones without the key, fail to
+    /// load with ```Error::Incompatible```. The tag is checked before the init symbol and
+    /// [HOST_API_INIT] are called. On reload the new version is checked before the old one is
+    /// unloaded so the old version stays in use. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // In the plugin
+    /// dynamic_reload::plugin_metadata! { name = "foo", abi = "foo-api-2" }
+    ///
+    /// // In the host
+    /// dr.set_abi_tag(Some("foo-api-2"));
+    /// ```
+    ///
+    pub fn set_abi_tag(&mut self, tag: Option<&str>) {
+        self.abi_tag = tag.map(|t| t.to_owned());
+    }
+
+    ///
+    /// Only accepts plugins built with the same version of rustc as the application (see
+    /// [RUSTC_VERSION]) for the same ```interface```, exported with [plugin_fingerprint].
+    /// Other libraries, including ones without a fingerprint, fail to load with
+    /// ```Error::Fingerprint``` telling what differs. The fingerprint is checked before the init
+    /// symbol and [HOST_API_INIT] are called. On reload the new version is checked before the
+    /// old one is unloaded so the old version stays in use. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // In the plugin
+    /// dynamic_reload::plugin_fingerprint!("renderer-api-3");
+    ///
+    /// // In the host
+    /// dr.set_fingerprint(Some("renderer-api-3"));
+    /// ```
+    ///
+    pub fn set_fingerprint(&mut self, interface: Option<&str>) {
+        self.fingerprint = interface.map(|i| i.to_owned());
+    }
+
+    ///
+    /// Only loads libraries with a detached Ed25519 signature made with the private half of
+    /// ```key```, placed next to the library (see [SIGNATURE_EXTENSION]). Libraries without a
+    /// valid signature fail to load with ```Error::SignatureInvalid```. The shadow copy is
+    /// verified so the file can't change between the check and the load. On reload the new
+    /// version is checked before the old one is unloaded so the old version stays in use.
+    /// Changes to the signature are watched as well, so it can be written after the library.
+    /// Requires the ```signatures``` feature. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let key = VerifyingKey::from_bytes(include_bytes!("plugins.pub"))?;
+    /// dr.set_public_key(Some(key));
+    /// ```
+    ///
+    #[cfg(feature = "signatures")]
+    pub fn set_public_key(&mut self, key: Option<VerifyingKey>) {
+        self.public_key = key;
+    }
+
+    ///
+    /// Only loads libraries whose SHA-256 digest is listed in the manifest at ```path```, in the
+    /// format written by ```sha256sum```. Entries are matched by file name. Libraries that aren't
+    /// listed or don't match fail to load with ```Error::ChecksumMismatch```. The manifest is
+    /// read on every load so new builds can be vetted by updating it. The shadow copy is checked
+    /// so the file can't change between the check and the load. On reload the new version is
+    /// checked before the old one is unloaded so the old version stays in use. Requires the
+    /// ```checksums``` feature. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // sha256sum libfoo.so libbar.so > plugins.sha256
+    /// dr.set_checksum_manifest(Some(Path::new("plugins/plugins.sha256")));
+    /// ```
+    ///
+    #[cfg(feature = "checksums")]
+    pub fn set_checksum_manifest(&mut self, path: Option<&Path>) {
+        self.checksum_manifest = path.map(Path::to_path_buf);
+    }
+
+    ///
+    /// Sets the name of an ```extern "C" fn()``` that is called right after a library has been
+    /// loaded (if the library exports it). Use this for plugins that needs to be initialized
+    /// before they are handed to the application.
+    ///
+    pub fn set_init_symbol(&mut self, symbol: Option<&str>) {
+        self.init_symbol = symbol.map(|s| s.to_owned());
+    }
+
+    ///
+    /// Runs loading of libraries (including constructors and the init symbol) under a watchdog.
+    /// If it doesn't finish within ```timeout``` the load fails with ```Error::InitTimeout```
+    /// (reported through ```ReloadFailed``` on reloads) instead of freezing the application.
+    /// The hung thread can't be stopped and is left running. The library is leaked, it's never
+    /// unloaded as its code may still be running, so each timeout costs the memory of one
+    /// copy of the library. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.set_init_symbol(Some("plugin_init"));
+    /// dr.set_init_timeout(Some(Duration::from_secs(5)));
+    /// ```
+    ///
+    pub fn set_init_timeout(&mut self, timeout: Option<Duration>) {
+        self.init_timeout = timeout;
+    }
+
+    ///
+    /// Sets a pointer to the API the application exposes to libraries, typically a
+    /// ```#[repr(C)]``` struct of function pointers. Each time a library is loaded or reloaded
+    /// it's passed to ```dr_plugin_init``` ([HOST_API_INIT]) if the library exports it, right
+    /// after the init symbol. The pointer has to stay valid for as long as libraries are loaded
+    /// and be usable from the thread loading them, see
+    /// [set_init_timeout](struct.DynamicReload.html#method.set_init_timeout).
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// #[repr(C)]
+    /// struct HostApi {
+    ///     log: extern "C" fn(*const c_char),
+    /// }
+    ///
+    /// static HOST_API: HostApi = HostApi { log: host_log };
+    ///
+    /// dr.set_host_api(Some(&HOST_API as *const HostApi as *const c_void));
+    ///
+    /// // In the library
+    /// #[no_mangle]
+    /// pub extern "C" fn dr_plugin_init(host_api: *const c_void) { ... }
+    /// ```
+    ///
+    pub fn set_host_api(&mut self, api: Option<*const c_void>) {
+        self.host_api = api.map(HostApi);
+    }
+
+    ///
+    /// Sets the functions of a library that are called around reloads, see
+    /// [LifecycleSymbols]. By default ```dr_before_reload``` is called before a library is
+    /// unloaded by a reload and ```dr_after_load``` each time it has been loaded, if the library
+    /// exports them.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.set_lifecycle_symbols(LifecycleSymbols {
+    ///     before_reload: Some("plugin_suspend".to_owned()),
+    ///     after_load: None,
+    /// });
+    /// ```
+    ///
+    pub fn set_lifecycle_symbols(&mut self, symbols: LifecycleSymbols) {
+        self.lifecycle = symbols;
+    }
+
+    ///
+    /// Adds a root directory that is searched and watched recursively.
+    ///
+    /// This is intended for plugin layouts where each plugin lives in its own sub directory
+    /// (```plugins/foo/libfoo.so```). [add_library](struct.DynamicReload.html#method.add_library)
+    /// will look for the library in all sub directories of the root (after the regular search
+    /// paths) and changes anywhere under the root are picked up, also for sub directories that
+    /// are created after this call.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.add_recursive_search_path("plugins")?;
+    /// // finds plugins/foo/libfoo.so
+    /// dr.add_library("foo", PlatformName::Yes)?;
+    /// ```
+    ///
+    pub fn add_recursive_search_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let root = winpath::canonicalize(path)
+            .map_err(|_| Error::Find(path.to_string_lossy().into_owned(), Vec::new()))?;
+
+        if !self.recursive_paths.contains(&root) {
+            self.watch_recursive(&root)?;
+            self.recursive_paths.push(root);
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Loads all libraries in a directory and keeps it in sync: libraries that show up in the
+    /// directory later on are loaded (calling ```update_call``` with ```UpdateState::Added```
+    /// from [update](struct.DynamicReload.html#method.update)) and libraries that are deleted
+    /// are reported with ```UpdateState::Removed``` and then removed.
+    ///
+    /// ```filter``` is a glob pattern for the file names to load, by default all files with
+    /// the library extension of the platform are loaded. Libraries that fail to load are
+    /// listed by [iter_libs](struct.DynamicReload.html#method.iter_libs) and tried again when
+    /// the file changes. Returns the libraries that were loaded.
+    ///
+    /// Loading new libraries, reloading and removing them requires a shadow directory.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// for lib in unsafe { dr.add_directory("plugins", Some("*_plugin.so"))? } {
+    ///     plugins.add_plugin(&lib);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library).
+    ///
+    pub unsafe fn add_directory<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        filter: Option<&str>,
+    ) -> Result<Vec<Arc<Lib>>> {
+        let path = path.as_ref();
+        let not_found = || Error::Find(path.to_string_lossy().into_owned(), Vec::new());
+        let dir = winpath::canonicalize(path).map_err(|_| not_found())?;
+
+        let filter = filter
+            .map(glob::Pattern::new)
+            .transpose()
+            .map_err(Error::Pattern)?;
+
+        let entries = fs::read_dir(&dir).map_err(|_| not_found())?;
+
+        if !self.directories.iter().any(|(d, _)| *d == dir) {
+            self.watch_path(&dir, RecursiveMode::NonRecursive)?;
+            self.directories.push((dir.clone(), filter));
+        }
+
+        let mut paths: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && self.directory_for(path).is_some())
+            .collect();
+        paths.sort();
+
+        let mut libs = Vec::new();
+
+        for path in paths {
+            match self.add_library_file(&path) {
+                Ok(lib) => libs.push(lib),
+                Err(e) => self.load_failed(&path, &e),
+            }
+        }
+
+        Ok(libs)
+    }
+
+    // The directory added with add_directory that the file belongs to, if it passes the filter
+    fn directory_for(&self, path: &Path) -> Option<&Path> {
+        let parent = path.parent()?;
+        let name = path.file_name()?.to_string_lossy();
+
+        self.directories
+            .iter()
+            .find(|(dir, _)| dir == parent)
+            .filter(|(_, filter)| match filter {
+                Some(filter) => filter.matches(&name),
+                None => name.ends_with(env::consts::DLL_SUFFIX),
+            })
+            .map(|(dir, _)| dir.as_path())
+    }
+
+    // Lists a library that couldn't be loaded as failed in iter_libs
+    fn load_failed(&mut self, path: &Path, e: &Error) {
+        self.failed.retain(|f| f.path != path);
+        self.failed.push(FailedLib {
+            path: path.to_path_buf(),
+            metadata: Metadata::new(),
+            userdata: Userdata::default(),
+            generation: None,
+            error: e.to_string(),
+        });
+    }
+
+    // Loads a library that has shown up in a directory added with add_directory
+    unsafe fn add_from_directory<F, T>(&mut self, path: &Path, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        if !path.is_file() {
+            return;
+        }
+
+        let lib = match self.load_library(path, None) {
+            Ok(lib) => lib,
+            Err(e) => {
+                self.load_failed(path, &e);
+                self.notify(update_call, data, UpdateState::ReloadFailed(e), None);
+                return;
+            }
+        };
+
+        match self.track_lib(lib) {
+            Ok(lib) => self.notify(update_call, data, UpdateState::Added, Some(&lib)),
+            Err(e) => self.notify(update_call, data, UpdateState::ReloadFailed(e), None),
+        }
+    }
+
+    fn watch_recursive(&mut self, root: &Path) -> Result<()> {
+        self.watch_path(root, RecursiveMode::Recursive)
+    }
+
+    // Watches the path, switching over to polling if the native backend isn't able to
+    fn watch_path(&mut self, path: &Path, mode: RecursiveMode) -> Result<()> {
+        let w = match self.watcher.as_mut() {
+            Some(w) => w,
+            None => return Ok(()),
+        };
+
+        // Deep build directories are past MAX_PATH on Windows
+        let path = &winpath::extended(path);
+        match w.watcher().watch(path, mode) {
+            Ok(()) => {
+                self.watched_dirs.add(path, mode);
+                Ok(())
+            }
+            Err(e) => match self.poll_fallback {
+                Some(interval)
+                    if w.backend() == WatcherBackend::Recommended
+                        && watcher::needs_fallback(&e) =>
+                {
+                    self.start_watcher(WatcherBackend::Poll { interval })?;
+                    self.watch_path(path, mode)
+                }
+                _ => Err(Error::Watcher(e)),
+            },
+        }
+    }
+
+    ///
+    /// Add a library to be loaded and to be reloaded once updated.
+    /// If PlatformName is set to Yes the input name will be formatted according
+    /// to the standard way libraries looks on that platform examples:
+    ///
+    /// ```ignore
+    /// Windows: foobar -> foobar.dll
+    /// Linux:   foobar -> libfoobar.so
+    /// Mac:     foobar -> libfoobar.dylib
+    /// ```
+    ///
+    /// If set to no the given input name will be used as is. This function
+    /// will also search for the file in this priority order
+    ///
+    /// ```ignore
+    /// 1. Current directory
+    /// 2. In the search paths (relative to current directory)
+    /// 3. In the recursive search paths and their sub directories
+    /// 4. The artifacts of the cargo profile if enabled with set_cargo_profile
+    /// 5. The directories of the dynamic linker if enabled with set_loader_paths
+    /// 6. Current directory of the executable
+    /// 7. Search backwards from executable if Backwards or System has been set in DynamicReload::new
+    /// 8. The standard locations of the OS if System has been set
+    /// ```
+    ///
+    /// The first file found is loaded unless another [SelectionPolicy] is set with
+    /// [set_selection_policy](struct.DynamicReload.html#method.set_selection_policy).
+    ///
+    /// If the file that is found is already loaded it's loaded again and replaces the version
+    /// that was loaded, which is no longer reloaded.
+    ///
+    /// With ```Search::Custom``` the [SearchStrategy] is used instead.
+    /// [resolve_library](struct.DynamicReload.html#method.resolve_library) shows which paths
+    /// are checked.
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Add a library named test_lib and format it according to standard platform standard.
+    /// add_library("test_lib", PlatformName::Yes)
+    /// ```
+    /// # Safety
+    /// Note taken from libloading that is used for library loading
+    ///
+    /// When a library is loaded, initialisation routines contained within it are executed.
+    /// For the purposes of safety, the execution of these routines is conceptually the same calling an unknown foreign function and may impose arbitrary requirements on the caller for the call to be sound.
+    /// Additionally, the callers of this function must also ensure that execution of the termination routines contained within
+    /// the library is safe as well. These routines may be executed when the library is unloaded.
+    ///
+    pub unsafe fn add_library(
+        &mut self,
+        name: &str,
+        name_format: PlatformName,
+    ) -> Result<Arc<Lib>> {
+        match Self::try_load_library(self, name, name_format) {
+            Ok(lib) => self.track_lib(lib),
+            Err(e) => Err(e),
+        }
+    }
+
+    ///
+    /// Searches for a library the same way as
+    /// [add_library](struct.DynamicReload.html#method.add_library) without loading it.
+    /// Returns the path that would be loaded together with all paths that were checked and
+    /// why they weren't used, to find out why a library is loaded from an unexpected place or
+    /// not found at all.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let report = dr.resolve_library("test_lib", PlatformName::Yes);
+    /// for candidate in &report.candidates {
+    ///     println!("{:?}: {:?}", candidate.path, candidate.rejected);
+    /// }
+    /// ```
+    ///
+    pub fn resolve_library(&self, name: &str, name_format: PlatformName) -> ResolveReport {
+        let lib_name = Self::get_library_name(name, name_format);
+        let mut searched = Searched::new(self.selection_policy != SelectionPolicy::FirstMatch);
+        let path = self
+            .select_library(name, lib_name.as_ref(), &mut searched)
+            .ok();
+
+        ResolveReport {
+            lib_name,
+            path,
+            candidates: searched.candidates,
+        }
+    }
+
+    ///
+    /// Same as [add_library](struct.DynamicReload.html#method.add_library) but with
+    /// [LoadOptions] for this library only. The options are kept for as long as the library is
+    /// loaded and are used when it's reloaded as well.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // Written in several steps by a slow build script
+    /// let lib = unsafe {
+    ///     dr.add_library_with_options("physics", LoadOptions {
+    ///         debounce: Some(Duration::from_secs(5)),
+    ///         ..LoadOptions::default()
+    ///     })?
+    /// };
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library).
+    ///
+    pub unsafe fn add_library_with_options(
+        &mut self,
+        name: &str,
+        options: LoadOptions,
+    ) -> Result<Arc<Lib>> {
+        let path = self.find_library(name, options.platform_name)?;
+
+        let previous = self.set_options(&path, Some(options));
+
+        match Self::load_library(self, &path, None) {
+            Ok(lib) => self.track_lib(lib),
+            Err(e) => {
+                self.set_options(&path, previous);
+                Err(e)
+            }
+        }
+    }
+
+    ///
+    /// Adds a library and resolves the functions of ```A``` from it, see [LibApi] and
+    /// [lib_api]. The returned [TypedLib] is loaded again each time the library is reloaded.
+    /// All functions of ```A``` are required symbols so a version of the library that doesn't
+    /// export them fails to load with ```Error::MissingSymbol``` and the previous version is
+    /// kept.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let math = unsafe { dr.add_typed::<MathApi>("math", PlatformName::Yes)? };
+    ///
+    /// loop {
+    ///     unsafe { dr.update(&Plugins::reload_callback, &mut plugins) };
+    ///     if let Some(api) = math.get() {
+    ///         println!("{}", (api.add)(1, 2));
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library). The types of the
+    /// functions in ```A``` must match what the library exports.
+    ///
+    pub unsafe fn add_typed<A: LibApi>(
+        &mut self,
+        name: &str,
+        name_format: PlatformName,
+    ) -> Result<TypedLib<A>> {
+        let options = LoadOptions {
+            platform_name: name_format,
+            required_symbols: A::SYMBOLS.iter().map(|s| s.to_string()).collect(),
+            ..LoadOptions::default()
+        };
+
+        let lib = self.add_library_with_options(name, options)?;
+        let typed = TypedLib::new(&lib)?;
+        self.bindings.add(&lib, typed.rebind());
+        Ok(typed)
+    }
+
+    ///
+    /// Sets the symbols ```lib``` has to export, see ```required_symbols``` of [LoadOptions].
+    /// The current version isn't checked, only the versions loaded by later reloads.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let lib = unsafe { dr.add_library("my_plugin", PlatformName::Yes)? };
+    /// dr.set_required_symbols(&lib, &["plugin_create", "plugin_destroy"]);
+    /// ```
+    ///
+    pub fn set_required_symbols(&mut self, lib: &Lib, symbols: &[&str]) {
+        let path = lib.source_path();
+        let mut options = self.set_options(path, None).unwrap_or_default();
+        options.required_symbols = symbols.iter().map(|s| s.to_string()).collect();
+        self.set_options(path, Some(options));
+    }
+
+    // Sets the options for the library loaded from path, returning the previous ones
+    fn set_options(&mut self, path: &Path, options: Option<LoadOptions>) -> Option<LoadOptions> {
+        let index = self.options.iter().position(|(p, _)| p == path);
+        let previous = index.map(|index| self.options.remove(index).1);
+
+        if let Some(options) = options {
+            self.options.push((path.to_path_buf(), options));
+        }
+
+        previous
+    }
+
+    fn load_options(&self, path: &Path) -> Option<&LoadOptions> {
+        self.options
+            .iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, options)| options)
+    }
+
+    ///
+    /// Builds the crate at ```crate_dir``` with ```cargo build``` and the given profile, then
+    /// loads and watches the dynamic library it produced like
+    /// [add_library_path](struct.DynamicReload.html#method.add_library_path). The build
+    /// blocks until cargo is done. The crate needs a ```cdylib``` or ```dylib``` crate type.
+    /// Requires the ```cargo``` feature.
+    ///
+    /// Fails with ```Error::Cargo```, which has the output of cargo, if the build fails. Use
+    /// [watch_sources](struct.DynamicReload.html#method.watch_sources) to build it again when
+    /// its sources change.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let lib = unsafe { dr.compile_and_add(Path::new("plugins/physics"), Profile::Debug)? };
+    /// dr.watch_sources(&lib, Path::new("plugins/physics"))?;
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library).
+    ///
+    #[cfg(feature = "cargo")]
+    pub unsafe fn compile_and_add<P: AsRef<Path>>(
+        &mut self,
+        crate_dir: P,
+        profile: Profile,
+    ) -> Result<Arc<Lib>> {
+        let path = cargo::compile(crate_dir.as_ref(), &profile)?;
+        self.add_library_path(&path)
+    }
+
+    ///
+    /// Rebuilds ```lib``` with ```cargo build``` when the sources in the ```src``` directory of
+    /// the crate at ```crate_dir``` change, for a full edit, build and reload loop. The build
+    /// runs in the background with the profile set with
+    /// [set_cargo_profile](struct.DynamicReload.html#method.set_cargo_profile), or the profile
+    /// of the application if none is set, and is reported from
+    /// [update](struct.DynamicReload.html#method.update) with ```UpdateState::BuildStarted```
+    /// followed by ```BuildFinished``` or ```BuildFailed```. The new version is reloaded as
+    /// usual when cargo writes it, so the library has to be loaded from where cargo puts it.
+    /// Sources that change during a build are built again once it's done.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let lib = unsafe { dr.add_library("my_plugin", PlatformName::Yes)? };
+    /// dr.watch_sources(&lib, Path::new("plugins/my_plugin"))?;
+    ///
+    /// // In the update callback
+    /// UpdateState::BuildFailed(e) => console.show(&e.to_string()),
+    /// ```
+    ///
+    pub fn watch_sources<P: AsRef<Path>>(&mut self, lib: &Lib, crate_dir: P) -> Result<()> {
+        let crate_dir = crate_dir.as_ref();
+        let manifest = crate_dir.join("Cargo.toml");
+        if !manifest.is_file() {
+            let name = manifest.to_string_lossy().into_owned();
+            return Err(Error::Find(name, vec![manifest]));
+        }
+
+        let src = crate_dir.join("src");
+        let dir = winpath::canonicalize(&src)
+            .map_err(|_| Error::Find(src.to_string_lossy().into_owned(), vec![src]))?;
+
+        self.watch_recursive(&dir)?;
+        let profile = self.cargo_profile.clone().unwrap_or_default();
+        self.rebuilds
+            .add(dir, manifest, lib.source_path().to_path_buf(), profile);
+        Ok(())
+    }
+
+    ///
+    /// Stops rebuilding ```lib``` when its sources change, see
+    /// [watch_sources](struct.DynamicReload.html#method.watch_sources). Returns false if its
+    /// sources weren't watched. A build that is running is still reported.
+    ///
+    pub fn unwatch_sources(&mut self, lib: &Lib) -> bool {
+        self.rebuilds.remove(lib.source_path())
+    }
+
+    ///
+    /// Loads and watches the dynamic library built for the cargo package ```package```, found
+    /// by running ```cargo metadata``` in the current directory. The library is taken from the
+    /// target directory of the workspace for the profile set with
+    /// [set_cargo_profile](struct.DynamicReload.html#method.set_cargo_profile), or the profile
+    /// of the application if none is set. The package has to be built already and have a
+    /// ```cdylib``` or ```dylib``` crate type. Requires the ```cargo``` feature.
+    ///
+    /// Fails with ```Error::Cargo``` if cargo can't be run or the package isn't found and with
+    /// ```Error::Find``` if the library hasn't been built.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // No more hard coded "target/debug" paths
+    /// let lib = unsafe { dr.add_cargo_artifact("my_plugin_crate")? };
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library).
+    ///
+    #[cfg(feature = "cargo")]
+    pub unsafe fn add_cargo_artifact(&mut self, package: &str) -> Result<Arc<Lib>> {
+        let profile = self.cargo_profile.clone().unwrap_or_default();
+        let path = cargo::artifact_path(package, &profile)?;
+        self.add_library_path(&path)
+    }
+
+    ///
+    /// Loads the library at ```path``` without any name formatting or searching, for hosts that
+    /// already know where the library is. It's shadow copied and watched the same way as with
+    /// [add_library](struct.DynamicReload.html#method.add_library). If the file is already
+    /// loaded the loaded library is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let lib = unsafe { dr.add_library_path(Path::new("/opt/app/plugins/libaudio.so"))? };
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library).
+    ///
+    pub unsafe fn add_library_path<P: AsRef<Path>>(&mut self, path: P) -> Result<Arc<Lib>> {
+        let path = path.as_ref();
+        let file = if self.follow_symlinks {
+            Ok(Self::canonical_path(path))
+        } else {
+            winpath::canonicalize(path)
+        };
+        let file = file
+            .ok()
+            .filter(|p| p.is_file())
+            .ok_or_else(|| Error::Find(path.to_string_lossy().into_owned(), Vec::new()))?;
+
+        self.add_library_file(&file)
+    }
+
+    ///
+    /// Loads all libraries matching a glob pattern. Only the file name part of the pattern is
+    /// formatted according to ```name_format``` so ```"plugins/*_plugin"``` matches
+    /// ```plugins/libfoo_plugin.so``` on Linux. The pattern is resolved relative to the current
+    /// directory, the search paths, the recursive search paths (including their sub
+    /// directories) and the directory of the executable.
+    ///
+    /// Returns each matching file with the result of loading it, so one broken library doesn't
+    /// stop the others from being loaded. Libraries that failed to load are also listed by
+    /// [iter_libs](struct.DynamicReload.html#method.iter_libs). Libraries that are already loaded
+    /// are returned as they are. Fails only if the pattern is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// for (path, lib) in unsafe { dr.add_libraries_matching("plugins/*_plugin", PlatformName::Yes)? } {
+    ///     match lib {
+    ///         Ok(lib) => plugins.add_plugin(&lib),
+    ///         Err(e) => println!("Unable to load {:?}: {}", path, e),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library).
+    ///
+    pub unsafe fn add_libraries_matching(
+        &mut self,
+        pattern: &str,
+        name_format: PlatformName,
+    ) -> Result<Vec<(PathBuf, Result<Arc<Lib>>)>> {
+        let pattern = Path::new(pattern);
+        let file_pattern = match pattern.file_name() {
+            Some(name) => Self::get_library_name(&name.to_string_lossy(), name_format),
+            None => return Ok(Vec::new()),
+        };
+
+        let pattern = pattern.with_file_name(file_pattern);
+        let pattern = pattern.to_string_lossy();
+        glob::Pattern::new(&pattern).map_err(Error::Pattern)?;
+
+        let mut patterns = Vec::new();
+
+        if Path::new(pattern.as_ref()).is_absolute() {
+            patterns.push(pattern.to_string());
+        } else {
+            let escape = |dir: &Path| glob::Pattern::escape(&dir.to_string_lossy());
+
+            patterns.push(pattern.to_string());
+            for dir in &self.search_paths {
+                patterns.push(format!("{}/{}", escape(dir), pattern));
+            }
+            for root in &self.recursive_paths {
+                patterns.push(format!("{}/**/{}", escape(root), pattern));
+            }
+            if let Some(dir) = search::exe_dir() {
+                patterns.push(format!("{}/{}", escape(&dir), pattern));
+            }
+        }
+
+        let mut paths: Vec<PathBuf> = Vec::new();
+
+        for pattern in patterns {
+            let matches = glob::glob(&pattern).map_err(Error::Pattern)?;
+            for path in matches.flatten().filter(|p| p.is_file()) {
+                let path = winpath::canonicalize(&path).unwrap_or(path);
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+
+        let mut libs = Vec::with_capacity(paths.len());
+        for path in paths {
+            let lib = self.add_library_file(&path);
+            if let Err(e) = lib.as_ref() {
+                self.load_failed(&path, e);
+            }
+            libs.push((path, lib));
+        }
+
+        Ok(libs)
+    }
+
+    // Loads the file unless it's already loaded
+    unsafe fn add_library_file(&mut self, path: &Path) -> Result<Arc<Lib>> {
+        if let Some(index) = self.libs.find(path) {
+            return Ok(self.libs[index].clone());
+        }
+
+        let lib = self.load_library(path, None)?;
+        self.track_lib(lib)
+    }
+
+    ///
+    /// Same as [add_library](struct.DynamicReload.html#method.add_library) but the symbols of
+    /// the library are made available to libraries loaded after it (```RTLD_GLOBAL```), also
+    /// when it's reloaded. Use together with
+    /// [add_dependency](struct.DynamicReload.html#method.add_dependency) for libraries that use
+    /// the symbols so they are reloaded along with it. On Windows this is the same as
+    /// add_library.
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library). In addition the global
+    /// symbols may be picked up by any library loaded later on.
+    ///
+    pub unsafe fn add_global_library(
+        &mut self,
+        name: &str,
+        name_format: PlatformName,
+    ) -> Result<Arc<Lib>> {
+        let options = LoadOptions {
+            platform_name: name_format,
+            global: true,
+            ..LoadOptions::default()
+        };
+
+        self.add_library_with_options(name, options)
+    }
+
+    ///
+    /// Declares that ```consumer``` uses symbols of ```provider```.
+    ///
+    /// When ```provider``` changes, ```consumer``` is unloaded before it and loaded again after
+    /// the new version of ```provider``` has been loaded. This cascades to libraries depending
+    /// on ```consumer``` and so on. The ```Before``` callbacks are made in unload order
+    /// (consumers first) and ```After``` in load order (providers first).
+    ///
+    /// Fails with ```Error::DependencyCycle``` if ```provider``` already depends on
+    /// ```consumer```.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let core = dr.add_global_library("core", PlatformName::Yes)?;
+    /// let render = dr.add_library("render", PlatformName::Yes)?;
+    /// dr.add_dependency(&render, &core)?;
+    /// ```
+    ///
+    pub fn add_dependency(&mut self, consumer: &Lib, provider: &Lib) -> Result<()> {
+        self.dependencies
+            .add(provider.source_path(), consumer.source_path())
+    }
+
+    ///
+    /// Stops tracking a library. It's no longer reloaded and the directory it's in is no longer
+    /// watched unless other libraries are in it. Dependencies, injected failures and pending
+    /// changes of the library are removed as well. Returns false if the library isn't tracked.
+    ///
+    /// DynamicReload drops its reference to the library, it's unloaded once all references
+    /// held by the application are dropped. Use
+    /// [remove_library_with](struct.DynamicReload.html#method.remove_library_with) to get a
+    /// callback before that happens.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let lib = dr.add_library("test_shared", PlatformName::Yes)?;
+    /// // ...
+    /// dr.remove_library(&lib);
+    /// ```
+    ///
+    pub fn remove_library(&mut self, lib: &Lib) -> bool {
+        let path = lib.source_path().to_path_buf();
+        let index = match self.libs.position(&path) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let removed = self.libs[index].clone();
+        let target = self.linked_target(&removed);
+        self.link_targets
+            .retain(|(link, _)| Some(link) != removed.original_path.as_ref());
+        Self::remove_lib(self, index);
+
+        let target_dir = target.as_deref().and_then(Path::parent);
+        for dir in self
+            .watch_dir(&removed)
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(target_dir)
+        {
+            self.release_watch(dir);
+        }
+
+        self.dependencies.remove(&path);
+        self.injections.take(&path);
+        self.failed.retain(|f| f.path != path);
+        self.retries.remove(&path);
+        self.missing_paths.retain(|p| *p != path);
+        self.guarded.retain(|p| *p != path);
+        self.missing_since.retain(|(p, _)| *p != path);
+        self.removed.retain(|p| *p != path);
+        self.delayed.retain(|(p, _, _)| *p != path);
+        self.set_options(&path, None);
+        self.relocated.retain(|(from, to)| *from != path && *to != path);
+
+        if let Err(e) = self.publish_to_cluster() {
+            log::warn!("Unable to publish to cluster: {}", e);
+        }
+
+        true
+    }
+
+    ///
+    /// Same as [remove_library](struct.DynamicReload.html#method.remove_library) but calls
+    /// ```update_call``` with ```UpdateState::Before``` and the library before it's removed so
+    /// the application can tear down any state tied to it.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.remove_library_with(&lib, &Plugins::reload_callback, &mut plugins);
+    /// ```
+    ///
+    pub fn remove_library_with<F, T>(&mut self, lib: &Lib, update_call: &F, data: &mut T) -> bool
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        let path = lib.source_path();
+        let tracked = match self.libs.get(path) {
+            Some(tracked) => tracked.clone(),
+            None => return false,
+        };
+
+        self.notify(update_call, data, UpdateState::Before, Some(&tracked));
+        self.remove_library(&tracked)
+    }
+
+    fn track_lib(&mut self, lib: Arc<Lib>) -> Result<Arc<Lib>> {
+        self.watch_lib(&lib);
+        self.failed.retain(|f| f.path != lib.source_path());
+        // Bump the ref here as we keep one around to keep track of files that needs to be reloaded
+        match self.libs.find(lib.source_path()) {
+            // Only one version of a file is tracked, also when it's found under another spelling
+            Some(index) => {
+                let old = self.libs.replace(index, lib.clone());
+                self.unloads.park(old);
+            }
+            None => self.libs.push(lib.clone()),
+        }
+        self.swap_in(&lib);
+
+        // The library is loaded and tracked at this point, failing would leave the caller
+        // without a way to remove it
+        if let Err(e) = self.publish_to_cluster() {
+            log::warn!("Unable to publish to cluster: {}", e);
+        }
+
+        Ok(lib)
+    }
+
+    ///
+    ///
+    /// Needs to be called in order to handle reloads of libraries.
+    ///
+    /// ```update_call``` function with its data needs to be supplied to allow the application to
+    /// take appropriate action depending on what needs to be done with the loaded library.
+    ///
+    /// Libraries loaded through a shadow directory are only reloaded when their content has
+    /// changed. Events for a file that has been touched or written again with the same content,
+    /// such as the trailing events some platforms report after the debounce duration, are
+    /// ignored.
+    ///
+    /// ```ignore
+    /// struct Plugins {
+    ///     // ...
+    /// }
+    ///
+    /// impl Plugins {
+    ///    fn reload_callback(&mut self, state: UpdateState, lib: Option<&Arc<Lib>>) {
+    ///        match state {
+    ///            UpdateState::Before => // save state, remove from lists, etc, here
+    ///            UpdateState::After => // shared lib reloaded, re-add, restore state
+    ///            UpdateState::ReloadFailed(Error) => // shared lib failed to reload due to error,
+    ///                                                // lib is the previous version
+    ///        }
+    ///    }
+    /// }
+    ///
+    /// fn main() {
+    ///     let plugins = Plugins { ... };
+    ///     let mut dr = DynamicReload::new(None, Some("target/debug"), Search::Backwards, Duration::from_secs(2));
+    ///     dr.add_library("test_shared", Search::Backwards);
+    ///     dr.update(Plugin::reload_callback, &mut plugins);
+    /// }
+    /// ```
+    /// # Safety
+    /// Note taken from libloading that is used for library loading
+    ///
+    /// When a library is loaded, initialisation routines contained within it are executed.
+    /// For the purposes of safety, the execution of these routines is conceptually the same calling an unknown foreign function and may impose arbitrary requirements on the caller for the call to be sound.
+    /// Additionally, the callers of this function must also ensure that execution of the termination routines contained within
+    /// the library is safe as well. These routines may be executed when the library is unloaded.
+    ///
+    pub unsafe fn update<F, T>(&mut self, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        self.report_referenced(update_call, data);
+        self.apply_commands(update_call, data);
+        self.unloads.collect();
+
+        // Events are left in the channel until resumed
+        if self.paused {
+            return;
+        }
+
+        for (from, to) in std::mem::take(&mut self.relocated) {
+            let index = self
+                .libs
+                .iter()
+                .position(|lib| lib.original_path.as_ref() == Some(&from));
+
+            let options = self.set_options(&from, None);
+            self.set_options(&to, options);
+
+            if let Some(index) = index {
+                Self::reload_lib(self, index, &to, update_call, data);
+            }
+
+            let moved = self
+                .libs
+                .iter()
+                .find(|lib| lib.original_path.as_ref() == Some(&to))
+                .cloned();
+
+            if let Some(lib) = moved {
+                self.watch_lib(&lib);
+            }
+        }
+
+        self.recover_watches();
+        let mut changed = self.changed_paths();
+        self.report_builds(&mut changed, update_call, data);
+
+        // Followers only reload what the coordinator has published
+        if self.cluster.as_ref().map(|c| c.role()) == Some(ClusterRole::Follower) {
+            self.apply_cluster_generation(update_call, data);
+            return;
+        }
+
+        if !self.queue_reloads && !self.queued.is_empty() {
+            Self::commit_pending(self, update_call, data);
+        }
+
+        for path in changed {
+            if let Some(debounce) = self.lib_debounce(&path) {
+                self.delayed.retain(|(p, _, _)| *p != path);
+                self.delayed.push((path, Instant::now(), debounce));
+                continue;
+            }
+
+            Self::apply_change(self, path, update_call, data);
+        }
+
+        for path in self.settled() {
+            Self::apply_change(self, path, update_call, data);
+        }
+
+        for path in self.unguarded() {
+            Self::apply_change(self, path, update_call, data);
+        }
+
+        Self::retry_failed(self, update_call, data);
+    }
+
+    ///
+    /// Same as [update](struct.DynamicReload.html#method.update) but takes a closure that is
+    /// called with the state and library directly, so it can capture what it needs from the
+    /// application instead of getting it passed as ```data```.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut reloads = 0;
+    ///
+    /// unsafe {
+    ///     dr.update_with(|state, lib| match state {
+    ///         UpdateState::Before => plugins.remove(lib.unwrap()),
+    ///         UpdateState::After => {
+    ///             plugins.add(lib.unwrap());
+    ///             reloads += 1;
+    ///         }
+    ///         _ => (),
+    ///     });
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [update](struct.DynamicReload.html#method.update).
+    ///
+    pub unsafe fn update_with<F>(&mut self, mut update_call: F)
+    where
+        F: FnMut(UpdateState, Option<&Arc<Lib>>),
+    {
+        self.update(
+            &|call: &mut F, state, lib| call(state, lib),
+            &mut update_call,
+        );
+    }
+
+    // Starts the builds of libraries whose sources are among the changed paths, which are taken
+    // out, and reports the builds that are done
+    fn report_builds<F, T>(&mut self, changed: &mut Vec<PathBuf>, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        // Done builds are collected before new ones are started so a build that fails right
+        // away is reported after it has been reported as started
+        let mut started = Vec::new();
+        let finished = self.rebuilds.finished(&mut started);
+        started.extend(self.rebuilds.changed(changed));
+
+        for (path, result) in finished {
+            let state = match result {
+                Ok(output) => UpdateState::BuildFinished(output),
+                Err(e) => UpdateState::BuildFailed(e),
+            };
+            let lib = self.libs.get(&path).cloned();
+            self.notify(update_call, data, state, lib.as_ref());
+        }
+
+        for path in started {
+            let lib = self.libs.get(&path).cloned();
+            self.notify(update_call, data, UpdateState::BuildStarted, lib.as_ref());
+        }
+    }
+
+    // Changed libraries that were in use and no longer are
+    fn unguarded(&mut self) -> Vec<PathBuf> {
+        let guarded = std::mem::take(&mut self.guarded);
+        let (ready, waiting) = guarded
+            .into_iter()
+            .partition(|path| !self.matching(path).any(|lib| lib.in_use()));
+
+        self.guarded = waiting;
+        ready
+    }
+
+    // Tries failed libraries again once their delay has passed and reports the ones that have
+    // been given up on
+    unsafe fn retry_failed<F, T>(&mut self, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        for path in self.retries.due() {
+            Self::retry(self, &path, update_call, data);
+        }
+
+        for path in self.retries.newly_blacklisted() {
+            let lib = self.libs.get(&path).cloned();
+            self.notify(
+                update_call,
+                data,
+                UpdateState::Blacklisted(path),
+                lib.as_ref(),
+            );
+        }
+    }
+
+    // Loads a library again after a failed reload. A library that is no longer loaded is
+    // reported with After as its Before was called when it was unloaded.
+    unsafe fn retry<F, T>(&mut self, path: &Path, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        let failed = match self.failed.iter().position(|f| f.path == path) {
+            Some(index) => self.failed.remove(index),
+            None => return Self::reload_libs(self, path, update_call, data),
+        };
+
+        let unloaded = Unloaded {
+            lib: None,
+            metadata: Metadata::new(),
+            all_metadata: failed.metadata,
+            userdata: failed.userdata,
+            generation: failed.generation,
+            injected: self.injections.take(path),
+            start: Instant::now(),
+            previous: None,
+        };
+
+        Self::load_again(self, unloaded, path, update_call, data);
+    }
+
+    ///
+    /// Retries reloads that failed, for example because the file was still being written, on
+    /// later calls to [update](struct.DynamicReload.html#method.update) with an exponential
+    /// backoff, see [ReloadRetry]. A library that keeps failing is blacklisted, which is
+    /// reported with ```UpdateState::Blacklisted```. Libraries that are no longer loaded after
+    /// a failed reload are also reloaded when their file changes. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.set_reload_retry(Some(ReloadRetry::default()));
+    ///
+    /// // In the update callback
+    /// UpdateState::Blacklisted(path) => ui.show_broken(&path),
+    /// ```
+    ///
+    pub fn set_reload_retry(&mut self, retry: Option<ReloadRetry>) {
+        if retry.is_none() {
+            self.retries = Retries::default();
+        }
+        self.retries.policy = retry;
+    }
+
+    ///
+    /// Returns true if the library at ```path``` has been blacklisted after failing to reload
+    /// too many times in a row, see
+    /// [set_reload_retry](struct.DynamicReload.html#method.set_reload_retry).
+    ///
+    pub fn is_blacklisted(&self, path: &Path) -> bool {
+        self.retries.is_blacklisted(path)
+    }
+
+    ///
+    /// Starts retrying a blacklisted library again on the next
+    /// [update](struct.DynamicReload.html#method.update), for example after the user has been
+    /// asked to fix it.
+    ///
+    pub fn clear_blacklist(&mut self, path: &Path) {
+        self.retries.reset(path);
+    }
+
+    ///
+    /// Keeps libraries that have been replaced or removed loaded for a while before letting go
+    /// of them, for threads that may still be running code in the old version, see
+    /// [DeferredUnload]. Parked libraries are let go of by
+    /// [update](struct.DynamicReload.html#method.update) once their grace period is over, by
+    /// [advance_epoch](struct.DynamicReload.html#method.advance_epoch) or by
+    /// [flush_unloads](struct.DynamicReload.html#method.flush_unloads). Has no effect with the
+    /// ```no-timestamps``` feature where the old version has to be unloaded before the new one
+    /// is loaded. ```Immediate``` by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.set_deferred_unload(DeferredUnload::Epoch);
+    ///
+    /// loop {
+    ///     unsafe { dr.update(&Plugins::reload_callback, &mut plugins) };
+    ///     run_jobs(&plugins);
+    ///     // No job is running code from a library replaced before this point
+    ///     dr.advance_epoch();
+    /// }
+    /// ```
+    ///
+    pub fn set_deferred_unload(&mut self, deferred: DeferredUnload) {
+        self.unloads.mode = deferred;
+        self.unloads.collect();
+    }
+
+    ///
+    /// Binds ```symbol``` to ```lib``` and keeps it pointing to the current version of the
+    /// library when it's reloaded, see [AtomicSymbol]. The symbol is forgotten once the
+    /// application drops it.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let update = Arc::new(unsafe { AtomicSymbol::<extern "C" fn()>::new("plugin_update") });
+    /// dr.bind_symbol(&lib, update.clone());
+    /// ```
+    ///
+    pub fn bind_symbol<T: Copy + Send + Sync + 'static>(
+        &mut self,
+        lib: &Lib,
+        symbol: Arc<AtomicSymbol<T>>,
+    ) {
+        self.bindings.add(lib, symbol);
+    }
+
+    ///
+    /// Adds an observer that is notified about reloads together with the callback passed to
+    /// [update](struct.DynamicReload.html#method.update) and the other functions taking a
+    /// callback, see [ReloadObserver]. Observers are called before the callback in the order
+    /// they were added.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let log = Arc::new(Mutex::new(ReloadLog::default()));
+    /// dr.add_observer(log.clone());
+    /// dr.add_observer(Arc::new(Mutex::new(ui.reload_observer())));
+    /// ```
+    ///
+    pub fn add_observer(&mut self, observer: Arc<Mutex<dyn ReloadObserver>>) {
+        self.observers.add(observer);
+    }
+
+    ///
+    /// Removes an observer added with
+    /// [add_observer](struct.DynamicReload.html#method.add_observer). Returns false if it
+    /// wasn't added.
+    ///
+    pub fn remove_observer<O: ReloadObserver + ?Sized>(
+        &mut self,
+        observer: &Arc<Mutex<O>>,
+    ) -> bool {
+        self.observers.remove(observer)
+    }
+
+    ///
+    /// Returns a receiver that gets a [ReloadNotification] for each state change the callback
+    /// passed to [update](struct.DynamicReload.html#method.update) is called with. Each
+    /// receiver gets all notifications, so independent parts of the application can subscribe
+    /// without going through the callback. Dropping the receiver unsubscribes it. A receiver
+    /// with [SUBSCRIPTION_CAPACITY] notifications waiting is unsubscribed instead of holding up
+    /// the reload, it sees the channel as disconnected once it has caught up.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let notifications = dr.subscribe();
+    ///
+    /// thread::spawn(move || {
+    ///     for notification in notifications {
+    ///         if let NotificationKind::ReloadFailed(e) = notification.kind {
+    ///             ui.show_error(notification.path, e);
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    ///
+    pub fn subscribe(&self) -> Receiver<ReloadNotification> {
+        self.subscribers.subscribe()
+    }
+
+    ///
+    /// Returns a trampoline for the symbol ```name``` in ```lib```. A trampoline is a stub
+    /// function that forwards to the symbol of the current version of the library, so the
+    /// pointer stays valid across reloads and can be handed to C code or stored in long-lived
+    /// structures. Asking for the same symbol again returns the same trampoline.
+    ///
+    /// Arguments are forwarded untouched so any signature works. Calling the trampoline while
+    /// the library is unloaded, or after the new version dropped the symbol, aborts the process.
+    /// Only [MAX_TRAMPOLINES] trampolines can be handed out over the life of the process, they
+    /// are not reused once this instance is dropped and calling them aborts from then on. Only
+    /// supported on x86_64, other architectures get
+    /// ```Error::Trampoline```. Requires the ```trampoline``` feature (Rust 1.88).
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let callback: extern "C" fn(i32) -> i32 = unsafe { dr.trampoline(&lib, "on_event")? };
+    /// unsafe { register_callback(callback) };
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// ```T``` has to be a function pointer type matching the signature of the symbol in every
+    /// version of the library.
+    ///
+    #[cfg(feature = "trampoline")]
+    pub unsafe fn trampoline<T: Copy>(&mut self, lib: &Lib, name: &str) -> Result<T> {
+        if !lib.has_symbol(name) {
+            return Err(Error::MissingSymbol(
+                lib.source_path().to_path_buf(),
+                name.to_owned(),
+            ));
+        }
+
+        let existing = self
+            .trampolines
+            .iter()
+            .find(|t| t.is(lib.source_path(), name));
+
+        let trampoline = match existing {
+            Some(trampoline) => trampoline.clone(),
+            None => {
+                let trampoline = Arc::new(Trampoline::new(lib, name)?);
+                self.bindings.add(lib, trampoline.clone());
+                self.trampolines.push(trampoline.clone());
+                trampoline
+            }
+        };
+
+        Ok(trampoline.stub())
+    }
+
+    ///
+    /// Decides what happens to libraries that have been replaced or removed while the
+    /// application still holds references to them, see [ExternalRefs]. A library is only
+    /// unloaded once all references are gone, so a reference kept by mistake keeps the old code
+    /// around. ```Retire``` and ```Warn``` help with finding and dealing with those. Retiring has
+    /// no effect with the ```no-timestamps``` feature. ```Ignore``` by default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// dr.set_external_refs(ExternalRefs::Warn);
+    ///
+    /// // In the update callback
+    /// UpdateState::StillReferenced(count) => {
+    ///     println!("{:?} is still referenced {} times", lib.unwrap().source_path(), count)
+    /// }
+    /// ```
+    ///
+    pub fn set_external_refs(&mut self, external: ExternalRefs) {
+        self.unloads.external = external;
+    }
+
+    ///
+    /// Paths of the replaced or removed libraries kept with ```ExternalRefs::Retire``` until the
+    /// application lets go of them.
+    ///
+    pub fn retired_libs(&self) -> Vec<PathBuf> {
+        self.unloads.retired_paths()
+    }
+
+    // Reports the libraries let go of since the last update that are still referenced
+    fn report_referenced<F, T>(&mut self, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        for lib in self.unloads.take_referenced() {
+            // Not counting the one held here
+            let count = Arc::strong_count(&lib) - 1;
+            self.notify(
+                update_call,
+                data,
+                UpdateState::StillReferenced(count),
+                Some(&lib),
+            );
+        }
+    }
+
+    ///
+    /// Lets go of the libraries parked with ```DeferredUnload::Epoch``` before this call and
+    /// returns the new epoch.
+    ///
+    pub fn advance_epoch(&mut self) -> u64 {
+        self.unloads.advance_epoch()
+    }
+
+    ///
+    /// Lets go of all parked libraries right away, whatever is left of their grace period.
+    ///
+    pub fn flush_unloads(&mut self) {
+        self.unloads.flush();
+    }
+
+    ///
+    /// Number of replaced or removed libraries that are kept loaded, see
+    /// [set_deferred_unload](struct.DynamicReload.html#method.set_deferred_unload).
+    ///
+    pub fn parked_unloads(&self) -> usize {
+        self.unloads.len()
+    }
+
+    // Reloads the libraries of a changed file, or loads it if it's new in a directory added
+    // with add_directory. Queued instead when reloads are committed by the application.
+    unsafe fn apply_change<F, T>(&mut self, path: PathBuf, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        if self.queue_reloads {
+            if !self.queued.contains(&path) {
+                self.queued.push(path);
+            }
+            return;
+        }
+
+        let tracked = self.libs.get(&path).is_some();
+
+        if !tracked && self.directory_for(&path).is_some() {
+            self.add_from_directory(&path, update_call, data);
+        } else if !tracked && self.retries.policy.is_some() {
+            Self::retry(self, &path, update_call, data);
+        } else {
+            Self::reload_libs(self, &path, update_call, data);
+        }
+    }
+
+    ///
+    /// Files that have changed and wait for
+    /// [commit_pending](struct.DynamicReload.html#method.commit_pending), see
+    /// [set_queue_reloads](struct.DynamicReload.html#method.set_queue_reloads). A file can
+    /// turn out to not need a reload when it's committed, for example if it was written with
+    /// the same content again.
+    ///
+    pub fn pending_reloads(&self) -> &[PathBuf] {
+        &self.queued
+    }
+
+    ///
+    /// Applies the reloads queued by [update](struct.DynamicReload.html#method.update), calling
+    /// ```update_call``` the same way ```update``` does, see
+    /// [set_queue_reloads](struct.DynamicReload.html#method.set_queue_reloads).
+    ///
+    /// # Safety
+    ///
+    /// See [update](struct.DynamicReload.html#method.update).
+    ///
+    pub unsafe fn commit_pending<F, T>(&mut self, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        let queue = std::mem::replace(&mut self.queue_reloads, false);
+
+        for path in std::mem::take(&mut self.queued) {
+            Self::apply_change(self, path, update_call, data);
+        }
+
+        self.queue_reloads = queue;
+    }
+
+    // The debounce of the options of the libraries a change of path reloads
+    fn lib_debounce(&self, path: &Path) -> Option<Duration> {
+        self.matching(path)
+            .find_map(|lib| self.load_options(lib.source_path())?.debounce)
+    }
+
+    // Delayed changes that have passed their debounce
+    fn settled(&mut self) -> Vec<PathBuf> {
+        let mut ready = Vec::new();
+
+        self.delayed.retain(|(path, since, debounce)| {
+            if since.elapsed() >= *debounce {
+                ready.push(path.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        ready
+    }
+
+    ///
+    /// Same as [update](struct.DynamicReload.html#method.update) but returns what has been
+    /// reloaded instead of calling a callback. Each event has both the old and the new version
+    /// of the library so the application can move its state over before dropping the old one,
+    /// except with the ```no-timestamps``` feature, see [ReloadEvent::old].
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// for event in unsafe { dr.poll_events() } {
+    ///     match event.phase {
+    ///         ReloadPhase::Reloaded => plugins.replace(event.old.unwrap(), event.new.unwrap()),
+    ///         ReloadPhase::Failed(e) => println!("Failed to reload {:?}: {}", event.path, e),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [update](struct.DynamicReload.html#method.update).
+    ///
+    pub unsafe fn poll_events(&mut self) -> Vec<ReloadEvent> {
+        let mut collector = Collector::default();
+        self.update(&Collector::update_call, &mut collector);
+        collector.events
+    }
+
+    ///
+    /// Returns a [Stream](futures_core::Stream) of [ReloadEvent] for use in async code. Polling
+    /// the stream does what [poll_events](struct.DynamicReload.html#method.poll_events) does,
+    /// and the task is woken when a library has changed. Requires the ```stream``` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut events = unsafe { dr.event_stream() };
+    ///
+    /// loop {
+    ///     tokio::select! {
+    ///         Some(event) = events.next() => plugins.handle(event),
+    ///         request = listener.accept() => serve(request),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// Libraries are reloaded each time the stream is polled, see
+    /// [update](struct.DynamicReload.html#method.update).
+    ///
+    #[cfg(feature = "stream")]
+    pub unsafe fn event_stream(&mut self) -> EventStream<'_> {
+        EventStream::new(self)
+    }
+
+    ///
+    /// Moves reloading to a thread of its own so the application doesn't have to call
+    /// [update](struct.DynamicReload.html#method.update). The thread wakes up when the file
+    /// watcher reports a change and calls ```update_call``` the same way as ```update``` does,
+    /// on the reload thread. The application has to make sure nothing from the old version of a
+    /// library is in use when ```Before``` has returned, for example by taking a lock that the
+    /// code calling into the library holds as well.
+    ///
+    /// Use [lock](struct.BackgroundReload.html#method.lock) to add libraries after the thread
+    /// has started and [stop](struct.BackgroundReload.html#method.stop) to get back to calling
+    /// ```update``` manually.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let plugins = Arc::new(RwLock::new(Plugins::new()));
+    /// let reload_plugins = plugins.clone();
+    ///
+    /// let background = unsafe {
+    ///     dr.spawn_background(move |state, lib| {
+    ///         reload_plugins.write().unwrap().reload_callback(state, lib)
+    ///     })?
+    /// };
+    ///
+    /// let lib = unsafe { background.lock().add_library("test_shared", PlatformName::Yes)? };
+    /// plugins.write().unwrap().add_plugin(&lib);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [update](struct.DynamicReload.html#method.update).
+    ///
+    pub unsafe fn spawn_background<F>(self, update_call: F) -> std::io::Result<BackgroundReload>
+    where
+        F: FnMut(UpdateState, Option<&Arc<Lib>>) + Send + 'static,
+    {
+        BackgroundReload::spawn(self, update_call)
+    }
+
+    ///
+    /// Reloads a library right away without waiting for a change to be detected, for example
+    /// when the application knows that a deploy step has just replaced it. Libraries that
+    /// depend on it are reloaded as well and ```update_call``` is called the same way as by
+    /// [update](struct.DynamicReload.html#method.update), including ```ReloadFailed``` if the
+    /// library can't be loaded again. The library is reloaded even if the file hasn't changed.
+    ///
+    /// Returns false if the library isn't tracked by this instance.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// deploy()?;
+    /// dr.reload_library(&lib, &Plugins::reload_callback, &mut plugins);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [update](struct.DynamicReload.html#method.update).
+    ///
+    pub unsafe fn reload_library<F, T>(&mut self, lib: &Lib, update_call: &F, data: &mut T) -> bool
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        let path = lib.source_path().to_path_buf();
+
+        match self.libs.position(&path) {
+            Some(index) => {
+                Self::reload_with_dependents(self, index, &path, update_call, data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Applies the commands sent from DynamicReloadHandles
+    unsafe fn apply_commands<F, T>(&mut self, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        loop {
+            let command = match self.commands.get_mut() {
+                Ok(commands) => commands.try_recv(),
+                Err(e) => e.into_inner().try_recv(),
+            };
+
+            let command = match command {
+                Ok(command) => command,
+                Err(_) => break,
+            };
+
+            match command {
+                Command::Add(name, name_format, reply) => {
+                    let _ = reply.send(self.add_library(&name, name_format));
+                }
+                Command::Remove(path) => {
+                    if let Some(lib) = self.libs.get(&path).cloned() {
+                        self.remove_library_with(&lib, update_call, data);
+                    }
+                }
+                Command::Reload(path) => {
+                    if let Some(lib) = self.libs.get(&path).cloned() {
+                        self.reload_library(&lib, update_call, data);
+                    }
+                }
+                Command::Pause(paused) => self.paused = paused,
+            }
+        }
+    }
+
+    fn receiver(&mut self) -> &Receiver<WatchMsg> {
+        self.watch_recv.get_mut().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Wakes the waker once the watcher has delivered an event
+    fn set_waker(&self, waker: &std::task::Waker) {
+        if let Some(watcher) = self.watcher.as_ref() {
+            watcher.set_waker(waker);
+        }
+    }
+
+    // True if update may have something to do even though no events have been delivered
+    fn needs_polling(&self) -> bool {
+        self.debounce_strategy.is_some()
+            || !self.missing_paths.is_empty()
+            || !self.relocated.is_empty()
+            || !self.delayed.is_empty()
+            || self.cluster.is_some()
+            || self.retries.is_waiting()
+            || self.unloads.is_waiting()
+            || !self.guarded.is_empty()
+    }
+
+    fn changed_paths(&mut self) -> Vec<PathBuf> {
+        // Paths that were missing last time are retried until the file shows up again
+        let mut paths = std::mem::take(&mut self.missing_paths);
+
+        while let Ok(msg) = self.receiver().try_recv() {
+            match msg {
+                WatchMsg::Debounced(Ok(events)) => {
+                    paths.extend(events.iter().map(|event| event.path.clone()));
+                    self.send_raw_event(RawEvent::Debounced(events));
+                }
+                WatchMsg::Debounced(Err(errors)) => {
+                    for error in errors {
+                        self.send_raw_event(RawEvent::Error(error));
+                    }
+                }
+                WatchMsg::Raw(Ok(event)) => {
+                    if let Some(strategy) = self.debounce_strategy.as_mut() {
+                        strategy.event(&event);
+                    }
+                    self.send_raw_event(RawEvent::Event(event));
+                }
+                WatchMsg::Raw(Err(error)) => self.send_raw_event(RawEvent::Error(error)),
+            }
+        }
+
+        if let Some(strategy) = self.debounce_strategy.as_mut() {
+            paths.extend(strategy.ready());
+        }
+
+        // A rename over the library can report the same path several times
+        let mut unique = Vec::with_capacity(paths.len());
+        for path in paths {
+            if self.is_ignored(&path) {
+                continue;
+            }
+
+            let path = self
+                .versioned_alias(&path)
+                .or_else(|| self.link_alias(&path))
+                .or_else(|| self.signature_alias(&path))
+                .unwrap_or(path);
+
+            if !unique.contains(&path) {
+                unique.push(path);
+            }
+        }
+
+        unique
+    }
+
+    // Versioned names of a shared object ("libfoo.so.1", "libfoo.so.1.2.3") usually link to each
+    // other, a change to one of them is a change to the library loaded through another
+    fn versioned_alias(&self, path: &Path) -> Option<PathBuf> {
+        let name = path.file_name()?;
+        if !self.libs.named(name).is_empty() {
+            return None;
+        }
+
+        let name = name.to_string_lossy();
+        let unversioned = naming::strip_version(&name);
+        let dir = Self::canonical_path(path).parent()?.to_path_buf();
+
+        self.libs
+            .iter()
+            .map(|lib| lib.source_path())
+            .find(|source| {
+                let source_name = source.file_name().unwrap_or_default().to_string_lossy();
+                naming::strip_version(&source_name) == unversioned
+                    && (self.match_policy == MatchPolicy::FileName
+                        || Self::canonical_path(source).parent() == Some(dir.as_path()))
+            })
+            .map(Path::to_path_buf)
+    }
+
+    // A change to the file a library links to is a change to the library
+    fn link_alias(&self, path: &Path) -> Option<PathBuf> {
+        if self.link_targets.is_empty() || self.matching(path).next().is_some() {
+            return None;
+        }
+
+        let path = Self::canonical_path(path);
+        self.link_targets
+            .iter()
+            .find(|(_, target)| *target == path)
+            .map(|(link, _)| link.clone())
+    }
+
+    // The signature is usually written after the library, a change to it is a change to the
+    // library that may now verify
+    fn signature_alias(&self, path: &Path) -> Option<PathBuf> {
+        #[cfg(feature = "signatures")]
+        if self.public_key.is_some() && path.extension()? == SIGNATURE_EXTENSION {
+            let lib = path.with_extension("");
+            return self.matching(&lib).next().map(|_| lib);
+        }
+
+        #[cfg(not(feature = "signatures"))]
+        let _ = path;
+        None
+    }
+
+    fn send_raw_event(&mut self, event: RawEvent) {
+        let sent = match self.raw_events.as_ref() {
+            Some(sink) => sink.send(event).is_ok(),
+            None => return,
+        };
+
+        // Nobody is listening anymore
+        if !sent {
+            self.raw_events = None;
+        }
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        match path.file_name() {
+            Some(name) => self
+                .ignore_patterns
+                .iter()
+                .any(|p| p.matches(&name.to_string_lossy())),
+            None => false,
+        }
+    }
+
+    fn watch_lib(&mut self, lib: &Lib) {
+        let watched = self.watch_dir(lib);
+        if let Some(dir) = watched.as_ref() {
+            let _ = self.watch_path(dir, RecursiveMode::NonRecursive);
+        }
+
+        // The link may have been pointed somewhere else
+        let old_target = self.linked_target(lib);
+        self.link_targets
+            .retain(|(l, _)| Some(l) != lib.original_path.as_ref());
+
+        if let Some((link, target)) = self.link_target(lib) {
+            if let Some(dir) = target.parent().filter(|d| watched.as_deref() != Some(*d)) {
+                let _ = self.watch_path(dir, RecursiveMode::NonRecursive);
+            }
+
+            self.link_targets.push((link, target));
+        }
+
+        if let Some(dir) = old_target.as_deref().and_then(Path::parent) {
+            self.release_watch(dir);
+        }
+    }
+
+    // The file the link of the library pointed to when it was last watched
+    fn linked_target(&self, lib: &Lib) -> Option<PathBuf> {
+        self.link_targets
+            .iter()
+            .find(|(link, _)| Some(link) == lib.original_path.as_ref())
+            .map(|(_, target)| target.clone())
+    }
+
+    // Stops watching the directory once no library, link target or added directory needs it
+    // and it isn't under a recursive watch
+    fn release_watch(&mut self, dir: &Path) {
+        let still_used = self
+            .libs
+            .iter()
+            .any(|l| self.watch_dir(l).as_deref() == Some(dir))
+            || self
+                .link_targets
+                .iter()
+                .any(|(_, t)| t.parent() == Some(dir))
+            || self.directories.iter().any(|(d, _)| d == dir)
+            || self.recursive_paths.iter().any(|root| dir.starts_with(root));
+
+        if !still_used {
+            if let Some(w) = self.watcher.as_mut() {
+                let _ = w.watcher().unwatch(dir);
+            }
+            self.watched_dirs.remove(dir);
+        }
+    }
+
+    // The file the library links to when following symbolic links
+    fn link_target(&self, lib: &Lib) -> Option<(PathBuf, PathBuf)> {
+        if !self.follow_symlinks || !self.is_watched(lib) {
+            return None;
+        }
+
+        let link = lib.original_path.as_ref()?;
+        if !fs::symlink_metadata(link).ok()?.file_type().is_symlink() {
+            return None;
+        }
+
+        let target = winpath::canonicalize(link).ok()?;
+        Some((link.clone(), target))
+    }
+
+    // A watch dies with the directory it's on. Set it up again once the directory is back and
+    // check the libraries in it as they may have changed while nothing was watched.
+    fn recover_watches(&mut self) {
+        for (dir, mode) in self.watched_dirs.lost() {
+            if let Some(w) = self.watcher.as_mut() {
+                let _ = w.watcher().unwatch(&dir);
+                let _ = w.watcher().watch(&dir, mode);
+            }
+
+            let changed: Vec<PathBuf> = self
+                .libs
+                .iter()
+                .filter(|lib| match mode {
+                    RecursiveMode::NonRecursive => self.watch_dir(lib).as_ref() == Some(&dir),
+                    RecursiveMode::Recursive => lib
+                        .original_path
+                        .as_ref()
+                        .and_then(|p| winpath::canonicalize(p.parent()?).ok())
+                        .is_some_and(|parent| parent.starts_with(&dir)),
+                })
+                .filter_map(|lib| lib.original_path.clone())
+                .collect();
+
+            self.missing_paths.extend(changed);
+        }
+    }
+
+    fn is_watched(&self, lib: &Lib) -> bool {
+        self.load_options(lib.source_path()).map(|o| o.watch).unwrap_or(true)
+    }
+
+    // The directory watched for changes of the library, if it needs a watch of its own
+    fn watch_dir(&self, lib: &Lib) -> Option<PathBuf> {
+        if !self.is_watched(lib) {
+            return None;
+        }
+
+        let parent = lib.original_path.as_ref()?.parent()?;
+
+        // Already covered by a recursive watch or a directory added with add_directory
+        if let Ok(dir) = winpath::canonicalize(parent) {
+            if self
+                .recursive_paths
+                .iter()
+                .any(|root| dir.starts_with(root))
+                || self.directories.iter().any(|(d, _)| *d == dir)
+            {
+                return None;
+            }
+        }
+
+        if cfg!(windows) {
+            winpath::canonicalize(parent).ok()
+        } else {
+            Some(parent.to_path_buf())
+        }
+    }
+
+    unsafe fn reload_libs<F, T>(&mut self, file_path: &Path, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        // Tools that do atomic saves (write to a temp file and rename it over the library)
+        // may leave a short window where the file doesn't exist. Keep the current version loaded
+        // and try again on the next update instead of failing the reload. Once the file is
+        // reported as removed it's no longer tried, the watch on its directory sees it come back.
+        if Self::is_file(&file_path.to_path_buf()).is_none() {
+            if self.matching(file_path).next().is_some() {
+                self.check_removed(file_path, update_call, data);
+
+                if !self.removed.iter().any(|p| p == file_path) {
+                    self.missing_paths.push(file_path.to_path_buf());
+                }
+            }
+            return;
+        }
+
+        self.missing_since.retain(|(p, _)| p != file_path);
+        self.removed.retain(|p| p != file_path);
+
+        let current = FileIdentity::from_path(file_path);
+        let mut content_hash = None;
+
+        let sources: Vec<PathBuf> = self
+            .matching(file_path)
+            .map(|lib| lib.source_path().to_path_buf())
+            .collect();
+
+        for source in sources.into_iter().rev() {
+            // Reloading dependents may have removed libraries that failed to load again
+            let i = match self.libs.position(&source) {
+                Some(i) => i,
+                None => continue,
+            };
+
+            if let (Some(loaded), Some(current)) = (self.libs[i].file_identity, current) {
+                match loaded.classify(&current) {
+                    // Nothing in the file has changed so no need to reload it
+                    FileChange::Touched if self.libs[i].content_hash.is_none() => continue,
+                    // A rebuild of the same size within one tick of the file system clock looks
+                    // the same, the content hash below tells them apart
+                    FileChange::Touched => (),
+                    // A new file means any watch on the old one is gone
+                    FileChange::Replaced => {
+                        let lib = self.libs[i].clone();
+                        self.watch_lib(&lib);
+                    }
+                    FileChange::Modified => (),
+                }
+            }
+
+            // Rebuilding without changes often gives an identical binary, don't load the same
+            // code again
+            if let Some(loaded) = self.libs[i].content_hash {
+                let hash = *content_hash.get_or_insert_with(|| hash::hash_file(file_path).ok());
+                if hash == Some(loaded) {
+                    continue;
+                }
+            }
+
+            // Swapped once the guards have been dropped
+            if self.libs[i].in_use() {
+                if !self.guarded.iter().any(|p| p == file_path) {
+                    self.guarded.push(file_path.to_path_buf());
+                }
+                continue;
+            }
+
+            if let Some(group) = self.transactional_group(&self.libs[i]) {
+                Self::reload_group(self, &group, update_call, data);
+                continue;
+            }
+
+            Self::reload_with_dependents(self, i, file_path, update_call, data);
+        }
+    }
+
+    fn transactional_group(&self, lib: &Lib) -> Option<String> {
+        if cfg!(feature = "no-timestamps") {
+            return None;
+        }
+
+        let groups = lib.metadata(GROUP_KEY)?;
+        self.transactional
+            .iter()
+            .find(|group| query::has_group(&groups, group))
+            .cloned()
+    }
+
+    // True if the file of the library has been changed since it was loaded, ignoring writes of
+    // the same content
+    fn has_changed(lib: &Lib) -> bool {
+        let path = lib.source_path();
+
+        match (lib.file_identity, FileIdentity::from_path(path)) {
+            (_, None) => return false,
+            (Some(loaded), Some(current)) if loaded.classify(&current) == FileChange::Touched => {
+                return false
+            }
+            _ => (),
+        }
+
+        match lib.content_hash {
+            Some(loaded) => hash::hash_file(path).is_ok_and(|hash| hash != loaded),
+            None => true,
+        }
+    }
+
+    // Loads all changed members of a transactional group next to the versions in use and only
+    // swaps them in once all of them have loaded.
+    unsafe fn reload_group<F, T>(&mut self, group: &str, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        let start = Instant::now();
+        let members: Vec<usize> = (0..self.libs.len())
+            .filter(|&i| {
+                let lib = &self.libs[i];
+                lib.original_path.is_some()
+                    && self.is_watched(lib)
+                    && self.transactional_group(lib).as_deref() == Some(group)
+                    && Self::has_changed(lib)
+            })
+            .collect();
+
+        let mut staged = Vec::with_capacity(members.len());
+        let mut errors = Vec::new();
+
+        for &i in &members {
+            let path = self.libs[i].source_path().to_path_buf();
+            let injected = self.injections.take(&path);
+
+            let loaded = Self::load_library(self, &path, injected)
+                .and_then(|lib| Self::check_health(self, &lib, injected).map(|_| lib));
+
+            match loaded {
+                Ok(lib) => staged.push((i, lib)),
+                Err(e) => errors.push((i, e)),
+            }
+        }
+
+        if let Some((failed, _)) = errors.first() {
+            let failed = self.libs[*failed].source_path().to_path_buf();
+            // Unloads the new versions
+            drop(staged);
+
+            for &i in &members {
+                let lib = self.libs[i].clone();
+                let error = match errors.iter().position(|(e, _)| *e == i) {
+                    Some(index) => errors.remove(index).1,
+                    None => Error::GroupAborted(group.to_owned(), failed.clone()),
+                };
+
+                telemetry::reload_failed(lib.source_path());
+                self.retries.failed(lib.source_path());
+                self.notify(
+                    update_call,
+                    data,
+                    UpdateState::ReloadFailed(error),
+                    Some(&lib),
+                );
+            }
+            return;
+        }
+
+        for (i, _) in &staged {
+            self.notify(update_call, data, UpdateState::Before, Some(&self.libs[*i]));
+        }
+
+        let mut old = Vec::with_capacity(staged.len());
+        for (i, lib) in &staged {
+            telemetry::reloaded(lib.source_path(), start.elapsed());
+            self.retries.remove(lib.source_path());
+            lib.set_user_metadata(self.libs[*i].user_metadata());
+            lib.share_userdata(&self.libs[*i]);
+            lib.set_generation(self.libs[*i].generation() + 1);
+            self.swap_out(&self.libs[*i]);
+            old.push(self.libs.replace(*i, lib.clone()));
+            self.swap_in(lib);
+        }
+
+        for (_, lib) in &staged {
+            self.notify(update_call, data, UpdateState::After, Some(lib));
+        }
+
+        for lib in old {
+            self.unloads.park(lib);
+        }
+
+        if let Err(e) = self.publish_to_cluster() {
+            log::warn!(
+                "Unable to publish group {} to cluster, error: {:?}",
+                group,
+                e
+            );
+        }
+    }
+
+    // A file that is still missing a debounce duration after it was first seen missing has been
+    // deleted rather than replaced
+    fn check_removed<F, T>(&mut self, file_path: &Path, update_call: &F, data: &mut T)
+    where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        if self.removed.iter().any(|p| p == file_path) {
+            return;
+        }
+
+        let since = match self.missing_since.iter().find(|(p, _)| p == file_path) {
+            Some((_, since)) => *since,
+            None => {
+                self.missing_since
+                    .push((file_path.to_path_buf(), Instant::now()));
+                return;
+            }
+        };
+
+        if since.elapsed() < self.debounce_duration {
+            return;
+        }
+
+        self.removed.push(file_path.to_path_buf());
+
+        let libs: Vec<Arc<Lib>> = self.matching(file_path).cloned().collect();
+
+        for lib in libs {
+            self.notify(update_call, data, UpdateState::Removed, Some(&lib));
+
+            // Libraries from a directory come and go with their files
+            if self.directory_for(lib.source_path()).is_some() {
+                self.remove_library(&lib);
+            }
+        }
+    }
+
+    unsafe fn reload_lib<F, T>(
+        &mut self,
+        index: usize,
+        file_path: &Path,
+        update_call: &F,
+        data: &mut T,
+    ) where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        let load_first = self.swap_order == SwapOrder::LoadFirst
+            || self.abi_tag.is_some()
+            || self.fingerprint.is_some()
+            || self.requires_signature()
+            || self.requires_checksum()
+            || self
+                .load_options(self.libs[index].source_path())
+                .is_some_and(|o| !o.required_symbols.is_empty());
+
+        if load_first && cfg!(not(feature = "no-timestamps")) {
+            return Self::load_then_swap(self, index, file_path, update_call, data);
+        }
+
+        self.notify(
+            update_call,
+            data,
+            UpdateState::Before,
+            Some(&self.libs[index]),
+        );
+        let unloaded = self.unload_lib(index);
+        Self::load_again(self, unloaded, file_path, update_call, data);
+    }
+
+    // Loads the new version while the old one is still in place and only swaps them once the
+    // load has succeeded.
+    unsafe fn load_then_swap<F, T>(
+        &mut self,
+        index: usize,
+        file_path: &Path,
+        update_call: &F,
+        data: &mut T,
+    ) where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        let start = Instant::now();
+        let old = self.libs[index].clone();
+        let injected = self.injections.take(old.source_path());
+
+        match Self::load_library(self, file_path, injected) {
+            Ok(lib) => {
+                if let Err(err) = Self::check_health(self, &lib, injected) {
+                    telemetry::reload_failed(file_path);
+                    self.retries.failed(file_path);
+                    drop(lib);
+                    self.notify(
+                        update_call,
+                        data,
+                        UpdateState::HealthCheckFailed(err),
+                        Some(&old),
+                    );
+                    return;
+                }
+
+                self.notify(update_call, data, UpdateState::Before, Some(&old));
+                telemetry::reloaded(file_path, start.elapsed());
+                self.retries.remove(file_path);
+                lib.set_user_metadata(old.user_metadata());
+                lib.share_userdata(&old);
+                lib.set_generation(old.generation() + 1);
+                self.swap_out(&old);
+                self.libs.replace(index, lib.clone());
+                self.swap_in(&lib);
+                self.notify(update_call, data, UpdateState::After, Some(&lib));
+                self.unloads.park(old);
+
+                if let Err(e) = self.publish_to_cluster() {
+                    log::warn!(
+                        "Unable to publish {:?} to cluster, error: {:?}",
+                        file_path,
+                        e
+                    );
+                }
+            }
+
+            Err(err) => {
+                telemetry::reload_failed(file_path);
+                self.retries.failed(file_path);
+                self.notify(
+                    update_call,
+                    data,
+                    UpdateState::ReloadFailed(err),
+                    Some(&old),
+                );
+            }
+        }
+    }
+
+    // Reloads a library together with all libraries that depend on it. The dependents are
+    // unloaded before the library and loaded again after it.
+    unsafe fn reload_with_dependents<F, T>(
+        &mut self,
+        index: usize,
+        file_path: &Path,
+        update_call: &F,
+        data: &mut T,
+    ) where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        let dependents = self
+            .dependencies
+            .dependents(self.libs[index].source_path());
+
+        if dependents.is_empty() {
+            return Self::reload_lib(self, index, file_path, update_call, data);
+        }
+
+        // (path to load from, library to reload), the changed library first
+        let source = self.libs[index].source_path().to_path_buf();
+        let mut chain = vec![(file_path.to_path_buf(), source)];
+        for path in dependents {
+            if self.libs.get(&path).is_some() {
+                chain.push((path.clone(), path));
+            }
+        }
+
+        for (_, source) in chain.iter().rev() {
+            if let Some(lib) = self.libs.get(source) {
+                self.notify(update_call, data, UpdateState::Before, Some(lib));
+            }
+        }
+
+        let mut unloaded = Vec::with_capacity(chain.len());
+        for (path, source) in chain.into_iter().rev() {
+            if let Some(i) = self.libs.position(&source) {
+                unloaded.push((path, self.unload_lib(i)));
+            }
+        }
+
+        for (path, lib) in unloaded.into_iter().rev() {
+            Self::load_again(self, lib, &path, update_call, data);
+        }
+    }
+
+    fn unload_lib(&mut self, index: usize) -> Unloaded {
+        let lib = &self.libs[index];
+        let unloaded = Unloaded {
+            lib: cfg!(not(feature = "no-timestamps")).then(|| lib.clone()),
+            metadata: lib.user_metadata(),
+            all_metadata: lib.all_metadata(),
+            userdata: lib.shared_userdata(),
+            generation: Some(lib.generation()),
+            injected: self.injections.take(lib.source_path()),
+            start: Instant::now(),
+            previous: match (lib.original_path.as_ref(), lib.content_hash) {
+                (Some(path), Some(hash)) => Some((
+                    path.clone(),
+                    lib.loaded_path.clone(),
+                    hash,
+                    lib.load_dir.clone(),
+                )),
+                _ => None,
+            },
+        };
+
+        self.swap_out(lib);
+        self.remove_lib(index);
+        unloaded
+    }
+
+    // Tells the observers, subscribers and the application about a state change
+    fn notify<F, T>(
+        &self,
+        update_call: &F,
+        data: &mut T,
+        state: UpdateState,
+        lib: Option<&Arc<Lib>>,
+    ) where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        self.observers.notify(&state, lib);
+        self.subscribers.send(&state, lib);
+        update_call(data, state, lib);
+    }
+
+    // The library is about to be unloaded by a reload
+    fn swap_out(&self, lib: &Lib) {
+        lifecycle::call(lib, self.lifecycle.before_reload.as_deref());
+        #[cfg(feature = "serde")]
+        state::save(lib);
+    }
+
+    // The library has been added or has replaced the previous version
+    fn swap_in(&mut self, lib: &Lib) {
+        self.bindings.bind(lib);
+        #[cfg(feature = "serde")]
+        state::restore(lib);
+        lifecycle::call(lib, self.lifecycle.after_load.as_deref());
+    }
+
+    unsafe fn load_again<F, T>(
+        &mut self,
+        unloaded: Unloaded,
+        file_path: &Path,
+        update_call: &F,
+        data: &mut T,
+    ) where
+        F: Fn(&mut T, UpdateState, Option<&Arc<Lib>>),
+    {
+        match Self::load_library(self, file_path, unloaded.injected) {
+            Ok(lib) => {
+                if let Err(err) = Self::check_health(self, &lib, unloaded.injected) {
+                    telemetry::reload_failed(file_path);
+                    self.retries.failed(file_path);
+                    drop(lib);
+
+                    // Nothing has been changed in the previous version so it can be used as it is
+                    match unloaded.lib.as_ref() {
+                        Some(old) => {
+                            self.libs.push(old.clone());
+                            self.swap_in(old);
+                        }
+                        None => self.failed.push(FailedLib {
+                            path: file_path.to_path_buf(),
+                            metadata: unloaded.all_metadata,
+                            userdata: unloaded.userdata,
+                            generation: unloaded.generation,
+                            error: err.to_string(),
+                        }),
+                    }
+
+                    self.notify(
+                        update_call,
+                        data,
+                        UpdateState::HealthCheckFailed(err),
+                        unloaded.lib.as_ref(),
+                    );
+                    return;
+                }
+
+                telemetry::reloaded(file_path, unloaded.start.elapsed());
+                self.retries.remove(file_path);
+                lib.set_user_metadata(unloaded.metadata);
+                lib.set_shared_userdata(unloaded.userdata);
+                lib.set_generation(unloaded.generation.map_or(0, |g| g + 1));
+                self.failed.retain(|f| f.path != lib.source_path());
+                self.libs.push(lib.clone());
+                self.swap_in(&lib);
+                self.notify(update_call, data, UpdateState::After, Some(&lib));
+
+                if let Err(e) = self.publish_to_cluster() {
+                    log::warn!(
+                        "Unable to publish {:?} to cluster, error: {:?}",
+                        file_path,
+                        e
+                    );
+                }
+            }
+
+            Err(err) => {
+                telemetry::reload_failed(file_path);
+                self.retries.failed(file_path);
+
+                let previous = match self.rollback {
+                    true => Self::load_previous(self, &unloaded),
+                    false => None,
+                };
+
+                if previous.is_none() {
+                    self.failed.push(FailedLib {
+                        path: file_path.to_path_buf(),
+                        metadata: unloaded.all_metadata,
+                        userdata: unloaded.userdata.clone(),
+                        generation: unloaded.generation,
+                        error: err.to_string(),
+                    });
+                }
+
+                self.notify(
+                    update_call,
+                    data,
+                    UpdateState::ReloadFailed(err),
+                    unloaded.lib.as_ref(),
+                );
+                //println!("Unable to reload lib {:?} err {:?}", file_path, err); // Removed due to move in previous line
+
+                if let Some(lib) = previous {
+                    lib.set_user_metadata(unloaded.metadata);
+                    lib.set_shared_userdata(unloaded.userdata);
+                    // The same version as before the reload
+                    lib.set_generation(unloaded.generation.unwrap_or(0));
+                    self.libs.push(lib.clone());
+                    self.swap_in(&lib);
+                    self.notify(update_call, data, UpdateState::RolledBack, Some(&lib));
+                }
+            }
+        }
+    }
+
+    unsafe fn check_health(&self, lib: &Lib, injected: Option<FailPoint>) -> Result<()> {
+        inject::check(injected, FailPoint::HealthCheck, lib.source_path())?;
+
+        match self.health_check.as_ref() {
+            Some(check) => check.run(lib),
+            None => Ok(()),
+        }
+    }
+
+    // Loads the shadow copy of the version that was loaded before a failed reload. Without the
+    // content hash in the name the copy may have been overwritten by the failed one so it's
+    // checked against the hash first.
+    unsafe fn load_previous(&self, unloaded: &Unloaded) -> Option<Arc<Lib>> {
+        let (original, path, hash, load_dir) = unloaded.previous.clone()?;
+
+        if hash::hash_file(&path).ok() != Some(hash) {
+            return None;
+        }
+
+        // The identity of the current file so the failed version isn't loaded again until the
+        // file changes
+        let identity = FileIdentity::from_path(&original);
+        self.init_library(Some(original), path, identity, Some(hash), load_dir)
+            .ok()
+    }
+
+    unsafe fn try_load_library(&self, name: &str, name_format: PlatformName) -> Result<Arc<Lib>> {
+        let path = self.find_library(name, name_format)?;
+        Self::load_library(self, &path, None)
+    }
+
+    unsafe fn load_library(
+        &self,
+        full_path: &Path,
+        injected: Option<FailPoint>,
+    ) -> Result<Arc<Lib>> {
+        let path;
+        let original_path;
+        let content_hash;
+        let mut load_dir = None;
+        let identity = FileIdentity::from_path(full_path);
+
+        let shadow = self.load_options(full_path).map(|o| o.shadow).unwrap_or(true);
+
+        if let (Some(sd), true) = (self.shadow_dir.as_ref(), shadow) {
+            inject::check(injected, FailPoint::Copy, full_path)?;
+            let deps = self
+                .load_options(full_path)
+                .map_or(&[][..], |o| &o.shadow_dependencies[..]);
+
+            // The dependencies and debug info keep their names so each load gets a directory of
+            // its own where they can't replace the copies of other libraries or versions still
+            // in use. So does the library with the no-timestamps feature when rollback needs the
+            // copy of the previous version, or once a version that is never unloaded (no-unload)
+            // holds its name as loading the same path again would give back that version.
+            let separate = cfg!(feature = "no-timestamps")
+                && (self.rollback
+                    || self
+                        .unloads
+                        .is_leaked(&Self::format_filename(sd.path(), full_path, 0)));
+            let dir = match deps.is_empty() && !self.copy_debug_info && !separate {
+                true => sd.path(),
+                false => load_dir
+                    .insert(Arc::new(LoadDir::create(sd.path(), full_path)?))
+                    .path(),
+            };
+
+            let (shadow_path, hash) = self.shadow_copy(dir, full_path)?;
+            self.copy_dependencies(dir, full_path, deps)?;
+            path = shadow_path;
+            content_hash = Some(hash);
+            original_path = Some(full_path.to_path_buf());
+        } else {
+            original_path = None;
+            content_hash = None;
+            path = full_path.to_path_buf();
+        }
+
+        inject::check(injected, FailPoint::Validate, &path)?;
+        #[cfg(feature = "signatures")]
+        if let Some(key) = self.public_key.as_ref() {
+            signature::verify(key, full_path, &path)?;
+        }
+        #[cfg(feature = "checksums")]
+        if let Some(manifest) = self.checksum_manifest.as_ref() {
+            checksum::verify(manifest, full_path, &path)?;
+        }
+        if let Some(preflight) = self.preflight.as_ref() {
+            preflight.run(&path)?;
+        }
+
+        inject::check(injected, FailPoint::Load, &path)?;
+        let lib = self.init_library(original_path, path, identity, content_hash, load_dir)?;
+        self.check_required_symbols(&lib)?;
+        Ok(lib)
+    }
+
+    fn requires_signature(&self) -> bool {
+        #[cfg(feature = "signatures")]
+        {
+            self.public_key.is_some()
+        }
+        #[cfg(not(feature = "signatures"))]
+        {
+            false
+        }
+    }
+
+    fn requires_checksum(&self) -> bool {
+        #[cfg(feature = "checksums")]
+        {
+            self.checksum_manifest.is_some()
+        }
+        #[cfg(not(feature = "checksums"))]
+        {
+            false
+        }
+    }
+
+    fn check_required_symbols(&self, lib: &Lib) -> Result<()> {
+        let options = match self.load_options(lib.source_path()) {
+            Some(options) => options,
+            None => return Ok(()),
+        };
+
+        match options.required_symbols.iter().find(|s| !lib.has_symbol(s)) {
+            Some(symbol) => Err(Error::MissingSymbol(
+                lib.loaded_path.clone(),
+                symbol.clone(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    unsafe fn init_library(
+        &self,
+        org_path: Option<PathBuf>,
+        path: PathBuf,
+        identity: Option<FileIdentity>,
+        content_hash: Option<u64>,
+        load_dir: Option<Arc<LoadDir>>,
+    ) -> Result<Arc<Lib>> {
+        let flags = self
+            .load_options(org_path.as_deref().unwrap_or(&path))
+            .map(|o| o.open_flags(org_path.as_deref()))
+            .unwrap_or_default();
+        let lib = init::open(&path, flags, self.init_timeout)?;
+        if let Some(interface) = self.fingerprint.as_ref() {
+            fingerprint::check(&lib, &path, interface)?;
+        }
+
+        let mut manifest = metadata::read_embedded(&lib);
+        manifest.extend(metadata::read_manifest(
+            org_path.as_deref().unwrap_or(&path),
+        ));
+
+        if let Some(tag) = self.abi_tag.as_ref() {
+            let abi = manifest.get(ABI_KEY);
+            if abi != Some(tag) {
+                return Err(Error::Incompatible(
+                    path,
+                    format!("abi {:?}, expected {:?}", abi.map_or("", String::as_str), tag),
+                ));
+            }
+        }
+
+        let lib = init::init(
+            lib,
+            &path,
+            self.init_symbol.as_deref(),
+            self.host_api,
+            self.init_timeout,
+        )?;
+
+        Ok(Arc::new(Lib {
+            original_path: org_path,
+            loaded_path: path,
+            lib,
+            file_identity: identity,
+            content_hash,
+            manifest,
+            metadata: RwLock::new(Metadata::new()),
+            userdata: RwLock::new(Userdata::default()),
+            users: AtomicUsize::new(0),
+            generation: AtomicU64::new(0),
+            loaded_at: SystemTime::now(),
+            load_dir,
+        }))
+    }
+
+    // The libraries a change of the path reloads, only the ones with the same file name are
+    // checked
+    fn matching(&self, path: &Path) -> impl Iterator<Item = &Arc<Lib>> + '_ {
+        let path = path.to_path_buf();
+        let indices = path
+            .file_name()
+            .map(|name| self.libs.named(name))
+            .unwrap_or_default();
+
+        indices
+            .into_iter()
+            .map(|i| &self.libs[i])
+            .filter(move |lib| self.should_reload(&path, lib))
+    }
+
+    fn should_reload(&self, reload_path: &Path, lib: &Lib) -> bool {
+        if !self.is_watched(lib) {
+            return false;
+        }
+
+        if let Some(p) = lib.original_path.as_ref() {
+            // Check if file names match.
+            if reload_path.file_name() == p.file_name() {
+                return self.match_policy == MatchPolicy::FileName
+                    || Self::canonical_path(reload_path) == Self::canonical_path(p);
+            }
+        }
+
+        false
+    }
+
+    // Resolves the directory only, the file itself may be missing in the middle of a save
+    fn canonical_path(path: &Path) -> PathBuf {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        match (winpath::canonicalize(dir), path.file_name()) {
+            (Ok(dir), Some(name)) => dir.join(name),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    // Fails with all the paths that were checked
+    fn find_library(&self, name: &str, name_format: PlatformName) -> Result<PathBuf> {
+        let lib_name = Self::get_library_name(name, name_format);
+        self.find_file(name, lib_name.as_ref())
+    }
+
+    // Searches for the file name of a library, name is the name it was added with
+    fn find_file(&self, name: &str, lib_name: &OsStr) -> Result<PathBuf> {
+        let mut searched = Searched::new(self.selection_policy != SelectionPolicy::FirstMatch);
+        self.select_library(name, lib_name, &mut searched)
+    }
+
+    // Picks the file to load from the ones that match according to the selection policy
+    fn select_library(
+        &self,
+        name: &str,
+        lib_name: &OsStr,
+        searched: &mut Searched,
+    ) -> Result<PathBuf> {
+        // Found by the first match or by a custom strategy
+        if let Some(path) = self.search_candidates(lib_name, searched) {
+            return Ok(path);
+        }
+
+        let found = searched.found();
+        match (self.selection_policy, found.len()) {
+            (_, 0) => {
+                let paths = searched.candidates.iter().map(|c| c.path.clone()).collect();
+                Err(Error::Find(name.into(), paths))
+            }
+            (SelectionPolicy::ErrorOnAmbiguity, n) if n > 1 => {
+                Err(Error::Ambiguous(name.into(), found))
+            }
+            (SelectionPolicy::NewestModified, _) => {
+                Ok(resolve::newest(&found).unwrap_or(&found[0]).to_path_buf())
+            }
+            _ => Ok(found[0].clone()),
+        }
+    }
+
+    // Adds each path that is checked to searched, in the order they are checked
+    fn search_candidates(&self, lib_name: &OsStr, searched: &mut Searched) -> Option<PathBuf> {
+        if let Search::Custom(strategy) = &self.search {
+            return strategy.find(&lib_name.to_string_lossy(), &self.search_paths);
+        }
+
+        // 1. Search the current directory
+        if let Some(path) = Self::search_current_dir(lib_name, searched) {
+            return Some(path);
+        }
+
+        // 2. Search the relative paths
+        if let Some(path) = Self::search_relative_paths(self, lib_name, searched) {
+            return Some(path);
+        }
+
+        // 3. Search the recursive paths, including all sub directories
+        for root in &self.recursive_paths {
+            if let Some(path) = Self::search_recursive(root, lib_name, searched) {
+                return Some(path);
+            }
+        }
+
+        // 4. Search the artifacts of the cargo profile if enabled
+        if let Some(profile) = self.cargo_profile.as_ref() {
+            if let Some(dir) = cargo_target_dir() {
+                let path = dir.join(profile.dir_name()).join(lib_name);
+                if let Some(path) = searched.check(path) {
+                    return Some(path);
+                }
+            }
+        }
+
+        // 5. Search the directories of the dynamic linker if enabled
+        if self.loader_paths {
+            for dir in system::loader_dirs(self.env_lookup) {
+                if let Some(path) = searched.check(dir.join(lib_name)) {
+                    return Some(winpath::canonicalize(&path).unwrap_or(path));
+                }
+            }
+        }
+
+        // 6. Search the executable dir and then go backwards if allowed
+        match self.search {
+            Search::Backwards => Self::search_backwards_from_exe(lib_name, searched),
+            // 7. Search the standard locations of the OS
+            Search::System => Self::search_backwards_from_exe(lib_name, searched).or_else(|| {
+                system::library_dirs(self.env_lookup)
+                    .iter()
+                    .find_map(|dir| searched.check(dir.join(lib_name)))
+                    .map(|path| winpath::canonicalize(&path).unwrap_or(path))
+            }),
+            _ => search::exe_dir().and_then(|dir| searched.check(dir.join(lib_name))),
+        }
+    }
+
+    fn search_current_dir(name: &OsStr, searched: &mut Searched) -> Option<PathBuf> {
+        let path = Path::new(name).to_path_buf();
+        // Reported with the full path to show which directory was current
+        let reported = env::current_dir().unwrap_or_default().join(&path);
+        searched.check_as(path, reported)
+    }
+
+    fn search_relative_paths(&self, name: &OsStr, searched: &mut Searched) -> Option<PathBuf> {
+        for p in self.search_paths.iter() {
+            if let Some(file) = searched.check(Path::new(p).join(name)) {
+                return Some(file);
+            }
+        }
+
+        None
+    }
+
+    fn search_recursive(dir: &Path, name: &OsStr, searched: &mut Searched) -> Option<PathBuf> {
+        if let Some(file) = searched.check(dir.join(name)) {
+            return Some(file);
+        }
+
+        let entries = fs::read_dir(dir).ok()?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if let Some(file) = Self::search_recursive(&path, name, searched) {
+                    return Some(file);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn get_parent_dir(path: &Path) -> Option<PathBuf> {
+        path.parent().map(|p| p.to_path_buf())
+    }
+
+    fn search_backwards_from_file(
+        path: &Path,
+        lib_name: &OsStr,
+        searched: &mut Searched,
+    ) -> Option<PathBuf> {
+        match Self::get_parent_dir(path) {
+            Some(p) => {
+                let new_path = Path::new(&p).join(lib_name);
+                if searched.check(new_path.clone()).is_some() {
+                    return Some(new_path);
+                }
+                Self::search_backwards_from_file(&p, lib_name, searched)
+            }
+            _ => None,
+        }
+    }
+
+    fn search_backwards_from_exe(lib_name: &OsStr, searched: &mut Searched) -> Option<PathBuf> {
+        let exe_path = env::current_exe().unwrap_or_default();
+        Self::search_backwards_from_file(&exe_path, lib_name, searched)
+    }
+
+    fn get_temp_dir(shadow_dir: Option<&str>) -> Option<TempDir> {
+        match shadow_dir {
+            Some(dir) => match TempDir::new_in(winpath::extended(Path::new(dir))) {
+                Ok(td) => {
+                    if !Path::exists(td.path()) {
+                        // TODO: Result
+                        log::warn!("Unable to create tempdir in {}", dir);
+                        None
+                    } else {
+                        Some(td)
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Unable to create tempdir in {}, error: {}", dir, e);
+                    None
+                }
+            },
+            _ => None,
+        }
+    }
+
+    fn is_file(path: &PathBuf) -> Option<PathBuf> {
+        match fs::metadata(path) {
+            Ok(md) => {
+                if md.is_file() {
+                    Some(path.clone())
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn get_watcher(
+        tx: Sender<WatchMsg>,
+        debounce_duration: Duration,
+    ) -> Result<Option<FileWatcher>> {
+        match FileWatcher::new(
+            tx,
+            WatcherBackend::Recommended,
+            Some(debounce_duration),
+            Some(DEFAULT_POLL_FALLBACK),
+        ) {
+            Ok(watcher) => Ok(watcher),
+            Err(e) => {
+                log::warn!(
+                    "Unable to create file watcher, no dynamic reloading will be done, \
+                     error: {:?}",
+                    e
+                );
+                Err(Error::Watcher(e))
+            }
+        }
+    }
+
+    fn get_search_paths<P: AsRef<Path>>(search_paths: Option<Vec<P>>) -> Vec<PathBuf> {
+        match search_paths {
+            Some(paths) => paths
+                .iter()
+                .map(|p| {
+                    let path_buf = p.as_ref().to_path_buf();
+                    winpath::canonicalize(&path_buf).unwrap_or(path_buf)
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn get_library_name(name: &str, name_format: PlatformName) -> String {
+        match name_format {
+            PlatformName::No => name.to_string(),
+            PlatformName::Yes if Self::is_dynamiclib_name(name) => name.to_string(),
+            // Cargo replaces dashes in crate names with underscores for the library file
+            PlatformName::Yes => Self::get_dynamiclib_name(&name.replace('-', "_")),
+            PlatformName::Target(triple) => {
+                let (prefix, suffix) = naming::target_affixes(triple);
+                let unversioned = naming::strip_version(name);
+                if unversioned.starts_with(prefix) && unversioned.ends_with(suffix) {
+                    name.to_string()
+                } else {
+                    format!("{}{}{}", prefix, name.replace('-', "_"), suffix)
+                }
+            }
+            PlatformName::Custom(format) => format(name),
+        }
+    }
+
+    /// Checks if the name is already formatted for the platform ("libfoo.so", "foo.dll"),
+    /// including versioned shared objects ("libfoo.so.1.2.3")
+    fn is_dynamiclib_name(name: &str) -> bool {
+        let name = naming::strip_version(name);
+        name.starts_with(env::consts::DLL_PREFIX) && name.ends_with(env::consts::DLL_SUFFIX)
+    }
+
+    fn remove_lib(&mut self, idx: usize) {
+        let lib = self.libs.swap_remove(idx);
+        self.bindings.unbind(lib.source_path());
+        self.unloads.park(lib);
+    }
+
+    // Copies the library to the shadow directory and names the copy after the hash of its
+    // content. The copy is made to a temporary name first so the hash is of exactly what was
+    // copied. If the same content has been copied before that copy is used instead.
+    fn shadow_copy(&self, shadow_dir: &Path, full_path: &Path) -> Result<(PathBuf, u64)> {
+        let filename = full_path.file_name().unwrap();
+        let temp = Self::temp_path(shadow_dir, full_path, filename)?;
+        copy::try_copy(full_path, &temp, &self.copy_retry, self.copy_verify)?;
+
+        let hash = hash::hash_file(&temp)
+            .map_err(|e| Error::Copy(e, full_path.to_path_buf(), temp.to_path_buf()))?;
+        let path = Self::format_filename(shadow_dir, full_path, hash);
+
+        // With the no-timestamps feature an existing copy is another version, which is removed
+        // unless it's loaded. Removing it fails on Windows if it's still in use elsewhere.
+        if cfg!(feature = "no-timestamps")
+            && path.is_file()
+            && hash::hash_file(&path).ok() != Some(hash)
+        {
+            let result = match self.libs.iter().any(|lib| lib.loaded_path == path) {
+                true => Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "a copy of another version is loaded",
+                )),
+                false => fs::remove_file(&path),
+            };
+            result.map_err(|e| Error::Copy(e, full_path.to_path_buf(), path.clone()))?;
+        }
+
+        // An existing copy has the same content and may be mapped by a loaded library so it's
+        // never replaced
+        if !path.is_file() {
+            // Signed after hashing so the hash still matches the original library
+            if self.codesign {
+                copy::codesign(&temp)?;
+            }
+            if let Err(e) = temp.persist_noclobber(&path) {
+                if !path.is_file() {
+                    return Err(Error::Copy(e.error, e.path.to_path_buf(), path));
+                }
+            }
+        }
+
+        if self.copy_debug_info {
+            copy::copy_debug_info(full_path, &path);
+        }
+
+        Ok((path, hash))
+    }
+
+    // Copies the dependencies listed in the load options of the library next to its shadow
+    // copy, in the directory of this load
+    fn copy_dependencies(&self, load_dir: &Path, full_path: &Path, deps: &[PathBuf]) -> Result<()> {
+        let dir = full_path.parent().unwrap_or(Path::new(""));
+
+        for dep in deps {
+            let src = dir.join(dep);
+            let filename = src
+                .file_name()
+                .ok_or_else(|| Error::Find(src.to_string_lossy().into(), Vec::new()))?;
+            let dest = load_dir.join(filename);
+            let temp = Self::temp_path(load_dir, &src, filename)?;
+
+            copy::try_copy(&src, &temp, &self.copy_retry, self.copy_verify)?;
+            if self.codesign {
+                copy::codesign(&temp)?;
+            }
+            temp.persist_noclobber(&dest)
+                .map_err(|e| Error::Copy(e.error, e.path.to_path_buf(), dest))?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no-timestamps"))]
+    fn format_filename(shadow_dir: &Path, full_path: &Path, hash: u64) -> PathBuf {
+        let mut path = shadow_dir.join(format!("{:016x}_", hash)).into_os_string();
+        path.push(full_path.file_name().unwrap());
+        PathBuf::from(path)
+    }
+
+    // Where a file is copied before it's moved in place in the shadow directory, unique so
+    // copies of libraries with the same file name don't overwrite each other. Removed when
+    // dropped unless it has been moved.
+    fn temp_path(shadow_dir: &Path, src: &Path, filename: &OsStr) -> Result<TempPath> {
+        tempfile::Builder::new()
+            .prefix(filename)
+            .suffix(".tmp")
+            .tempfile_in(shadow_dir)
+            .map(NamedTempFile::into_temp_path)
+            .map_err(|e| Error::Copy(e, src.to_path_buf(), shadow_dir.to_path_buf()))
+    }
+
+    #[cfg(feature = "no-timestamps")]
+    fn format_filename(shadow_dir: &Path, full_path: &Path, _hash: u64) -> PathBuf {
+        shadow_dir.join(full_path.file_name().unwrap())
+    }
+
+    /// Formats dll name on Windows ("test_foo" -> "test_foo.dll")
+    #[cfg(target_os = "windows")]
+    fn get_dynamiclib_name(name: &str) -> String {
+        format!("{}.dll", name)
+    }
+
+    /// Formats dll name on Mac ("test_foo" -> "libtest_foo.dylib")
+    #[cfg(target_os = "macos")]
+    fn get_dynamiclib_name(name: &str) -> String {
+        format!("lib{}.dylib", name)
+    }
+
+    /// Formats dll name on *nix ("test_foo" -> "libtest_foo.so")
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "android"
+    ))]
+    fn get_dynamiclib_name(name: &str) -> String {
+        format!("lib{}.so", name)
+    }
+}
+
+impl Lib {
+    /// Returns the metadata value for ```key```. Values set with
+    /// [set_metadata](struct.Lib.html#method.set_metadata) take precedence over the manifest.
+    pub fn metadata(&self, key: &str) -> Option<String> {
+        self.user_metadata()
+            .get(key)
+            .or_else(|| self.manifest.get(key))
+            .cloned()
+    }
+
+    /// Returns all metadata of the library, both from the manifest and set from code.
+    pub fn all_metadata(&self) -> Metadata {
+        let mut metadata = self.manifest.clone();
+        metadata.extend(self.user_metadata());
+        metadata
+    }
+
+    /// Sets a metadata value for the library. Values set this way are kept when the library is
+    /// reloaded, while the manifest is read again.
+    pub fn set_metadata(&self, key: &str, value: &str) {
+        if let Ok(mut metadata) = self.metadata.write() {
+            metadata.insert(key.to_owned(), value.to_owned());
+        }
+    }
+
+    /// Attaches ```value``` to the library, replacing the previous value of the same type which
+    /// is returned. Values are shared with the new version when the library is reloaded so they
+    /// can be used to keep track of the library in callbacks, see
+    /// [userdata](struct.Lib.html#method.userdata).
+    ///
+    /// ```ignore
+    /// lib.set_userdata(PluginInfo { name: "physics".into(), instances: Mutex::new(Vec::new()) });
+    /// ```
+    pub fn set_userdata<T: Any + Send + Sync>(&self, value: T) -> Option<Arc<T>> {
+        self.shared_userdata().insert(value)
+    }
+
+    /// Returns the value of type ```T``` attached with
+    /// [set_userdata](struct.Lib.html#method.set_userdata).
+    ///
+    /// ```ignore
+    /// fn reload_callback(plugins: &mut Plugins, state: UpdateState, lib: Option<&Arc<Lib>>) {
+    ///     if let (UpdateState::After, Some(lib)) = (state, lib) {
+    ///         let info = lib.userdata::<PluginInfo>().unwrap();
+    ///         println!("reloaded {}", info.name);
+    ///     }
+    /// }
+    /// ```
+    pub fn userdata<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.shared_userdata().get()
+    }
+
+    /// Removes the value of type ```T``` from the library and all its versions.
+    pub fn remove_userdata<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.shared_userdata().remove()
+    }
+
+    /// Stores state that is handed over to the next version of the library, meant to be called
+    /// with ```UpdateState::Before``` and taken with
+    /// [take_reload_state](struct.Lib.html#method.take_reload_state) by ```After```. If the
+    /// reload fails the state is kept for the version that is used instead, or until the
+    /// library loads again. Replaces state that hasn't been taken.
+    ///
+    /// ```ignore
+    /// fn reload_callback(plugins: &mut Plugins, state: UpdateState, lib: Option<&Arc<Lib>>) {
+    ///     let lib = lib.unwrap();
+    ///     match state {
+    ///         UpdateState::Before => lib.set_reload_state(Box::new(plugins.save(lib))),
+    ///         UpdateState::After | UpdateState::RolledBack => {
+    ///             if let Some(saved) = lib.take_reload_state() {
+    ///                 plugins.restore(lib, *saved.downcast::<Saved>().unwrap());
+    ///             }
+    ///         }
+    ///         _ => (),
+    ///     }
+    /// }
+    /// ```
+    pub fn set_reload_state(&self, state: Box<dyn Any + Send>) {
+        self.shared_userdata().set_state(state);
+    }
+
+    /// Takes the state stored by
+    /// [set_reload_state](struct.Lib.html#method.set_reload_state), from this or the previous
+    /// version of the library.
+    pub fn take_reload_state(&self) -> Option<Box<dyn Any + Send>> {
+        self.shared_userdata().take_state()
+    }
+
+    /// Hash of the content of the library when it was loaded through a shadow directory. The
+    /// shadow copy is named after this hash.
+    pub fn content_hash(&self) -> Option<u64> {
+        self.content_hash
+    }
+
+    /// Number of successful reloads before this version of the library was loaded, 0 for the
+    /// version loaded by ```add_library```. Increases with each reload, also when the previous
+    /// version failed to load, so data produced by a library can be tagged with the generation
+    /// that produced it.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// When this version of the library was loaded.
+    pub fn loaded_at(&self) -> SystemTime {
+        self.loaded_at
+    }
+
+    /// Returns the size, modification time and hash of the file the library was loaded from
+    /// together with when it was loaded and how many times it has been reloaded. Comparing the
+    /// size and hash with the file on disk tells if there is anything new to load.
+    ///
+    /// ```ignore
+    /// let info = lib.info();
+    /// if info.content_hash != Some(hash_of(&build_output)) {
+    ///     deploy(&build_output)?;
+    /// }
+    /// ```
+    pub fn info(&self) -> LibInfo {
+        LibInfo {
+            file_size: self.file_identity.map(|identity| identity.len()),
+            modified: self.file_identity.and_then(|identity| identity.modified()),
+            content_hash: self.content_hash,
+            loaded_at: self.loaded_at,
+            reloads: self.generation(),
+        }
+    }
+
+    /// Marks the library as in use until the guard is dropped so it isn't swapped out by a
+    /// reload, see [LibGuard].
+    ///
+    /// ```ignore
+    /// let guard = lib.use_guard();
+    /// let run: Symbol<fn()> = unsafe { guard.lib.get(b"run\0")? };
+    /// run();
+    /// ```
+    pub fn use_guard(&self) -> LibGuard<'_> {
+        LibGuard::new(self)
+    }
+
+    /// Returns true if there are guards from [use_guard](struct.Lib.html#method.use_guard)
+    /// alive.
+    pub fn in_use(&self) -> bool {
+        self.users.load(Ordering::Acquire) > 0
+    }
+
+    /// Returns true if the library exports ```name```.
+    pub fn has_symbol(&self, name: &str) -> bool {
+        // Safety: the symbol is only looked up, never used
+        unsafe { self.lib.get::<*const ()>(name.as_bytes()).is_ok() }
+    }
+
+    /// Returns the names of the symbols exported by the library, sorted, by parsing the loaded
+    /// file. Requires the ```exports``` feature.
+    ///
+    /// ```ignore
+    /// // Plugins register entry points by naming them plugin_*
+    /// for name in lib.exported_symbols()?.iter().filter(|n| n.starts_with("plugin_")) {
+    ///     register(&lib, name);
+    /// }
+    /// ```
+    #[cfg(feature = "exports")]
+    pub fn exported_symbols(&self) -> Result<Vec<String>> {
+        exports::read_exports(&self.loaded_path)
+    }
+
+    // The file to watch and reload from
+    fn source_path(&self) -> &Path {
+        self.original_path.as_deref().unwrap_or(&self.loaded_path)
+    }
+
+    fn user_metadata(&self) -> Metadata {
+        self.metadata.read().map(|m| m.clone()).unwrap_or_default()
+    }
+
+    fn set_user_metadata(&self, metadata: Metadata) {
+        if let Ok(mut m) = self.metadata.write() {
+            *m = metadata;
+        }
+    }
+
+    fn shared_userdata(&self) -> Userdata {
+        self.userdata.read().map(|u| u.clone()).unwrap_or_default()
+    }
+
+    fn set_shared_userdata(&self, userdata: Userdata) {
+        if let Ok(mut u) = self.userdata.write() {
+            *u = userdata;
+        }
+    }
+
+    // Uses the userdata of the previous version
+    fn share_userdata(&self, previous: &Lib) {
+        self.set_shared_userdata(previous.shared_userdata());
+    }
+
+    fn set_generation(&self, generation: u64) {
+        self.generation.store(generation, Ordering::Release);
+    }
+}
+
+impl PartialEq for Lib {
+    fn eq(&self, other: &Lib) -> bool {
+        self.original_path == other.original_path
+    }
+
+    /*
+    fn ne(&self, other: &Lib) -> bool {
+        self.original_path != other.original_path
+    }
+    */
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc::channel;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[derive(Debug, Default)]
+    struct TestNotifyCallback {
+        update_call_done: bool,
+        after_update_done: bool,
+        fail_update_done: bool,
+        removed_done: bool,
+        added_done: bool,
+        rolled_back_done: bool,
+        health_check_failed_done: bool,
+        blacklisted_done: bool,
+        still_referenced_done: bool,
+    }
+
+    impl TestNotifyCallback {
+        fn update_call(&mut self, state: UpdateState, _lib: Option<&Arc<Lib>>) {
+            match state {
+                UpdateState::Before => self.update_call_done = true,
+                UpdateState::After => self.after_update_done = true,
+                UpdateState::ReloadFailed(_) => self.fail_update_done = true,
+                UpdateState::Removed => self.removed_done = true,
+                UpdateState::Added => self.added_done = true,
+                UpdateState::RolledBack => self.rolled_back_done = true,
+                UpdateState::HealthCheckFailed(_) => self.health_check_failed_done = true,
+                UpdateState::Blacklisted(_) => self.blacklisted_done = true,
+                UpdateState::StillReferenced(_) => self.still_referenced_done = true,
+                UpdateState::BuildStarted
+                | UpdateState::BuildFinished(_)
+                | UpdateState::BuildFailed(_) => (),
+            }
+
+            println!("Update state {:?}", self);
+        }
+    }
+
+    fn get_test_shared_lib() -> PathBuf {
+        let exe_path = env::current_exe().unwrap();
+        let lib_path = exe_path.parent().unwrap().parent().unwrap();
+        let lib_name = "test_shared";
+        Path::new(&lib_path).join(DynamicReload::get_dynamiclib_name(lib_name))
+    }
+
+    // Same library built with shared_fun returning another value
+    fn get_test_shared_lib_v2() -> PathBuf {
+        get_test_shared_lib().with_file_name(DynamicReload::get_dynamiclib_name("test_shared_v2"))
+    }
+
+    // The build of the test library with other content than the one at path
+    fn other_test_shared_lib(path: &Path) -> PathBuf {
+        let v1 = get_test_shared_lib();
+        if hash::hash_file(path).ok() == hash::hash_file(&v1).ok() {
+            get_test_shared_lib_v2()
+        } else {
+            v1
+        }
+    }
+
+    // Overwrites the library with different content so it's actually reloaded
+    fn modify_test_shared_lib(path: &Path) {
+        fs::copy(other_test_shared_lib(path), path).unwrap();
+    }
+
+    // Makes a private copy of the test library so tests that overwrite it don't race each other
+    fn copy_test_shared_lib(name: &str) -> (String, PathBuf) {
+        let target_path = get_test_shared_lib();
+        let file_name = DynamicReload::get_dynamiclib_name(name);
+        let dest_path = target_path.with_file_name(&file_name);
+        fs::copy(&target_path, &dest_path).unwrap();
+        (file_name, dest_path)
+    }
+
+    #[test]
+    fn test_search_paths_none() {
+        assert_eq!(DynamicReload::get_search_paths(None::<Vec<&str>>).len(), 0);
+    }
+
+    #[test]
+    fn test_search_paths_some() {
+        assert_eq!(
+            DynamicReload::get_search_paths(Some(vec!["test", "test"])).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_get_watcher() {
+        let (tx, _) = channel();
+        // We expect this to always work
+        assert!(DynamicReload::get_watcher(tx, Duration::from_secs(2))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_quiet_period_strategy() {
+        let mut strategy = QuietPeriod::new(Duration::from_millis(100));
+        let event = notify::Event::new(notify::EventKind::Any).add_path(PathBuf::from("foo"));
+
+        strategy.event(&event);
+        assert!(strategy.ready().is_empty());
+
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(strategy.ready(), vec![PathBuf::from("foo")]);
+        assert!(strategy.ready().is_empty());
+    }
+
+    #[test]
+    fn test_debounce_strategy_update() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_strategy");
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        dr.set_debounce_strategy(Some(Box::new(QuietPeriod::new(Duration::from_millis(200)))))
+            .unwrap();
+
+        for i in 0..10 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if i == 2 {
+                modify_test_shared_lib(&dest_path);
+            }
+
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        assert!(notify_callback.update_call_done);
+        assert!(notify_callback.after_update_done);
+    }
+
+    #[test]
+    fn test_close_write_strategy_rename() {
+        use notify::event::{ModifyKind, RenameMode};
+
+        let mut strategy = CloseWrite::new();
+        let event = notify::Event::new(notify::EventKind::Modify(ModifyKind::Name(
+            RenameMode::Both,
+        )))
+        .add_path(PathBuf::from("foo.tmp"))
+        .add_path(PathBuf::from("foo"));
+
+        strategy.event(&event);
+        assert_eq!(
+            strategy.ready(),
+            vec![PathBuf::from("foo.tmp"), PathBuf::from("foo")]
+        );
+    }
+
+    #[test]
+    fn test_reload_waits_for_missing_file() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_missing");
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        fs::remove_file(&dest_path).unwrap();
+
+        unsafe {
+            dr.reload_libs(&dest_path, &TestNotifyCallback::update_call, &mut notify_callback);
+        }
+
+        assert!(!notify_callback.update_call_done);
+        assert_eq!(dr.libs.len(), 1);
+        assert_eq!(dr.missing_paths, vec![dest_path.clone()]);
+
+        fs::copy(get_test_shared_lib_v2(), &dest_path).unwrap();
+
+        unsafe {
+            dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+        }
+
+        assert!(notify_callback.update_call_done);
+        assert!(notify_callback.after_update_done);
+    }
+
+    #[test]
+    fn test_removed() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_removed");
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_millis(100),
+        );
+
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        fs::remove_file(&dest_path).unwrap();
+
+        // Not reported until it has been missing for the debounce duration
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(!notify_callback.removed_done);
+
+        thread::sleep(Duration::from_millis(300));
+        unsafe {
+            dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+        }
+        assert!(notify_callback.removed_done);
+        assert!(!notify_callback.update_call_done);
+        assert_eq!(dr.libs.len(), 1);
+
+        // Only reported once and no longer checked after that
+        notify_callback.removed_done = false;
+        unsafe {
+            dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+        }
+        assert!(!notify_callback.removed_done);
+        assert!(dr.missing_paths.is_empty());
+
+        // Picked up again by the watch when the file is back
+        fs::copy(get_test_shared_lib_v2(), &dest_path).unwrap();
+
+        for _ in 0..20 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if notify_callback.after_update_done {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        assert!(notify_callback.after_update_done);
+        assert!(dr.removed.is_empty());
+    }
+
+    #[test]
+    fn test_add_directory() {
+        let dir = get_test_shared_lib().with_file_name("test_add_directory");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+
+        let first = dir.join(DynamicReload::get_dynamiclib_name("first"));
+        let second = dir.join(DynamicReload::get_dynamiclib_name("second"));
+        fs::copy(get_test_shared_lib(), &first).unwrap();
+        fs::write(dir.join("readme.txt"), "not a library").unwrap();
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_millis(100),
+        );
+
+        let libs = unsafe { dr.add_directory(&dir, None).unwrap() };
+        assert_eq!(libs.len(), 1);
+        assert_eq!(libs[0].source_path(), first.canonicalize().unwrap());
+
+        let mut notify_callback = TestNotifyCallback::default();
+        fs::copy(get_test_shared_lib(), &second).unwrap();
+
+        for _ in 0..20 {
+            unsafe { dr.update(&TestNotifyCallback::update_call, &mut notify_callback) };
+            if notify_callback.added_done {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        assert!(notify_callback.added_done);
+        assert_eq!(dr.libs.len(), 2);
+
+        drop(libs);
+        fs::remove_file(&first).unwrap();
+
+        for _ in 0..20 {
+            unsafe { dr.update(&TestNotifyCallback::update_call, &mut notify_callback) };
+            if notify_callback.removed_done {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        assert!(notify_callback.removed_done);
+        assert_eq!(dr.libs.len(), 1);
+        assert_eq!(dr.libs[0].source_path(), second.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_add_libraries_matching() {
+        let dir = get_test_shared_lib().with_file_name("test_matching");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+
+        for name in ["one_plugin", "two_plugin", "other"] {
+            let path = dir.join(DynamicReload::get_dynamiclib_name(name));
+            fs::copy(get_test_shared_lib(), path).unwrap();
+        }
+
+        // Not a valid library
+        let broken = dir.join(DynamicReload::get_dynamiclib_name("broken_plugin"));
+        fs::write(&broken, "not a library").unwrap();
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let libs = unsafe {
+            dr.add_libraries_matching("test_matching/*_plugin", PlatformName::Yes)
+                .unwrap()
+        };
+
+        let names: Vec<String> = libs
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+
+        assert_eq!(
+            names,
+            vec![
+                DynamicReload::get_dynamiclib_name("broken_plugin"),
+                DynamicReload::get_dynamiclib_name("one_plugin"),
+                DynamicReload::get_dynamiclib_name("two_plugin"),
+            ]
+        );
+        assert!(libs[0].1.is_err());
+        assert!(libs[1].1.is_ok() && libs[2].1.is_ok());
+        assert_eq!(dr.libs.len(), 2);
+
+        let failed: Vec<LibHandle> = dr.iter_libs().failed().collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].path, libs[0].0);
+
+        assert!(unsafe { dr.add_libraries_matching("[", PlatformName::No) }.is_err());
+    }
+
+    #[test]
+    fn test_add_library_path() {
+        let (_, dest_path) = copy_test_shared_lib("test_add_library_path");
+
+        // Not found through searching
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Custom(Box::new(|_: &str, _: &[PathBuf]| None)),
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library_path(&dest_path).unwrap() };
+        assert_eq!(lib.source_path(), dest_path.canonicalize().unwrap());
+        assert_eq!(
+            dr.status()[0].original_path.as_ref(),
+            Some(&lib.source_path().to_path_buf())
+        );
+
+        let again = unsafe { dr.add_library_path(&dest_path).unwrap() };
+        assert!(Arc::ptr_eq(&lib, &again));
+
+        assert!(matches!(
+            unsafe { dr.add_library_path(dest_path.with_extension("missing")) },
+            Err(Error::Find(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_shadow_dependencies() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_shadow_deps");
+        let (dep_name, _) = copy_test_shared_lib("test_shadow_deps_dep");
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_millis(100),
+        );
+
+        let options = LoadOptions {
+            platform_name: PlatformName::No,
+            shadow_dependencies: vec![PathBuf::from(&dep_name)],
+            ..LoadOptions::default()
+        };
+
+        let lib = unsafe {
+            dr.add_library_with_options(&file_name, options.clone())
+                .unwrap()
+        };
+        let dep_copy = lib.loaded_path.with_file_name(&dep_name);
+        assert!(dep_copy.is_file());
+        let load_dir = lib.loaded_path.parent().unwrap().to_path_buf();
+        assert_ne!(load_dir, dr.shadow_dir.as_ref().unwrap().path());
+
+        // Another library with the same dependency gets its own copy
+        let (other_name, _) = copy_test_shared_lib("test_shadow_deps_other");
+        let other = unsafe { dr.add_library_with_options(&other_name, options).unwrap() };
+        assert_ne!(other.loaded_path.parent(), lib.loaded_path.parent());
+        assert!(other.loaded_path.with_file_name(&dep_name).is_file());
+        drop(lib);
+
+        // Copied to a new directory when the library is reloaded, the directory of the old
+        // version is removed once it's unloaded
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+        let lib = dr.get_by_path(&dest_path).unwrap();
+        assert!(lib.loaded_path.with_file_name(&dep_name).is_file());
+        assert_ne!(lib.loaded_path.parent().unwrap(), load_dir);
+        #[cfg(not(feature = "no-unload"))]
+        assert!(!load_dir.exists());
+    }
+
+    #[test]
+    fn test_load_options() {
+        let (no_shadow, no_shadow_path) = copy_test_shared_lib("test_options_no_shadow");
+        let (no_watch, no_watch_path) = copy_test_shared_lib("test_options_no_watch");
+        let (slow, slow_path) = copy_test_shared_lib("test_options_slow");
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_millis(100),
+        );
+
+        let options = LoadOptions {
+            platform_name: PlatformName::No,
+            ..LoadOptions::default()
+        };
+
+        let lib = unsafe {
+            dr.add_library_with_options(
+                &no_shadow,
+                LoadOptions {
+                    shadow: false,
+                    ..options.clone()
+                },
+            )
+            .unwrap()
+        };
+        assert!(lib.original_path.is_none());
+        assert_eq!(lib.loaded_path.file_name(), no_shadow_path.file_name());
+
+        unsafe {
+            dr.add_library_with_options(
+                &no_watch,
+                LoadOptions {
+                    watch: false,
+                    ..options.clone()
+                },
+            )
+            .unwrap();
+
+            dr.add_library_with_options(
+                &slow,
+                LoadOptions {
+                    debounce: Some(Duration::from_millis(500)),
+                    ..options.clone()
+                },
+            )
+            .unwrap();
+        }
+
+        let mut notify_callback = TestNotifyCallback::default();
+        modify_test_shared_lib(&no_watch_path);
+
+        for _ in 0..5 {
+            unsafe { dr.update(&TestNotifyCallback::update_call, &mut notify_callback) };
+            thread::sleep(Duration::from_millis(100));
+        }
+        assert!(!notify_callback.update_call_done);
+
+        let start = Instant::now();
+        modify_test_shared_lib(&slow_path);
+
+        for _ in 0..40 {
+            unsafe { dr.update(&TestNotifyCallback::update_call, &mut notify_callback) };
+            if notify_callback.after_update_done {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        assert!(notify_callback.after_update_done);
+        assert!(start.elapsed() >= Duration::from_millis(600));
+    }
+
+    #[test]
+    fn test_load_options_flags() {
+        let (file_name, _) = copy_test_shared_lib("test_options_flags");
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_secs(1),
+        );
+
+        let options = LoadOptions {
+            platform_name: PlatformName::No,
+            bind_now: true,
+            ..LoadOptions::default()
+        };
+
+        unsafe {
+            assert!(dr
+                .add_library_with_options(&file_name, options.clone())
+                .is_ok());
+        }
+
+        // dlopen requires either RTLD_LAZY or RTLD_NOW
+        #[cfg(unix)]
+        unsafe {
+            let options = LoadOptions {
+                raw_flags: Some(0),
+                ..options
+            };
+            assert!(matches!(
+                dr.add_library_with_options(&file_name, options),
+                Err(Error::Load(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_atomic_rename_update() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_rename");
+        let temp_path = dest_path.with_extension("tmp");
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(500),
+        );
+
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        for i in 0..10 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if i == 2 {
+                fs::copy(other_test_shared_lib(&dest_path), &temp_path).unwrap();
+                fs::rename(&temp_path, &dest_path).unwrap();
+            }
+
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        assert!(notify_callback.update_call_done);
+        assert!(notify_callback.after_update_done);
+    }
+
+    #[test]
+    fn test_recursive_search_path_fail() {
+        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+        assert!(dr.add_recursive_search_path("_no_such_dir").is_err());
+    }
+
+    #[test]
+    fn test_recursive_search_path_update() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let root = get_test_shared_lib().with_file_name("test_plugins");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(500),
+        );
+
+        dr.add_recursive_search_path(root.to_str().unwrap()).unwrap();
+
+        // The plugin directory is created after the root is being watched
+        let plugin_dir = root.join("foo");
+        fs::create_dir_all(&plugin_dir).unwrap();
+        let file_name = DynamicReload::get_dynamiclib_name("test_nested");
+        let dest_path = plugin_dir.join(&file_name);
+        fs::copy(get_test_shared_lib(), &dest_path).unwrap();
+
+        let lib = unsafe { dr.add_library("test_nested", PlatformName::Yes).unwrap() };
+        assert_eq!(
+            lib.original_path.as_ref().unwrap().parent().unwrap(),
+            plugin_dir.canonicalize().unwrap()
+        );
+
+        for i in 0..10 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if i == 2 {
+                modify_test_shared_lib(&dest_path);
+            }
+
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        assert!(notify_callback.update_call_done);
+        assert!(notify_callback.after_update_done);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_file_identity_classify() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = get_test_shared_lib().with_file_name("test_identity");
+        let temp_path = path.with_extension("tmp");
+        fs::write(&path, "first").unwrap();
+        let first = FileIdentity::from_path(&path).unwrap();
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        let touched = FileIdentity::from_path(&path).unwrap();
+        assert_eq!(first.classify(&touched), FileChange::Touched);
+
+        fs::write(&path, "second write").unwrap();
+        let modified = FileIdentity::from_path(&path).unwrap();
+        assert_eq!(first.classify(&modified), FileChange::Modified);
+
+        fs::write(&temp_path, "third").unwrap();
+        fs::rename(&temp_path, &path).unwrap();
+        let replaced = FileIdentity::from_path(&path).unwrap();
+        assert_eq!(modified.classify(&replaced), FileChange::Replaced);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_touched_lib_not_reloaded() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_touch");
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        fs::set_permissions(&dest_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        unsafe {
+            dr.reload_libs(&dest_path, &TestNotifyCallback::update_call, &mut notify_callback);
+        }
+
+        assert!(!notify_callback.update_call_done);
+    }
+
+    #[test]
+    fn test_same_size_rebuild_reloaded() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_same_size");
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        // Rebuilt in place with the same size and within the same modification time
+        let modified = fs::metadata(&dest_path).unwrap().modified().unwrap();
+        let mut data = fs::read(&dest_path).unwrap();
+        let name = b"name = test_shared\n";
+        let pos = data.windows(name.len()).position(|w| w == name).unwrap();
+        data[pos + name.len() - 2] = b'e';
+        fs::write(&dest_path, &data).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&dest_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        unsafe {
+            dr.reload_libs(&dest_path, &TestNotifyCallback::update_call, &mut notify_callback);
+        }
+
+        assert!(notify_callback.after_update_done);
+    }
+
+    #[test]
+    fn test_copy_retry_delay() {
+        let retry = CopyRetry {
+            jitter: 0.0,
+            ..CopyRetry::default()
+        };
+
+        assert_eq!(retry.delay(0), Duration::from_millis(20));
+        assert_eq!(retry.delay(1), Duration::from_millis(40));
+        assert_eq!(retry.delay(3), Duration::from_millis(160));
+        assert_eq!(retry.delay(4), Duration::from_millis(200));
+        assert_eq!(retry.delay(100), Duration::from_millis(200));
+
+        let retry = CopyRetry::default();
+        let delay = retry.delay(2);
+        assert!(delay <= Duration::from_millis(80) && delay >= Duration::from_millis(60));
+    }
+
+    #[test]
+    fn test_copy_time_out() {
+        let retry = CopyRetry {
+            attempts: 2,
+            ..CopyRetry::default()
+        };
+
+        match copy::try_copy(
+            Path::new("_no_such_file"),
+            Path::new("target/debug/_no_such_copy"),
+            &retry,
+            CopyVerify::Size,
+        ) {
+            Err(Error::CopyTimeOut(_, _)) => (),
+            _ => panic!("expected copy time out"),
+        }
+    }
+
+    #[test]
+    fn test_copy_verify_hash() {
+        let target_path = get_test_shared_lib();
+        let dest_path = target_path.with_file_name("test_verify");
+
+        assert!(copy::try_copy(
+            &target_path,
+            &dest_path,
+            &CopyRetry::default(),
+            CopyVerify::Hash
+        )
+        .is_ok());
+
+        assert_eq!(
+            hash::hash_file(&target_path).unwrap(),
+            hash::hash_file(&dest_path).unwrap()
+        );
+        assert_ne!(
+            hash::hash_file(&target_path).unwrap(),
+            hash::hash_file(Path::new("Cargo.toml")).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_preflight() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut dr = DynamicReload::new(None, None, Search::Backwards, Duration::from_secs(2));
+
+        dr.set_preflight(Some(Preflight::program("true")));
+        unsafe {
+            assert!(dr.add_library("test_shared", PlatformName::Yes).is_ok());
+        }
+
+        dr.set_preflight(Some(Preflight::program("false")));
+        unsafe {
+            match dr.add_library("test_shared", PlatformName::Yes) {
+                Err(Error::Preflight(_, _)) => (),
+                _ => panic!("expected pre-flight to fail"),
+            }
+        }
+
+        // More output than fits in the pipe
+        let dir = tempfile::tempdir().unwrap();
+        let helper = dir.path().join("helper.sh");
+        fs::write(
+            &helper,
+            "#!/bin/sh\nhead -c 1000000 /dev/zero | tr '\\0' x >&2\nexit 1\n",
+        )
+        .unwrap();
+        fs::set_permissions(&helper, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut preflight = Preflight::program(&helper);
+        preflight.timeout = Duration::from_secs(5);
+        dr.set_preflight(Some(preflight));
+        unsafe {
+            match dr.add_library("test_shared", PlatformName::Yes) {
+                Err(Error::Preflight(_, reason)) => assert!(reason.ends_with("xxx")),
+                _ => panic!("expected pre-flight to fail"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_init_timeout() {
+        let mut dr = DynamicReload::new(None, None, Search::Backwards, Duration::from_secs(2));
+        dr.set_init_timeout(Some(Duration::from_secs(5)));
+
+        unsafe {
+            assert!(dr.add_library("test_shared", PlatformName::Yes).is_ok());
+        }
+
+        dr.set_init_symbol(Some("test_init_slow"));
+        dr.set_init_timeout(Some(Duration::from_millis(200)));
+
+        unsafe {
+            match dr.add_library("test_shared", PlatformName::Yes) {
+                Err(Error::InitTimeout(_)) => (),
+                _ => panic!("expected init to time out"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_stop_watching() {
+        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_millis(100));
+        assert!(dr.is_watching());
+        assert_eq!(dr.watcher_thread().unwrap().name(), Some(WATCHER_THREAD_NAME));
+
+        dr.stop_watching();
+        assert!(!dr.is_watching());
+        assert!(dr.watcher_thread().is_none());
+
+        dr.start_watching().unwrap();
+        assert!(dr.is_watching());
+    }
+
+    #[test]
+    fn test_stop_discards_pending() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_stop_discard");
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        dr.set_debounce_strategy(Some(Box::new(QuietPeriod::new(Duration::from_secs(10)))))
+            .unwrap();
+
+        modify_test_shared_lib(&dest_path);
+        thread::sleep(Duration::from_millis(300));
+
+        dr.stop();
+        assert!(!dr.is_watching());
+
+        unsafe {
+            dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+        }
+
+        assert!(!notify_callback.update_call_done);
+    }
+
+    #[test]
+    fn test_stop_and_flush() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_stop_flush");
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        dr.set_debounce_strategy(Some(Box::new(QuietPeriod::new(Duration::from_secs(10)))))
+            .unwrap();
+
+        modify_test_shared_lib(&dest_path);
+        thread::sleep(Duration::from_millis(300));
+
+        unsafe {
+            dr.stop_and_flush(&TestNotifyCallback::update_call, &mut notify_callback);
+        }
+
+        assert!(!dr.is_watching());
+        assert!(notify_callback.update_call_done);
+        assert!(notify_callback.after_update_done);
+    }
+
+    #[test]
+    fn test_poll_fallback() {
+        use std::io;
+
+        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(1));
+        assert_eq!(
+            dr.active_watcher_backend(),
+            Some(WatcherBackend::Recommended)
+        );
+
+        // Missing paths fail with polling as well so they don't switch backend
+        assert!(dr.watch_recursive(Path::new("_no_such_dir")).is_err());
+        assert_eq!(
+            dr.active_watcher_backend(),
+            Some(WatcherBackend::Recommended)
+        );
+
+        assert!(watcher::needs_fallback(&notify::Error::new(
+            notify::ErrorKind::MaxFilesWatch
+        )));
+        assert!(watcher::needs_fallback(&notify::Error::io(
+            io::Error::from(io::ErrorKind::Unsupported)
+        )));
+        assert!(!watcher::needs_fallback(&notify::Error::io(
+            io::Error::from(io::ErrorKind::NotFound)
+        )));
+
+        let interval = Duration::from_millis(100);
+        dr.set_watcher_backend(WatcherBackend::Poll { interval })
+            .unwrap();
+        assert_eq!(
+            dr.active_watcher_backend(),
+            Some(WatcherBackend::Poll { interval })
+        );
+
+        dr.stop_watching();
+        assert_eq!(dr.active_watcher_backend(), None);
+    }
+
+    #[test]
+    fn test_builder_watcher_backend() {
+        let interval = Duration::from_millis(100);
+        let dr = DynamicReload::builder()
+            .watcher_backend(WatcherBackend::Poll { interval })
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            dr.active_watcher_backend(),
+            Some(WatcherBackend::Poll { interval })
+        );
+
+        let dr = DynamicReload::builder()
+            .watcher_backend(WatcherBackend::None)
+            .build()
+            .unwrap();
+
+        assert!(!dr.is_watching());
+    }
+
+    #[test]
+    fn test_poll_backend_update() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_poll");
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(300),
+        );
+
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        dr.set_watcher_backend(WatcherBackend::Poll {
+            interval: Duration::from_millis(100),
+        })
+        .unwrap();
+
+        for i in 0..10 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if i == 2 {
+                // Polling only sees modification times with a resolution of a second
+                modify_test_shared_lib(&dest_path);
+                let file = fs::File::options().write(true).open(&dest_path).unwrap();
+                file.set_modified(std::time::SystemTime::now() + Duration::from_secs(2))
+                    .unwrap();
+            }
+
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        assert!(notify_callback.update_call_done);
+        assert!(notify_callback.after_update_done);
+
+        dr.set_watcher_backend(WatcherBackend::None).unwrap();
+        assert!(!dr.is_watching());
+    }
+
+    #[test]
+    fn test_ignore_patterns() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_ignore");
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(300),
+        );
+
+        assert!(dr.set_ignore_patterns(&["[invalid"]).is_err());
+        dr.set_ignore_patterns(&["*test_ignore*"]).unwrap();
+
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        for i in 0..6 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if i == 1 {
+                modify_test_shared_lib(&dest_path);
+            }
+
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        assert!(!notify_callback.update_call_done);
+    }
+
+    #[test]
+    fn test_set_search_paths_relocates() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let file_name = DynamicReload::get_dynamiclib_name("test_relocate");
+        let base = get_test_shared_lib().with_file_name("test_relocate");
+        let dir_a = base.join("a");
+        let dir_b = base.join("b");
+        fs::create_dir_all(&dir_a).unwrap();
+        fs::create_dir_all(&dir_b).unwrap();
+        fs::copy(get_test_shared_lib(), dir_a.join(&file_name)).unwrap();
+        fs::copy(get_test_shared_lib(), dir_b.join(&file_name)).unwrap();
+
+        let mut dr = DynamicReload::new(
+            Some(vec![dir_a.to_str().unwrap()]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        assert!(lib.original_path.as_ref().unwrap().starts_with(dir_a.canonicalize().unwrap()));
+
+        dr.set_search_paths(vec![dir_b.to_str().unwrap()]);
+
+        unsafe {
+            dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+        }
+
+        assert!(notify_callback.after_update_done);
+        assert!(dr.libs[0]
+            .original_path
+            .as_ref()
+            .unwrap()
+            .starts_with(dir_b.canonicalize().unwrap()));
+    }
+
+    #[test]
+    fn test_cluster_follower_applies_generations() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, coordinator_path) = copy_test_shared_lib("test_cluster");
+        let cluster_dir = get_test_shared_lib().with_file_name("test_cluster");
+        let _ = fs::remove_dir_all(&cluster_dir);
+
+        let mut coordinator = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        coordinator
+            .set_cluster(Some(Cluster::coordinator(&cluster_dir).unwrap()))
+            .unwrap();
+
+        let mut follower = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        follower
+            .set_cluster(Some(Cluster::follower(&cluster_dir).unwrap()))
+            .unwrap();
+
+        unsafe {
+            assert!(coordinator.add_library(&file_name, PlatformName::No).is_ok());
+            assert!(follower.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        assert_eq!(coordinator.cluster().unwrap().generation(), 1);
+
+        // Nothing has changed compared to what the follower has loaded
+        unsafe {
+            follower.update(&TestNotifyCallback::update_call, &mut notify_callback);
+        }
+
+        assert!(!notify_callback.update_call_done);
+        assert_eq!(follower.libs[0].generation(), 0);
+        assert_eq!(follower.cluster().unwrap().generation(), 1);
+
+        modify_test_shared_lib(&coordinator_path);
+
+        unsafe {
+            coordinator.reload_libs(
+                &coordinator_path,
+                &|_: &mut (), _, _| (),
+                &mut (),
+            );
+        }
+
+        assert_eq!(coordinator.cluster().unwrap().generation(), 2);
+
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            follower.update(&TestNotifyCallback::update_call, &mut notify_callback);
+        }
+
+        assert!(notify_callback.after_update_done);
+        assert_eq!(follower.cluster().unwrap().generation(), 2);
+        assert!(follower.libs[0]
+            .original_path
+            .as_ref()
+            .unwrap()
+            .starts_with(cluster_dir.join("2")));
+    }
+
+    #[test]
+    fn test_metadata_from_manifest_and_code() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_metadata");
+        let manifest = dest_path.with_file_name(format!("{}.meta", file_name));
+        fs::write(&manifest, "# comment\nauthor = Jane Doe\n\ngroup=render\n").unwrap();
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        assert_eq!(lib.metadata("author").as_deref(), Some("Jane Doe"));
+        assert_eq!(lib.metadata("group").as_deref(), Some("render"));
+
+        lib.set_metadata("group", "ui");
+        assert_eq!(lib.metadata("group").as_deref(), Some("ui"));
+        assert_eq!(dr.status()[0].metadata.get("author").unwrap(), "Jane Doe");
+
+        fs::write(&manifest, "author = John Doe\n").unwrap();
+
+        for i in 0..10 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if i == 2 {
+                modify_test_shared_lib(&dest_path);
+            }
+
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        assert!(notify_callback.after_update_done);
+
+        let status = dr.status();
+        assert_eq!(status[0].metadata.get("author").unwrap(), "John Doe");
+        assert_eq!(status[0].metadata.get("group").unwrap(), "ui");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_iter_libs_filters() {
+        let (render_name, render_path) = copy_test_shared_lib("test_iter_render");
+        let (other_name, _) = copy_test_shared_lib("test_iter_other");
+        fs::write(
+            render_path.with_file_name(format!("{}.meta", render_name)),
+            "group = render, debug\n",
+        )
+        .unwrap();
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe {
+            dr.add_library(&render_name, PlatformName::No).unwrap();
+            dr.add_library(&other_name, PlatformName::No).unwrap();
+        }
+
+        assert_eq!(dr.iter_libs().count(), 2);
+        assert_eq!(dr.iter_libs().in_group("render").count(), 1);
+        assert_eq!(dr.iter_libs().in_group("debug").count(), 1);
+        assert_eq!(dr.iter_libs().filter_stale().count(), 0);
+
+        modify_test_shared_lib(&render_path);
+
+        let stale: Vec<LibHandle> = dr.iter_libs().filter_stale().collect();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].path, render_path);
+
+        dr.set_preflight(Some(Preflight::program("false")));
+        unsafe {
+            dr.reload_libs(&render_path, &|_: &mut (), _, _| (), &mut ());
+        }
+
+        let failed: Vec<LibHandle> = dr.iter_libs().failed().collect();
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].error.is_some());
+        assert!(failed[0].in_group("render"));
+        assert_eq!(dr.iter_libs().loaded().count(), 1);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-timestamps"))]
+    fn test_reload_failed_previous() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_failed_previous");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let loaded_path = lib.loaded_path.clone();
+        dr.inject_failure(&lib, FailPoint::Load);
+        drop(lib);
+
+        let failed = |previous: &mut Option<Arc<Lib>>, state, lib: Option<&Arc<Lib>>| {
+            if let UpdateState::ReloadFailed(_) = state {
+                *previous = lib.cloned();
+            }
+        };
+
+        modify_test_shared_lib(&dest_path);
+        let mut previous = None;
+        unsafe {
+            dr.reload_libs(&dest_path, &failed, &mut previous);
+        }
+
+        // Still usable after the failure
+        let previous = previous.unwrap();
+        assert_eq!(previous.loaded_path, loaded_path);
+        assert!(unsafe { previous.lib.get::<fn() -> i32>(b"shared_fun\0") }.is_ok());
+        assert!(dr.libs.is_empty());
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-timestamps"))]
+    fn test_swap_order_load_first() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_load_first");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        dr.set_swap_order(SwapOrder::LoadFirst);
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let loaded_path = lib.loaded_path.clone();
+        dr.inject_failure(&lib, FailPoint::Load);
+        drop(lib);
+
+        // A failed load doesn't touch the loaded version
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        assert!(notify_callback.fail_update_done);
+        assert!(!notify_callback.update_call_done);
+        assert_eq!(dr.libs.len(), 1);
+        assert_eq!(dr.libs[0].loaded_path, loaded_path);
+        assert!(dr.failed.is_empty());
+
+        modify_test_shared_lib(&dest_path);
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        assert!(notify_callback.update_call_done);
+        assert!(notify_callback.after_update_done);
+        assert_eq!(dr.libs.len(), 1);
+        assert_ne!(dr.libs[0].loaded_path, loaded_path);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-timestamps"))]
+    fn test_transactional_group() {
+        let (first, first_path) = copy_test_shared_lib("test_group_first");
+        let (second, second_path) = copy_test_shared_lib("test_group_second");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        dr.set_transactional("group", true);
+
+        let first = unsafe { dr.add_library(&first, PlatformName::No).unwrap() };
+        let second = unsafe { dr.add_library(&second, PlatformName::No).unwrap() };
+        first.set_metadata(GROUP_KEY, "group");
+        second.set_metadata(GROUP_KEY, "other, group");
+        let loaded: Vec<PathBuf> = dr.libs.iter().map(|l| l.loaded_path.clone()).collect();
+        dr.inject_failure(&second, FailPoint::Load);
+        drop((first, second));
+
+        let record = |events: &mut Vec<&str>, state, _: Option<&Arc<Lib>>| {
+            events.push(match state {
+                UpdateState::Before => "before",
+                UpdateState::After => "after",
+                UpdateState::ReloadFailed(Error::GroupAborted(_, _)) => "aborted",
+                UpdateState::ReloadFailed(_) => "failed",
+                _ => "other",
+            });
+        };
+
+        // One failing member keeps both at the version in use
+        modify_test_shared_lib(&first_path);
+        modify_test_shared_lib(&second_path);
+        let mut events = Vec::new();
+        unsafe { dr.reload_libs(&first_path, &record, &mut events) };
+
+        events.sort();
+        assert_eq!(events, ["aborted", "failed"]);
+        let current: Vec<PathBuf> = dr.libs.iter().map(|l| l.loaded_path.clone()).collect();
+        assert_eq!(current, loaded);
+
+        // Both are swapped together once they load
+        modify_test_shared_lib(&first_path);
+        modify_test_shared_lib(&first_path);
+        modify_test_shared_lib(&second_path);
+        modify_test_shared_lib(&second_path);
+        let mut events = Vec::new();
+        unsafe { dr.reload_libs(&second_path, &record, &mut events) };
+
+        assert_eq!(events, ["before", "before", "after", "after"]);
+        assert!(dr.libs.iter().all(|l| !loaded.contains(&l.loaded_path)));
+        assert!(dr.libs.iter().all(|l| l.metadata(GROUP_KEY).is_some()));
+    }
+
+    #[test]
+    fn test_queue_reloads() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_queue_reloads");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(100),
+        );
+        dr.set_queue_reloads(true);
+
+        unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let loaded_path = dr.libs[0].loaded_path.clone();
+        modify_test_shared_lib(&dest_path);
+
+        let mut notify_callback = TestNotifyCallback::default();
+        for _ in 0..20 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if !dr.pending_reloads().is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        // Detected twice before the commit, reloaded once
+        fs::copy(get_test_shared_lib_v2(), &dest_path).unwrap();
+        for _ in 0..5 {
+            thread::sleep(Duration::from_millis(100));
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+        }
+
+        assert_eq!(dr.pending_reloads(), std::slice::from_ref(&dest_path));
+        assert!(!notify_callback.update_call_done);
+        assert_eq!(dr.libs[0].loaded_path, loaded_path);
+
+        unsafe { dr.commit_pending(&TestNotifyCallback::update_call, &mut notify_callback) };
+        assert!(notify_callback.after_update_done);
+        assert!(dr.pending_reloads().is_empty());
+        // The copy has the same name without a content hash
+        #[cfg(not(feature = "no-timestamps"))]
+        assert_ne!(dr.libs[0].loaded_path, loaded_path);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-timestamps"))]
+    fn test_health_check() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_health_check");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        dr.set_health_check(Some(HealthCheck::Custom(Box::new(|lib| {
+            match lib.has_symbol("missing_fun") {
+                true => Ok(()),
+                false => Err("missing_fun is missing".to_string()),
+            }
+        }))));
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        lib.set_metadata("kept", "yes");
+        let loaded_path = lib.loaded_path.clone();
+        drop(lib);
+
+        // The previous version is used again
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        assert!(notify_callback.update_call_done);
+        assert!(notify_callback.health_check_failed_done);
+        assert!(!notify_callback.after_update_done);
+        assert_eq!(dr.libs.len(), 1);
+        assert_eq!(dr.libs[0].loaded_path, loaded_path);
+        assert_eq!(dr.libs[0].metadata("kept").as_deref(), Some("yes"));
+
+        // The test library doesn't export dr_health_check so it passes
+        dr.set_health_check(Some(HealthCheck::Symbol));
+        let lib = dr.libs[0].clone();
+        dr.inject_failure(&lib, FailPoint::HealthCheck);
+        drop(lib);
+
+        modify_test_shared_lib(&dest_path);
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.health_check_failed_done);
+
+        modify_test_shared_lib(&dest_path);
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+        assert_ne!(dr.libs[0].loaded_path, loaded_path);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-timestamps"))]
+    fn test_required_symbols() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_required_symbols");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let options = LoadOptions {
+            platform_name: PlatformName::No,
+            required_symbols: vec!["missing_fun".to_string()],
+            ..LoadOptions::default()
+        };
+
+        match unsafe { dr.add_library_with_options(&file_name, options) } {
+            Err(Error::MissingSymbol(_, symbol)) => assert_eq!(symbol, "missing_fun"),
+            _ => panic!("expected missing symbol"),
+        }
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let loaded_path = lib.loaded_path.clone();
+        dr.set_required_symbols(&lib, &["shared_fun", "missing_fun"]);
+        drop(lib);
+
+        // Checked before the loaded version is touched
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        assert!(notify_callback.fail_update_done);
+        assert!(!notify_callback.update_call_done);
+        assert_eq!(dr.libs.len(), 1);
+        assert_eq!(dr.libs[0].loaded_path, loaded_path);
+
+        let lib = dr.libs[0].clone();
+        dr.set_required_symbols(&lib, &["shared_fun"]);
+        modify_test_shared_lib(&dest_path);
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+    }
+
+    #[test]
+    #[cfg(feature = "exports")]
+    fn test_exported_symbols() {
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            None,
+            Search::Backwards,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library("test_shared", PlatformName::Yes).unwrap() };
+        let symbols = lib.exported_symbols().unwrap();
+
+        assert!(symbols.iter().any(|s| s == "shared_fun"));
+        assert!(symbols.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_plugin_metadata() {
+        crate::plugin_metadata! {
+            name = "foo",
+            version = env!("CARGO_PKG_VERSION"),
+            abi = "foo-api-2",
+        }
+
+        let text = unsafe { std::ffi::CStr::from_ptr(dr_metadata()) };
+        assert_eq!(
+            text.to_str().unwrap(),
+            format!(
+                "name = foo\nversion = {}\nabi = foo-api-2\n",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        assert!(valid_metadata_value("foo-api-2"));
+        assert!(!valid_metadata_value("a = b"));
+        assert!(!valid_metadata_value("foo\nabi = other"));
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-timestamps"))]
+    fn test_abi_tag() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_abi_tag");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        // The tag is checked before any init function of the library is called
+        dr.set_abi_tag(Some("other"));
+        dr.set_init_symbol(Some("test_init_hang"));
+        dr.set_init_timeout(Some(Duration::from_millis(200)));
+        match unsafe { dr.add_library(&file_name, PlatformName::No) } {
+            Err(Error::Incompatible(_, _)) => (),
+            _ => panic!("expected incompatible library"),
+        }
+
+        dr.set_init_symbol(None);
+        dr.set_abi_tag(Some("test"));
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        assert_eq!(lib.metadata("name").as_deref(), Some("test_shared"));
+        assert_eq!(lib.metadata(ABI_KEY).as_deref(), Some("test"));
+        let loaded_path = lib.loaded_path.clone();
+        drop(lib);
+
+        // The new version is checked before the loaded one is unloaded
+        dr.set_abi_tag(Some("other"));
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        assert!(notify_callback.fail_update_done);
+        assert!(!notify_callback.update_call_done);
+        assert_eq!(dr.libs[0].loaded_path, loaded_path);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        crate::plugin_fingerprint!("test");
+
+        let text = unsafe { std::ffi::CStr::from_ptr(dr_fingerprint()) };
+        assert_eq!(text.to_str().unwrap(), format!("{}\ntest", RUSTC_VERSION));
+
+        let (file_name, _) = copy_test_shared_lib("test_fingerprint");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        // The fingerprint is checked before any init function of the library is called
+        dr.set_fingerprint(Some("other"));
+        dr.set_init_symbol(Some("test_init_hang"));
+        dr.set_init_timeout(Some(Duration::from_millis(200)));
+        match unsafe { dr.add_library(&file_name, PlatformName::No) } {
+            Err(Error::Fingerprint(_, reason)) => assert!(reason.contains("interface")),
+            _ => panic!("expected fingerprint mismatch"),
+        }
+
+        dr.set_init_symbol(None);
+        dr.set_fingerprint(Some("test"));
+        assert!(unsafe { dr.add_library(&file_name, PlatformName::No) }.is_ok());
+    }
+
+    #[test]
+    #[cfg(all(feature = "signatures", not(feature = "no-timestamps")))]
+    fn test_signatures() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let (file_name, dest_path) = copy_test_shared_lib("test_signatures");
+        let sig_path = PathBuf::from(format!("{}.{}", dest_path.display(), SIGNATURE_EXTENSION));
+        let _ = fs::remove_file(&sig_path);
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        dr.set_public_key(Some(key.verifying_key()));
+
+        match unsafe { dr.add_library(&file_name, PlatformName::No) } {
+            Err(Error::SignatureInvalid(_, _)) => (),
+            _ => panic!("expected invalid signature"),
+        }
+
+        let sig = key.sign(&fs::read(&dest_path).unwrap());
+        fs::write(&sig_path, sig.to_bytes()).unwrap();
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let loaded_path = lib.loaded_path.clone();
+        drop(lib);
+
+        // The signature no longer matches the new version so the old one is kept
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        assert!(notify_callback.fail_update_done);
+        assert!(!notify_callback.update_call_done);
+        assert_eq!(dr.libs[0].loaded_path, loaded_path);
+
+        // Signing the new version afterwards is a change to the library
+        let sig = key.sign(&fs::read(&dest_path).unwrap());
+        fs::write(&sig_path, sig.to_bytes()).unwrap();
+        assert_eq!(dr.signature_alias(&sig_path), Some(dest_path.clone()));
+
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.update_call_done);
+        assert_ne!(dr.libs[0].loaded_path, loaded_path);
+    }
+
+    #[test]
+    #[cfg(feature = "checksums")]
+    fn test_checksum_manifest() {
+        use sha2::{Digest, Sha256};
+
+        let (file_name, dest_path) = copy_test_shared_lib("test_checksum_manifest");
+        let manifest = PathBuf::from("target/debug/test_checksum_manifest.sha256");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        dr.set_checksum_manifest(Some(&manifest));
+
+        fs::write(&manifest, format!("{}  {}\n", "00".repeat(32), file_name)).unwrap();
+        match unsafe { dr.add_library(&file_name, PlatformName::No) } {
+            Err(Error::ChecksumMismatch(_, _)) => (),
+            _ => panic!("expected checksum mismatch"),
+        }
+
+        let digest: String = Sha256::digest(fs::read(&dest_path).unwrap())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        fs::write(&manifest, format!("# vetted\n{} *{}\n", digest, file_name)).unwrap();
+        assert!(unsafe { dr.add_library(&file_name, PlatformName::No) }.is_ok());
+    }
+
+    #[test]
+    fn test_reload_retry_delay() {
+        let retry = ReloadRetry {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+            ..ReloadRetry::default()
+        };
+        assert_eq!(retry.delay(1), Duration::from_millis(100));
+        assert_eq!(retry.delay(2), Duration::from_millis(200));
+        assert_eq!(retry.delay(3), Duration::from_millis(300));
+        assert_eq!(retry.delay(100), Duration::from_millis(300));
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-timestamps"))]
+    fn test_reload_retry() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_reload_retry");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        dr.set_reload_retry(Some(ReloadRetry {
+            attempts: 2,
+            initial_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }));
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        dr.inject_failure(&lib, FailPoint::Load);
+        drop(lib);
+
+        // The library is unloaded by the failed reload and loaded again by the retry
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.fail_update_done);
+        assert!(dr.libs.is_empty());
+
+        unsafe { dr.retry_failed(&TestNotifyCallback::update_call, &mut notify_callback) };
+        assert!(notify_callback.after_update_done);
+        assert_eq!(dr.libs.len(), 1);
+        assert!(dr.failed.is_empty());
+
+        // Keeps failing until it's blacklisted
+        dr.set_abi_tag(Some("other"));
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+            dr.retry_failed(&TestNotifyCallback::update_call, &mut notify_callback);
+        }
+        assert!(notify_callback.blacklisted_done);
+        assert!(dr.is_blacklisted(&dest_path));
+        assert!(dr.retries.due().is_empty());
+
+        dr.set_abi_tag(None);
+        dr.clear_blacklist(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe { dr.retry_failed(&TestNotifyCallback::update_call, &mut notify_callback) };
+        assert!(notify_callback.after_update_done);
+        assert!(!dr.is_blacklisted(&dest_path));
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "no-timestamps", feature = "no-unload")))]
+    fn test_deferred_unload() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_deferred_unload");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        dr.set_deferred_unload(DeferredUnload::Epoch);
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let old = Arc::downgrade(&lib);
+        drop(lib);
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+        assert_eq!(dr.parked_unloads(), 1);
+        assert!(old.upgrade().is_some());
+
+        assert_eq!(dr.advance_epoch(), 1);
+        assert_eq!(dr.parked_unloads(), 0);
+        assert!(old.upgrade().is_none());
+
+        dr.set_deferred_unload(DeferredUnload::Period(Duration::from_secs(60)));
+        let old = Arc::downgrade(&dr.libs[0]);
+        dr.remove_library(&dr.libs[0].clone());
+        assert!(old.upgrade().is_some());
+
+        dr.flush_unloads();
+        assert!(old.upgrade().is_none());
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "no-timestamps", feature = "no-unload")))]
+    fn test_external_refs() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_external_refs");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        dr.set_external_refs(ExternalRefs::Retire);
+
+        let held = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        dr.unloads.collect();
+        assert_eq!(dr.retired_libs(), vec![dest_path.clone()]);
+        drop(held);
+        dr.unloads.collect();
+        assert!(dr.retired_libs().is_empty());
+
+        dr.set_external_refs(ExternalRefs::Warn);
+        let held = dr.libs[0].clone();
+        modify_test_shared_lib(&dest_path);
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        let mut counts = Vec::new();
+        dr.report_referenced(
+            &|counts: &mut Vec<usize>, state, _: Option<&Arc<Lib>>| {
+                if let UpdateState::StillReferenced(count) = state {
+                    counts.push(count);
+                }
+            },
+            &mut counts,
+        );
+        assert_eq!(counts, vec![1]);
+        drop(held);
+    }
+
+    #[test]
+    fn test_use_guard() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_use_guard");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let guard = lib.use_guard();
+        assert!(lib.in_use());
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(!notify_callback.update_call_done);
+        assert!(dr.unguarded().is_empty());
+
+        drop(guard);
+        assert!(!lib.in_use());
+        drop(lib);
+
+        for path in dr.unguarded() {
+            unsafe {
+                dr.apply_change(path, &TestNotifyCallback::update_call, &mut notify_callback);
+            }
+        }
+        assert!(notify_callback.after_update_done);
+        assert!(dr.guarded.is_empty());
+    }
+
+    #[test]
+    fn test_atomic_symbol() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_atomic_symbol");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let shared_fun = Arc::new(unsafe { AtomicSymbol::<fn() -> i32>::new("shared_fun") });
+        let missing_fun = Arc::new(unsafe { AtomicSymbol::<fn() -> i32>::new("missing_fun") });
+        dr.bind_symbol(&lib, shared_fun.clone());
+        dr.bind_symbol(&lib, missing_fun.clone());
+        assert_eq!(shared_fun.name(), "shared_fun");
+        assert_eq!(shared_fun.get().unwrap()(), 42);
+        assert!(missing_fun.get().is_none());
+        drop(lib);
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+        assert_eq!(shared_fun.get().unwrap()(), 43);
+
+        let lib = dr.libs[0].clone();
+        assert!(dr.remove_library(&lib));
+        assert!(shared_fun.get().is_none());
+    }
+
+    #[test]
+    #[cfg(all(feature = "trampoline", target_arch = "x86_64"))]
+    fn test_trampoline() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_trampoline");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let shared_fun: fn() -> i32 = unsafe { dr.trampoline(&lib, "shared_fun").unwrap() };
+        assert_eq!(shared_fun(), 42);
+
+        match unsafe { dr.trampoline::<fn() -> i32>(&lib, "missing_fun") } {
+            Err(Error::MissingSymbol(_, symbol)) => assert_eq!(symbol, "missing_fun"),
+            _ => panic!("expected a missing symbol"),
+        }
+        drop(lib);
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+        assert_eq!(shared_fun(), 43);
+
+        let lib = dr.libs[0].clone();
+        let again: fn() -> i32 = unsafe { dr.trampoline(&lib, "shared_fun").unwrap() };
+        assert_eq!(again as usize, shared_fun as usize);
+        assert_eq!(dr.trampolines.len(), 1);
+
+        // The slot isn't handed out again once the instance is dropped
+        drop(dr);
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let other: fn() -> i32 = unsafe { dr.trampoline(&lib, "shared_fun").unwrap() };
+        assert_ne!(other as usize, shared_fun as usize);
+        assert_eq!(other(), 43);
+    }
+
+    crate::lib_api! {
+        struct TestLibApi {
+            shared_fun: fn() -> i32,
+        }
+    }
+
+    crate::lib_api! {
+        // Only used to check that loading fails
+        #[allow(dead_code)]
+        struct MissingLibApi {
+            shared_fun: fn() -> i32,
+            missing_fun: fn(),
+        }
+    }
+
+    #[test]
+    fn test_add_typed() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_add_typed");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        match unsafe { dr.add_typed::<MissingLibApi>(&file_name, PlatformName::No) } {
+            Err(Error::MissingSymbol(_, symbol)) => assert_eq!(symbol, "missing_fun"),
+            _ => panic!("expected a missing symbol"),
+        }
+
+        let api = unsafe {
+            dr.add_typed::<TestLibApi>(&file_name, PlatformName::No)
+                .unwrap()
+        };
+        assert_eq!((api.get().unwrap().shared_fun)(), 42);
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+        assert_eq!((api.get().unwrap().shared_fun)(), 43);
+
+        let lib = dr.libs[0].clone();
+        assert!(dr.remove_library(&lib));
+        assert!(api.get().is_none());
+    }
+
+    // Function table of the plugin in test_shared
+    #[cfg(not(feature = "no-unload"))]
+    #[repr(C)]
+    pub struct TestPlugin {
+        value: unsafe extern "C" fn(*mut c_void) -> i32,
+    }
+
+    #[cfg(not(feature = "no-unload"))]
+    fn plugin_value(plugin: &PluginInstance<TestPlugin>) -> i32 {
+        unsafe { (plugin.vtable().value)(plugin.instance()) }
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-unload"))]
+    fn test_typed_plugin() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_typed_plugin");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let mut plugin = unsafe {
+            TypedPlugin::<TestPlugin>::new(&mut dr, &file_name, PlatformName::No).unwrap()
+        };
+        assert_eq!(plugin_value(plugin.get().unwrap()), 42);
+
+        modify_test_shared_lib(&dest_path);
+        unsafe {
+            dr.reload_libs(&dest_path, &TypedPlugin::update_call, &mut plugin);
+        }
+        assert!(plugin.take_error().is_none());
+        assert_eq!(plugin_value(plugin.get().unwrap()), 43);
+        assert!(Arc::ptr_eq(plugin.lib().unwrap(), &dr.libs[0]));
+
+        // The plugin keeps its library loaded
+        let lib = dr.libs[0].clone();
+        assert!(dr.remove_library(&lib));
+        drop(lib);
+        assert_eq!(plugin_value(plugin.get_mut().unwrap()), 43);
+    }
+
+    #[cfg(not(feature = "no-unload"))]
+    static HOST_API_VALUE: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+    #[cfg(not(feature = "no-unload"))]
+    extern "C" fn host_api_report(value: i32) {
+        HOST_API_VALUE.store(value, Ordering::SeqCst);
+    }
+
+    #[cfg(not(feature = "no-unload"))]
+    #[repr(C)]
+    struct TestHostApi {
+        report: extern "C" fn(i32),
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-unload"))]
+    fn test_host_api() {
+        static API: TestHostApi = TestHostApi {
+            report: host_api_report,
+        };
+
+        let (file_name, dest_path) = copy_test_shared_lib("test_host_api");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        dr.set_host_api(Some(&API as *const TestHostApi as *const c_void));
+
+        unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        assert_eq!(HOST_API_VALUE.load(Ordering::SeqCst), 42);
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+        assert_eq!(HOST_API_VALUE.load(Ordering::SeqCst), 43);
+    }
+
+    #[test]
+    fn test_userdata() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_userdata");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        assert!(lib.set_userdata(String::from("physics")).is_none());
+        assert!(lib.set_userdata(1u32).is_none());
+        assert_eq!(*lib.set_userdata(2u32).unwrap(), 1);
+        drop(lib);
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+
+        let lib = dr.libs[0].clone();
+        assert_eq!(*lib.userdata::<String>().unwrap(), "physics");
+        assert_eq!(*lib.remove_userdata::<u32>().unwrap(), 2);
+        assert!(lib.userdata::<u32>().is_none());
+    }
+
+    #[derive(Default)]
+    struct StateHandoff {
+        restored: Option<u32>,
+    }
+
+    impl StateHandoff {
+        fn update_call(&mut self, state: UpdateState, lib: Option<&Arc<Lib>>) {
+            let lib = lib.unwrap();
+            match state {
+                UpdateState::Before => lib.set_reload_state(Box::new(7u32)),
+                UpdateState::After => {
+                    let saved = lib.take_reload_state().unwrap();
+                    self.restored = saved.downcast::<u32>().ok().map(|s| *s);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reload_state() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_reload_state");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        modify_test_shared_lib(&dest_path);
+
+        let mut handoff = StateHandoff::default();
+        unsafe {
+            dr.reload_libs(&dest_path, &StateHandoff::update_call, &mut handoff);
+        }
+        assert_eq!(handoff.restored, Some(7));
+        assert!(dr.libs[0].take_reload_state().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    impl PersistentState for Vec<u32> {
+        const VERSION: u32 = 2;
+
+        // Version 1 was a single value
+        fn migrate(
+            from: u32,
+            state: serde_json::Value,
+        ) -> std::result::Result<serde_json::Value, String> {
+            match from {
+                1 => Ok(serde_json::Value::Array(vec![state])),
+                _ => Err(format!("unknown version {}", from)),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_persistent_state() {
+        let saved = save_state(&vec![1u32, 2]).unwrap();
+        assert_eq!(load_state::<Vec<u32>>(&saved).unwrap(), vec![1, 2]);
+        assert_eq!(
+            load_state::<Vec<u32>>(r#"{"version":1,"state":5}"#).unwrap(),
+            vec![5]
+        );
+        assert!(matches!(
+            load_state::<Vec<u32>>(r#"{"version":3,"state":[]}"#),
+            Err(Error::State(_))
+        ));
+
+        let (file_name, dest_path) = copy_test_shared_lib("test_persistent_state");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        modify_test_shared_lib(&dest_path);
+
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+
+        // Saved by the previous version and handed to the new one
+        let loaded = unsafe {
+            let loaded = dr.libs[0]
+                .lib
+                .get::<extern "C" fn() -> *const std::os::raw::c_char>(b"test_loaded_state\0")
+                .unwrap();
+            std::ffi::CStr::from_ptr(loaded())
+                .to_str()
+                .unwrap()
+                .to_owned()
+        };
+        assert_eq!(loaded, r#"{"version":1,"state":42}"#);
+    }
+
+    // Same layout as in test_shared
+    #[cfg(not(any(feature = "no-timestamps", feature = "no-unload")))]
+    #[repr(C)]
+    struct LifecycleCalls {
+        before_reload: u32,
+        after_load: u32,
+    }
+
+    #[cfg(not(any(feature = "no-timestamps", feature = "no-unload")))]
+    fn lifecycle_calls(lib: &Lib) -> [u32; 2] {
+        let calls = unsafe {
+            lib.lib
+                .get::<extern "C" fn() -> LifecycleCalls>(b"test_lifecycle_calls\0")
+                .unwrap()
+        };
+        let calls = calls();
+        [calls.before_reload, calls.after_load]
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "no-timestamps", feature = "no-unload")))]
+    fn test_lifecycle_symbols() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_lifecycle_symbols");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let old = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        assert_eq!(lifecycle_calls(&old), [0, 1]);
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+        assert_eq!(lifecycle_calls(&old), [1, 1]);
+        assert_eq!(lifecycle_calls(&dr.libs[0]), [0, 1]);
+        // The next reload may reuse the path of the first copy
+        drop(old);
+
+        // Turned off
+        dr.set_lifecycle_symbols(LifecycleSymbols {
+            before_reload: None,
+            after_load: None,
+        });
+        let current = dr.libs[0].clone();
+        modify_test_shared_lib(&dest_path);
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert_eq!(lifecycle_calls(&current), [0, 1]);
+        assert_eq!(lifecycle_calls(&dr.libs[0]), [0, 0]);
+    }
+
+    #[derive(Default)]
+    struct CountingObserver {
+        before: usize,
+        after: usize,
+        failed: usize,
+    }
+
+    impl ReloadObserver for CountingObserver {
+        fn before(&mut self, _lib: &Arc<Lib>) {
+            self.before += 1;
+        }
+
+        fn after(&mut self, _lib: &Arc<Lib>) {
+            self.after += 1;
+        }
+
+        fn failed(&mut self, _error: &Error, _lib: Option<&Arc<Lib>>) {
+            self.failed += 1;
+        }
+    }
+
+    #[test]
+    fn test_observers() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_observers");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+
+        let first = Arc::new(Mutex::new(CountingObserver::default()));
+        let second = Arc::new(Mutex::new(CountingObserver::default()));
+        dr.add_observer(first.clone());
+        dr.add_observer(second.clone());
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        assert!(notify_callback.after_update_done);
+        for observer in [&first, &second] {
+            let observer = observer.lock().unwrap();
+            assert_eq!(
+                (observer.before, observer.after, observer.failed),
+                (1, 1, 0)
+            );
+        }
+
+        assert!(dr.remove_observer(&first));
+        assert!(!dr.remove_observer(&first));
+
+        modify_test_shared_lib(&dest_path);
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        assert_eq!(first.lock().unwrap().after, 1);
+        assert_eq!(second.lock().unwrap().after, 2);
+    }
+
+    #[test]
+    fn test_update_with() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_update_with");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let handle = dr.handle();
+        modify_test_shared_lib(&dest_path);
+        assert!(handle.reload_library(&lib));
+        drop(lib);
+
+        let mut before = 0;
+        let mut reloaded = Vec::new();
+        unsafe {
+            dr.update_with(|state, lib| match state {
+                UpdateState::Before => before += 1,
+                UpdateState::After => reloaded.push(lib.unwrap().source_path().to_path_buf()),
+                _ => (),
+            });
+        }
+
+        assert_eq!(before, 1);
+        assert_eq!(reloaded, vec![dest_path]);
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_subscribe");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let first = dr.subscribe();
+        let second = dr.subscribe();
+        drop(dr.subscribe());
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        let expected = vec![
+            ReloadNotification {
+                kind: NotificationKind::Before,
+                path: Some(dest_path.clone()),
+                generation: Some(0),
+            },
+            ReloadNotification {
+                kind: NotificationKind::After,
+                path: Some(dest_path.clone()),
+                generation: Some(1),
+            },
+        ];
+
+        assert_eq!(first.try_iter().collect::<Vec<_>>(), expected);
+        assert_eq!(second.try_iter().collect::<Vec<_>>(), expected);
+
+        // A subscriber that doesn't keep up is dropped
+        let stalled = dr.subscribe();
+        for _ in 0..=SUBSCRIPTION_CAPACITY {
+            dr.subscribers.send(&UpdateState::Before, None);
+            assert!(first.try_recv().is_ok());
+        }
+        assert_eq!(stalled.try_iter().count(), SUBSCRIPTION_CAPACITY);
+        assert!(matches!(
+            stalled.try_recv(),
+            Err(std::sync::mpsc::TryRecvError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn test_lib_lookup() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_lib_lookup");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let name = file_name
+            .strip_prefix(env::consts::DLL_PREFIX)
+            .and_then(|name| name.strip_suffix(env::consts::DLL_SUFFIX))
+            .unwrap();
+
+        assert_eq!(dr.libs().count(), 1);
+        assert!(Arc::ptr_eq(dr.libs().next().unwrap(), &lib));
+        assert!(Arc::ptr_eq(
+            dr.get_by_name(&file_name, PlatformName::No).unwrap(),
+            &lib
+        ));
+        assert!(Arc::ptr_eq(
+            dr.get_by_name(name, PlatformName::Yes).unwrap(),
+            &lib
+        ));
+        assert!(Arc::ptr_eq(dr.get_by_path(&dest_path).unwrap(), &lib));
+        assert!(dr
+            .get_by_name("test_lib_lookup_missing", PlatformName::Yes)
+            .is_none());
+        assert!(dr.get_by_path(Path::new("target/debug/missing")).is_none());
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let names = ["test_registry_a", "test_registry_b", "test_registry_c"];
+        let mut paths = Vec::new();
+        for name in names {
+            let (file_name, dest_path) = copy_test_shared_lib(name);
+            unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+            paths.push(dest_path);
+        }
+
+        // Adding a library again replaces the tracked version
+        let (file_name, _) = copy_test_shared_lib(names[0]);
+        let again = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        assert_eq!(dr.libs.len(), 3);
+        assert!(Arc::ptr_eq(dr.get_by_path(&paths[0]).unwrap(), &again));
+
+        let removed = dr.get_by_path(&paths[0]).unwrap().clone();
+        assert!(dr.remove_library(&removed));
+        assert!(dr.get_by_path(&paths[0]).is_none());
+        assert!(dr.matching(&paths[0]).next().is_none());
+
+        for path in &paths[1..] {
+            let lib = dr.get_by_path(path).unwrap();
+            assert_eq!(lib.source_path(), path);
+            assert_eq!(
+                dr.matching(path)
+                    .map(|l| l.source_path())
+                    .collect::<Vec<_>>(),
+                vec![path.as_path()]
+            );
+        }
+    }
+
+    #[test]
+    fn test_generation() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_generation");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        assert_eq!(lib.generation(), 0);
+        drop(lib);
+
+        let mut notify_callback = TestNotifyCallback::default();
+        for generation in 1..3 {
+            modify_test_shared_lib(&dest_path);
+            unsafe {
+                dr.reload_libs(
+                    &dest_path,
+                    &TestNotifyCallback::update_call,
+                    &mut notify_callback,
+                );
+            }
+
+            assert_eq!(dr.libs[0].generation(), generation);
+            assert_eq!(dr.status()[0].generation, generation);
+        }
+
+        // A version that fails to load doesn't count
+        fs::write(&dest_path, b"not a library").unwrap();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.fail_update_done);
+
+        fs::copy(get_test_shared_lib(), &dest_path).unwrap();
+        unsafe {
+            dr.retry(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            )
+        };
+        assert_eq!(dr.get_by_path(&dest_path).unwrap().generation(), 3);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-timestamps"))]
+    fn test_lib_info() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_lib_info");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let before = SystemTime::now();
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let info = lib.info();
+        let md = fs::metadata(&dest_path).unwrap();
+
+        assert_eq!(info.file_size, Some(md.len()));
+        assert_eq!(info.modified, md.modified().ok());
+        assert_eq!(
+            info.content_hash,
+            Some(hash::hash_file(&dest_path).unwrap())
+        );
+        assert!(info.loaded_at >= before && info.loaded_at <= SystemTime::now());
+        assert_eq!(info.reloads, 0);
+        drop(lib);
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        let reloaded = dr.libs[0].info();
+        assert_eq!(reloaded.reloads, 1);
+        assert!(reloaded.loaded_at >= info.loaded_at);
+        assert_ne!(reloaded.content_hash, info.content_hash);
+    }
+
+    #[test]
+    fn test_watcher_status() {
+        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_millis(100));
+        assert!(matches!(dr.watcher_status(), WatcherStatus::Active(_)));
+
+        dr.stop_watching();
+        assert!(matches!(dr.watcher_status(), WatcherStatus::Stopped));
+
+        dr.watcher_error = Some(Error::Watcher(
+            notify_debouncer_mini::notify::Error::generic("no watcher"),
+        ));
+        match dr.watcher_status() {
+            WatcherStatus::Failed(Error::Watcher(_)) => (),
+            status => panic!("unexpected status {:?}", status),
+        }
+
+        // Starting the watcher again clears the error
+        dr.start_watching().unwrap();
+        assert!(matches!(dr.watcher_status(), WatcherStatus::Active(_)));
+    }
+
+    #[test]
+    fn test_try_new() {
+        let dr = DynamicReload::try_new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(100),
+        )
+        .unwrap();
+        assert!(dr.is_watching());
+        assert!(dr.shadow_dir.is_some());
+
+        let err = DynamicReload::try_new(
+            None,
+            Some("target/debug/no_such_shadow_dir"),
+            Search::Default,
+            Duration::from_millis(100),
+        );
+        assert!(matches!(err, Err(Error::ShadowDir(_, _))));
+
+        // new carries on without the shadow directory
+        let dr = DynamicReload::new(
+            None,
+            Some("target/debug/no_such_shadow_dir"),
+            Search::Default,
+            Duration::from_millis(100),
+        );
+        assert!(dr.shadow_dir.is_none());
+    }
+
+    #[test]
+    fn test_find_searched_paths() {
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            None,
+            Search::Backwards,
+            Duration::from_secs(2),
+        );
+
+        let searched = match unsafe { dr.add_library("_no_such_lib", PlatformName::No) } {
+            Err(Error::Find(name, searched)) => {
+                assert_eq!(name, "_no_such_lib");
+                searched
+            }
+            _ => panic!("expected Error::Find"),
+        };
+
+        let exe_dir = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+        assert_eq!(
+            &searched[..3],
+            [
+                env::current_dir().unwrap().join("_no_such_lib"),
+                Path::new("target/debug")
+                    .canonicalize()
+                    .unwrap()
+                    .join("_no_such_lib"),
+                exe_dir.join("_no_such_lib"),
+            ]
+        );
+        // Walks back to the root
+        let root = searched.last().unwrap().parent().unwrap();
+        assert!(root.parent().is_none());
+    }
+
+    #[test]
+    fn test_resolve_library() {
+        let dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            None,
+            Search::Default,
+            Duration::from_secs(2),
+        );
+
+        let report = dr.resolve_library("test_shared", PlatformName::Yes);
+        assert_eq!(report.path, Some(get_test_shared_lib()));
+        assert_eq!(report.candidates.len(), 2);
+        assert_eq!(report.candidates[0].rejected, Some(Rejected::Missing));
+        assert_eq!(report.candidates[1].path, get_test_shared_lib());
+        assert_eq!(report.candidates[1].rejected, None);
+        assert_eq!(dr.libs().count(), 0);
+
+        // target/debug/deps is a directory
+        let report = dr.resolve_library("deps", PlatformName::No);
+        assert_eq!(report.path, None);
+        assert_eq!(report.lib_name, "deps");
+        assert_eq!(report.candidates[1].rejected, Some(Rejected::NotAFile));
+    }
+
+    #[test]
+    fn test_search_path_env() {
+        // Read through a lookup instead of changing the environment of the process
+        let var = "DYNAMIC_RELOAD_TEST_SEARCH_PATH_ENV";
+        let lookup = |name: &str| {
+            assert_eq!(name, var);
+            env::join_paths(["target/debug", "", "src"]).ok()
+        };
+        assert_eq!(
+            search::env_paths(var, lookup),
+            [PathBuf::from("target/debug"), PathBuf::from("src")]
+        );
+        assert!(search::env_paths(var, |_| None).is_empty());
+
+        // Not set
+        let dr = DynamicReload::builder()
+            .search_path("examples")
+            .search_path_env(Some(var))
+            .build()
+            .unwrap();
+        assert_eq!(
+            dr.search_paths,
+            [Path::new("examples").canonicalize().unwrap()]
+        );
+
+        let dr = DynamicReload::builder()
+            .search_path_env(None)
+            .build()
+            .unwrap();
+        assert!(dr.search_paths.is_empty());
+    }
+
+    #[test]
+    fn test_cargo_profile() {
+        assert_eq!(Profile::Debug.dir_name(), "debug");
+        assert_eq!(Profile::Custom("dev".to_owned()).dir_name(), "debug");
+        assert_eq!(Profile::Custom("bench".to_owned()).dir_name(), "release");
+        assert_eq!(
+            Profile::Custom("profiling".to_owned()).dir_name(),
+            "profiling"
+        );
+
+        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+        assert!(dr.find_library("test_shared", PlatformName::Yes).is_err());
+
+        let current_dir = env::current_dir().unwrap();
+        assert_eq!(
+            cargo::target_dir(&current_dir, |_| None),
+            Some(current_dir.join("target"))
+        );
+        assert_eq!(
+            cargo::target_dir(&current_dir, |var| match var {
+                "CARGO_BUILD_TARGET_DIR" => Some("other".into()),
+                _ => None,
+            }),
+            Some(current_dir.join("other"))
+        );
+
+        // The library is built by build.rs into target/debug of the current directory, which
+        // is only where the search looks when the target directory isn't moved elsewhere
+        dr.set_cargo_profile(Some(Profile::Debug));
+        if cargo_target_dir() == Some(current_dir.join("target")) {
+            let path = dr.find_library("test_shared", PlatformName::Yes).unwrap();
+            assert_eq!(
+                path.canonicalize().unwrap(),
+                get_test_shared_lib().canonicalize().unwrap()
+            );
+        }
+    }
+
+    #[cfg(feature = "cargo")]
+    #[test]
+    fn test_cargo_artifact() {
+        let metadata = serde_json::json!({
+            "target_directory": "/work/target",
+            "packages": [
+                {
+                    "name": "host",
+                    "targets": [{ "name": "host", "crate_types": ["bin"] }],
+                },
+                {
+                    "name": "my-plugin",
+                    "targets": [{ "name": "my_plugin", "crate_types": ["rlib", "cdylib"] }],
+                },
+            ],
+        });
+
+        assert_eq!(
+            cargo::find_artifact(&metadata, "my-plugin", &Profile::Release).unwrap(),
+            Path::new("/work/target/release").join(DynamicReload::get_dynamiclib_name("my_plugin"))
+        );
+        assert!(matches!(
+            cargo::find_artifact(&metadata, "host", &Profile::Debug),
+            Err(Error::Cargo(_))
+        ));
+        assert!(matches!(
+            cargo::find_artifact(&metadata, "missing", &Profile::Debug),
+            Err(Error::Cargo(_))
+        ));
+
+        // This crate is only built as an rlib
+        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+        assert!(matches!(
+            unsafe { dr.add_cargo_artifact("dynamic_reload") },
+            Err(Error::Cargo(_))
+        ));
+    }
+
+    #[test]
+    fn test_watch_sources() {
+        let crate_dir = Path::new("target/debug/test_watch_sources");
+        let _ = fs::remove_dir_all(crate_dir);
+        fs::create_dir_all(crate_dir.join("src")).unwrap();
+        fs::write(
+            crate_dir.join("Cargo.toml"),
+            "[package]\nname = \"test_watch_sources\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+             [lib]\ncrate-type = [\"cdylib\"]\n\n[workspace]\n",
+        )
+        .unwrap();
+        let source = crate_dir.join("src/lib.rs");
+        fs::write(
+            &source,
+            "#[no_mangle]\npub extern \"C\" fn value() -> u32 { 1 }\n",
+        )
+        .unwrap();
+
+        let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let status = std::process::Command::new(cargo)
+            .arg("build")
+            .arg("--manifest-path")
+            .arg(crate_dir.join("Cargo.toml"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let artifact = env::var_os("CARGO_TARGET_DIR")
+            .map_or_else(|| crate_dir.join("target"), PathBuf::from)
+            .join("debug")
+            .join(DynamicReload::get_dynamiclib_name("test_watch_sources"));
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(100),
+        );
+        dr.set_cargo_profile(Some(Profile::Debug));
+        let lib = unsafe { dr.add_library_path(&artifact).unwrap() };
+        let lib_path = lib.source_path().to_path_buf();
+
+        assert!(matches!(
+            dr.watch_sources(&lib, Path::new("target/debug/_no_such_crate")),
+            Err(Error::Find(_, _))
+        ));
+        dr.watch_sources(&lib, crate_dir).unwrap();
+        drop(lib);
+
+        // Waits until the build started by a change is done and no other build is running,
+        // returning its result and the value of the library if the artifact was reloaded
+        let build = |dr: &mut DynamicReload, contents: &str, reload: bool| {
+            fs::write(&source, contents).unwrap();
+            let mut started = false;
+            let mut running = false;
+            let mut result = None;
+            let mut value = None;
+            for _ in 0..1200 {
+                unsafe {
+                    dr.update_with(|state, build_lib| {
+                        assert_eq!(build_lib.unwrap().source_path(), lib_path);
+                        match state {
+                            UpdateState::BuildStarted => {
+                                started = true;
+                                running = true;
+                            }
+                            UpdateState::BuildFinished(_) => {
+                                assert!(started);
+                                running = false;
+                                result = Some(Ok(()));
+                            }
+                            UpdateState::BuildFailed(e) => {
+                                assert!(started);
+                                running = false;
+                                result = Some(Err(e));
+                            }
+                            UpdateState::After => {
+                                let lib = &build_lib.unwrap().lib;
+                                let fun = lib.get::<extern "C" fn() -> u32>(b"value\0").unwrap();
+                                value = Some(fun());
+                            }
+                            _ => (),
+                        }
+                    })
+                };
+                if !running && (!reload || value.is_some()) {
+                    if let Some(result) = result.take() {
+                        return (result, value);
+                    }
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            panic!("build didn't finish");
+        };
+
+        match build(&mut dr, "pub extern \"C\" fn value() -> u32 { 2 ", false) {
+            (Err(Error::Cargo(output)), None) => assert!(output.contains("error")),
+            _ => panic!("expected the build to fail"),
+        }
+        let contents = "#[no_mangle]\npub extern \"C\" fn value() -> u32 { 2 }\n";
+        assert!(matches!(build(&mut dr, contents, true), (Ok(()), Some(2))));
+
+        let lib = dr.get_by_path(&lib_path).unwrap().clone();
+        assert!(dr.unwatch_sources(&lib));
+        assert!(!dr.unwatch_sources(&lib));
+    }
+
+    #[cfg(feature = "cargo")]
+    #[test]
+    fn test_compile_and_add() {
+        let crate_dir = Path::new("target/debug/test_compile_and_add");
+        let _ = fs::remove_dir_all(crate_dir);
+        fs::create_dir_all(crate_dir.join("src")).unwrap();
+        fs::write(
+            crate_dir.join("Cargo.toml"),
+            "[package]\nname = \"test-compile-and-add\"\nversion = \"0.1.0\"\n\
+             edition = \"2021\"\n\n[lib]\ncrate-type = [\"cdylib\"]\n\n[workspace]\n",
+        )
+        .unwrap();
+        let source = crate_dir.join("src/lib.rs");
+        fs::write(&source, "pub extern \"C\" fn value() -> u32 { 1 ").unwrap();
+
+        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+        match unsafe { dr.compile_and_add(crate_dir, Profile::Debug) } {
+            Err(Error::Cargo(output)) => assert!(output.contains("error")),
+            _ => panic!("expected the build to fail"),
+        }
+
+        fs::write(
+            &source,
+            "#[no_mangle]\npub extern \"C\" fn value() -> u32 { 7 }\n",
+        )
+        .unwrap();
+        let lib = unsafe { dr.compile_and_add(crate_dir, Profile::Debug).unwrap() };
+        assert_eq!(
+            lib.source_path().file_name().unwrap(),
+            DynamicReload::get_dynamiclib_name("test_compile_and_add").as_str()
+        );
+        let value: Symbol<extern "C" fn() -> u32> = unsafe { lib.lib.get(b"value\0").unwrap() };
+        assert_eq!(value(), 7);
+        assert_eq!(dr.libs().count(), 1);
+    }
+
+    #[test]
+    fn test_platform_name_formats() {
+        let windows = PlatformName::Target("x86_64-pc-windows-gnu");
+        assert_eq!(
+            DynamicReload::get_library_name("my-plugin", windows),
+            "my_plugin.dll"
+        );
+        assert_eq!(
+            DynamicReload::get_library_name("my_plugin.dll", windows),
+            "my_plugin.dll"
+        );
+        assert_eq!(
+            DynamicReload::get_library_name("foo", PlatformName::Target("aarch64-apple-darwin")),
+            "libfoo.dylib"
+        );
+        assert_eq!(
+            DynamicReload::get_library_name(
+                "foo",
+                PlatformName::Target("x86_64-unknown-linux-gnu")
+            ),
+            "libfoo.so"
+        );
+
+        let mingw = PlatformName::Custom(|name| format!("lib{}.dll", name));
+        assert_eq!(DynamicReload::get_library_name("foo", mingw), "libfoo.dll");
+        assert_eq!(mingw, mingw);
+        assert_ne!(mingw, PlatformName::Yes);
+
+        // Plugins named by another toolchain
+        let (_, dest_path) = copy_test_shared_lib("test_platform_name_formats");
+        let plugin = dest_path.with_file_name("test_platform_name_formats.plugin");
+        fs::copy(&dest_path, &plugin).unwrap();
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            None,
+            Search::Default,
+            Duration::from_secs(2),
+        );
+        let format = PlatformName::Custom(|name| format!("{}.plugin", name));
+        let lib = unsafe {
+            dr.add_library("test_platform_name_formats", format)
+                .unwrap()
+        };
+        assert_eq!(lib.source_path(), plugin.canonicalize().unwrap());
+        assert!(dr
+            .get_by_name("test_platform_name_formats", format)
+            .is_some());
+    }
+
+    #[test]
+    fn test_versioned_names() {
+        assert_eq!(naming::strip_version("libfoo.so.1.2.3"), "libfoo.so");
+        assert_eq!(naming::strip_version("libfoo.so.1"), "libfoo.so");
+        assert_eq!(naming::strip_version("libfoo.so"), "libfoo.so");
+        assert_eq!(naming::strip_version("libfoo.so.bak"), "libfoo.so.bak");
+        assert_eq!(naming::strip_version("libfoo.so.1."), "libfoo.so.1.");
+
+        let linux = PlatformName::Target("x86_64-unknown-linux-gnu");
+        assert_eq!(
+            DynamicReload::get_library_name("libfoo.so.1", linux),
+            "libfoo.so.1"
+        );
+    }
+
+    // A CMake build installs libfoo.so.1.2.4 and points libfoo.so.1 at it
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_reload_versioned_soname() {
+        use std::os::unix::fs::symlink;
+
+        let mut notify_callback = TestNotifyCallback::default();
+        let dir = get_test_shared_lib().with_file_name("test_soname");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::copy(get_test_shared_lib(), dir.join("libtest_soname.so.1.2.3")).unwrap();
+        symlink("libtest_soname.so.1.2.3", dir.join("libtest_soname.so.1")).unwrap();
+        symlink("libtest_soname.so.1", dir.join("libtest_soname.so")).unwrap();
+
+        let mut dr = DynamicReload::new(
+            Some(vec![dir.to_str().unwrap()]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(100),
+        );
+        let lib = unsafe { dr.add_library("test_soname", PlatformName::Yes).unwrap() };
+        assert!(lib.source_path().ends_with("libtest_soname.so"));
+
+        fs::copy(
+            get_test_shared_lib_v2(),
+            dir.join("libtest_soname.so.1.2.4"),
+        )
+        .unwrap();
+        fs::remove_file(dir.join("libtest_soname.so.1")).unwrap();
+        symlink("libtest_soname.so.1.2.4", dir.join("libtest_soname.so.1")).unwrap();
+
+        assert_eq!(
+            dr.versioned_alias(&dir.join("libtest_soname.so.1")),
+            Some(lib.source_path().to_path_buf())
+        );
+        assert_eq!(
+            dr.versioned_alias(&dir.join("libtest_soname.so.1.2.4")),
+            Some(lib.source_path().to_path_buf())
+        );
+        assert_eq!(dr.versioned_alias(lib.source_path()), None);
+        assert_eq!(dr.versioned_alias(&dir.join("libother.so.1")), None);
+        assert!(!lib.has_symbol("test_v2_only"));
+        drop(lib);
+
+        for _ in 0..20 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if notify_callback.after_update_done {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        assert!(notify_callback.after_update_done);
+        assert_eq!(dr.libs.len(), 1);
+        assert!(dr.libs[0].source_path().ends_with("libtest_soname.so"));
+        assert!(dr.libs[0].has_symbol("test_v2_only"));
+    }
+
+    #[test]
+    fn test_selection_policy() {
+        let root = get_test_shared_lib().with_file_name("test_selection_policy");
+        let (stale, fresh) = (root.join("stale"), root.join("fresh"));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&stale).unwrap();
+        fs::create_dir_all(&fresh).unwrap();
+
+        let file_name = DynamicReload::get_dynamiclib_name("test_selection");
+        fs::copy(get_test_shared_lib(), stale.join(&file_name)).unwrap();
+        fs::copy(get_test_shared_lib(), fresh.join(&file_name)).unwrap();
+        let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(stale.join(&file_name))
+            .unwrap()
+            .set_modified(hour_ago)
+            .unwrap();
+
+        let mut dr = DynamicReload::new(
+            Some(vec![stale.to_str().unwrap(), fresh.to_str().unwrap()]),
+            None,
+            Search::Default,
+            Duration::from_secs(2),
+        );
+        let found = |dr: &DynamicReload| {
+            let path = dr
+                .find_library("test_selection", PlatformName::Yes)
+                .unwrap();
+            path.parent().unwrap().file_name().unwrap().to_owned()
+        };
+
+        assert_eq!(found(&dr), "stale");
+
+        dr.set_selection_policy(SelectionPolicy::NewestModified);
+        assert_eq!(found(&dr), "fresh");
+        assert_eq!(
+            dr.find_library("test_selection", PlatformName::Yes).unwrap(),
+            fresh.join(&file_name).canonicalize().unwrap()
+        );
+
+        dr.set_selection_policy(SelectionPolicy::ErrorOnAmbiguity);
+        match dr.find_library("test_selection", PlatformName::Yes) {
+            Err(Error::Ambiguous(name, paths)) => {
+                assert_eq!(name, "test_selection");
+                assert_eq!(paths.len(), 2);
+            }
+            other => panic!("expected an ambiguous match, got {:?}", other),
+        }
+
+        let report = dr.resolve_library("test_selection", PlatformName::Yes);
+        assert!(report.path.is_none());
+        let matched = report.candidates.iter().filter(|c| c.rejected.is_none());
+        assert_eq!(matched.count(), 2);
+
+        fs::remove_file(stale.join(&file_name)).unwrap();
+        assert_eq!(found(&dr), "fresh");
+    }
+
+    // Linux file names are bytes, macOS only allows UTF-8 names
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_non_utf8_paths() {
+        use std::{ffi::OsString, os::unix::ffi::OsStrExt};
+
+        let root = get_test_shared_lib().with_file_name("test_non_utf8_paths");
+        let dir = root.join(OsStr::from_bytes(b"plugins_\xff"));
+        let shadow = root.join(OsStr::from_bytes(b"shadow_\xfe"));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&dir).unwrap();
+        fs::create_dir_all(&shadow).unwrap();
+
+        let mut file_name = OsString::from("libtest_\u{e9}");
+        file_name.push(OsStr::from_bytes(b"\xff.so"));
+        fs::copy(get_test_shared_lib(), dir.join(&file_name)).unwrap();
+
+        let mut dr = DynamicReload::builder()
+            .shadow_dir(&shadow)
+            .debounce(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let lib = unsafe { dr.add_library_path(dir.join(&file_name)).unwrap() };
+        assert_eq!(lib.source_path().file_name(), Some(file_name.as_os_str()));
+        assert!(lib.loaded_path.starts_with(shadow.canonicalize().unwrap()));
+
+        // Found again under its own name when the search paths change
+        fs::create_dir_all(root.join("moved")).unwrap();
+        fs::copy(
+            get_test_shared_lib_v2(),
+            root.join("moved").join(&file_name),
+        )
+        .unwrap();
+        dr.set_search_paths(vec![root.join("moved")]);
+        assert_eq!(dr.relocated.len(), 1);
+
+        let mut dr = DynamicReload::builder()
+            .shadow_dir(&shadow)
+            .build()
+            .unwrap();
+        let libs = unsafe { dr.add_directory(&dir, None).unwrap() };
+        assert_eq!(libs.len(), 1);
+        dr.add_recursive_search_path(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_long_paths() {
+        let root = get_test_shared_lib().with_file_name("test_long_paths");
+        let _ = fs::remove_dir_all(&root);
+        let dir = (0..8).fold(root.clone(), |dir, i| {
+            dir.join(format!("{}_{}", i, "d".repeat(40)))
+        });
+        fs::create_dir_all(&dir).unwrap();
+        assert!(dir.canonicalize().unwrap().as_os_str().len() > 260);
+
+        let (_, dest_path) = copy_test_shared_lib("test_long_paths");
+        let path = dir.join(dest_path.file_name().unwrap());
+        fs::copy(&dest_path, &path).unwrap();
+
+        let mut dr = DynamicReload::builder()
+            .shadow_dir(&dir)
+            .debounce(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let lib = unsafe { dr.add_library_path(&path).unwrap() };
+        assert!(lib.has_symbol("shared_fun"));
+        assert!(matches!(dr.watcher_status(), WatcherStatus::Active(_)));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_extended_paths() {
+        let extended = |path: &str| winpath::extended(Path::new(path));
+
+        assert_eq!(extended(r"C:\a\..\b/c"), Path::new(r"\\?\C:\b\c"));
+        assert_eq!(extended(r"C:\"), Path::new(r"\\?\C:\"));
+        assert_eq!(
+            extended(r"\\server\share\plugins\foo.dll"),
+            Path::new(r"\\?\UNC\server\share\plugins\foo.dll")
+        );
+        assert_eq!(extended(r"\\?\C:\a"), Path::new(r"\\?\C:\a"));
+        assert!(extended("target").to_string_lossy().starts_with(r"\\?\"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let mut notify_callback = TestNotifyCallback::default();
+        let root = get_test_shared_lib().with_file_name("test_follow_symlinks");
+        let (out, link_dir) = (root.join("out"), root.join("link"));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&out).unwrap();
+        fs::create_dir_all(&link_dir).unwrap();
+
+        let file_name = DynamicReload::get_dynamiclib_name("test_follow_symlinks");
+        let target = out.canonicalize().unwrap().join(&file_name);
+        fs::copy(get_test_shared_lib(), &target).unwrap();
+        symlink(&target, link_dir.join(&file_name)).unwrap();
+
+        let mut dr = DynamicReload::builder()
+            .shadow_dir("target/debug")
+            .follow_symlinks(true)
+            .debounce(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let lib = unsafe { dr.add_library_path(link_dir.join(&file_name)).unwrap() };
+        let link = link_dir.canonicalize().unwrap().join(&file_name);
+        assert_eq!(lib.source_path(), link);
+        assert_eq!(dr.link_alias(&target), Some(link.clone()));
+        drop(lib);
+
+        for i in 0..10 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if i == 2 {
+                modify_test_shared_lib(&target);
+            }
+
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        assert!(notify_callback.after_update_done);
+        assert_eq!(dr.libs[0].source_path(), link);
+
+        // Pointing the link elsewhere stops watching the old target
+        let (out2, new_link) = (root.join("out2"), root.join("new_link"));
+        fs::create_dir_all(&out2).unwrap();
+        let target2 = out2.canonicalize().unwrap().join(&file_name);
+        fs::copy(get_test_shared_lib(), &target2).unwrap();
+        symlink(&target2, &new_link).unwrap();
+        fs::rename(&new_link, link_dir.join(&file_name)).unwrap();
+
+        for _ in 0..20 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if dr.link_alias(&target2).is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        assert_eq!(dr.link_alias(&target2), Some(link.clone()));
+
+        // Changes in a directory only reported when it's watched
+        let (tx, rx) = channel();
+        dr.set_raw_events(Some(tx));
+        let mut reported = |dr: &mut DynamicReload, file: &Path| {
+            modify_test_shared_lib(file);
+            for _ in 0..5 {
+                unsafe {
+                    dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+                }
+                thread::sleep(Duration::from_millis(200));
+            }
+
+            let dir = file.parent().unwrap();
+            rx.try_iter().any(|event| match event {
+                RawEvent::Debounced(events) => events.iter().any(|e| e.path.starts_with(dir)),
+                _ => false,
+            })
+        };
+
+        assert!(reported(&mut dr, &target2));
+        assert_eq!(dr.link_alias(&target), None);
+        assert!(!reported(&mut dr, &target));
+
+        dr.set_follow_symlinks(false);
+        assert_eq!(dr.link_alias(&target2), None);
+        assert!(!reported(&mut dr, &target2));
+    }
+
+    #[test]
+    fn test_cluster_keeps_followed_generations() {
+        let dir = get_test_shared_lib().with_file_name("test_cluster_generations");
+        let _ = fs::remove_dir_all(&dir);
+        let lib = get_test_shared_lib();
+        let file_name = lib.file_name().unwrap();
+
+        let mut coordinator = Cluster::coordinator(&dir).unwrap();
+        let mut follower = Cluster::follower(&dir).unwrap();
+        coordinator.publish(&[(&lib, &lib)]).unwrap();
+        assert!(follower.poll().is_some());
+
+        // The generation the follower is at is kept, unchanged libraries are linked
+        for _ in 0..5 {
+            coordinator.publish(&[(&lib, &lib)]).unwrap();
+        }
+        assert!(dir.join("1").join(file_name).is_file());
+        assert!(!dir.join("2").exists());
+        assert!(dir.join("4").is_dir());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |gen: &str| fs::metadata(dir.join(gen).join(file_name)).unwrap().ino();
+            assert_eq!(inode("5"), inode("6"));
+        }
+
+        assert!(follower.poll().is_some());
+        coordinator.publish(&[(&lib, &lib)]).unwrap();
+        assert!(!dir.join("1").exists());
+        assert!(!dir.join("4").exists());
+        assert!(dir.join("5").is_dir());
+
+        // Generations are no longer kept for a follower that is gone
+        drop(follower);
+        coordinator.publish(&[(&lib, &lib)]).unwrap();
+        assert!(!dir.join("5").exists());
+        assert!(dir.join("6").is_dir());
+    }
+
+    #[test]
+    fn test_rollback() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_rollback");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        dr.set_rollback(true);
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let previous = lib.loaded_path.clone();
+        // The copy keeps the name of the library, in a directory of its own
+        #[cfg(feature = "no-timestamps")]
+        assert_eq!(previous.file_name().unwrap().to_str().unwrap(), file_name);
+        dr.inject_failure(&lib, FailPoint::Load);
+        drop(lib);
+
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+
+        assert!(notify_callback.fail_update_done);
+        assert!(notify_callback.rolled_back_done);
+        assert!(!notify_callback.after_update_done);
+        assert!(dr.failed.is_empty());
+        assert_eq!(dr.libs.len(), 1);
+        assert_eq!(dr.libs[0].loaded_path, previous);
+
+        // The rolled back version is reloaded as usual when the file changes again
+        modify_test_shared_lib(&dest_path);
+        modify_test_shared_lib(&dest_path);
+        let mut notify_callback = TestNotifyCallback::default();
+        unsafe {
+            dr.reload_libs(
+                &dest_path,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback,
+            );
+        }
+        assert!(notify_callback.after_update_done);
+        assert_ne!(dr.libs[0].loaded_path, previous);
+    }
+
+    #[test]
+    fn test_inject_failure() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_inject");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        dr.inject_failure(&lib, FailPoint::Copy);
+
+        let injected = |failed: &mut Option<FailPoint>, state, _: Option<&Arc<Lib>>| {
+            if let UpdateState::ReloadFailed(Error::Injected(_, at)) = state {
+                *failed = Some(at);
+            }
+        };
+
+        modify_test_shared_lib(&dest_path);
+
+        let mut failed = None;
+        unsafe {
+            dr.reload_libs(&dest_path, &injected, &mut failed);
+        }
+
+        assert_eq!(failed, Some(FailPoint::Copy));
+        assert!(dr.libs.is_empty());
+
+        // Only the next reload fails
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        dr.inject_failure(&lib, FailPoint::Load);
+        modify_test_shared_lib(&dest_path);
+
+        let mut failed = None;
+        unsafe {
+            dr.reload_libs(&dest_path, &injected, &mut failed);
+        }
+        assert_eq!(failed, Some(FailPoint::Load));
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        modify_test_shared_lib(&dest_path);
+
+        let mut failed = None;
+        unsafe {
+            dr.reload_libs(&dest_path, &injected, &mut failed);
+        }
+        assert_eq!(failed, None);
+        assert_eq!(dr.libs.len(), 1);
+        assert!(!Arc::ptr_eq(&dr.libs[0], &lib));
+    }
+
+    #[test]
+    #[cfg(feature = "test-utils")]
+    fn test_soak_reloads() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_soak");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(100),
+        );
+
+        unsafe {
+            dr.add_library(&file_name, PlatformName::No).unwrap();
+        }
+
+        let report = unsafe {
+            soak::SoakTest::new(get_test_shared_lib(), &dest_path)
+                .alternate(get_test_shared_lib_v2())
+                .rewrites(5)
+                .rewrite_interval(Duration::from_millis(400))
+                .run(&mut dr)
+        };
+
+        report.assert_ok();
+        assert!(report.reloads > 0);
+    }
+
+    // Only used to check that loading fails
+    #[allow(dead_code)]
+    mod missing {
+        crate::plugin_api! {
+            pub struct MissingApi {
+                fn shared_fun() -> i32;
+                fn missing_fun();
+            }
+        }
+    }
+
+    // Both versions are used at the same time, which no-timestamps can't do
+    #[test]
+    #[cfg(not(feature = "no-timestamps"))]
+    fn test_safe_plugin() {
+        crate::plugin_api! {
+            struct TestApi {
+                fn shared_fun() -> i32;
+            }
+        }
+
+        let (file_name, dest_path) = copy_test_shared_lib("test_safe");
+        let dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_secs(1),
+        );
+
+        let mut plugins = SafeDynamicReload::<TestApi>::new(dr);
+        let plugin = unsafe { plugins.add(&file_name, PlatformName::No).unwrap() };
+        assert_eq!(plugin.shared_fun().unwrap(), 42);
+
+        let mut reloaded = false;
+        for i in 0..10 {
+            for event in plugins.update() {
+                reloaded |= matches!(event, PluginEvent::Reloaded(ref name) if *name == file_name);
+            }
+
+            if i == 2 {
+                modify_test_shared_lib(&dest_path);
+            }
+
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        assert!(reloaded);
+        let current = plugins.get(&file_name).unwrap();
+        assert!(!Arc::ptr_eq(&plugin, &current));
+        // The old version is still usable as it's kept alive by the plugin
+        assert_eq!(plugin.shared_fun().unwrap(), 42);
+        assert_eq!(current.shared_fun().unwrap(), 43);
+
+        let mut missing = SafeDynamicReload::<missing::MissingApi>::new(DynamicReload::new(
+            None,
+            None,
+            Search::Backwards,
+            Duration::from_secs(1),
+        ));
+
+        match unsafe { missing.add("test_shared", PlatformName::Yes) } {
+            Err(Error::MissingSymbol(_, symbol)) => assert_eq!(symbol, "missing_fun"),
+            _ => panic!("expected missing symbol"),
+        }
+    }
+
+    // Tests that load libraries with global symbols can't run alongside the ones checking for
+    // global conflicts
+    static GLOBAL_SYMBOLS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    // Libraries opened with RTLD_GLOBAL by other tests are never closed with no-unload
+    #[test]
+    #[cfg(not(feature = "no-unload"))]
+    fn test_symbol_conflicts() {
+        let _lock = GLOBAL_SYMBOLS.lock();
+        let (first, _) = copy_test_shared_lib("test_symbols_a");
+        let (second, _) = copy_test_shared_lib("test_symbols_b");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            None,
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        unsafe {
+            dr.add_library(&first, PlatformName::No).unwrap();
+        }
+        assert!(dr.symbol_conflicts(&["shared_fun"]).is_empty());
+
+        unsafe {
+            dr.add_library(&second, PlatformName::No).unwrap();
+        }
+
+        let conflicts = dr.symbol_conflicts(&["shared_fun", "not_exported"]);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].name, "shared_fun");
+        assert_eq!(conflicts[0].libs.len(), 2);
+        assert!(!conflicts[0].global);
+    }
+
+    #[test]
+    fn test_global_dependency_cascade() {
+        let _lock = GLOBAL_SYMBOLS.lock();
+        let (core_name, core_path) = copy_test_shared_lib("test_deps_core");
+        let (render_name, _) = copy_test_shared_lib("test_deps_render");
+        let (ui_name, _) = copy_test_shared_lib("test_deps_ui");
+
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let (core, render, ui) = unsafe {
+            (
+                dr.add_global_library(&core_name, PlatformName::No).unwrap(),
+                dr.add_library(&render_name, PlatformName::No).unwrap(),
+                dr.add_library(&ui_name, PlatformName::No).unwrap(),
+            )
+        };
+
+        #[cfg(unix)]
+        assert!(dr.symbol_conflicts(&["shared_fun"])[0].global);
+
+        dr.add_dependency(&ui, &render).unwrap();
+        dr.add_dependency(&render, &core).unwrap();
+        assert!(matches!(
+            dr.add_dependency(&core, &ui),
+            Err(Error::DependencyCycle(_, _))
+        ));
+        drop((core, render, ui));
+
+        modify_test_shared_lib(&core_path);
+
+        let record = |events: &mut Vec<String>, state, lib: Option<&Arc<Lib>>| {
+            // Only Before and After come with the library, a failed reload has none
+            let name = || {
+                let lib = lib.unwrap();
+                lib.source_path().file_name().unwrap().to_string_lossy().into_owned()
+            };
+
+            events.push(match state {
+                UpdateState::Before => format!("before {}", name()),
+                UpdateState::After => format!("after {}", name()),
+                UpdateState::ReloadFailed(_) => "failed".to_owned(),
+                _ => "unexpected".to_owned(),
+            });
+        };
+
+        let mut events = Vec::new();
+        unsafe {
+            dr.reload_libs(&core_path, &record, &mut events);
+        }
+
+        assert_eq!(
+            events,
+            vec![
+                format!("before {}", ui_name),
+                format!("before {}", render_name),
+                format!("before {}", core_name),
+                format!("after {}", core_name),
+                format!("after {}", render_name),
+                format!("after {}", ui_name),
+            ]
+        );
+        assert_eq!(dr.libs.len(), 3);
+    }
+
+    #[test]
+    fn test_app_runs_until_done() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_app");
+        let dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(100),
+        );
+
+        let mut app = unsafe { App::new(dr, &file_name, PlatformName::No).unwrap() };
+        let first = app.lib().clone();
+        assert!(unsafe { app.step().unwrap() });
+
+        modify_test_shared_lib(&dest_path);
+
+        // The state is kept across the reload and app_update returns false the third time
+        let mut steps = 1;
+        while unsafe { app.step().unwrap() } {
+            steps += 1;
+            thread::sleep(Duration::from_millis(300));
+        }
+        assert_eq!(steps, 2);
+        assert!(!Arc::ptr_eq(&first, app.lib()));
+        drop(first);
+
+        unsafe { app.run().unwrap() };
+    }
+
+    #[test]
+    fn test_codesign() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_codesign");
+        let mut dr = DynamicReload::builder()
+            .search_path("target/debug")
+            .shadow_dir("target/debug")
+            .codesign(true)
+            .build()
+            .unwrap();
+
+        // The copy is signed after hashing so the hash is still of the original
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        assert_eq!(
+            lib.content_hash(),
+            Some(hash::hash_file(&dest_path).unwrap())
+        );
+        assert_ne!(lib.loaded_path, dest_path);
+    }
+
+    #[test]
+    fn test_copy_debug_info() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_debug_info");
+        fs::write(dest_path.with_extension("pdb"), b"pdb").unwrap();
+        let dsym = dest_path.with_file_name(format!("{}.dSYM", file_name));
+        fs::create_dir_all(dsym.join("Contents")).unwrap();
+        fs::write(dsym.join("Contents").join("Info.plist"), b"plist").unwrap();
+
+        let mut dr = DynamicReload::builder()
+            .search_path("target/debug")
+            .shadow_dir("target/debug")
+            .copy_debug_info(true)
+            .build()
+            .unwrap();
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let loaded = lib.loaded_path.clone();
+        let loaded_name = loaded.file_name().unwrap().to_str().unwrap();
+        let pdb_name = dest_path.with_extension("pdb");
+
+        assert_ne!(loaded.parent(), Some(dr.shadow_dir.as_ref().unwrap().path()));
+        assert!(loaded.with_file_name(pdb_name.file_name().unwrap()).is_file());
+        assert!(loaded
+            .with_file_name(format!("{}.dSYM", loaded_name))
+            .join("Contents")
+            .join("Info.plist")
+            .is_file());
+
+        // Removed with the library
+        assert!(dr.remove_library(&lib));
+        drop(lib);
+        dr.flush_unloads();
+        #[cfg(not(feature = "no-unload"))]
+        assert!(!loaded.parent().unwrap().exists());
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_pdb_name() {
+        let put = |data: &mut Vec<u8>, at: usize, bytes: &[u8]| {
+            data[at..at + bytes.len()].copy_from_slice(bytes);
+        };
+
+        // PE32+ headers with one section that holds the debug directory
+        let mut data = vec![0; 0x400];
+        put(&mut data, 0, b"MZ");
+        put(&mut data, 0x3c, &0x80u32.to_le_bytes());
+        put(&mut data, 0x80, b"PE\0\0");
+        put(&mut data, 0x86, &1u16.to_le_bytes());
+        put(&mut data, 0x94, &0xf0u16.to_le_bytes());
+        put(&mut data, 0x98, &0x20bu16.to_le_bytes());
+        put(&mut data, 0x98 + 108, &16u32.to_le_bytes());
+        put(&mut data, 0x98 + 112 + 6 * 8, &0x1000u32.to_le_bytes());
+        put(&mut data, 0x98 + 112 + 6 * 8 + 4, &28u32.to_le_bytes());
+        put(&mut data, 0x188 + 8, &0x200u32.to_le_bytes());
+        put(&mut data, 0x188 + 12, &0x1000u32.to_le_bytes());
+        put(&mut data, 0x188 + 16, &0x200u32.to_le_bytes());
+        put(&mut data, 0x188 + 20, &0x200u32.to_le_bytes());
+
+        // A CodeView entry in the debug directory pointing at the record
+        let record = b"RSDS\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0C:\\build\\recorded.pdb\0";
+        put(&mut data, 0x200 + 12, &2u32.to_le_bytes());
+        put(&mut data, 0x200 + 16, &(record.len() as u32).to_le_bytes());
+        put(&mut data, 0x200 + 24, &0x300u32.to_le_bytes());
+        put(&mut data, 0x300, record);
+
+        let path = Path::new("target/debug/test_pdb_name.dll");
+        fs::write(path, &data).unwrap();
+        assert_eq!(copy::pdb_name(path), Some("recorded.pdb".into()));
+
+        // A record outside of the debug directory isn't used
+        put(&mut data, 0x200 + 12, &0u32.to_le_bytes());
+        fs::write(path, &data).unwrap();
+        assert_eq!(copy::pdb_name(path), None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-timestamps"))]
+    fn test_shadow_copy_by_content_hash() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_content_hash");
+        let mut dr = DynamicReload::new(
+            Some(vec!["target/debug"]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let hash = hash::hash_file(&dest_path).unwrap();
+        assert_eq!(lib.content_hash(), Some(hash));
+        assert_eq!(
+            lib.loaded_path.file_name().unwrap().to_str().unwrap(),
+            format!("{:016x}_{}", hash, file_name)
+        );
+
+        // Writing the same content again doesn't reload
+        let mut notify_callback = TestNotifyCallback::default();
+        fs::copy(get_test_shared_lib(), &dest_path).unwrap();
+        unsafe {
+            dr.reload_libs(&dest_path, &TestNotifyCallback::update_call, &mut notify_callback);
+        }
+        assert!(!notify_callback.update_call_done);
+
+        // Going back to a previous version reuses its shadow copy
+        modify_test_shared_lib(&dest_path);
+        unsafe {
+            dr.reload_libs(&dest_path, &TestNotifyCallback::update_call, &mut notify_callback);
+        }
+        assert!(notify_callback.after_update_done);
+        assert_ne!(dr.libs[0].loaded_path, lib.loaded_path);
+        drop(lib);
+
+        modify_test_shared_lib(&dest_path);
+        unsafe {
+            dr.reload_libs(&dest_path, &TestNotifyCallback::update_call, &mut notify_callback);
+        }
+        assert_eq!(dr.libs[0].content_hash(), Some(hash));
+
+        // Temporary copies are moved in place or removed
+        let names: Vec<_> = fs::read_dir(dr.shadow_dir.as_ref().unwrap().path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.contains(&file_name))
+            .collect();
+        assert_eq!(names.iter().filter(|n| n.ends_with(&file_name)).count(), 2);
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    fn test_shadow_copy_same_file_name() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_same_file_name");
+        let other_dir = Path::new("target/debug/test_same_file_name");
+        fs::create_dir_all(other_dir).unwrap();
+        let other_path = other_dir.join(&file_name);
+        fs::copy(get_test_shared_lib_v2(), &other_path).unwrap();
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
+        );
+        let lib = unsafe { dr.add_library_path(&dest_path).unwrap() };
+        let other = unsafe { dr.add_library_path(&other_path) };
+
+        // Libraries with the same file name and different content get their own copies, except
+        // with no-timestamps where the copy of the loaded one isn't replaced
+        #[cfg(feature = "no-timestamps")]
+        {
+            assert!(matches!(other, Err(Error::Copy(_, _, _))));
+            assert!(lib.loaded_path.is_file());
+        }
+        #[cfg(not(feature = "no-timestamps"))]
+        {
+            let other = other.unwrap();
+            assert_ne!(lib.loaded_path, other.loaded_path);
+            let fun = |lib: &Lib| unsafe { lib.lib.get::<fn() -> i32>(b"shared_fun\0").unwrap()() };
+            assert_eq!(fun(&lib), 42);
+            assert_eq!(fun(&other), 43);
+        }
+    }
+
+    #[test]
+    fn test_get_temp_dir_fail() {
+        assert!(DynamicReload::get_temp_dir(Some("_no_such_dir")).is_none());
+    }
+
+    #[test]
+    fn test_get_temp_dir_none() {
+        assert!(DynamicReload::get_temp_dir(None).is_none());
+    }
+
+    #[test]
+    fn test_get_temp_dir_ok() {
+        assert!(DynamicReload::get_temp_dir(Some("")).is_some());
+    }
+
+    #[test]
+    fn test_is_file_fail() {
+        assert!(
+            DynamicReload::is_file(&Path::new("haz_no_file_with_this_name").to_path_buf())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_is_file_ok() {
+        assert!(DynamicReload::is_file(&env::current_exe().unwrap()).is_some());
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_get_library_name_mac() {
+        assert_eq!(
+            DynamicReload::get_library_name("foobar", PlatformName::Yes),
+            "libfoobar.dylib"
+        );
+    }
+
+    #[test]
+    fn test_get_library_name() {
+        assert_eq!(
+            DynamicReload::get_library_name("foobar", PlatformName::No),
+            "foobar"
+        );
+    }
+
+    #[test]
+    fn test_get_library_name_formatted() {
+        let formatted = format!("{}foobar{}", env::consts::DLL_PREFIX, env::consts::DLL_SUFFIX);
+
+        assert_eq!(
+            DynamicReload::get_library_name(&formatted, PlatformName::Yes),
+            formatted
+        );
+        assert_eq!(
+            DynamicReload::get_library_name("foo-bar", PlatformName::Yes),
+            format!("{}foo_bar{}", env::consts::DLL_PREFIX, env::consts::DLL_SUFFIX)
+        );
+        assert_eq!(
+            DynamicReload::get_library_name("foo-bar", PlatformName::No),
+            "foo-bar"
+        );
+    }
+
+    #[test]
+    fn test_search_backwards_from_file_ok() {
+        // While this relays on having a Cargo project, it should be fine
+        assert!(DynamicReload::search_backwards_from_exe(
+            OsStr::new("Cargo.toml"),
+            &mut Searched::new(false)
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn test_search_backwards_from_file_fail() {
+        assert!(DynamicReload::search_backwards_from_exe(
+            OsStr::new("_no_such_file"),
+            &mut Searched::new(false)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_add_library_fail() {
+        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+        unsafe {
+            assert!(dr
+                .add_library("wont_find_this_lib", PlatformName::No)
+                .is_err());
         }
     }
 
-    fn search_backwards_from_exe(lib_name: &String) -> Option<PathBuf> {
-        let exe_path = env::current_exe().unwrap_or_default();
-        Self::search_backwards_from_file(&exe_path, lib_name)
+    #[test]
+    fn test_add_shared_lib_ok() {
+        let mut dr = DynamicReload::new(None, None, Search::Backwards, Duration::from_secs(2));
+        unsafe {
+            assert!(dr.add_library("test_shared", PlatformName::Yes).is_ok());
+        }
     }
 
-    fn get_temp_dir(shadow_dir: Option<&str>) -> Option<TempDir> {
-        match shadow_dir {
-            Some(dir) => match TempDir::new_in(dir) {
-                Ok(td) => {
-                    if !Path::exists(td.path()) {
-                        // TODO: Result
-                        println!("Unable to create tempdir in {}", dir);
-                        None
-                    } else {
-                        Some(td)
-                    }
-                }
-                Err(_er) => {
-                    None
-                }
-            },
-            _ => None,
+    #[test]
+    fn test_add_shared_lib_search_paths() {
+        let mut dr = DynamicReload::new(
+            Some(vec!["../..", "../test"]),
+            None,
+            Search::Backwards,
+            Duration::from_secs(2),
+        );
+        unsafe {
+            assert!(dr.add_library("test_shared", PlatformName::Yes).is_ok());
         }
     }
 
-    fn is_file(path: &PathBuf) -> Option<PathBuf> {
-        match fs::metadata(path) {
-            Ok(md) => {
-                if md.is_file() {
-                    Some(path.clone())
-                } else {
-                    None
-                }
-            }
-            _ => None,
+    #[test]
+    fn test_add_shared_lib_spelled_differently() {
+        // The same file is found under another spelling the second time
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let strategy = move |name: &str, _: &[PathBuf]| {
+            let dir = match calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => "target/debug",
+                _ => "target/../target/debug",
+            };
+            Some(Path::new(dir).join(name))
+        };
+        let mut dr = DynamicReload::new(
+            None,
+            None,
+            Search::Custom(Box::new(strategy)),
+            Duration::from_secs(2),
+        );
+        unsafe {
+            dr.add_library("test_shared", PlatformName::Yes).unwrap();
+            dr.add_library("test_shared", PlatformName::Yes).unwrap();
         }
+        assert_eq!(dr.libs.len(), 1);
     }
 
-    // In some cases when a file has been set so that it's reloaded, it's actually not possible
-    // to read from it directly so this code does some testing first to ensure we
-    // can actually read from it (by using metadata which does a stat on the file).
-    // If we can't read from it, we wait for 100 ms before we try again, if we can't
-    // do it within 1 sec we give up
-    //
-    fn try_copy(src: &Path, dest: &Path) -> Result<()> {
-        for _ in 0..10 {
-            if let Ok(file) = fs::metadata(src) {
-                let len = file.len();
-                if len > 0 {
-                    // ignore copy errors, library file might be locked by the compiler
-                    if fs::copy(src, dest).is_ok() {
-                        return Ok(());
-                    }
-                }
-            }
+    #[test]
+    fn test_add_shared_lib_system_search() {
+        let dir = Path::new("target/debug/test_system_search");
+        fs::create_dir_all(dir).unwrap();
+        let file_name = DynamicReload::get_dynamiclib_name("test_shared_system");
+        fs::copy(get_test_shared_lib(), dir.join(&file_name)).unwrap();
 
-            thread::sleep(Duration::from_millis(100));
-        }
+        let rel_dir = Path::new("target/debug/test_loader_paths");
+        fs::create_dir_all(rel_dir).unwrap();
+        let rel_name = DynamicReload::get_dynamiclib_name("test_shared_loader");
+        fs::copy(get_test_shared_lib(), rel_dir.join(&rel_name)).unwrap();
 
-        Err(Error::CopyTimeOut(src.to_path_buf(), dest.to_path_buf()))
-    }
+        // Instead of changing the variables of the process, which other tests read
+        let lookup: fn(&str) -> Option<OsString> = |var| {
+            let dirs = [
+                "target/debug/test_system_search",
+                "target/debug/test_loader_paths",
+            ];
+            (var == system::PATH_VARS[0]).then(|| env::join_paths(dirs).unwrap())
+        };
+        assert!(system::library_dirs(lookup).contains(&dir.to_path_buf()));
 
-    fn get_watcher(
-        tx: Sender<DebounceEventResult>,
-        debounce_duration: Duration,
-    ) -> Option<Debouncer<RecommendedWatcher>> {
-        match new_debouncer(debounce_duration, None, tx) {
-            Ok(watcher) => Some(watcher),
-            Err(e) => {
-                println!(
-                    "Unable to create file watcher, no dynamic reloading will be done, \
-                     error: {:?}",
-                    e
-                );
-                None
-            }
+        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+        dr.env_lookup = lookup;
+        unsafe {
+            assert!(dr.add_library("test_shared_system", PlatformName::Yes).is_err());
         }
-    }
 
-    fn get_search_paths(search_paths: Option<Vec<&str>>) -> Vec<PathBuf> {
-        match search_paths {
-            Some(paths) => paths
-                .iter()
-                .map(|p| {
-                    let path_buf = Path::new(p).to_path_buf();
-                    path_buf.canonicalize().unwrap_or(path_buf)
-                })
-                .collect(),
-            None => Vec::new(),
-        }
-    }
+        let mut dr = DynamicReload::new(None, None, Search::System, Duration::from_secs(2));
+        dr.env_lookup = lookup;
+        let lib = unsafe { dr.add_library("test_shared_system", PlatformName::Yes).unwrap() };
+        assert_eq!(
+            lib.loaded_path,
+            dir.canonicalize().unwrap().join(&file_name)
+        );
 
-    fn get_library_name(name: &str, name_format: PlatformName) -> String {
-        if name_format == PlatformName::Yes {
-            Self::get_dynamiclib_name(name)
-        } else {
-            name.to_string()
-        }
+        // A relative entry is resolved to an absolute path
+        let mut dr = DynamicReload::builder()
+            .shadow_dir("target/debug")
+            .loader_paths(true)
+            .build()
+            .unwrap();
+        dr.env_lookup = lookup;
+        let lib = unsafe {
+            dr.add_library("test_shared_loader", PlatformName::Yes)
+                .unwrap()
+        };
+        assert_eq!(
+            lib.original_path,
+            Some(rel_dir.canonicalize().unwrap().join(&rel_name))
+        );
+
+        // Without backwards search or the system directories
+        dr.set_loader_paths(false);
+        assert!(dr
+            .resolve_library("test_shared_loader", PlatformName::Yes)
+            .path
+            .is_none());
     }
 
-    fn remove_lib(&mut self, idx: usize) {
-        #[cfg(feature = "no-unload")]
-        std::mem::forget(self.libs.swap_remove(idx));
+    #[test]
+    fn test_builder() {
+        let dr = DynamicReload::builder()
+            .search_path("target/debug")
+            .shadow_dir("target/debug")
+            .debounce(Duration::from_millis(100))
+            .ignore_pattern("*.pdb")
+            .build()
+            .unwrap();
 
-        #[cfg(not(feature = "no-unload"))]
-        self.libs.swap_remove(idx);
+        assert!(dr.shadow_dir.is_some());
+        assert!(dr.is_watching());
+        assert_eq!(dr.search_paths.len(), 1);
+        assert_eq!(dr.ignore_patterns.len(), 1);
+
+        let dr = DynamicReload::builder().watch(false).build().unwrap();
+        assert!(dr.shadow_dir.is_none());
+        assert!(!dr.is_watching());
     }
 
-    #[cfg(not(feature = "no-timestamps"))]
-    fn format_filename(shadow_dir: &Path, full_path: &Path) -> PathBuf {
-        let ts = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("Time went backwards");
-        let filename = full_path.file_name().unwrap();
-        shadow_dir.join(format!("{}_{}", ts.as_millis(), filename.to_str().unwrap()))
+    #[test]
+    fn test_builder_fail() {
+        let result = DynamicReload::builder().shadow_dir("_no_such_dir").build();
+        assert!(matches!(result, Err(Error::ShadowDir(_, _))));
+
+        let result = DynamicReload::builder().ignore_pattern("[").build();
+        assert!(matches!(result, Err(Error::Pattern(_))));
+
+        let result = DynamicReload::builder()
+            .recursive_search_path("_no_such_dir")
+            .build();
+        assert!(matches!(result, Err(Error::Find(_, _))));
     }
 
-    #[cfg(feature = "no-timestamps")]
-    fn format_filename(shadow_dir: &Path, full_path: &PathBuf) -> PathBuf {
-        shadow_dir.join(full_path.file_name().unwrap())
+    #[test]
+    fn test_search_default_backwards() {
+        // The test executable is in target/debug/deps and the library in target/debug
+        let dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+        assert!(dr.find_library("test_shared", PlatformName::Yes).is_err());
+
+        let dr = DynamicReload::new(None, None, Search::Backwards, Duration::from_secs(2));
+        assert_eq!(
+            dr.find_library("test_shared", PlatformName::Yes).unwrap(),
+            get_test_shared_lib()
+        );
     }
 
-    /// Formats dll name on Windows ("test_foo" -> "test_foo.dll")
-    #[cfg(target_os = "windows")]
-    fn get_dynamiclib_name(name: &str) -> String {
-        format!("{}.dll", name)
+    #[test]
+    fn test_search_custom() {
+        let strategy = |name: &str, paths: &[PathBuf]| {
+            assert_eq!(paths, [PathBuf::from("plugins")]);
+            Some(get_test_shared_lib().with_file_name(name))
+        };
+
+        let dr = DynamicReload::new(
+            Some(vec!["plugins"]),
+            None,
+            Search::Custom(Box::new(strategy)),
+            Duration::from_secs(2),
+        );
+
+        assert_eq!(
+            dr.find_library("test_shared", PlatformName::Yes).unwrap(),
+            get_test_shared_lib()
+        );
     }
 
-    /// Formats dll name on Mac ("test_foo" -> "libtest_foo.dylib")
-    #[cfg(target_os = "macos")]
-    fn get_dynamiclib_name(name: &str) -> String {
-        format!("lib{}.dylib", name)
+    #[test]
+    fn test_add_shared_lib_fail_load() {
+        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+        unsafe {
+            assert!(dr.add_library("Cargo.toml", PlatformName::No).is_err());
+        }
     }
 
-    /// Formats dll name on *nix ("test_foo" -> "libtest_foo.so")
-    #[cfg(any(
-        target_os = "linux",
-        target_os = "freebsd",
-        target_os = "dragonfly",
-        target_os = "netbsd",
-        target_os = "openbsd",
-        target_os = "android"
-    ))]
-    fn get_dynamiclib_name(name: &str) -> String {
-        format!("lib{}.so", name)
+    #[test]
+    fn test_add_shared_shadow_dir_ok() {
+        let dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(2),
+        );
+        assert!(dr.shadow_dir.is_some());
     }
-}
 
-impl PartialEq for Lib {
-    fn eq(&self, other: &Lib) -> bool {
-        self.original_path == other.original_path
+    #[test]
+    fn test_add_shared_string_arg_ok() {
+        let shadow_dir_string = "target/debug".to_owned();
+        let dr = DynamicReload::new(
+            None,
+            Some(&shadow_dir_string),
+            Search::Default,
+            Duration::from_secs(2),
+        );
+        assert!(dr.shadow_dir.is_some());
     }
 
-    /*
-    fn ne(&self, other: &Lib) -> bool {
-        self.original_path != other.original_path
+    #[test]
+    fn test_add_shared_lib_search_paths_strings() {
+        let path1 = "../..".to_owned();
+        let path2 = "../test".to_owned();
+        let mut dr = DynamicReload::new(
+            Some(vec![&path1, &path2]),
+            None,
+            Search::Backwards,
+            Duration::from_secs(2),
+        );
+        unsafe {
+            assert!(dr.add_library("test_shared", PlatformName::Yes).is_ok());
+        }
     }
-    */
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env;
-    use std::fs;
-    use std::path::{Path, PathBuf};
-    use std::sync::mpsc::channel;
-    use std::sync::Arc;
-    use std::thread;
-    use std::time::Duration;
+    #[test]
+    fn test_add_shared_update() {
+        let mut notify_callback = TestNotifyCallback::default();
+        // Identical content isn't reloaded so the library has to really change. Use a private
+        // copy to not change test_shared under the other tests.
+        let (file_name, dest_path) = copy_test_shared_lib("test_add_shared");
 
-    #[derive(Debug, Default)]
-    struct TestNotifyCallback {
-        update_call_done: bool,
-        after_update_done: bool,
-        fail_update_done: bool,
-    }
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_secs(1),
+        );
 
-    impl TestNotifyCallback {
-        fn update_call(&mut self, state: UpdateState, _lib: Option<&Arc<Lib>>) {
-            match state {
-                UpdateState::Before => self.update_call_done = true,
-                UpdateState::After => self.after_update_done = true,
-                UpdateState::ReloadFailed(_) => self.fail_update_done = true,
+        unsafe {
+            assert!(dr.add_library(&file_name, PlatformName::No).is_ok());
+        }
+
+        for i in 0..10 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
             }
 
-            println!("Update state {:?}", self);
+            if i == 2 {
+                modify_test_shared_lib(&dest_path);
+            }
+
+            thread::sleep(Duration::from_millis(200));
         }
+
+        assert!(notify_callback.update_call_done);
+        assert!(notify_callback.after_update_done);
     }
 
-    fn get_test_shared_lib() -> PathBuf {
-        let exe_path = env::current_exe().unwrap();
-        let lib_path = exe_path.parent().unwrap().parent().unwrap();
-        let lib_name = "test_shared";
-        Path::new(&lib_path).join(DynamicReload::get_dynamiclib_name(lib_name))
+    #[test]
+    fn test_remove_library() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_remove_library");
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_millis(100),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        dr.inject_failure(&lib, FailPoint::Load);
+
+        assert!(dr.remove_library_with(
+            &lib,
+            &TestNotifyCallback::update_call,
+            &mut notify_callback
+        ));
+        assert!(notify_callback.update_call_done);
+        assert!(dr.libs.is_empty());
+        assert!(!dr.remove_library(&lib));
+        assert!(dr.injections.take(lib.source_path()).is_none());
+
+        // Changes of a removed library are ignored
+        notify_callback = TestNotifyCallback::default();
+        modify_test_shared_lib(&dest_path);
+
+        for _ in 0..5 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        assert!(!notify_callback.update_call_done);
+        assert!(!notify_callback.after_update_done);
     }
 
-    #[test]
-    fn test_search_paths_none() {
-        assert_eq!(DynamicReload::get_search_paths(None).len(), 0);
+    #[test]
+    fn test_plugin_manager() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_plugin_manager");
+
+        let dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_millis(100),
+        );
+
+        let mut plugins = PluginManager::new(dr, |lib| {
+            let fun: Symbol<extern "C" fn() -> i32> =
+                unsafe { lib.lib.get(b"shared_fun\0").map_err(Error::Load)? };
+            Ok(*fun)
+        });
+
+        unsafe {
+            assert!(plugins.add("missing", PlatformName::Yes).is_err());
+            plugins.add(&file_name, PlatformName::No).unwrap();
+        }
+
+        assert_eq!(plugins.get(&file_name).unwrap()(), 42);
+        assert_eq!(plugins.iter().count(), 1);
+
+        modify_test_shared_lib(&dest_path);
+
+        let mut events = Vec::new();
+        for _ in 0..20 {
+            events.extend(unsafe { plugins.update() });
+            if !events.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        assert!(matches!(&events[..], [PluginEvent::Reloaded(name)] if *name == file_name));
+        assert_eq!(plugins.get(&file_name).unwrap()(), 43);
+
+        assert!(plugins.remove(&file_name));
+        assert!(plugins.get(&file_name).is_none());
+        assert!(plugins.reload_mut().libs.is_empty());
     }
 
     #[test]
-    fn test_search_paths_some() {
-        assert_eq!(
-            DynamicReload::get_search_paths(Some(vec!["test", "test"])).len(),
-            2
+    fn test_skip_identical_content() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_skip_identical");
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_secs(1),
         );
-    }
 
-    #[test]
-    fn test_get_watcher() {
-        let (tx, _) = channel();
-        // We expect this to always work
-        assert!(DynamicReload::get_watcher(tx, Duration::from_secs(2)).is_some());
-    }
+        unsafe {
+            dr.add_library(&file_name, PlatformName::No).unwrap();
+        }
 
-    #[test]
-    fn test_get_temp_dir_fail() {
-        assert!(DynamicReload::get_temp_dir(Some("_no_such_dir")).is_none());
-    }
+        modify_test_shared_lib(&dest_path);
+        unsafe {
+            dr.reload_libs(&dest_path, &TestNotifyCallback::update_call, &mut notify_callback);
+        }
+        assert!(notify_callback.after_update_done);
 
-    #[test]
-    fn test_get_temp_dir_none() {
-        assert!(DynamicReload::get_temp_dir(None).is_none());
+        // A trailing event after the file has been written again with the same content, which
+        // on Windows (no file ids) can't be told apart from a real change without the hash
+        notify_callback = TestNotifyCallback::default();
+        fs::write(&dest_path, fs::read(&dest_path).unwrap()).unwrap();
+        unsafe {
+            dr.reload_libs(&dest_path, &TestNotifyCallback::update_call, &mut notify_callback);
+        }
+        assert!(!notify_callback.update_call_done);
+        assert_eq!(dr.libs[0].content_hash(), hash::hash_file(&dest_path).ok());
     }
 
     #[test]
-    fn test_get_temp_dir_ok() {
-        assert!(DynamicReload::get_temp_dir(Some("")).is_some());
-    }
+    fn test_match_policy() {
+        let root = get_test_shared_lib().with_file_name("test_match_policy");
+        let file_name = DynamicReload::get_dynamiclib_name("test_match");
+        let paths: Vec<PathBuf> = ["a", "b"]
+            .iter()
+            .map(|d| root.join(d).join(&file_name))
+            .collect();
 
-    #[test]
-    fn test_is_file_fail() {
-        assert!(
-            DynamicReload::is_file(&Path::new("haz_no_file_with_this_name").to_path_buf())
-                .is_none()
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_secs(1),
         );
-    }
 
-    #[test]
-    fn test_is_file_ok() {
-        assert!(DynamicReload::is_file(&env::current_exe().unwrap()).is_some());
-    }
+        for path in &paths {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::copy(get_test_shared_lib(), path).unwrap();
+            let lib = unsafe { dr.load_library(path, None).unwrap() };
+            dr.track_lib(lib).unwrap();
+        }
 
-    #[test]
-    #[cfg(target_os = "macos")]
-    fn test_get_library_name_mac() {
-        assert_eq!(
-            DynamicReload::get_library_name("foobar", PlatformName::Yes),
-            "libfoobar.dylib"
-        );
+        let before = |reloaded: &mut Vec<PathBuf>, state: UpdateState, lib: Option<&Arc<Lib>>| {
+            if let (UpdateState::Before, Some(lib)) = (state, lib) {
+                reloaded.push(lib.source_path().to_path_buf());
+            }
+        };
+
+        let mut reloaded = Vec::new();
+        modify_test_shared_lib(&paths[0]);
+        unsafe { dr.reload_libs(&paths[0], &before, &mut reloaded) };
+        assert_eq!(reloaded, vec![paths[0].clone()]);
+
+        // Same file as the first library but through another path
+        let other = root.join("b").join("..").join("a").join(&file_name);
+        assert!(dr
+            .libs
+            .iter()
+            .all(|lib| dr.should_reload(&other, lib) == (lib.source_path() == paths[0])));
+
+        dr.set_match_policy(MatchPolicy::FileName);
+        assert!(dr.libs.iter().all(|lib| dr.should_reload(&paths[0], lib)));
     }
 
     #[test]
-    fn test_get_library_name() {
-        assert_eq!(
-            DynamicReload::get_library_name("foobar", PlatformName::No),
-            "foobar"
+    fn test_rewatch_replaced_dir() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let dir = get_test_shared_lib().with_file_name("test_rewatch");
+        let file_name = DynamicReload::get_dynamiclib_name("test_rewatch");
+        let dest_path = dir.join(&file_name);
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(get_test_shared_lib(), &dest_path).unwrap();
+
+        let mut dr = DynamicReload::new(
+            Some(vec![dir.to_str().unwrap()]),
+            Some("target/debug"),
+            Search::Default,
+            Duration::from_millis(100),
         );
-    }
 
-    #[test]
-    fn test_search_backwards_from_file_ok() {
-        // While this relays on having a Cargo project, it should be fine
-        assert!(DynamicReload::search_backwards_from_exe(&"Cargo.toml".to_string()).is_some());
-    }
+        unsafe {
+            dr.add_library(&file_name, PlatformName::No).unwrap();
+        }
 
-    #[test]
-    fn test_search_backwards_from_file_fail() {
-        assert!(DynamicReload::search_backwards_from_exe(&"_no_such_file".to_string()).is_none());
+        // Removing the directory takes the watch with it
+        fs::remove_dir_all(&dir).unwrap();
+        unsafe {
+            dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+        }
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(get_test_shared_lib_v2(), &dest_path).unwrap();
+
+        for _ in 0..20 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if notify_callback.after_update_done {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        assert!(notify_callback.after_update_done);
+        assert_eq!(dr.libs[0].content_hash(), hash::hash_file(&dest_path).ok());
+
+        // Changes are seen through the new watch
+        notify_callback = TestNotifyCallback::default();
+        thread::sleep(Duration::from_millis(200));
+        modify_test_shared_lib(&dest_path);
+
+        for _ in 0..20 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            if notify_callback.after_update_done {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        assert!(notify_callback.after_update_done);
     }
 
     #[test]
-    fn test_add_library_fail() {
-        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+    fn test_reload_library() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_reload_library");
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_secs(1),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        modify_test_shared_lib(&dest_path);
+
         unsafe {
-            assert!(dr
-                .add_library("wont_find_this_lib", PlatformName::No)
-                .is_err());
+            assert!(dr.reload_library(
+                &lib,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback
+            ));
         }
-    }
 
-    #[test]
-    fn test_add_shared_lib_ok() {
-        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+        assert!(notify_callback.update_call_done);
+        assert!(notify_callback.after_update_done);
+        assert_eq!(dr.libs[0].content_hash(), hash::hash_file(&dest_path).ok());
+
+        dr.remove_library(&lib);
         unsafe {
-            assert!(dr.add_library("test_shared", PlatformName::Yes).is_ok());
+            assert!(!dr.reload_library(
+                &lib,
+                &TestNotifyCallback::update_call,
+                &mut notify_callback
+            ));
         }
     }
 
     #[test]
-    fn test_add_shared_lib_search_paths() {
+    fn test_raw_events() {
+        let mut notify_callback = TestNotifyCallback::default();
+        let (file_name, dest_path) = copy_test_shared_lib("test_raw_events");
+        let (tx, rx) = channel();
+
         let mut dr = DynamicReload::new(
-            Some(vec!["../..", "../test"]),
             None,
-            Search::Default,
-            Duration::from_secs(2),
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_millis(100),
         );
+
+        dr.set_raw_events(Some(tx));
+
         unsafe {
-            assert!(dr.add_library("test_shared", PlatformName::Yes).is_ok());
+            dr.add_library(&file_name, PlatformName::No).unwrap();
+        }
+
+        modify_test_shared_lib(&dest_path);
+
+        let mut events = Vec::new();
+        for _ in 0..20 {
+            unsafe {
+                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
+            }
+
+            events.extend(rx.try_iter());
+            if notify_callback.after_update_done {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(100));
         }
+
+        assert!(events.iter().any(|event| match event {
+            RawEvent::Debounced(events) => events.iter().any(|e| e.path == dest_path),
+            _ => false,
+        }));
+
+        // A dropped receiver stops the events
+        drop(rx);
+        dr.send_raw_event(RawEvent::Debounced(Vec::new()));
+        assert!(dr.raw_events.is_none());
     }
 
     #[test]
-    fn test_add_shared_lib_fail_load() {
-        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
-        unsafe {
-            assert!(dr.add_library("Cargo.toml", PlatformName::No).is_err());
+    fn test_poll_events() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_poll_events");
+
+        let mut dr = DynamicReload::new(
+            None,
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_millis(100),
+        );
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        #[cfg(feature = "no-timestamps")]
+        drop(lib);
+        modify_test_shared_lib(&dest_path);
+
+        let mut events = Vec::new();
+        for _ in 0..20 {
+            events.extend(unsafe { dr.poll_events() });
+            if !events.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
         }
+
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0].phase, ReloadPhase::Reloaded));
+        assert_eq!(events[0].path, dest_path);
+        #[cfg(not(feature = "no-timestamps"))]
+        assert!(Arc::ptr_eq(events[0].old.as_ref().unwrap(), &lib));
+        #[cfg(feature = "no-timestamps")]
+        assert!(events[0].old.is_none());
+        assert!(Arc::ptr_eq(events[0].new.as_ref().unwrap(), &dr.libs[0]));
     }
 
     #[test]
-    fn test_add_shared_shadow_dir_ok() {
-        let dr = DynamicReload::new(
+    fn test_poll_events_untied_failure() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_untied_failure");
+        let mut dr = DynamicReload::new(
             None,
             Some("target/debug"),
-            Search::Default,
-            Duration::from_secs(2),
+            Search::Backwards,
+            Duration::from_secs(1),
         );
-        assert!(dr.shadow_dir.is_some());
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        let other = PathBuf::from("other");
+
+        // A library from a directory failing while another one is pending takes nothing from it
+        let mut collector = Collector::default();
+        collector.update_call(UpdateState::Before, Some(&lib));
+        collector.update_call(UpdateState::BuildStarted, None);
+        collector.update_call(
+            UpdateState::ReloadFailed(Error::Injected(other.clone(), FailPoint::Load)),
+            None,
+        );
+        collector.update_call(
+            UpdateState::ReloadFailed(Error::Load(libloading::Error::IncompatibleSize)),
+            None,
+        );
+        collector.update_call(UpdateState::After, Some(&lib));
+
+        let events = collector.events;
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].path, other);
+        assert!(events[0].old.is_none());
+        assert_eq!(events[1].path, PathBuf::new());
+        assert!(matches!(events[2].phase, ReloadPhase::Reloaded));
+        #[cfg(not(feature = "no-timestamps"))]
+        assert!(events[2].old.is_some());
+
+        // Right after Before it's the reload of that library
+        let mut collector = Collector::default();
+        collector.update_call(UpdateState::Before, Some(&lib));
+        collector.update_call(
+            UpdateState::ReloadFailed(Error::Load(libloading::Error::IncompatibleSize)),
+            None,
+        );
+        collector.update_call(UpdateState::After, Some(&lib));
+
+        let events = collector.events;
+        assert!(matches!(events[0].phase, ReloadPhase::Failed(_)));
+        assert_eq!(events[0].path, dest_path);
+        assert!(events[1].old.is_none());
     }
 
+    #[cfg(feature = "stream")]
     #[test]
-    fn test_add_shared_string_arg_ok() {
-        let shadow_dir_string = "target/debug".to_owned();
-        let dr = DynamicReload::new(
+    fn test_event_stream() {
+        use futures_core::Stream;
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct Unpark(thread::Thread);
+
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let (file_name, dest_path) = copy_test_shared_lib("test_event_stream");
+
+        let mut dr = DynamicReload::new(
             None,
-            Some(&shadow_dir_string),
-            Search::Default,
-            Duration::from_secs(2),
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_millis(100),
         );
-        assert!(dr.shadow_dir.is_some());
+
+        let lib = unsafe { dr.add_library(&file_name, PlatformName::No).unwrap() };
+        #[cfg(feature = "no-timestamps")]
+        drop(lib);
+
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut events = unsafe { dr.event_stream() };
+
+        assert!(Pin::new(&mut events).poll_next(&mut cx).is_pending());
+        modify_test_shared_lib(&dest_path);
+
+        // Only a wake up from the watcher gets here before the timeout
+        let start = Instant::now();
+        let event = loop {
+            match Pin::new(&mut events).poll_next(&mut cx) {
+                Poll::Ready(event) => break event.unwrap(),
+                Poll::Pending => thread::park_timeout(Duration::from_secs(10)),
+            }
+        };
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        assert!(matches!(event.phase, ReloadPhase::Reloaded));
+        #[cfg(not(feature = "no-timestamps"))]
+        assert!(Arc::ptr_eq(event.old.as_ref().unwrap(), &lib));
+        #[cfg(feature = "no-timestamps")]
+        assert!(event.old.is_none());
     }
 
     #[test]
-    fn test_add_shared_lib_search_paths_strings() {
-        let path1 = "../..".to_owned();
-        let path2 = "../test".to_owned();
-        let mut dr = DynamicReload::new(
-            Some(vec![&path1, &path2]),
+    fn test_spawn_background() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_spawn_background");
+
+        let dr = DynamicReload::new(
             None,
-            Search::Default,
-            Duration::from_secs(2),
+            Some("target/debug"),
+            Search::Backwards,
+            Duration::from_millis(100),
         );
-        unsafe {
-            assert!(dr.add_library("test_shared", PlatformName::Yes).is_ok());
-        }
+
+        let (tx, rx) = channel();
+        let background = unsafe {
+            dr.spawn_background(move |state, lib| {
+                if let UpdateState::After = state {
+                    tx.send(lib.unwrap().clone()).unwrap();
+                }
+            })
+            .unwrap()
+        };
+
+        assert_eq!(
+            background.thread().unwrap().name(),
+            Some(RELOAD_THREAD_NAME)
+        );
+
+        let lib = unsafe {
+            background
+                .lock()
+                .add_library(&file_name, PlatformName::No)
+                .unwrap()
+        };
+
+        modify_test_shared_lib(&dest_path);
+
+        let new = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(!Arc::ptr_eq(&new, &lib));
+
+        let dr = background.stop();
+        assert!(Arc::ptr_eq(&dr.libs[0], &new));
     }
 
     #[test]
-    fn test_add_shared_update() {
-        let mut notify_callback = TestNotifyCallback::default();
-        let target_path = get_test_shared_lib();
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<DynamicReload>();
+        assert_send_sync::<Lib>();
+    }
 
-        let mut dest_path = Path::new(&target_path).to_path_buf();
+    #[test]
+    fn test_handle() {
+        let (file_name, dest_path) = copy_test_shared_lib("test_handle");
 
         let mut dr = DynamicReload::new(
             None,
             Some("target/debug"),
-            Search::Default,
-            Duration::from_secs(1),
+            Search::Backwards,
+            Duration::from_millis(100),
         );
 
-        dest_path.set_file_name("test_file");
+        let mut notify_callback = TestNotifyCallback::default();
+        let handle = dr.handle();
 
-        fs::copy(&target_path, &dest_path).unwrap();
+        let rx = thread::spawn(move || {
+            handle.pause();
+            handle.add_library(&file_name, PlatformName::No)
+        })
+        .join()
+        .unwrap();
 
-        unsafe {
-            assert!(dr.add_library("test_shared", PlatformName::Yes).is_ok());
-        }
+        unsafe { dr.update(&TestNotifyCallback::update_call, &mut notify_callback) };
+        let lib = rx.recv().unwrap().unwrap();
+        assert!(dr.is_paused());
 
-        for i in 0..10 {
-            unsafe {
-                dr.update(&TestNotifyCallback::update_call, &mut notify_callback);
-            }
+        // Changes are kept until resumed
+        modify_test_shared_lib(&dest_path);
+        thread::sleep(Duration::from_millis(500));
+        unsafe { dr.update(&TestNotifyCallback::update_call, &mut notify_callback) };
+        assert!(!notify_callback.after_update_done);
 
-            if i == 2 {
-                fs::copy(&dest_path, &target_path).unwrap();
+        dr.handle().resume();
+        for _ in 0..20 {
+            unsafe { dr.update(&TestNotifyCallback::update_call, &mut notify_callback) };
+            if notify_callback.after_update_done {
+                break;
             }
-
-            thread::sleep(Duration::from_millis(200));
+            thread::sleep(Duration::from_millis(100));
         }
-
-        assert!(notify_callback.update_call_done);
         assert!(notify_callback.after_update_done);
+
+        assert!(dr.handle().remove_library(&lib));
+        unsafe { dr.update(&TestNotifyCallback::update_call, &mut notify_callback) };
+        assert!(dr.libs.is_empty());
+
+        let handle = dr.handle();
+        drop(dr);
+        assert!(!handle.pause());
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_add_shared_update_fail_after() {
         let mut notify_callback = TestNotifyCallback::default();
         let target_path = get_test_shared_lib();
@@ -796,7 +9780,7 @@ mod tests {
 
         dest_path.set_file_name(&test_file);
 
-        DynamicReload::try_copy(&target_path, &dest_path).unwrap();
+        copy::try_copy(&target_path, &dest_path, &CopyRetry::default(), CopyVerify::Hash).unwrap();
 
         // Wait a while before open the file. Not sure why this is needed.
         thread::sleep(Duration::from_millis(2000));
@@ -826,7 +9810,7 @@ mod tests {
 
     #[test]
     fn test_lib_equals_true() {
-        let mut dr = DynamicReload::new(None, None, Search::Default, Duration::from_secs(2));
+        let mut dr = DynamicReload::new(None, None, Search::Backwards, Duration::from_secs(2));
         let lib = unsafe { dr.add_library("test_shared", PlatformName::Yes).unwrap() };
         let lib2 = lib.clone();
         assert!(lib == lib2);
@@ -847,7 +9831,7 @@ mod tests {
 
         dest_path.set_file_name(&test_file);
 
-        let _ = DynamicReload::try_copy(&target_path, &dest_path);
+        let _ = copy::try_copy(&target_path, &dest_path, &CopyRetry::default(), CopyVerify::Hash);
         thread::sleep(Duration::from_millis(100));
 
         let lib0 = unsafe { dr.add_library(&test_file, PlatformName::No).unwrap() };
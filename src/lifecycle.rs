@@ -0,0 +1,45 @@
+use crate::Lib;
+
+/// Called right before a library is unloaded by a reload, after ```UpdateState::Before```.
+pub const BEFORE_RELOAD_SYMBOL: &str = "dr_before_reload";
+/// Called each time a library has been loaded, before ```UpdateState::After```.
+pub const AFTER_LOAD_SYMBOL: &str = "dr_after_load";
+
+/// Functions a library can export that are called around reloads, see
+/// [set_lifecycle_symbols](struct.DynamicReload.html#method.set_lifecycle_symbols).
+///
+/// Both are ```extern "C" fn()``` and optional, a library that doesn't export them is loaded as
+/// usual. Plugins use them to flush caches and stop their threads before the code goes away
+/// and to register callbacks again in the new version. The default uses
+/// [BEFORE_RELOAD_SYMBOL] and [AFTER_LOAD_SYMBOL], ```None``` turns the call off.
+#[derive(Clone, Debug)]
+pub struct LifecycleSymbols {
+    /// Called before the library is unloaded by a reload.
+    pub before_reload: Option<String>,
+    /// Called after the library has been loaded, the first time as well as on reloads.
+    pub after_load: Option<String>,
+}
+
+impl Default for LifecycleSymbols {
+    fn default() -> LifecycleSymbols {
+        LifecycleSymbols {
+            before_reload: Some(BEFORE_RELOAD_SYMBOL.to_owned()),
+            after_load: Some(AFTER_LOAD_SYMBOL.to_owned()),
+        }
+    }
+}
+
+// Calls the symbol if the library exports it
+pub(crate) fn call(lib: &Lib, name: Option<&str>) {
+    let name = match name {
+        Some(name) => name,
+        None => return,
+    };
+
+    // Safety: the signature is part of the convention and loading the library is already unsafe
+    unsafe {
+        if let Ok(f) = lib.lib.get::<unsafe extern "C" fn()>(name.as_bytes()) {
+            f();
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{DynamicReload, Lib, PlatformName, PluginEvent, Result, UpdateState};
+use std::{path::PathBuf, sync::Arc};
+
+type Create<P> = dyn Fn(&Arc<Lib>) -> Result<P>;
+
+/// Keeps track of plugin instances created from reloadable libraries.
+///
+/// Each plugin is added under a name and an instance of ```P``` is created from its library with
+/// the function given to [new](PluginManager::new). When the library is reloaded the instance is
+/// dropped before the old version is unloaded and created again from the new version, so ```P```
+/// may hold symbols or objects from the library as long as they are dropped with it.
+///
+/// ```ignore
+/// let mut plugins = PluginManager::new(reload, |lib| {
+///     let create: Symbol<fn() -> Box<dyn Plugin>> = unsafe { lib.lib.get(b"create_plugin\0") }
+///         .map_err(Error::Load)?;
+///     Ok(create())
+/// });
+///
+/// unsafe { plugins.add("render", PlatformName::Yes)? };
+///
+/// loop {
+///     unsafe { plugins.update() };
+///     for (_, plugin) in plugins.iter_mut() {
+///         plugin.run();
+///     }
+/// }
+/// ```
+pub struct PluginManager<P> {
+    reload: DynamicReload,
+    create: Box<Create<P>>,
+    entries: Vec<Entry<P>>,
+}
+
+struct Entry<P> {
+    name: String,
+    path: PathBuf,
+    // Declared before lib so the instance is dropped first
+    plugin: Option<P>,
+    lib: Option<Arc<Lib>>,
+}
+
+// Passed through DynamicReload::update to swap the instances
+struct Swap<'a, P> {
+    entries: &'a mut Vec<Entry<P>>,
+    create: &'a Create<P>,
+    before: Option<PathBuf>,
+    events: Vec<PluginEvent>,
+}
+
+impl<'a, P> Swap<'a, P> {
+    fn update_call(&mut self, state: UpdateState, lib: Option<&Arc<Lib>>) {
+        let path = lib.map(|l| l.source_path().to_path_buf());
+        let entry = path
+            .as_ref()
+            .and_then(|path| self.entries.iter_mut().find(|e| e.path == *path));
+
+        match state {
+            UpdateState::Before => {
+                if let Some(entry) = entry {
+                    entry.plugin = None;
+                    entry.lib = None;
+                }
+                self.before = path;
+            }
+            UpdateState::After => {
+                if let (Some(entry), Some(lib)) = (entry, lib) {
+                    match (self.create)(lib) {
+                        Ok(plugin) => {
+                            entry.plugin = Some(plugin);
+                            self.events.push(PluginEvent::Reloaded(entry.name.clone()));
+                        }
+                        Err(e) => self.events.push(PluginEvent::Failed(entry.name.clone(), e)),
+                    }
+                    entry.lib = Some(lib.clone());
+                }
+            }
+            UpdateState::ReloadFailed(e) => {
+                // Before isn't called when the new version is loaded first
+                let before = self.before.take().or(path);
+                let entry = self
+                    .entries
+                    .iter()
+                    .find(|entry| Some(&entry.path) == before.as_ref());
+
+                if let Some(entry) = entry {
+                    self.events.push(PluginEvent::Failed(entry.name.clone(), e));
+                }
+            }
+            UpdateState::Removed => {
+                if let Some(entry) = entry {
+                    entry.plugin = None;
+                    entry.lib = None;
+                    self.events.push(PluginEvent::Removed(entry.name.clone()));
+                }
+            }
+            // The failure has been reported, keep running the previous version
+            UpdateState::RolledBack => {
+                if let (Some(entry), Some(lib)) = (entry, lib) {
+                    match (self.create)(lib) {
+                        Ok(plugin) => entry.plugin = Some(plugin),
+                        Err(e) => self.events.push(PluginEvent::Failed(entry.name.clone(), e)),
+                    }
+                    entry.lib = Some(lib.clone());
+                }
+            }
+            UpdateState::HealthCheckFailed(e) => {
+                let before = self.before.take().or(path);
+                let entry = self
+                    .entries
+                    .iter_mut()
+                    .find(|entry| Some(&entry.path) == before.as_ref());
+
+                if let Some(entry) = entry {
+                    // The previous version is used again, the plugin is gone if Before was called
+                    if let (None, Some(lib)) = (entry.plugin.as_ref(), lib) {
+                        if let Ok(plugin) = (self.create)(lib) {
+                            entry.plugin = Some(plugin);
+                            entry.lib = Some(lib.clone());
+                        }
+                    }
+                    self.events.push(PluginEvent::Failed(entry.name.clone(), e));
+                }
+            }
+            // Only plugins added by name are managed
+            UpdateState::Added => (),
+            // The failures have already been reported
+            UpdateState::Blacklisted(_) => (),
+            // Instances are dropped with their library so these are held by the application
+            UpdateState::StillReferenced(_) => (),
+            // The new version is reported once it has been reloaded
+            UpdateState::BuildStarted
+            | UpdateState::BuildFinished(_)
+            | UpdateState::BuildFailed(_) => (),
+        }
+    }
+}
+
+impl<P> PluginManager<P> {
+    /// Takes over ```reload``` to manage the plugins. ```create``` makes an instance of the
+    /// plugin from a newly loaded library.
+    pub fn new<F>(reload: DynamicReload, create: F) -> PluginManager<P>
+    where
+        F: Fn(&Arc<Lib>) -> Result<P> + 'static,
+    {
+        PluginManager {
+            reload,
+            create: Box::new(create),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Loads a library, see [add_library](struct.DynamicReload.html#method.add_library), and
+    /// creates the plugin from it. A plugin already added with the same name is replaced.
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library).
+    pub unsafe fn add(&mut self, name: &str, name_format: PlatformName) -> Result<&mut P> {
+        self.remove(name);
+
+        let lib = self.reload.add_library(name, name_format)?;
+        let plugin = match (self.create)(&lib) {
+            Ok(plugin) => plugin,
+            Err(e) => {
+                self.reload.remove_library(&lib);
+                return Err(e);
+            }
+        };
+
+        self.entries.push(Entry {
+            name: name.to_owned(),
+            path: lib.source_path().to_path_buf(),
+            plugin: Some(plugin),
+            lib: Some(lib),
+        });
+
+        Ok(self
+            .entries
+            .last_mut()
+            .and_then(|e| e.plugin.as_mut())
+            .unwrap())
+    }
+
+    /// Drops the plugin and stops tracking its library. Returns false if there is no plugin
+    /// with the name.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let index = match self.entries.iter().position(|e| e.name == name) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let entry = self.entries.remove(index);
+        if let Some(lib) = entry.lib.as_ref() {
+            self.reload.remove_library(lib);
+        }
+
+        true
+    }
+
+    /// Reloads changed libraries and re-creates their plugins. A plugin is missing from
+    /// [get](PluginManager::get) when its library failed to reload, ```create``` failed or the
+    /// library has been deleted, until the next successful reload.
+    ///
+    /// # Safety
+    ///
+    /// See [update](struct.DynamicReload.html#method.update).
+    pub unsafe fn update(&mut self) -> Vec<PluginEvent> {
+        let mut swap = Swap {
+            entries: &mut self.entries,
+            create: self.create.as_ref(),
+            before: None,
+            events: Vec::new(),
+        };
+
+        self.reload.update(&Swap::update_call, &mut swap);
+        swap.events
+    }
+
+    /// The plugin with the given name.
+    pub fn get(&self, name: &str) -> Option<&P> {
+        self.entries
+            .iter()
+            .find(|e| e.name == name)
+            .and_then(|e| e.plugin.as_ref())
+    }
+
+    /// The plugin with the given name.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut P> {
+        self.entries
+            .iter_mut()
+            .find(|e| e.name == name)
+            .and_then(|e| e.plugin.as_mut())
+    }
+
+    /// The library the plugin with the given name is currently created from.
+    pub fn lib(&self, name: &str) -> Option<&Arc<Lib>> {
+        self.entries
+            .iter()
+            .find(|e| e.name == name)
+            .and_then(|e| e.lib.as_ref())
+    }
+
+    /// All plugins that are currently available, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &P)> {
+        self.entries
+            .iter()
+            .filter_map(|e| Some((e.name.as_str(), e.plugin.as_ref()?)))
+    }
+
+    /// All plugins that are currently available, in the order they were added.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut P)> {
+        self.entries
+            .iter_mut()
+            .filter_map(|e| Some((e.name.as_str(), e.plugin.as_mut()?)))
+    }
+
+    /// The underlying [DynamicReload] for configuration.
+    pub fn reload_mut(&mut self) -> &mut DynamicReload {
+        &mut self.reload
+    }
+}
@@ -0,0 +1,113 @@
+use libloading::Library;
+use std::{collections::BTreeMap, ffi::CStr, fs, os::raw::c_char, path::Path};
+
+/// String key/value pairs describing a library (author, description, capability flags, etc).
+pub type Metadata = BTreeMap<String, String>;
+
+/// Extension of the metadata manifest that can be placed next to a library.
+///
+/// For ```libfoo.so``` the manifest is ```libfoo.so.meta```. Each line is a ```key = value```
+/// pair, empty lines and lines starting with ```#``` are ignored.
+///
+/// ```ignore
+/// # libfoo.so.meta
+/// author = Jane Doe
+/// description = Renders the foo
+/// group = render
+/// ```
+pub const METADATA_EXTENSION: &str = "meta";
+
+/// Symbol a library can export to embed its metadata, with the signature
+/// ```extern "C" fn() -> *const c_char```. It returns a NUL terminated string in the same format
+/// as the manifest, usually generated with [plugin_metadata]. Values in the manifest take
+/// precedence over the embedded ones.
+pub const METADATA_SYMBOL: &str = "dr_metadata";
+
+/// Metadata key for the ABI a library was built for, compared with the tag set with
+/// [set_abi_tag](struct.DynamicReload.html#method.set_abi_tag).
+pub const ABI_KEY: &str = "abi";
+
+/// Embeds metadata in a plugin by exporting ```dr_metadata```, see [METADATA_SYMBOL]. Values
+/// have to be literals (or macros expanding to literals such as ```env!```) and fail to compile
+/// if they contain a ```=```, a newline or a NUL as they're pasted into the text as is.
+///
+/// ```ignore
+/// dynamic_reload::plugin_metadata! {
+///     name = "foo",
+///     version = env!("CARGO_PKG_VERSION"),
+///     abi = "foo-api-2",
+/// }
+/// ```
+#[macro_export]
+macro_rules! plugin_metadata {
+    ($($key:ident = $value:expr),* $(,)?) => {
+        $(
+            const _: () = assert!(
+                $crate::valid_metadata_value(concat!($value)),
+                concat!("invalid value for metadata key ", stringify!($key)),
+            );
+        )*
+
+        #[no_mangle]
+        pub extern "C" fn dr_metadata() -> *const ::std::os::raw::c_char {
+            concat!($(stringify!($key), " = ", $value, "\n",)* "\0").as_ptr()
+                as *const ::std::os::raw::c_char
+        }
+    };
+}
+
+/// Checks a value for [plugin_metadata] at compile time.
+#[doc(hidden)]
+pub const fn valid_metadata_value(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if matches!(bytes[i], b'=' | b'\n' | b'\r' | b'\0') {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+/// Reads the metadata embedded with ```dr_metadata```. A library not exporting it gives empty
+/// metadata.
+pub(crate) unsafe fn read_embedded(lib: &Library) -> Metadata {
+    let text = match lib.get::<unsafe extern "C" fn() -> *const c_char>(METADATA_SYMBOL.as_bytes())
+    {
+        Ok(embedded) => embedded(),
+        Err(_) => return Metadata::new(),
+    };
+
+    if text.is_null() {
+        return Metadata::new();
+    }
+
+    parse(&CStr::from_ptr(text).to_string_lossy())
+}
+
+/// Reads the manifest belonging to the library at ```lib_path```. A missing manifest gives
+/// empty metadata.
+pub(crate) fn read_manifest(lib_path: &Path) -> Metadata {
+    let mut manifest = lib_path.as_os_str().to_owned();
+    manifest.push(".");
+    manifest.push(METADATA_EXTENSION);
+
+    match fs::read_to_string(manifest) {
+        Ok(text) => parse(&text),
+        Err(_) => Metadata::new(),
+    }
+}
+
+fn parse(text: &str) -> Metadata {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            Some((key.trim().to_owned(), value.trim().to_owned()))
+        })
+        .collect()
+}
@@ -0,0 +1,28 @@
+// Prefix and suffix of dynamic libraries built for the target triple
+pub(crate) fn target_affixes(triple: &str) -> (&'static str, &'static str) {
+    if triple.contains("windows") {
+        ("", ".dll")
+    } else if triple.contains("apple") {
+        ("lib", ".dylib")
+    } else if triple.starts_with("wasm") {
+        ("", ".wasm")
+    } else {
+        ("lib", ".so")
+    }
+}
+
+// The name without the version of a versioned shared object ("libfoo.so.1.2.3" -> "libfoo.so"),
+// other names are returned as is
+pub(crate) fn strip_version(name: &str) -> &str {
+    if let Some(index) = name.find(".so.") {
+        let version = &name[index + 4..];
+        if version
+            .split('.')
+            .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return &name[..index + 3];
+        }
+    }
+
+    name
+}
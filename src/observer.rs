@@ -0,0 +1,84 @@
+use crate::{Error, Lib, UpdateState};
+use std::sync::{Arc, Mutex};
+
+/// Gets notified about reloads next to the callback passed to
+/// [update](struct.DynamicReload.html#method.update), see
+/// [add_observer](struct.DynamicReload.html#method.add_observer).
+///
+/// Allows parts of an application that care about reloads for different reasons (logging,
+/// UI, plugin management) to be registered on their own instead of all going through one
+/// callback. Observers are called before the callback, in the order they were added.
+///
+/// ```ignore
+/// struct Logger;
+///
+/// impl ReloadObserver for Logger {
+///     fn after(&mut self, lib: &Arc<Lib>) {
+///         println!("Reloaded {:?}", lib.source_path());
+///     }
+///
+///     fn failed(&mut self, error: &Error, _lib: Option<&Arc<Lib>>) {
+///         println!("Reload failed: {}", error);
+///     }
+/// }
+///
+/// dr.add_observer(Arc::new(Mutex::new(Logger)));
+/// ```
+pub trait ReloadObserver: Send {
+    /// Called with each state the callback is called with. The default calls
+    /// [before](ReloadObserver::before), [after](ReloadObserver::after) and
+    /// [failed](ReloadObserver::failed) and ignores the other states.
+    fn notify(&mut self, state: &UpdateState, lib: Option<&Arc<Lib>>) {
+        match (state, lib) {
+            (UpdateState::Before, Some(lib)) => self.before(lib),
+            (UpdateState::After, Some(lib)) | (UpdateState::RolledBack, Some(lib)) => {
+                self.after(lib)
+            }
+            (UpdateState::ReloadFailed(e), lib) | (UpdateState::HealthCheckFailed(e), lib) => {
+                self.failed(e, lib)
+            }
+            _ => (),
+        }
+    }
+
+    /// The library is about to be unloaded for a reload.
+    fn before(&mut self, lib: &Arc<Lib>) {
+        let _ = lib;
+    }
+
+    /// The library has been loaded again, also after a rollback.
+    fn after(&mut self, lib: &Arc<Lib>) {
+        let _ = lib;
+    }
+
+    /// A reload or health check failed, ```lib``` is the version that is used instead.
+    fn failed(&mut self, error: &Error, lib: Option<&Arc<Lib>>) {
+        let _ = (error, lib);
+    }
+}
+
+// Observers added with DynamicReload::add_observer
+#[derive(Default)]
+pub(crate) struct Observers(Vec<Arc<Mutex<dyn ReloadObserver>>>);
+
+impl Observers {
+    pub(crate) fn add(&mut self, observer: Arc<Mutex<dyn ReloadObserver>>) {
+        self.0.push(observer);
+    }
+
+    pub(crate) fn remove<O: ReloadObserver + ?Sized>(&mut self, observer: &Arc<Mutex<O>>) -> bool {
+        let ptr = Arc::as_ptr(observer) as *const ();
+        let len = self.0.len();
+        self.0.retain(|o| Arc::as_ptr(o) as *const () != ptr);
+        self.0.len() != len
+    }
+
+    pub(crate) fn notify(&self, state: &UpdateState, lib: Option<&Arc<Lib>>) {
+        for observer in &self.0 {
+            observer
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .notify(state, lib);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{init::OpenFlags, PlatformName};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// How a single library is loaded and reloaded, see
+/// [add_library_with_options](struct.DynamicReload.html#method.add_library_with_options).
+///
+/// The default formats the name for the platform, shadow copies the library (if DynamicReload
+/// has a shadow directory), watches it and reloads it with the debounce duration of
+/// DynamicReload. On Unix symbols are resolved lazily and kept local to the library.
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    /// How the name of the library is formatted.
+    pub platform_name: PlatformName,
+    /// Load a copy of the library from the shadow directory. Without a copy the file is in use
+    /// so the library can't be reloaded and isn't watched.
+    pub shadow: bool,
+    /// Reload the library when it changes.
+    pub watch: bool,
+    /// Reload once no changes have been reported for this long instead of right away. For
+    /// libraries that are written in several steps that the debouncer of DynamicReload doesn't
+    /// cover.
+    pub debounce: Option<Duration>,
+    /// Make the symbols available to libraries loaded after it, see
+    /// [add_global_library](struct.DynamicReload.html#method.add_global_library).
+    pub global: bool,
+    /// Resolve all symbols when the library is loaded (```RTLD_NOW```) so missing symbols fail
+    /// the load instead of the first call using them. Windows always does this.
+    pub bind_now: bool,
+    /// Flags passed as they are to ```dlopen``` (```RTLD_*```) or ```LoadLibraryExW```
+    /// (```LOAD_LIBRARY_*```), replacing the ones from ```global```, ```bind_now``` and
+    /// ```original_dir_dependencies```.
+    pub raw_flags: Option<u32>,
+    /// Windows only. Look for the DLLs a shadow copied library depends on in the directory of
+    /// the original file instead of next to the copy. The directory is added with
+    /// ```AddDllDirectory``` and the library is loaded with ```LOAD_LIBRARY_SEARCH_DEFAULT_DIRS```
+    /// which means that the directories in ```PATH``` are no longer searched.
+    pub original_dir_dependencies: bool,
+    /// Libraries the library links against that are copied to the shadow directory next to the
+    /// copy of the library, for dependencies found relative to the library (```$ORIGIN```,
+    /// ```@loader_path``` or next to the DLL). Relative paths are relative to the directory of
+    /// the library. The copies keep their file names so each load of the library gets a
+    /// directory of its own in the shadow directory, removed once that version is unloaded.
+    pub shadow_dependencies: Vec<PathBuf>,
+    /// Symbols the library has to export. A version missing any of them fails to load with
+    /// ```Error::MissingSymbol```. On reload the new version is checked before the old one is
+    /// unloaded (as with ```SwapOrder::LoadFirst```) so a broken build reports ```ReloadFailed```
+    /// and the old version stays in use.
+    pub required_symbols: Vec<String>,
+}
+
+impl LoadOptions {
+    pub(crate) fn open_flags(&self, original_path: Option<&Path>) -> OpenFlags {
+        let dependency_dir = match self.original_dir_dependencies {
+            true => original_path.and_then(Path::parent).map(Path::to_path_buf),
+            false => None,
+        };
+
+        OpenFlags {
+            global: self.global,
+            bind_now: self.bind_now,
+            raw: self.raw_flags,
+            dependency_dir,
+        }
+    }
+}
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions {
+            platform_name: PlatformName::Yes,
+            shadow: true,
+            watch: true,
+            debounce: None,
+            global: false,
+            bind_now: false,
+            raw_flags: None,
+            original_dir_dependencies: false,
+            shadow_dependencies: Vec::new(),
+            required_symbols: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{winpath, Error, Result};
+use libloading::Library;
+use std::{
+    env,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Environment variable used to tell the helper process which library to load.
+pub const PREFLIGHT_ENV: &str = "DYNAMIC_RELOAD_PREFLIGHT";
+
+/// Test loads a library in a short-lived helper process before it's loaded into the host.
+///
+/// A library with broken constructors or missing dependencies can crash the process that loads
+/// it. With pre-flight enabled the library is first loaded by a helper process and only if that
+/// succeeds it's loaded into the host. Otherwise the load fails with ```Error::Preflight```
+/// which is reported as ```ReloadFailed``` during reloads.
+///
+/// The helper is started with the path of the library in the ```DYNAMIC_RELOAD_PREFLIGHT```
+/// environment variable and as the only argument. An exit code of 0 means the library loaded
+/// fine.
+#[derive(Clone, Debug)]
+pub struct Preflight {
+    /// Program to run. ```None``` runs the current executable which then has to call
+    /// [preflight_main] at the start of ```main```.
+    pub program: Option<PathBuf>,
+    /// The helper is killed and the library rejected if it doesn't finish within this time.
+    pub timeout: Duration,
+}
+
+impl Preflight {
+    /// Runs the current executable as the helper. See [preflight_main].
+    pub fn current_exe() -> Preflight {
+        Preflight {
+            program: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Runs the given program as the helper.
+    pub fn program<P: Into<PathBuf>>(program: P) -> Preflight {
+        Preflight {
+            program: Some(program.into()),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub(crate) fn run(&self, path: &Path) -> Result<()> {
+        let program = match self.program.as_ref() {
+            Some(p) => p.clone(),
+            None => env::current_exe().map_err(|e| Self::error(path, e.to_string()))?,
+        };
+
+        let mut child = Command::new(program)
+            .arg(path)
+            .env(PREFLIGHT_ENV, path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Self::error(path, e.to_string()))?;
+
+        // Read while waiting, a helper that fills the pipe would otherwise block until killed
+        let stderr = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut output = String::new();
+                let _ = stderr.read_to_string(&mut output);
+                output
+            })
+        });
+
+        let start = Instant::now();
+
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => return Ok(()),
+                Ok(Some(status)) => {
+                    let output = stderr
+                        .and_then(|reader| reader.join().ok())
+                        .unwrap_or_default();
+                    return Err(Self::error(path, format!("{} {}", status, output.trim())));
+                }
+                Ok(None) if start.elapsed() > self.timeout => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(Self::error(path, "timed out".to_string()));
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(e) => return Err(Self::error(path, e.to_string())),
+            }
+        }
+    }
+
+    fn error(path: &Path, reason: String) -> Error {
+        Error::Preflight(path.to_path_buf(), reason)
+    }
+}
+
+///
+/// Entry point for the pre-flight helper when using [Preflight::current_exe].
+///
+/// Call this first thing in ```main```. When the process has been started as a helper this
+/// loads the requested library and exits the process, otherwise it returns right away.
+///
+/// ```ignore
+/// fn main() {
+///     dynamic_reload::preflight_main();
+///     // ...
+/// }
+/// ```
+///
+pub fn preflight_main() {
+    if let Some(path) = env::var_os(PREFLIGHT_ENV) {
+        // Safety: this process only exists to find out if loading the library is safe.
+        match unsafe { Library::new(winpath::extended(Path::new(&path))) } {
+            Ok(_) => process::exit(0),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1)
+            }
+        }
+    }
+}
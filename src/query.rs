@@ -0,0 +1,140 @@
+use crate::userdata::Userdata;
+use crate::{FileChange, FileIdentity, Lib, Metadata};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    vec,
+};
+
+/// Metadata key used to put a library in one or more groups, separated by ```,```.
+pub const GROUP_KEY: &str = "group";
+
+/// A library that failed to reload and is no longer loaded.
+pub(crate) struct FailedLib {
+    pub(crate) path: PathBuf,
+    pub(crate) metadata: Metadata,
+    pub(crate) userdata: Userdata,
+    // Generation of the last version that was loaded
+    pub(crate) generation: Option<u64>,
+    pub(crate) error: String,
+}
+
+/// A library managed by [DynamicReload](struct.DynamicReload.html) as returned by
+/// [iter_libs](struct.DynamicReload.html#method.iter_libs).
+#[derive(Clone)]
+pub struct LibHandle {
+    /// The loaded library. ```None``` if the last reload failed.
+    pub lib: Option<Arc<Lib>>,
+    /// The file the library is loaded from (the original location when using a shadow directory).
+    pub path: PathBuf,
+    /// All metadata of the library.
+    pub metadata: Metadata,
+    /// True if the file on disk has changed since the library was loaded.
+    pub stale: bool,
+    /// Error of the last reload if it failed.
+    pub error: Option<String>,
+}
+
+impl LibHandle {
+    pub(crate) fn loaded(lib: &Arc<Lib>) -> LibHandle {
+        let path = lib.source_path().to_path_buf();
+
+        LibHandle {
+            stale: is_stale(&path, lib.file_identity),
+            metadata: lib.all_metadata(),
+            lib: Some(lib.clone()),
+            path,
+            error: None,
+        }
+    }
+
+    pub(crate) fn failed(failed: &FailedLib) -> LibHandle {
+        LibHandle {
+            lib: None,
+            path: failed.path.clone(),
+            metadata: failed.metadata.clone(),
+            stale: false,
+            error: Some(failed.error.clone()),
+        }
+    }
+
+    /// Returns true if the last reload of the library failed.
+    pub fn is_failed(&self) -> bool {
+        self.lib.is_none()
+    }
+
+    /// Returns true if the library is in ```group``` according to its ```group``` metadata.
+    pub fn in_group(&self, group: &str) -> bool {
+        self.metadata
+            .get(GROUP_KEY)
+            .is_some_and(|groups| has_group(groups, group))
+    }
+}
+
+// Checks a comma separated list of groups
+pub(crate) fn has_group(groups: &str, group: &str) -> bool {
+    groups.split(',').any(|g| g.trim() == group)
+}
+
+// A file that has been removed is stale as well as the loaded version is all that's left
+fn is_stale(path: &Path, loaded: Option<FileIdentity>) -> bool {
+    match (loaded, FileIdentity::from_path(path)) {
+        (Some(loaded), Some(current)) => {
+            loaded != current && loaded.classify(&current) != FileChange::Touched
+        }
+        (_, None) => true,
+        (None, Some(_)) => false,
+    }
+}
+
+/// Iterator over the libraries managed by [DynamicReload](struct.DynamicReload.html) with
+/// helpers to filter them.
+///
+/// ```ignore
+/// for handle in dr.iter_libs().in_group("render").filter_stale() {
+///     println!("{:?} has changed on disk", handle.path);
+/// }
+/// ```
+pub struct LibIter {
+    handles: vec::IntoIter<LibHandle>,
+}
+
+impl LibIter {
+    pub(crate) fn new(handles: Vec<LibHandle>) -> LibIter {
+        LibIter {
+            handles: handles.into_iter(),
+        }
+    }
+
+    fn retain<F: Fn(&LibHandle) -> bool>(self, f: F) -> LibIter {
+        LibIter::new(self.handles.filter(f).collect())
+    }
+
+    /// Only libraries that have changed on disk since they were loaded.
+    pub fn filter_stale(self) -> LibIter {
+        self.retain(|h| h.stale)
+    }
+
+    /// Only libraries in the given group, see [LibHandle::in_group].
+    pub fn in_group(self, group: &str) -> LibIter {
+        self.retain(|h| h.in_group(group))
+    }
+
+    /// Only libraries that failed to reload.
+    pub fn failed(self) -> LibIter {
+        self.retain(|h| h.is_failed())
+    }
+
+    /// Only libraries that are currently loaded.
+    pub fn loaded(self) -> LibIter {
+        self.retain(|h| !h.is_failed())
+    }
+}
+
+impl Iterator for LibIter {
+    type Item = LibHandle;
+
+    fn next(&mut self) -> Option<LibHandle> {
+        self.handles.next()
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{cargo, Error, Profile};
+use std::{
+    path::{Path, PathBuf},
+    process::Output,
+    thread::{self, JoinHandle},
+};
+
+// The sources of a library that is rebuilt when they change
+struct Source {
+    dir: PathBuf,
+    manifest: PathBuf,
+    lib: PathBuf,
+    profile: Profile,
+    build: Option<JoinHandle<std::io::Result<Output>>>,
+    // Changed again while building
+    dirty: bool,
+}
+
+// Runs cargo build in the background for libraries added with DynamicReload::watch_sources.
+// The reload is left to the watcher of the library, which sees the artifact change.
+#[derive(Default)]
+pub(crate) struct Rebuilds {
+    sources: Vec<Source>,
+}
+
+impl Rebuilds {
+    pub(crate) fn add(&mut self, dir: PathBuf, manifest: PathBuf, lib: PathBuf, profile: Profile) {
+        self.sources.retain(|s| s.lib != lib);
+        self.sources.push(Source {
+            dir,
+            manifest,
+            lib,
+            profile,
+            build: None,
+            dirty: false,
+        });
+    }
+
+    pub(crate) fn remove(&mut self, lib: &Path) -> bool {
+        let len = self.sources.len();
+        self.sources.retain(|s| s.lib != lib);
+        self.sources.len() != len
+    }
+
+    // Takes out the changed source files and starts their builds, returns the libraries
+    // whose build was started
+    pub(crate) fn changed(&mut self, paths: &mut Vec<PathBuf>) -> Vec<PathBuf> {
+        if self.sources.is_empty() {
+            return Vec::new();
+        }
+
+        let mut started = Vec::new();
+        paths.retain(|path| {
+            let sources = self.sources.iter_mut().filter(|s| path.starts_with(&s.dir));
+            let mut matched = false;
+
+            for source in sources {
+                matched = true;
+                if source.build.is_some() {
+                    source.dirty = true;
+                } else if !started.contains(&source.lib) {
+                    source.build = Some(build(&source.manifest, &source.profile));
+                    started.push(source.lib.clone());
+                }
+            }
+
+            !matched
+        });
+
+        started
+    }
+
+    // Builds that are done with the cargo output or the error, builds of sources that changed
+    // while building are started again and returned in started
+    pub(crate) fn finished(
+        &mut self,
+        started: &mut Vec<PathBuf>,
+    ) -> Vec<(PathBuf, Result<String, Error>)> {
+        let mut finished = Vec::new();
+
+        for source in &mut self.sources {
+            if !source.build.as_ref().is_some_and(|b| b.is_finished()) {
+                continue;
+            }
+
+            let result = match source.build.take().map(JoinHandle::join) {
+                Some(Ok(Ok(output))) => {
+                    let text = String::from_utf8_lossy(&output.stderr).into_owned();
+                    if output.status.success() {
+                        Ok(text)
+                    } else {
+                        Err(Error::Cargo(text))
+                    }
+                }
+                Some(Ok(Err(e))) => Err(Error::Cargo(format!("unable to run cargo build: {}", e))),
+                _ => Err(Error::Cargo("cargo build panicked".to_owned())),
+            };
+            finished.push((source.lib.clone(), result));
+
+            if std::mem::take(&mut source.dirty) {
+                source.build = Some(build(&source.manifest, &source.profile));
+                started.push(source.lib.clone());
+            }
+        }
+
+        finished
+    }
+}
+
+fn build(manifest: &Path, profile: &Profile) -> JoinHandle<std::io::Result<Output>> {
+    let mut command = cargo::build_command(manifest, profile);
+    thread::spawn(move || command.output())
+}
@@ -0,0 +1,125 @@
+use crate::{DynamicReload, Lib};
+use std::{
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// The loaded libraries in the order they were added, indexed by the path of their file and
+// its file name so changes can be matched without going through all of them. Derefs to the
+// list of libraries, changes have to go through push, replace and swap_remove to keep the
+// indices up to date.
+#[derive(Clone, Default)]
+pub(crate) struct Registry {
+    libs: Vec<Arc<Lib>>,
+    by_path: HashMap<PathBuf, Vec<usize>>,
+    by_name: HashMap<OsString, Vec<usize>>,
+}
+
+impl Registry {
+    pub(crate) fn push(&mut self, lib: Arc<Lib>) {
+        self.libs.push(lib);
+        self.index(self.libs.len() - 1);
+    }
+
+    // Returns the library that was at the index
+    pub(crate) fn replace(&mut self, index: usize, lib: Arc<Lib>) -> Arc<Lib> {
+        self.unindex(index);
+        let old = std::mem::replace(&mut self.libs[index], lib);
+        self.index(index);
+        old
+    }
+
+    pub(crate) fn swap_remove(&mut self, index: usize) -> Arc<Lib> {
+        let last = self.libs.len() - 1;
+        self.unindex(index);
+        if index != last {
+            self.unindex(last);
+        }
+
+        let lib = self.libs.swap_remove(index);
+        if index != last {
+            self.index(index);
+        }
+        lib
+    }
+
+    // Index of the library with the source path
+    pub(crate) fn position(&self, path: &Path) -> Option<usize> {
+        self.by_path.get(path)?.iter().min().copied()
+    }
+
+    pub(crate) fn get(&self, path: &Path) -> Option<&Arc<Lib>> {
+        self.position(path).map(|index| &self.libs[index])
+    }
+
+    // Same as position but the path doesn't have to be spelled the same way
+    pub(crate) fn find(&self, path: &Path) -> Option<usize> {
+        if let Some(index) = self.position(path) {
+            return Some(index);
+        }
+
+        let canonical = DynamicReload::canonical_path(path);
+        self.named(path.file_name()?)
+            .into_iter()
+            .find(|&i| DynamicReload::canonical_path(self.libs[i].source_path()) == canonical)
+    }
+
+    // Indices of the libraries with the file name, in the order they are in the list
+    pub(crate) fn named(&self, name: &OsStr) -> Vec<usize> {
+        let mut indices = self.by_name.get(name).cloned().unwrap_or_default();
+        indices.sort_unstable();
+        indices
+    }
+
+    fn index(&mut self, index: usize) {
+        let path = self.libs[index].source_path();
+        self.by_path
+            .entry(path.to_path_buf())
+            .or_default()
+            .push(index);
+        if let Some(name) = path.file_name() {
+            self.by_name.entry(name.to_owned()).or_default().push(index);
+        }
+    }
+
+    fn unindex(&mut self, index: usize) {
+        let path = self.libs[index].source_path();
+        remove_index(&mut self.by_path, path, index);
+        if let Some(name) = path.file_name() {
+            remove_index(&mut self.by_name, name, index);
+        }
+    }
+}
+
+fn remove_index<K, Q>(map: &mut HashMap<K, Vec<usize>>, key: &Q, index: usize)
+where
+    K: std::borrow::Borrow<Q> + std::hash::Hash + Eq,
+    Q: std::hash::Hash + Eq + ?Sized,
+{
+    if let Some(indices) = map.get_mut(key) {
+        indices.retain(|&i| i != index);
+        if indices.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+impl IntoIterator for Registry {
+    type Item = Arc<Lib>;
+    type IntoIter = std::vec::IntoIter<Arc<Lib>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.libs.into_iter()
+    }
+}
+
+impl Deref for Registry {
+    type Target = [Arc<Lib>];
+
+    fn deref(&self) -> &[Arc<Lib>] {
+        &self.libs
+    }
+}
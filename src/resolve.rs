@@ -0,0 +1,121 @@
+use crate::winpath;
+use std::{
+    cmp::Reverse,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Which file is loaded when a library name matches files in several of the searched locations,
+/// see [set_selection_policy](struct.DynamicReload.html#method.set_selection_policy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelectionPolicy {
+    /// The first file found in the search order.
+    #[default]
+    FirstMatch,
+    /// The file that was modified last, the first one found when they were modified at the
+    /// same time.
+    NewestModified,
+    /// Fail with ```Error::Ambiguous``` when more than one file matches.
+    ErrorOnAmbiguity,
+}
+
+/// Why a path that was checked while searching for a library wasn't used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rejected {
+    /// Nothing exists at the path.
+    Missing,
+    /// The path exists but isn't a file, usually a directory with the name of the library.
+    NotAFile,
+    /// The path couldn't be checked, for example because of missing permissions.
+    Unreadable(String),
+}
+
+/// A path that was checked while searching for a library.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    /// The path that was checked.
+    pub path: PathBuf,
+    /// Why the path wasn't used, ```None``` for the path that was found.
+    pub rejected: Option<Rejected>,
+}
+
+/// Result of [resolve_library](struct.DynamicReload.html#method.resolve_library).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolveReport {
+    /// File name that was searched for, formatted according to the
+    /// [PlatformName](enum.PlatformName.html).
+    pub lib_name: String,
+    /// The path that would be loaded, ```None``` if the library wasn't found.
+    pub path: Option<PathBuf>,
+    /// All paths that were checked in the order they were checked, the last one is the path
+    /// that was found. With a [SelectionPolicy] other than ```FirstMatch``` all locations are
+    /// checked and every file that matches is reported without a rejection. Empty with
+    /// ```Search::Custom``` as the strategy doesn't report them.
+    pub candidates: Vec<Candidate>,
+}
+
+// The paths checked while searching for a library. With all set the search doesn't stop at the
+// first file that exists so the files can be compared afterwards.
+pub(crate) struct Searched {
+    pub(crate) candidates: Vec<Candidate>,
+    all: bool,
+}
+
+impl Searched {
+    pub(crate) fn new(all: bool) -> Searched {
+        Searched {
+            candidates: Vec::new(),
+            all,
+        }
+    }
+
+    // Returns the path if the search stops there
+    pub(crate) fn check(&mut self, path: PathBuf) -> Option<PathBuf> {
+        self.check_as(path.clone(), path)
+    }
+
+    // Same as check but the path is reported as another one
+    pub(crate) fn check_as(&mut self, path: PathBuf, reported: PathBuf) -> Option<PathBuf> {
+        let rejected = check(&path);
+        let file = (rejected.is_none() && !self.all).then_some(path);
+        self.candidates.push(Candidate {
+            path: reported,
+            rejected,
+        });
+        file
+    }
+
+    // The canonical paths of the files that matched in the order they were found, without the
+    // ones found again through another path
+    pub(crate) fn found(&self) -> Vec<PathBuf> {
+        let mut found: Vec<PathBuf> = Vec::new();
+
+        for candidate in self.candidates.iter().filter(|c| c.rejected.is_none()) {
+            let canonical =
+                winpath::canonicalize(&candidate.path).unwrap_or_else(|_| candidate.path.clone());
+            if !found.contains(&canonical) {
+                found.push(canonical);
+            }
+        }
+
+        found
+    }
+}
+
+// The file that was modified last, the first one on ties
+pub(crate) fn newest(paths: &[PathBuf]) -> Option<&Path> {
+    paths
+        .iter()
+        .min_by_key(|path| Reverse(fs::metadata(path).and_then(|md| md.modified()).ok()))
+        .map(PathBuf::as_path)
+}
+
+// Checks if the library can be loaded from the path
+pub(crate) fn check(path: &PathBuf) -> Option<Rejected> {
+    match fs::metadata(path) {
+        Ok(md) if md.is_file() => None,
+        Ok(_) => Some(Rejected::NotAFile),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Some(Rejected::Missing),
+        Err(e) => Some(Rejected::Unreadable(e.to_string())),
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Retries failed reloads on later updates, see
+/// [set_reload_retry](struct.DynamicReload.html#method.set_reload_retry).
+///
+/// A library that failed to reload is tried again once ```initial_delay``` has passed, with the
+/// delay doubling for each failure in a row up to ```max_delay```. After ```attempts``` failures
+/// in a row the library is blacklisted and no longer retried, which is reported with
+/// ```UpdateState::Blacklisted```. Changes to the file are still reloaded as usual.
+///
+/// The default blacklists a library after 5 failures, retrying after 500 ms at first and at
+/// most every 8 seconds.
+#[derive(Clone, Debug)]
+pub struct ReloadRetry {
+    /// Number of failures in a row before the library is blacklisted.
+    pub attempts: u32,
+    /// Delay after the first failure.
+    pub initial_delay: Duration,
+    /// Upper limit of the delay between two attempts.
+    pub max_delay: Duration,
+}
+
+impl Default for ReloadRetry {
+    fn default() -> ReloadRetry {
+        ReloadRetry {
+            attempts: 5,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+        }
+    }
+}
+
+impl ReloadRetry {
+    /// Returns the delay before the next attempt after the given number of failures in a row.
+    pub fn delay(&self, failures: u32) -> Duration {
+        self.initial_delay
+            .checked_mul(1 << failures.saturating_sub(1).min(16))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+}
+
+// Failures in a row of a library
+struct Entry {
+    path: PathBuf,
+    failures: u32,
+    next: Instant,
+    reported: bool,
+}
+
+// Keeps track of the libraries that failed to reload when retrying is enabled
+#[derive(Default)]
+pub(crate) struct Retries {
+    pub(crate) policy: Option<ReloadRetry>,
+    entries: Vec<Entry>,
+}
+
+impl Retries {
+    pub(crate) fn failed(&mut self, path: &Path) {
+        let policy = match self.policy.as_ref() {
+            Some(policy) => policy,
+            None => return,
+        };
+
+        let index = match self.entries.iter().position(|e| e.path == path) {
+            Some(index) => index,
+            None => {
+                self.entries.push(Entry {
+                    path: path.to_path_buf(),
+                    failures: 0,
+                    next: Instant::now(),
+                    reported: false,
+                });
+                self.entries.len() - 1
+            }
+        };
+
+        let entry = &mut self.entries[index];
+        entry.failures += 1;
+        entry.next = Instant::now() + policy.delay(entry.failures);
+    }
+
+    // The library has been loaded or removed
+    pub(crate) fn remove(&mut self, path: &Path) {
+        self.entries.retain(|e| e.path != path);
+    }
+
+    // Starts over as if the library had never failed
+    pub(crate) fn reset(&mut self, path: &Path) {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.path == path) {
+            entry.failures = 0;
+            entry.next = Instant::now();
+            entry.reported = false;
+        }
+    }
+
+    pub(crate) fn is_blacklisted(&self, path: &Path) -> bool {
+        self.entries
+            .iter()
+            .any(|e| e.path == path && self.blacklisted(e))
+    }
+
+    // Libraries to retry now
+    pub(crate) fn due(&self) -> Vec<PathBuf> {
+        let now = Instant::now();
+
+        self.entries
+            .iter()
+            .filter(|e| !self.blacklisted(e) && e.next <= now)
+            .map(|e| e.path.clone())
+            .collect()
+    }
+
+    // Libraries that have been blacklisted since the last call
+    pub(crate) fn newly_blacklisted(&mut self) -> Vec<PathBuf> {
+        let attempts = self.attempts();
+        let mut paths = Vec::new();
+
+        for entry in self.entries.iter_mut() {
+            if entry.failures >= attempts && !entry.reported {
+                entry.reported = true;
+                paths.push(entry.path.clone());
+            }
+        }
+
+        paths
+    }
+
+    // True if there are libraries waiting to be retried
+    pub(crate) fn is_waiting(&self) -> bool {
+        self.entries.iter().any(|e| !self.blacklisted(e))
+    }
+
+    fn blacklisted(&self, entry: &Entry) -> bool {
+        entry.failures >= self.attempts()
+    }
+
+    fn attempts(&self) -> u32 {
+        self.policy.as_ref().map_or(u32::MAX, |p| p.attempts)
+    }
+}
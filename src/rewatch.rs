@@ -0,0 +1,55 @@
+use crate::notify::RecursiveMode;
+use crate::FileIdentity;
+use std::path::{Path, PathBuf};
+
+/// Keeps track of the directories that are watched so watches that died can be set up again.
+///
+/// A watch is tied to the directory itself and not its path. When the directory is removed
+/// (```cargo clean```, some build tools and deploy scripts) or replaced the watch is gone and
+/// no more changes would be reported even after the directory is back.
+#[derive(Default)]
+pub(crate) struct WatchedDirs {
+    // Identity of each directory when it was watched, None if it was missing
+    dirs: Vec<(PathBuf, RecursiveMode, Option<FileIdentity>)>,
+}
+
+impl WatchedDirs {
+    pub(crate) fn add(&mut self, dir: &Path, mode: RecursiveMode) {
+        self.remove(dir);
+        self.dirs
+            .push((dir.to_path_buf(), mode, FileIdentity::from_path(dir)));
+    }
+
+    pub(crate) fn remove(&mut self, dir: &Path) {
+        self.dirs.retain(|(d, _, _)| d != dir);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.dirs.clear();
+    }
+
+    /// Returns the directories that have come back or been replaced since they were watched.
+    /// They are expected to be watched again. Directories that are gone are kept until they are
+    /// back.
+    pub(crate) fn lost(&mut self) -> Vec<(PathBuf, RecursiveMode)> {
+        let mut lost = Vec::new();
+
+        for (dir, mode, identity) in self.dirs.iter_mut() {
+            let current = FileIdentity::from_path(dir);
+
+            let replaced = match (identity.as_ref(), current.as_ref()) {
+                (None, Some(_)) => true,
+                (Some(old), Some(new)) => !old.is_same_file(new),
+                _ => false,
+            };
+
+            if replaced {
+                lost.push((dir.clone(), *mode));
+            }
+
+            *identity = current;
+        }
+
+        lost
+    }
+}
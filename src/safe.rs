@@ -0,0 +1,322 @@
+use crate::{DynamicReload, Error, Lib, PlatformName, ReloadRetry, Result, UpdateState};
+use std::{
+    any::Any,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The interface of a plugin, declared with [plugin_api].
+///
+/// Implemented by the macro, which knows the names and types of all functions the plugin has to
+/// export. Implementing it by hand is possible but then the types of the symbols are up to the
+/// implementation.
+pub trait PluginApi: Sized {
+    /// Names of the symbols the library has to export.
+    const SYMBOLS: &'static [&'static str];
+
+    /// Resolves all symbols of the interface.
+    ///
+    /// # Safety
+    ///
+    /// The types of the symbols must match what the library exports and the result must not
+    /// outlive ```lib```.
+    unsafe fn load(lib: &Lib) -> Result<Self>;
+}
+
+/// A loaded plugin. Derefs to the interface so the functions can be called directly.
+///
+/// The plugin keeps its version of the library loaded for as long as it's alive, even if a new
+/// version has been reloaded in the meantime.
+pub struct Plugin<A> {
+    api: A,
+    lib: Arc<Lib>,
+}
+
+impl<A> Plugin<A> {
+    /// The library the plugin was loaded from.
+    pub fn lib(&self) -> &Arc<Lib> {
+        &self.lib
+    }
+}
+
+impl<A> Deref for Plugin<A> {
+    type Target = A;
+
+    fn deref(&self) -> &A {
+        &self.api
+    }
+}
+
+/// Reported by [SafeDynamicReload::update].
+#[derive(Debug)]
+pub enum PluginEvent {
+    /// The plugin with the given name has been reloaded.
+    Reloaded(String),
+    /// Reloading the plugin failed. The previous version is still used.
+    Failed(String, Error),
+    /// The library of the plugin has been deleted. The plugin is reloaded if it shows up again.
+    Removed(String),
+}
+
+/// A layer on top of [DynamicReload] for plugins with a declared interface.
+///
+/// Plugins are declared with [plugin_api] and validated when they are loaded: a library that
+/// doesn't export all the functions isn't used. Calls into the plugin catch panics and report
+/// them as ```Error::Panic```. A plugin keeps its library loaded for as long as it's alive so a
+/// reload never unloads code that is still in use, the new version is used by the next call to
+/// [get](SafeDynamicReload::get).
+///
+/// Only the names of the functions are checked, their types are trusted to match the library,
+/// and loading a library runs its initialization code. That is the one unsafe contract, taken
+/// on by [add](SafeDynamicReload::add) for the library and every later version of it. The rest
+/// of the interface is safe, which is why only settings that don't change what code runs when a
+/// library is loaded are exposed.
+///
+/// ```ignore
+/// dynamic_reload::plugin_api! {
+///     pub struct MathApi {
+///         fn add(a: i32, b: i32) -> i32;
+///     }
+/// }
+///
+/// let mut plugins = SafeDynamicReload::<MathApi>::new(DynamicReload::new(...));
+/// unsafe { plugins.add("math", PlatformName::Yes)? };
+///
+/// loop {
+///     plugins.update();
+///     let math = plugins.get("math").unwrap();
+///     println!("{}", math.add(1, 2)?);
+/// }
+/// ```
+pub struct SafeDynamicReload<A: PluginApi> {
+    reload: DynamicReload,
+    plugins: Vec<(String, Arc<Plugin<A>>)>,
+}
+
+// Collects what happened during DynamicReload::update
+#[derive(Default)]
+struct Reloads {
+    before: Option<PathBuf>,
+    loaded: Vec<Arc<Lib>>,
+    failed: Vec<(PathBuf, Error)>,
+    removed: Vec<PathBuf>,
+}
+
+impl Reloads {
+    fn update_call(&mut self, state: UpdateState, lib: Option<&Arc<Lib>>) {
+        match state {
+            UpdateState::Before => self.before = lib.map(|l| l.source_path().to_path_buf()),
+            UpdateState::After | UpdateState::RolledBack => self.loaded.extend(lib.cloned()),
+            // The plugin keeps using the previous version until a new one has been validated
+            UpdateState::ReloadFailed(e) | UpdateState::HealthCheckFailed(e) => {
+                // Before isn't called when the new version is loaded first
+                let path = self.before.take();
+                if let Some(path) = path.or_else(|| lib.map(|l| l.source_path().to_path_buf())) {
+                    self.failed.push((path, e));
+                }
+            }
+            UpdateState::Removed => self
+                .removed
+                .extend(lib.map(|l| l.source_path().to_path_buf())),
+            // Only plugins added by name are managed, failures have already been reported
+            UpdateState::Added | UpdateState::Blacklisted(_) => (),
+            // Plugins let go of old versions as soon as they are replaced
+            UpdateState::StillReferenced(_) => (),
+            // The plugin keeps running until the new version is reloaded
+            UpdateState::BuildStarted
+            | UpdateState::BuildFinished(_)
+            | UpdateState::BuildFailed(_) => (),
+        }
+    }
+}
+
+impl<A: PluginApi> SafeDynamicReload<A> {
+    /// Takes over ```reload``` to manage the plugins.
+    pub fn new(reload: DynamicReload) -> SafeDynamicReload<A> {
+        SafeDynamicReload {
+            reload,
+            plugins: Vec::new(),
+        }
+    }
+
+    /// Loads a plugin, see [add_library](struct.DynamicReload.html#method.add_library) for how
+    /// the library is found. Fails with ```Error::MissingSymbol``` if the library doesn't export
+    /// the whole interface.
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library). The functions the library
+    /// exports must have the types declared by the interface, also in every version of the
+    /// library that is reloaded later by [update](SafeDynamicReload::update).
+    pub unsafe fn add(&mut self, name: &str, name_format: PlatformName) -> Result<Arc<Plugin<A>>> {
+        let lib = self.reload.add_library(name, name_format)?;
+        let plugin = Self::plugin(lib)?;
+
+        self.plugins.retain(|(n, _)| n != name);
+        self.plugins.push((name.to_owned(), plugin.clone()));
+        Ok(plugin)
+    }
+
+    /// Returns the current version of the plugin.
+    pub fn get(&self, name: &str) -> Option<Arc<Plugin<A>>> {
+        self.plugins
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, p)| p.clone())
+    }
+
+    /// Reloads changed plugins. A plugin that fails to reload or to validate keeps its previous
+    /// version.
+    pub fn update(&mut self) -> Vec<PluginEvent> {
+        let mut reloads = Reloads::default();
+        // Safety: add requires every version of the plugins to match the interface
+        unsafe { self.reload.update(&Reloads::update_call, &mut reloads) };
+
+        let mut events = Vec::new();
+
+        for lib in reloads.loaded {
+            let index = match self.index_of(lib.source_path().to_path_buf()) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let name = self.plugins[index].0.clone();
+            match Self::plugin(lib) {
+                Ok(plugin) => {
+                    self.plugins[index].1 = plugin;
+                    events.push(PluginEvent::Reloaded(name));
+                }
+                Err(e) => events.push(PluginEvent::Failed(name, e)),
+            }
+        }
+
+        for (path, e) in reloads.failed {
+            if let Some(index) = self.index_of(path) {
+                events.push(PluginEvent::Failed(self.plugins[index].0.clone(), e));
+            }
+        }
+
+        for path in reloads.removed {
+            if let Some(index) = self.index_of(path) {
+                events.push(PluginEvent::Removed(self.plugins[index].0.clone()));
+            }
+        }
+
+        events
+    }
+
+    /// The underlying [DynamicReload], for looking up the state of the libraries.
+    pub fn reload(&self) -> &DynamicReload {
+        &self.reload
+    }
+
+    /// See [set_search_paths](struct.DynamicReload.html#method.set_search_paths).
+    pub fn set_search_paths<P: AsRef<Path>>(&mut self, search_paths: Vec<P>) {
+        self.reload.set_search_paths(search_paths);
+    }
+
+    /// See [set_reload_retry](struct.DynamicReload.html#method.set_reload_retry).
+    pub fn set_reload_retry(&mut self, retry: Option<ReloadRetry>) {
+        self.reload.set_reload_retry(retry);
+    }
+
+    /// See [set_rollback](struct.DynamicReload.html#method.set_rollback).
+    pub fn set_rollback(&mut self, rollback: bool) {
+        self.reload.set_rollback(rollback);
+    }
+
+    fn index_of(&self, path: PathBuf) -> Option<usize> {
+        self.plugins
+            .iter()
+            .position(|(_, p)| p.lib.source_path() == path)
+    }
+
+    fn plugin(lib: Arc<Lib>) -> Result<Arc<Plugin<A>>> {
+        for symbol in A::SYMBOLS {
+            if !lib.has_symbol(symbol) {
+                return Err(Error::MissingSymbol(
+                    lib.loaded_path.clone(),
+                    symbol.to_string(),
+                ));
+            }
+        }
+
+        // Safety: the symbols are all there and the plugin keeps the library alive
+        let api = unsafe { A::load(&lib)? };
+        Ok(Arc::new(Plugin { api, lib }))
+    }
+}
+
+#[doc(hidden)]
+pub fn panic_error(name: &str, payload: Box<dyn Any + Send>) -> Error {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+
+    Error::Panic(name.to_owned(), message)
+}
+
+/// Declares the interface of a plugin for use with [SafeDynamicReload].
+///
+/// Each function becomes a method returning ```Result``` with panics inside the plugin caught
+/// and returned as ```Error::Panic```. Functions are expected to use the Rust ABI unless
+/// declared ```extern "C"```. Note that a panic can't unwind out of an ```extern "C"```
+/// function so those abort instead.
+///
+/// ```ignore
+/// dynamic_reload::plugin_api! {
+///     pub struct RenderApi {
+///         fn init(width: u32, height: u32) -> bool;
+///         extern "C" fn render(time: f32);
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! plugin_api {
+    (@ret) => { () };
+    (@ret $ret:ty) => { $ret };
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$fn_meta:meta])*
+                $(extern $abi:literal)? fn $fn_name:ident($($arg:ident: $arg_ty:ty),* $(,)?) $(-> $ret:ty)?;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($fn_name: $(extern $abi)? fn($($arg_ty),*) $(-> $ret)?,)*
+        }
+
+        impl $crate::PluginApi for $name {
+            const SYMBOLS: &'static [&'static str] = &[$(stringify!($fn_name)),*];
+
+            unsafe fn load(lib: &$crate::Lib) -> $crate::Result<Self> {
+                Ok($name {
+                    $($fn_name: *lib
+                        .lib
+                        .get::<$(extern $abi)? fn($($arg_ty),*) $(-> $ret)?>(
+                            concat!(stringify!($fn_name), "\0").as_bytes(),
+                        )
+                        .map_err($crate::Error::Load)?,)*
+                })
+            }
+        }
+
+        impl $name {
+            $(
+                $(#[$fn_meta])*
+                #[allow(clippy::unused_unit)]
+                $vis fn $fn_name(&self, $($arg: $arg_ty),*) -> $crate::Result<$crate::plugin_api!(@ret $($ret)?)> {
+                    let f = self.$fn_name;
+                    ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(move || f($($arg),*)))
+                        .map_err(|e| $crate::panic_error(stringify!($fn_name), e))
+                }
+            )*
+        }
+    };
+}
@@ -0,0 +1,53 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+/// Environment variable with extra search paths that are added at construction, separated the
+/// same way as ```PATH``` (```:``` and on Windows ```;```). Another variable can be used with
+/// [search_path_env](struct.DynamicReloadBuilder.html#method.search_path_env).
+pub const SEARCH_PATH_ENV: &str = "DYNAMIC_RELOAD_PATH";
+
+/// Resolves the name of a library to the file to load, set with
+/// [Search::Custom](enum.Search.html#variant.Custom).
+///
+/// Replaces all the built in searching so the strategy is in full control of where libraries
+/// are loaded from. It's also used when the search paths are changed with
+/// [set_search_paths](struct.DynamicReload.html#method.set_search_paths) to find out if a
+/// loaded library has moved.
+///
+/// Strategies have to be ```Send + Sync``` as [DynamicReload](struct.DynamicReload.html) may be
+/// shared between threads.
+pub trait SearchStrategy: Send + Sync {
+    /// Returns the path of the library ```lib_name```, already formatted according to the
+    /// [PlatformName](enum.PlatformName.html) given when it was added. ```search_paths``` are
+    /// the search paths of DynamicReload.
+    fn find(&self, lib_name: &str, search_paths: &[PathBuf]) -> Option<PathBuf>;
+}
+
+impl<F> SearchStrategy for F
+where
+    F: Fn(&str, &[PathBuf]) -> Option<PathBuf> + Send + Sync,
+{
+    fn find(&self, lib_name: &str, search_paths: &[PathBuf]) -> Option<PathBuf> {
+        self(lib_name, search_paths)
+    }
+}
+
+// The paths listed in the environment variable read with lookup, empty if it isn't set
+pub(crate) fn env_paths<F: Fn(&str) -> Option<OsString>>(var: &str, lookup: F) -> Vec<PathBuf> {
+    match lookup(var) {
+        Some(paths) => env::split_paths(&paths)
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// The directory of the executable
+pub(crate) fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
@@ -0,0 +1,28 @@
+use crate::{Error, Result};
+use ed25519_dalek::Signature;
+pub use ed25519_dalek::VerifyingKey;
+use std::{fs, path::Path};
+
+/// Extension of the detached signature placed next to a library when a public key is set with
+/// [set_public_key](struct.DynamicReload.html#method.set_public_key).
+///
+/// For ```libfoo.so``` the signature is ```libfoo.so.sig```, the 64 byte Ed25519 signature of
+/// the library file.
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// Verifies the file at ```loaded_path``` (the shadow copy if there is one so the bytes that
+/// are checked are the ones that get loaded) against the signature next to ```lib_path```.
+pub(crate) fn verify(key: &VerifyingKey, lib_path: &Path, loaded_path: &Path) -> Result<()> {
+    let invalid = |reason: String| Error::SignatureInvalid(lib_path.to_path_buf(), reason);
+
+    let mut sig_path = lib_path.as_os_str().to_owned();
+    sig_path.push(".");
+    sig_path.push(SIGNATURE_EXTENSION);
+
+    let sig = fs::read(&sig_path).map_err(|e| invalid(format!("{:?}: {}", sig_path, e)))?;
+    let sig = Signature::from_slice(&sig).map_err(|e| invalid(e.to_string()))?;
+    let data = fs::read(loaded_path).map_err(|e| invalid(e.to_string()))?;
+
+    key.verify_strict(&data, &sig)
+        .map_err(|_| invalid("the signature doesn't match".to_string()))
+}
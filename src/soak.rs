@@ -0,0 +1,312 @@
+//! Stress testing of the reload path, enabled with the ```test-utils``` feature.
+//!
+//! [SoakTest] repeatedly rewrites a library on disk while driving
+//! [update](../struct.DynamicReload.html#method.update) and checks that every change ends up
+//! loaded exactly once and that old versions are released.
+
+use crate::{hash, DynamicReload, FileIdentity, Lib, UpdateState};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Drives rewrites of a library and update cycles at configurable rates.
+///
+/// ```ignore
+/// let mut dr = DynamicReload::new(None, Some("target/debug"), Search::Default, Duration::from_millis(100));
+/// dr.add_library("my_plugin", PlatformName::Yes)?;
+///
+/// let report = unsafe {
+///     SoakTest::new("target/debug/pristine/libmy_plugin.so", "target/debug/libmy_plugin.so")
+///         .rewrites(20)
+///         .run(&mut dr)
+/// };
+/// report.assert_ok();
+/// ```
+pub struct SoakTest {
+    source: PathBuf,
+    alternate: Option<PathBuf>,
+    target: PathBuf,
+    rewrites: u32,
+    rewrite_interval: Duration,
+    update_interval: Duration,
+    settle: Duration,
+}
+
+/// The outcome of a [SoakTest].
+#[derive(Clone, Debug, Default)]
+pub struct SoakReport {
+    /// Number of times the library was rewritten.
+    pub rewrites: u32,
+    /// Number of successful reloads.
+    pub reloads: u32,
+    /// Number of failed reloads.
+    pub failures: u32,
+    /// Broken invariants, empty if the run was fine.
+    pub violations: Vec<String>,
+}
+
+impl SoakReport {
+    /// Returns true if no invariant was broken.
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Panics with the broken invariants, if any.
+    pub fn assert_ok(&self) {
+        assert!(self.is_ok(), "soak test failed: {:#?}", self);
+    }
+}
+
+#[derive(Default)]
+struct Tracker {
+    reloads: u32,
+    failures: u32,
+    in_reload: bool,
+    last_identity: Option<FileIdentity>,
+    released: Vec<Weak<Lib>>,
+    violations: Vec<String>,
+}
+
+impl Tracker {
+    fn update_call(&mut self, state: UpdateState, lib: Option<&Arc<Lib>>) {
+        match state {
+            UpdateState::Before => {
+                if self.in_reload {
+                    self.violations
+                        .push("Before called twice without After".to_string());
+                }
+                self.in_reload = true;
+                if let Some(lib) = lib {
+                    self.released.push(Arc::downgrade(lib));
+                }
+            }
+            UpdateState::After => {
+                self.in_reload = false;
+                self.reloads += 1;
+
+                let identity = lib.and_then(|l| l.file_identity);
+                if identity.is_some() && identity == self.last_identity {
+                    self.violations
+                        .push(format!("Reloaded the same file twice ({:?})", identity));
+                }
+                self.last_identity = identity;
+            }
+            UpdateState::ReloadFailed(e) => {
+                self.in_reload = false;
+                self.failures += 1;
+                self.violations.push(format!("Reload failed: {}", e));
+            }
+            UpdateState::Removed => {
+                self.violations
+                    .push("Library reported as removed".to_string());
+            }
+            UpdateState::Added => {
+                self.violations
+                    .push("Library reported as added".to_string());
+            }
+            // The failure has already been recorded
+            UpdateState::RolledBack => (),
+            UpdateState::StillReferenced(count) => {
+                self.violations
+                    .push(format!("Old library still referenced {} times", count));
+            }
+            UpdateState::Blacklisted(path) => {
+                self.violations
+                    .push(format!("Library blacklisted ({:?})", path));
+            }
+            UpdateState::HealthCheckFailed(e) => {
+                self.in_reload = false;
+                self.failures += 1;
+                self.violations.push(format!("Health check failed: {}", e));
+            }
+            // Sources aren't watched by soak tests
+            UpdateState::BuildStarted
+            | UpdateState::BuildFinished(_)
+            | UpdateState::BuildFailed(_) => (),
+        }
+    }
+}
+
+impl SoakTest {
+    /// Rewrites ```target``` (the library added to DynamicReload) with the content of
+    /// ```source```. Defaults to 10 rewrites, one every 250 ms with an update every 10 ms.
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(source: P, target: Q) -> SoakTest {
+        SoakTest {
+            source: source.as_ref().to_path_buf(),
+            alternate: None,
+            target: target.as_ref().to_path_buf(),
+            rewrites: 10,
+            rewrite_interval: Duration::from_millis(250),
+            update_interval: Duration::from_millis(10),
+            settle: Duration::from_secs(2),
+        }
+    }
+
+    /// Alternates between ```source``` and ```alternate``` when rewriting. Rewriting with the
+    /// same content doesn't cause a reload when a shadow directory is used, so use this to
+    /// have every rewrite give a new version of the library.
+    pub fn alternate<P: AsRef<Path>>(mut self, alternate: P) -> SoakTest {
+        self.alternate = Some(alternate.as_ref().to_path_buf());
+        self
+    }
+
+    /// Number of times the library is rewritten.
+    pub fn rewrites(mut self, rewrites: u32) -> SoakTest {
+        self.rewrites = rewrites;
+        self
+    }
+
+    /// Time between two rewrites. Rewrites closer than the debounce duration are expected to
+    /// be coalesced into one reload.
+    pub fn rewrite_interval(mut self, interval: Duration) -> SoakTest {
+        self.rewrite_interval = interval;
+        self
+    }
+
+    /// Time between two calls to update.
+    pub fn update_interval(mut self, interval: Duration) -> SoakTest {
+        self.update_interval = interval;
+        self
+    }
+
+    /// How long to keep updating after the last rewrite for the final reload to happen.
+    pub fn settle(mut self, settle: Duration) -> SoakTest {
+        self.settle = settle;
+        self
+    }
+
+    ///
+    /// Runs the test against ```dr``` which must already have the target library added.
+    ///
+    /// The following is checked
+    ///
+    /// ```ignore
+    /// 1. No reload fails and Before is always followed by After
+    /// 2. The same version of the file is never reloaded twice
+    /// 3. There are never more reloads than rewrites
+    /// 4. The last rewrite is the version that is loaded at the end
+    /// 5. The target library is tracked exactly once
+    /// 6. Replaced versions are released (unless the no-unload feature is used)
+    /// ```
+    ///
+    /// For the last check the host must not hold on to the ```Arc<Lib>``` of the target,
+    /// including the one returned by ```add_library```.
+    ///
+    /// # Safety
+    ///
+    /// Loads and unloads the library repeatedly, see
+    /// [update](../struct.DynamicReload.html#method.update).
+    ///
+    pub unsafe fn run(&self, dr: &mut DynamicReload) -> SoakReport {
+        let mut tracker = Tracker::default();
+        let start = SystemTime::now();
+
+        for rewrite in 0..self.rewrites {
+            let source = match self.alternate.as_ref() {
+                Some(alternate) if rewrite % 2 == 0 => alternate,
+                _ => &self.source,
+            };
+
+            if let Err(e) = self.rewrite(source, start + Duration::from_secs(rewrite as u64 + 1)) {
+                tracker
+                    .violations
+                    .push(format!("Unable to rewrite {:?}: {}", self.target, e));
+                break;
+            }
+
+            self.update_for(dr, self.rewrite_interval, &mut tracker);
+        }
+
+        self.update_for(dr, self.settle, &mut tracker);
+        self.check(dr, &mut tracker);
+
+        SoakReport {
+            rewrites: self.rewrites,
+            reloads: tracker.reloads,
+            failures: tracker.failures,
+            violations: tracker.violations,
+        }
+    }
+
+    // Each rewrite gets its own modification time so it's seen as a new version even on file
+    // systems with coarse timestamps.
+    fn rewrite(&self, source: &Path, modified: SystemTime) -> std::io::Result<()> {
+        fs::copy(source, &self.target)?;
+        fs::File::options()
+            .write(true)
+            .open(&self.target)?
+            .set_modified(modified)
+    }
+
+    unsafe fn update_for(&self, dr: &mut DynamicReload, duration: Duration, tracker: &mut Tracker) {
+        let start = Instant::now();
+
+        while start.elapsed() < duration {
+            dr.update(&Tracker::update_call, tracker);
+            thread::sleep(self.update_interval);
+        }
+    }
+
+    fn check(&self, dr: &DynamicReload, tracker: &mut Tracker) {
+        if tracker.in_reload {
+            tracker.violations.push("Reload never finished".to_string());
+        }
+
+        if tracker.reloads > self.rewrites {
+            tracker.violations.push(format!(
+                "{} reloads for {} rewrites",
+                tracker.reloads, self.rewrites
+            ));
+        }
+
+        let file_name = self.target.file_name();
+        let libs: Vec<&Arc<Lib>> = dr
+            .libs
+            .iter()
+            .filter(|lib| lib.source_path().file_name() == file_name)
+            .collect();
+
+        if libs.len() != 1 {
+            tracker.violations.push(format!(
+                "{} libraries tracked for {:?}",
+                libs.len(),
+                self.target
+            ));
+        }
+
+        // Identical content isn't reloaded so compare the content when possible
+        if let Some(lib) = libs.first() {
+            let outdated = match lib.content_hash() {
+                Some(loaded) => hash::hash_file(&self.target).ok() != Some(loaded),
+                None => {
+                    lib.file_identity.is_some()
+                        && lib.file_identity != FileIdentity::from_path(&self.target)
+                }
+            };
+
+            if outdated {
+                tracker
+                    .violations
+                    .push("The last rewrite was never loaded".to_string());
+            }
+        }
+
+        if cfg!(not(feature = "no-unload")) {
+            let leaked = tracker
+                .released
+                .iter()
+                .filter(|lib| lib.strong_count() > 0)
+                .count();
+
+            if leaked > 0 {
+                tracker
+                    .violations
+                    .push(format!("{} replaced versions are still alive", leaked));
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{Error, Lib, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+};
+
+/// Symbol a plugin exports to save its state before it's unloaded for a reload, with the
+/// signature ```extern "C" fn() -> *mut c_char```. Returns the state as a NUL terminated string
+/// (null if there is nothing to save) that is freed with ```dr_free_state```. Usually generated
+/// with [persistent_state].
+pub const SAVE_STATE_SYMBOL: &str = "dr_save_state";
+/// Symbol a plugin exports to free the string returned by ```dr_save_state```, with the
+/// signature ```extern "C" fn(*mut c_char)```.
+pub const FREE_STATE_SYMBOL: &str = "dr_free_state";
+/// Symbol a plugin exports to restore the state saved by the previous version, with the
+/// signature ```extern "C" fn(*const c_char) -> bool```.
+pub const LOAD_STATE_SYMBOL: &str = "dr_load_state";
+
+type SaveFn = unsafe extern "C" fn() -> *mut c_char;
+type FreeFn = unsafe extern "C" fn(*mut c_char);
+type LoadFn = unsafe extern "C" fn(*const c_char) -> bool;
+
+/// State of a plugin that is kept across reloads, saved as JSON together with its version.
+///
+/// When the layout of the state changes ```VERSION``` is increased and ```migrate``` upgrades
+/// state saved by older versions one version at a time, so a plugin can be reloaded with a new
+/// layout without losing its state. Used by the functions generated with [persistent_state] or
+/// directly with [save_state] and [load_state], for example together with
+/// [set_reload_state](struct.Lib.html#method.set_reload_state).
+pub trait PersistentState: Serialize + DeserializeOwned {
+    /// Version of the layout of the state.
+    const VERSION: u32;
+
+    /// Upgrades state saved by version ```from``` to version ```from + 1```. Fails by default,
+    /// which leaves the plugin without its previous state.
+    fn migrate(from: u32, state: Value) -> std::result::Result<Value, String> {
+        let _ = state;
+        Err(format!("no migration from version {}", from))
+    }
+}
+
+/// Serializes ```state``` together with its version.
+pub fn save_state<S: PersistentState>(state: &S) -> Result<String> {
+    let state = serde_json::to_value(state).map_err(|e| Error::State(e.to_string()))?;
+    Ok(json!({ "version": S::VERSION, "state": state }).to_string())
+}
+
+/// Deserializes state saved with [save_state], migrating it if it was saved by an older version.
+pub fn load_state<S: PersistentState>(saved: &str) -> Result<S> {
+    let mut saved: Value = serde_json::from_str(saved).map_err(|e| Error::State(e.to_string()))?;
+
+    let mut version = saved["version"]
+        .as_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .ok_or_else(|| Error::State("missing version".to_owned()))?;
+    let mut state = saved["state"].take();
+
+    if version > S::VERSION {
+        return Err(Error::State(format!(
+            "saved by version {}, newer than {}",
+            version,
+            S::VERSION
+        )));
+    }
+
+    while version < S::VERSION {
+        state = S::migrate(version, state).map_err(Error::State)?;
+        version += 1;
+    }
+
+    serde_json::from_value(state).map_err(|e| Error::State(e.to_string()))
+}
+
+// The saved state of a plugin, kept in the userdata of the library between the versions
+struct Saved(CString);
+
+// Saves the state of the plugin before it's unloaded
+pub(crate) fn save(lib: &Lib) {
+    let userdata = lib.shared_userdata();
+    // State left from a version that failed to load is replaced as well
+    userdata.remove::<Saved>();
+
+    // Safety: the signatures are part of the convention
+    unsafe {
+        let save = match lib.lib.get::<SaveFn>(SAVE_STATE_SYMBOL.as_bytes()) {
+            Ok(save) => save,
+            Err(_) => return,
+        };
+
+        let state = save();
+        if state.is_null() {
+            return;
+        }
+
+        userdata.insert(Saved(CStr::from_ptr(state).to_owned()));
+
+        if let Ok(free) = lib.lib.get::<FreeFn>(FREE_STATE_SYMBOL.as_bytes()) {
+            free(state);
+        }
+    }
+}
+
+// Hands the saved state to the new version of the plugin
+pub(crate) fn restore(lib: &Lib) {
+    let saved = match lib.shared_userdata().remove::<Saved>() {
+        Some(saved) => saved,
+        None => return,
+    };
+
+    // Safety: see save
+    unsafe {
+        if let Ok(load) = lib.lib.get::<LoadFn>(LOAD_STATE_SYMBOL.as_bytes()) {
+            if !load(saved.0.as_ptr()) {
+                log::warn!("Unable to restore the state of {:?}", lib.source_path());
+            }
+        }
+    }
+}
+
+/// Exports ```dr_save_state```, ```dr_load_state``` and ```dr_free_state``` from a plugin so its
+/// state is kept across reloads, see [PersistentState].
+///
+/// Takes the type of the state, a function returning the state to save and a function that is
+/// given the restored state in the new version of the plugin.
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize)]
+/// struct Counters {
+///     frames: u64,
+/// }
+///
+/// impl PersistentState for Counters {
+///     const VERSION: u32 = 1;
+/// }
+///
+/// static COUNTERS: Mutex<Counters> = Mutex::new(Counters { frames: 0 });
+///
+/// dynamic_reload::persistent_state!(
+///     Counters,
+///     || std::mem::replace(&mut *COUNTERS.lock().unwrap(), Counters { frames: 0 }),
+///     |counters| *COUNTERS.lock().unwrap() = counters
+/// );
+/// ```
+#[macro_export]
+macro_rules! persistent_state {
+    ($state:ty, $save:expr, $load:expr) => {
+        #[no_mangle]
+        pub extern "C" fn dr_save_state() -> *mut ::std::os::raw::c_char {
+            let state: $state = ($save)();
+            $crate::save_state(&state)
+                .ok()
+                .and_then(|saved| ::std::ffi::CString::new(saved).ok())
+                .map_or(::std::ptr::null_mut(), ::std::ffi::CString::into_raw)
+        }
+
+        /// # Safety
+        ///
+        /// Only to be called by the host with a string returned by ```dr_save_state```.
+        #[no_mangle]
+        pub unsafe extern "C" fn dr_free_state(saved: *mut ::std::os::raw::c_char) {
+            drop(::std::ffi::CString::from_raw(saved));
+        }
+
+        /// # Safety
+        ///
+        /// Only to be called by the host with a string saved by ```dr_save_state```.
+        #[no_mangle]
+        pub unsafe extern "C" fn dr_load_state(saved: *const ::std::os::raw::c_char) -> bool {
+            let saved = ::std::ffi::CStr::from_ptr(saved).to_string_lossy();
+            match $crate::load_state::<$state>(&saved) {
+                Ok(state) => {
+                    ($load)(state);
+                    true
+                }
+                Err(_) => false,
+            }
+        }
+    };
+}
@@ -0,0 +1,101 @@
+use crate::{DynamicReload, ReloadEvent};
+use futures_core::Stream;
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    thread,
+    time::Duration,
+};
+
+// How often the stream checks again when reloads don't come from watcher events alone
+const TICK: Duration = Duration::from_millis(100);
+
+/// Reloads delivered as a [Stream], created with
+/// [event_stream](struct.DynamicReload.html#method.event_stream).
+///
+/// Libraries are reloaded when the stream is polled, on the task polling it. The task is woken
+/// when the file watcher reports a change so no polling loop is needed. The stream never ends.
+pub struct EventStream<'a> {
+    reload: &'a mut DynamicReload,
+    pending: VecDeque<ReloadEvent>,
+    ticker: Option<Ticker>,
+}
+
+// Wakes the task periodically while there are changes that don't arrive as watcher events
+struct Ticker {
+    waker: Arc<Mutex<Option<Waker>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Ticker {
+    fn start() -> Ticker {
+        let waker: Arc<Mutex<Option<Waker>>> = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_waker = waker.clone();
+        let thread_stop = stop.clone();
+
+        thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(TICK);
+                if let Some(waker) = thread_waker.lock().unwrap().take() {
+                    waker.wake();
+                }
+            }
+        });
+
+        Ticker { waker, stop }
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<'a> EventStream<'a> {
+    pub(crate) fn new(reload: &'a mut DynamicReload) -> EventStream<'a> {
+        EventStream {
+            reload,
+            pending: VecDeque::new(),
+            ticker: None,
+        }
+    }
+}
+
+impl<'a> Stream for EventStream<'a> {
+    type Item = ReloadEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ReloadEvent>> {
+        let this = self.get_mut();
+
+        if let Some(event) = this.pending.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+
+        // Registered before checking so an event arriving in between still wakes the task
+        this.reload.set_waker(cx.waker());
+
+        // Safety is up to the caller of event_stream
+        this.pending.extend(unsafe { this.reload.poll_events() });
+
+        if let Some(event) = this.pending.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+
+        if this.reload.needs_polling() {
+            let ticker = this.ticker.get_or_insert_with(Ticker::start);
+            *ticker.waker.lock().unwrap() = Some(cx.waker().clone());
+        } else {
+            this.ticker = None;
+        }
+
+        Poll::Pending
+    }
+}
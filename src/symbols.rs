@@ -0,0 +1,78 @@
+use crate::Lib;
+use std::{path::PathBuf, sync::Arc};
+
+/// A symbol exported by more than one library, or shadowed by the global namespace, as found by
+/// [symbol_conflicts](struct.DynamicReload.html#method.symbol_conflicts).
+///
+/// Lookups through [Lib] always use the handle of that library so exporting the same name from
+/// several plugins is fine for the host. It becomes a problem when a library is loaded with
+/// ```RTLD_GLOBAL``` (by the host, another plugin or as a dependency) as references from inside
+/// a plugin may then bind to the global definition instead of its own.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolConflict {
+    /// Name of the symbol.
+    pub name: String,
+    /// All loaded libraries that export the symbol.
+    pub libs: Vec<PathBuf>,
+    /// True if a lookup in the global namespace finds another definition than the one in the
+    /// libraries. Always false on Windows where there is no global namespace.
+    pub global: bool,
+}
+
+pub(crate) fn conflicts(libs: &[Arc<Lib>], names: &[&str]) -> Vec<SymbolConflict> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let exporting: Vec<(&Arc<Lib>, usize)> = libs
+                .iter()
+                .filter_map(|lib| Some((lib, address(lib, name)?)))
+                .collect();
+
+            let global = match global_address(name) {
+                Some(global) => exporting.iter().any(|(_, addr)| *addr != global),
+                None => false,
+            };
+
+            if exporting.len() < 2 && !global {
+                return None;
+            }
+
+            Some(SymbolConflict {
+                name: name.to_string(),
+                libs: exporting
+                    .iter()
+                    .map(|(lib, _)| lib.loaded_path.clone())
+                    .collect(),
+                global,
+            })
+        })
+        .collect()
+}
+
+fn address(lib: &Lib, name: &str) -> Option<usize> {
+    // Safety: the symbol is only looked up to compare addresses, never used
+    unsafe {
+        lib.lib
+            .get::<*const ()>(name.as_bytes())
+            .ok()
+            .map(|s| *s as usize)
+    }
+}
+
+// Looks the symbol up in the global namespace (the executable and everything loaded with
+// RTLD_GLOBAL), which is what dynamic linking of other libraries would bind to.
+#[cfg(unix)]
+fn global_address(name: &str) -> Option<usize> {
+    let this = libloading::os::unix::Library::this();
+    // Safety: the symbol is only looked up to compare addresses, never used
+    unsafe {
+        this.get::<*const ()>(name.as_bytes())
+            .ok()
+            .map(|s| *s as usize)
+    }
+}
+
+#[cfg(not(unix))]
+fn global_address(_name: &str) -> Option<usize> {
+    None
+}
@@ -0,0 +1,95 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Environment variables listing directories the dynamic linker searches.
+#[cfg(windows)]
+pub(crate) const PATH_VARS: &[&str] = &["PATH"];
+#[cfg(target_os = "macos")]
+pub(crate) const PATH_VARS: &[&str] = &["DYLD_LIBRARY_PATH", "DYLD_FALLBACK_LIBRARY_PATH"];
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) const PATH_VARS: &[&str] = &["LD_LIBRARY_PATH"];
+
+// Used when nothing else is configured
+#[cfg(windows)]
+const DEFAULT_DIRS: &[&str] = &[];
+#[cfg(target_os = "macos")]
+const DEFAULT_DIRS: &[&str] = &["/usr/local/lib", "/usr/lib"];
+#[cfg(all(unix, not(target_os = "macos")))]
+const DEFAULT_DIRS: &[&str] = &["/lib", "/usr/lib", "/lib64", "/usr/lib64", "/usr/local/lib"];
+
+/// Directories listed in the environment variables of the dynamic linker, read with lookup.
+pub(crate) fn loader_dirs<F: Fn(&str) -> Option<OsString>>(lookup: F) -> Vec<PathBuf> {
+    PATH_VARS
+        .iter()
+        .filter_map(|var| lookup(var))
+        .flat_map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+        .filter(|p| !p.as_os_str().is_empty())
+        .collect()
+}
+
+/// Directories the OS looks in for shared libraries, in the order they are searched.
+pub(crate) fn library_dirs<F: Fn(&str) -> Option<OsString>>(lookup: F) -> Vec<PathBuf> {
+    let mut dirs = loader_dirs(lookup);
+
+    dirs.extend(linker_config_dirs());
+    dirs.extend(DEFAULT_DIRS.iter().map(PathBuf::from));
+
+    let mut unique = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        if !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+
+    unique
+}
+
+// The directories configured for ld.so, including the files it includes
+#[cfg(all(unix, not(target_os = "macos")))]
+fn linker_config_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    read_linker_config(Path::new("/etc/ld.so.conf"), &mut dirs, 0);
+    dirs
+}
+
+#[cfg(any(windows, target_os = "macos"))]
+fn linker_config_dirs() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+#[cfg_attr(any(windows, target_os = "macos"), allow(dead_code))]
+fn read_linker_config(path: &Path, dirs: &mut Vec<PathBuf>, depth: usize) {
+    // Guard against include loops
+    if depth > 4 {
+        return;
+    }
+
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return,
+    };
+
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+
+        if let Some(pattern) = line.strip_prefix("include") {
+            let pattern = pattern.trim();
+            let pattern = match path.parent() {
+                Some(parent) if !Path::new(pattern).is_absolute() => parent.join(pattern),
+                _ => PathBuf::from(pattern),
+            };
+
+            if let Ok(paths) = glob::glob(&pattern.to_string_lossy()) {
+                for include in paths.flatten() {
+                    read_linker_config(&include, dirs, depth + 1);
+                }
+            }
+        } else if !line.is_empty() {
+            dirs.push(PathBuf::from(line));
+        }
+    }
+}
@@ -0,0 +1,43 @@
+// Reports reload statistics through the metrics facade when the ```metrics``` feature is
+// enabled. Without the feature all of these compile to nothing.
+//
+// dynamic_reload_reloads_total          counter, reloaded libraries (label: library)
+// dynamic_reload_reload_failures_total  counter, failed reloads (label: library)
+// dynamic_reload_swap_seconds           histogram, time from unloading until the new version is loaded
+// dynamic_reload_copy_bytes_total       counter, bytes copied to the shadow directory
+
+use std::{path::Path, time::Duration};
+
+#[cfg(feature = "metrics")]
+fn library_label(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn reloaded(path: &Path, swap_time: Duration) {
+    metrics::counter!("dynamic_reload_reloads_total", "library" => library_label(path))
+        .increment(1);
+    metrics::histogram!("dynamic_reload_swap_seconds").record(swap_time.as_secs_f64());
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn reload_failed(path: &Path) {
+    metrics::counter!("dynamic_reload_reload_failures_total", "library" => library_label(path))
+        .increment(1);
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn copied(bytes: u64) {
+    metrics::counter!("dynamic_reload_copy_bytes_total").increment(bytes);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn reloaded(_path: &Path, _swap_time: Duration) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn reload_failed(_path: &Path) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn copied(_bytes: u64) {}
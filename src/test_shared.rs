@@ -1,5 +1,170 @@
 
 #[no_mangle]
 pub fn shared_fun() -> i32 {
-    42
+    if cfg!(test_shared_v2) {
+        43
+    } else {
+        42
+    }
+}
+
+// Only exported by the second version of the library
+#[cfg(test_shared_v2)]
+#[no_mangle]
+pub extern "C" fn test_v2_only() {}
+
+#[no_mangle]
+pub extern "C" fn test_init_hang() {
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn test_init_slow() {
+    std::thread::sleep(std::time::Duration::from_secs(1));
+}
+
+#[no_mangle]
+pub extern "C" fn app_init() -> *mut std::ffi::c_void {
+    Box::into_raw(Box::new(0u32)) as *mut std::ffi::c_void
+}
+
+#[no_mangle]
+pub extern "C" fn app_update(state: *mut std::ffi::c_void) -> bool {
+    let count = unsafe { &mut *(state as *mut u32) };
+    *count += 1;
+    *count < 3
+}
+
+#[no_mangle]
+pub extern "C" fn app_shutdown(state: *mut std::ffi::c_void) {
+    drop(unsafe { Box::from_raw(state as *mut u32) });
+}
+
+#[no_mangle]
+pub extern "C" fn dr_metadata() -> *const std::os::raw::c_char {
+    "name = test_shared\nabi = test\n\0".as_ptr() as *const std::os::raw::c_char
+}
+
+#[no_mangle]
+pub extern "C" fn dr_fingerprint() -> *const std::os::raw::c_char {
+    concat!(env!("DR_RUSTC_VERSION"), "\ntest\0").as_ptr() as *const std::os::raw::c_char
+}
+
+/// # Safety
+///
+/// Called by the host with a pointer to an ```extern "C" fn(i32)```.
+#[no_mangle]
+pub unsafe extern "C" fn dr_plugin_init(host_api: *const std::ffi::c_void) {
+    let report = *(host_api as *const extern "C" fn(i32));
+    report(shared_fun());
+}
+
+// Same function table as in the tests of the host
+#[repr(C)]
+pub struct TestPlugin {
+    pub value: unsafe extern "C" fn(*mut std::ffi::c_void) -> i32,
+}
+
+// Same layout as PluginInstance<TestPlugin> of the host
+#[repr(C)]
+pub struct PluginInstance {
+    instance: *mut std::ffi::c_void,
+    vtable: TestPlugin,
+    drop_instance: unsafe extern "C" fn(*mut std::ffi::c_void),
+}
+
+struct Shared;
+
+unsafe extern "C" fn shared_value(_instance: *mut std::ffi::c_void) -> i32 {
+    shared_fun()
+}
+
+unsafe extern "C" fn drop_shared(instance: *mut std::ffi::c_void) {
+    drop(Box::from_raw(instance as *mut Shared));
+}
+
+// What declare_plugin!(TestPlugin, Shared, TestPlugin { value: shared_value }) expands to
+#[no_mangle]
+pub extern "C" fn plugin_create() -> *mut std::ffi::c_void {
+    let plugin = PluginInstance {
+        instance: Box::into_raw(Box::new(Shared)) as *mut std::ffi::c_void,
+        vtable: TestPlugin {
+            value: shared_value,
+        },
+        drop_instance: drop_shared,
+    };
+    Box::into_raw(Box::new(plugin)) as *mut std::ffi::c_void
+}
+
+/// # Safety
+///
+/// Only to be called by the host with a plugin created by ```plugin_create```.
+#[no_mangle]
+pub unsafe extern "C" fn plugin_destroy(plugin: *mut std::ffi::c_void) {
+    let plugin = Box::from_raw(plugin as *mut PluginInstance);
+    (plugin.drop_instance)(plugin.instance);
+}
+
+static LOADED_STATE: std::sync::Mutex<Option<std::ffi::CString>> = std::sync::Mutex::new(None);
+
+// What persistent_state! generates, with the state saved by hand
+#[no_mangle]
+pub extern "C" fn dr_save_state() -> *mut std::os::raw::c_char {
+    let saved = format!("{{\"version\":1,\"state\":{}}}", shared_fun());
+    std::ffi::CString::new(saved).unwrap().into_raw()
+}
+
+/// # Safety
+///
+/// Only to be called by the host with a string returned by ```dr_save_state```.
+#[no_mangle]
+pub unsafe extern "C" fn dr_free_state(saved: *mut std::os::raw::c_char) {
+    drop(std::ffi::CString::from_raw(saved));
+}
+
+/// # Safety
+///
+/// Only to be called by the host with a string saved by ```dr_save_state```.
+#[no_mangle]
+pub unsafe extern "C" fn dr_load_state(saved: *const std::os::raw::c_char) -> bool {
+    *LOADED_STATE.lock().unwrap() = Some(std::ffi::CStr::from_ptr(saved).to_owned());
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn test_loaded_state() -> *const std::os::raw::c_char {
+    match LOADED_STATE.lock().unwrap().as_ref() {
+        Some(state) => state.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+static BEFORE_RELOADS: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+static AFTER_LOADS: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+#[no_mangle]
+pub extern "C" fn dr_before_reload() {
+    BEFORE_RELOADS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+}
+
+#[no_mangle]
+pub extern "C" fn dr_after_load() {
+    AFTER_LOADS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+}
+
+#[repr(C)]
+pub struct LifecycleCalls {
+    before_reload: u32,
+    after_load: u32,
+}
+
+// Number of calls to dr_before_reload and dr_after_load in this copy of the library
+#[no_mangle]
+pub extern "C" fn test_lifecycle_calls() -> LifecycleCalls {
+    LifecycleCalls {
+        before_reload: BEFORE_RELOADS.load(std::sync::atomic::Ordering::SeqCst),
+        after_load: AFTER_LOADS.load(std::sync::atomic::Ordering::SeqCst),
+    }
 }
@@ -0,0 +1,129 @@
+use crate::atomic_symbol::Rebind;
+use crate::{Error, Lib, Result};
+use std::{
+    ffi::c_void,
+    mem,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+/// Number of trampolines that can be handed out over the life of the process, see
+/// [trampoline](struct.DynamicReload.html#method.trampoline).
+pub const MAX_TRAMPOLINES: usize = 256;
+
+// Size of each stub in the table
+#[cfg(target_arch = "x86_64")]
+const STUB_SIZE: usize = 8;
+
+// The function each stub jumps to, patched on reload
+static TARGETS: [AtomicPtr<c_void>; MAX_TRAMPOLINES] =
+    [const { AtomicPtr::new(std::ptr::null_mut()) }; MAX_TRAMPOLINES];
+
+// Slots handed out to a trampoline. They are never freed, a pointer to a stub may still be held
+// after its trampoline is dropped and must keep aborting rather than call into another library
+static IN_USE: [AtomicBool; MAX_TRAMPOLINES] = [const { AtomicBool::new(false) }; MAX_TRAMPOLINES];
+
+// One stub per slot that jumps to its target, the arguments are passed on untouched so the
+// same stub works for any signature
+#[cfg(target_arch = "x86_64")]
+#[unsafe(naked)]
+unsafe extern "C" fn stubs() {
+    std::arch::naked_asm!(
+        ".set dr_trampoline_index, 0",
+        ".rept {count}",
+        // 6 bytes, padded to STUB_SIZE
+        "jmp qword ptr [rip + {targets} + 8 * dr_trampoline_index]",
+        "int3",
+        "int3",
+        ".set dr_trampoline_index, dr_trampoline_index + 1",
+        ".endr",
+        count = const MAX_TRAMPOLINES,
+        targets = sym TARGETS,
+    )
+}
+
+#[cfg(target_arch = "x86_64")]
+fn stub(index: usize) -> Option<*const c_void> {
+    Some((stubs as *const u8).wrapping_add(index * STUB_SIZE) as *const c_void)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn stub(_index: usize) -> Option<*const c_void> {
+    None
+}
+
+// Jumped to by trampolines of libraries that are unloaded or no longer export the symbol
+extern "C" fn unbound() -> ! {
+    log::error!("Called a trampoline to a library that isn't loaded");
+    std::process::abort();
+}
+
+// A stub that forwards to a symbol of the current version of a library
+pub(crate) struct Trampoline {
+    path: PathBuf,
+    name: String,
+    index: usize,
+}
+
+impl Trampoline {
+    pub(crate) fn new(lib: &Lib, name: &str) -> Result<Trampoline> {
+        let error =
+            |reason: &str| Error::Trampoline(lib.source_path().to_path_buf(), reason.into());
+
+        if stub(0).is_none() {
+            return Err(error("not supported on this architecture"));
+        }
+
+        let index = IN_USE
+            .iter()
+            .position(|in_use| {
+                in_use
+                    .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+            })
+            .ok_or_else(|| error("all trampolines have been handed out"))?;
+
+        Ok(Trampoline {
+            path: lib.source_path().to_path_buf(),
+            name: name.to_owned(),
+            index,
+        })
+    }
+
+    pub(crate) fn is(&self, path: &Path, name: &str) -> bool {
+        self.path == path && self.name == name
+    }
+
+    // Address of the stub, stays the same for the life of the process
+    pub(crate) unsafe fn stub<T: Copy>(&self) -> T {
+        assert_eq!(
+            mem::size_of::<T>(),
+            mem::size_of::<*const c_void>(),
+            "trampoline has to be a function pointer"
+        );
+
+        let stub = stub(self.index).unwrap_or(std::ptr::null());
+        mem::transmute_copy::<*const c_void, T>(&stub)
+    }
+}
+
+impl Rebind for Trampoline {
+    fn bind(&self, lib: &Lib) {
+        // Safety: only the address is read
+        let ptr = unsafe { lib.lib.get::<*mut c_void>(self.name.as_bytes()) }
+            .map(|symbol| *symbol)
+            .unwrap_or(unbound as *mut c_void);
+        TARGETS[self.index].store(ptr, Ordering::Release);
+    }
+
+    fn unbind(&self) {
+        TARGETS[self.index].store(unbound as *mut c_void, Ordering::Release);
+    }
+}
+
+impl Drop for Trampoline {
+    fn drop(&mut self) {
+        // The slot stays taken, see IN_USE
+        self.unbind();
+    }
+}
@@ -0,0 +1,114 @@
+use crate::atomic_symbol::Rebind;
+use crate::{Lib, Result};
+use std::sync::{Arc, RwLock};
+
+/// A struct of function pointers resolved from a library, declared with [lib_api].
+///
+/// Used with [add_typed](struct.DynamicReload.html#method.add_typed) which loads the struct
+/// again each time the library is reloaded.
+pub trait LibApi: Sized + Send + Sync + 'static {
+    /// Names of the symbols the library has to export.
+    const SYMBOLS: &'static [&'static str];
+
+    /// Resolves all symbols of the struct.
+    ///
+    /// # Safety
+    ///
+    /// The types of the symbols must match what the library exports and the result must not
+    /// be used after ```lib``` has been unloaded.
+    unsafe fn load(lib: &Lib) -> Result<Self>;
+}
+
+/// The API of a library added with [add_typed](struct.DynamicReload.html#method.add_typed).
+///
+/// The API is loaded again from the new version of the library before ```After``` is called
+/// and is ```None``` while the library is unloaded. As with
+/// [AtomicSymbol](struct.AtomicSymbol.html) the function pointers are only valid while the
+/// library is loaded, so copies shouldn't be kept across calls to
+/// [update](struct.DynamicReload.html#method.update).
+pub struct TypedLib<A> {
+    api: Arc<Slot<A>>,
+}
+
+impl<A: LibApi + Clone> TypedLib<A> {
+    /// The API of the current version of the library.
+    pub fn get(&self) -> Option<A> {
+        self.api.0.read().unwrap().clone()
+    }
+}
+
+impl<A: LibApi> TypedLib<A> {
+    pub(crate) fn new(lib: &Lib) -> Result<TypedLib<A>> {
+        // Safety: up to the caller of add_typed
+        let api = unsafe { A::load(lib)? };
+        Ok(TypedLib {
+            api: Arc::new(Slot(RwLock::new(Some(api)))),
+        })
+    }
+
+    pub(crate) fn rebind(&self) -> Arc<dyn Rebind> {
+        self.api.clone()
+    }
+}
+
+struct Slot<A>(RwLock<Option<A>>);
+
+impl<A: LibApi> Rebind for Slot<A> {
+    fn bind(&self, lib: &Lib) {
+        // The symbols have been checked with required_symbols before the library was swapped in
+        *self.0.write().unwrap() = unsafe { A::load(lib).ok() };
+    }
+
+    fn unbind(&self) {
+        *self.0.write().unwrap() = None;
+    }
+}
+
+/// Declares a struct of function pointers that implements [LibApi].
+///
+/// Each field is looked up in the library by its name. Functions are expected to use the Rust
+/// ABI unless declared ```extern "C"```. The struct derives ```Clone``` and ```Copy```.
+///
+/// ```ignore
+/// dynamic_reload::lib_api! {
+///     pub struct MathApi {
+///         pub add: fn(i32, i32) -> i32,
+///         pub render: extern "C" fn(f32),
+///     }
+/// }
+///
+/// let math = unsafe { dr.add_typed::<MathApi>("math", PlatformName::Yes)? };
+/// let add = math.get().unwrap().add;
+/// println!("{}", add(1, 2));
+/// ```
+#[macro_export]
+macro_rules! lib_api {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident: $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy)]
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty,)*
+        }
+
+        impl $crate::LibApi for $name {
+            const SYMBOLS: &'static [&'static str] = &[$(stringify!($field)),*];
+
+            unsafe fn load(lib: &$crate::Lib) -> $crate::Result<Self> {
+                Ok($name {
+                    $($field: *lib
+                        .lib
+                        .get::<$ty>(concat!(stringify!($field), "\0").as_bytes())
+                        .map_err($crate::Error::Load)?,)*
+                })
+            }
+        }
+    };
+}
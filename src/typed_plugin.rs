@@ -0,0 +1,259 @@
+use crate::{DynamicReload, Error, Lib, LoadOptions, PlatformName, Result, UpdateState};
+use std::{ffi::c_void, path::PathBuf, sync::Arc};
+
+/// Creates the plugin and returns it as a boxed [PluginInstance], see [declare_plugin].
+pub const PLUGIN_CREATE: &str = "plugin_create";
+/// Destroys a plugin created by ```plugin_create```.
+pub const PLUGIN_DESTROY: &str = "plugin_destroy";
+
+type CreateFn = unsafe extern "C" fn() -> *mut c_void;
+type DestroyFn = unsafe extern "C" fn(*mut c_void);
+
+/// An instance created by a plugin together with the table of functions ```V``` to use it.
+///
+/// ```V``` is a ```#[repr(C)]``` struct of ```extern "C"``` functions taking the instance as
+/// their first argument, shared by the plugin and the host. Only ```#[repr(C)]``` types cross
+/// the library boundary so the layout is the same on both sides, unlike trait objects.
+#[repr(C)]
+pub struct PluginInstance<V> {
+    instance: *mut c_void,
+    vtable: V,
+    drop_instance: unsafe extern "C" fn(*mut c_void),
+}
+
+impl<V> PluginInstance<V> {
+    /// Boxes ```instance``` for the functions of ```vtable```, used by [declare_plugin].
+    pub fn new<P>(instance: P, vtable: V) -> PluginInstance<V> {
+        unsafe extern "C" fn drop_instance<P>(instance: *mut c_void) {
+            drop(Box::from_raw(instance as *mut P));
+        }
+
+        PluginInstance {
+            instance: Box::into_raw(Box::new(instance)) as *mut c_void,
+            vtable,
+            drop_instance: drop_instance::<P>,
+        }
+    }
+
+    /// The instance, passed to the functions of the table.
+    pub fn instance(&self) -> *mut c_void {
+        self.instance
+    }
+
+    /// The table of functions of the plugin.
+    pub fn vtable(&self) -> &V {
+        &self.vtable
+    }
+}
+
+impl<V> Drop for PluginInstance<V> {
+    fn drop(&mut self) {
+        // Safety: the instance was created by new with the matching drop function
+        unsafe { (self.drop_instance)(self.instance) }
+    }
+}
+
+/// A plugin created by a library declared with [declare_plugin] for the function table ```V```,
+/// see [PluginInstance].
+///
+/// The plugin is destroyed before its library is unloaded for a reload and created again from
+/// the new version, so it has to be passed to
+/// [update](struct.DynamicReload.html#method.update) through
+/// [update_call](TypedPlugin::update_call). State that should survive a reload has to be kept
+/// by the host.
+///
+/// ```ignore
+/// // Shared by the host and the plugin
+/// #[repr(C)]
+/// pub struct Renderer {
+///     pub render: unsafe extern "C" fn(instance: *mut c_void, time: f32),
+/// }
+///
+/// let mut renderer = unsafe {
+///     TypedPlugin::<Renderer>::new(&mut dr, "renderer", PlatformName::Yes)?
+/// };
+///
+/// loop {
+///     unsafe { dr.update(&TypedPlugin::update_call, &mut renderer) };
+///     if let Some(renderer) = renderer.get() {
+///         unsafe { (renderer.vtable().render)(renderer.instance(), time) };
+///     }
+/// }
+/// ```
+pub struct TypedPlugin<V> {
+    path: PathBuf,
+    lib: Option<Arc<Lib>>,
+    // Box<PluginInstance<V>> from plugin_create, null while the library is unloaded
+    plugin: *mut PluginInstance<V>,
+    error: Option<Error>,
+}
+
+impl<V> TypedPlugin<V> {
+    /// Loads the library and creates the plugin. Both functions declared by [declare_plugin]
+    /// are required symbols, see [LoadOptions].
+    ///
+    /// # Safety
+    ///
+    /// See [add_library](struct.DynamicReload.html#method.add_library). The library must be
+    /// declared with [declare_plugin] for the same function table as ```V``` and the same
+    /// version of dynamic_reload as the host.
+    pub unsafe fn new(
+        reload: &mut DynamicReload,
+        name: &str,
+        name_format: PlatformName,
+    ) -> Result<TypedPlugin<V>> {
+        let options = LoadOptions {
+            platform_name: name_format,
+            required_symbols: vec![PLUGIN_CREATE.to_owned(), PLUGIN_DESTROY.to_owned()],
+            ..LoadOptions::default()
+        };
+
+        let lib = reload.add_library_with_options(name, options)?;
+        let mut plugin = TypedPlugin {
+            path: lib.source_path().to_path_buf(),
+            lib: None,
+            plugin: std::ptr::null_mut(),
+            error: None,
+        };
+
+        plugin.create(&lib)?;
+        Ok(plugin)
+    }
+
+    /// The plugin, ```None``` if the last reload didn't leave a version of the library to
+    /// create it from.
+    pub fn get(&self) -> Option<&PluginInstance<V>> {
+        // Safety: created by plugin_create and destroyed before the library is unloaded
+        unsafe { self.plugin.as_ref() }
+    }
+
+    /// See [get](TypedPlugin::get).
+    pub fn get_mut(&mut self) -> Option<&mut PluginInstance<V>> {
+        // Safety: see get
+        unsafe { self.plugin.as_mut() }
+    }
+
+    /// The library the plugin was created from.
+    pub fn lib(&self) -> Option<&Arc<Lib>> {
+        self.lib.as_ref()
+    }
+
+    /// Returns the error of the last failed reload or plugin creation, if any.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// Destroys and creates the plugin around reloads of its library, other libraries are
+    /// ignored. Meant to be passed to [update](struct.DynamicReload.html#method.update) or
+    /// called from the callback of the application.
+    pub fn update_call(&mut self, state: UpdateState, lib: Option<&Arc<Lib>>) {
+        let lib = match lib {
+            Some(lib) if lib.source_path() == self.path => lib,
+            _ => return,
+        };
+
+        match state {
+            UpdateState::Before => self.destroy(),
+            // The previous version is loaded again after a failure
+            UpdateState::After | UpdateState::RolledBack => self.recreate(lib),
+            UpdateState::ReloadFailed(e) => self.error = Some(e),
+            // The previous version is used again, create the plugin if it went through Before
+            UpdateState::HealthCheckFailed(e) => {
+                if self.plugin.is_null() {
+                    self.recreate(lib);
+                }
+                self.error = Some(e);
+            }
+            // The plugin keeps its library loaded
+            UpdateState::Removed
+            | UpdateState::Added
+            | UpdateState::Blacklisted(_)
+            | UpdateState::StillReferenced(_)
+            | UpdateState::BuildStarted
+            | UpdateState::BuildFinished(_)
+            | UpdateState::BuildFailed(_) => (),
+        }
+    }
+
+    fn recreate(&mut self, lib: &Arc<Lib>) {
+        self.destroy();
+        // Safety: the library was loaded with plugin_create as a required symbol
+        if let Err(e) = unsafe { self.create(lib) } {
+            self.error = Some(e);
+        }
+    }
+
+    unsafe fn create(&mut self, lib: &Arc<Lib>) -> Result<()> {
+        let create = lib
+            .lib
+            .get::<CreateFn>(PLUGIN_CREATE.as_bytes())
+            .map_err(Error::Load)?;
+
+        self.plugin = create() as *mut PluginInstance<V>;
+        self.lib = Some(lib.clone());
+        Ok(())
+    }
+
+    fn destroy(&mut self) {
+        let lib = match self.lib.take() {
+            Some(lib) => lib,
+            None => return,
+        };
+
+        // Safety: the symbol was checked when the library was loaded and the plugin was created
+        // by the same library
+        unsafe {
+            if let Ok(destroy) = lib.lib.get::<DestroyFn>(PLUGIN_DESTROY.as_bytes()) {
+                destroy(self.plugin as *mut c_void);
+            }
+        }
+
+        self.plugin = std::ptr::null_mut();
+    }
+}
+
+impl<V> Drop for TypedPlugin<V> {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
+
+/// Exports ```plugin_create``` and ```plugin_destroy``` from a plugin library for use with
+/// [TypedPlugin].
+///
+/// Takes the function table type shared with the host, an expression creating the instance and
+/// the table of functions for it, see [PluginInstance].
+///
+/// ```ignore
+/// pub struct Forward {
+///     frames: u32,
+/// }
+///
+/// unsafe extern "C" fn render(instance: *mut c_void, time: f32) {
+///     let forward = &mut *(instance as *mut Forward);
+///     forward.frames += 1;
+/// }
+///
+/// dynamic_reload::declare_plugin!(Renderer, Forward { frames: 0 }, Renderer { render });
+/// ```
+#[macro_export]
+macro_rules! declare_plugin {
+    ($vtable:ty, $create:expr, $table:expr) => {
+        #[no_mangle]
+        pub extern "C" fn plugin_create() -> *mut ::std::ffi::c_void {
+            let plugin: $crate::PluginInstance<$vtable> =
+                $crate::PluginInstance::new($create, $table);
+            ::std::boxed::Box::into_raw(::std::boxed::Box::new(plugin)) as *mut ::std::ffi::c_void
+        }
+
+        /// # Safety
+        ///
+        /// Only to be called by the host with a plugin created by ```plugin_create```.
+        #[no_mangle]
+        pub unsafe extern "C" fn plugin_destroy(plugin: *mut ::std::ffi::c_void) {
+            drop(::std::boxed::Box::from_raw(
+                plugin as *mut $crate::PluginInstance<$vtable>,
+            ));
+        }
+    };
+}
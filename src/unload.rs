@@ -0,0 +1,167 @@
+use crate::Lib;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+/// When DynamicReload lets go of a library that has been replaced or removed, see
+/// [set_deferred_unload](struct.DynamicReload.html#method.set_deferred_unload).
+///
+/// The library is unloaded once the last reference to it is dropped, so references kept by the
+/// application still keep it loaded. Deferring covers code that can't hold a reference, such as
+/// function pointers handed to other threads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeferredUnload {
+    /// Let go of the library right away.
+    #[default]
+    Immediate,
+    /// Keep the library loaded for the given time after it was replaced or removed.
+    Period(Duration),
+    /// Keep the library loaded until
+    /// [advance_epoch](struct.DynamicReload.html#method.advance_epoch) has been called, for
+    /// example once all worker threads have finished a frame.
+    Epoch,
+}
+
+/// What to do when the application still has references to a library that has been replaced
+/// or removed, see [set_external_refs](struct.DynamicReload.html#method.set_external_refs).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExternalRefs {
+    /// Let go of the library, it's unloaded when the application drops its last reference on
+    /// whatever thread that happens.
+    #[default]
+    Ignore,
+    /// Keep the library until the application has dropped its references so it's unloaded by
+    /// [update](struct.DynamicReload.html#method.update), see
+    /// [retired_libs](struct.DynamicReload.html#method.retired_libs).
+    Retire,
+    /// Report libraries that are still referenced on the next
+    /// [update](struct.DynamicReload.html#method.update) with
+    /// ```UpdateState::StillReferenced```.
+    Warn,
+}
+
+// A library that is kept loaded until its grace period is over
+struct Parked {
+    lib: Arc<Lib>,
+    since: Instant,
+    epoch: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct Unloads {
+    pub(crate) mode: DeferredUnload,
+    pub(crate) external: ExternalRefs,
+    parked: Vec<Parked>,
+    epoch: u64,
+    // Kept until the application has let go of them
+    retired: Vec<Arc<Lib>>,
+    // Checked for references left on the next update
+    released: Vec<Weak<Lib>>,
+    // Where the libraries that are never unloaded with the no-unload feature were loaded from
+    leaked: Vec<PathBuf>,
+}
+
+impl Unloads {
+    // Lets go of a library that has been replaced or removed
+    pub(crate) fn park(&mut self, lib: Arc<Lib>) {
+        if cfg!(feature = "no-unload") {
+            if !self.is_leaked(&lib.loaded_path) {
+                self.leaked.push(lib.loaded_path.clone());
+            }
+            std::mem::forget(lib);
+            return;
+        }
+
+        // Without the hash in the name the old version has to be unloaded before the new copy
+        // can be loaded
+        if self.mode == DeferredUnload::Immediate || cfg!(feature = "no-timestamps") {
+            return self.retire(lib);
+        }
+
+        self.parked.push(Parked {
+            lib,
+            since: Instant::now(),
+            epoch: self.epoch,
+        });
+    }
+
+    // The grace period of the library is over
+    fn retire(&mut self, lib: Arc<Lib>) {
+        match self.external {
+            ExternalRefs::Ignore => (),
+            // Held on to as long as the application does, which the new copy with the same name
+            // can't be loaded next to
+            ExternalRefs::Retire if cfg!(feature = "no-timestamps") => (),
+            ExternalRefs::Retire => self.retired.push(lib),
+            ExternalRefs::Warn => self.released.push(Arc::downgrade(&lib)),
+        }
+    }
+
+    // Lets go of the libraries whose grace period is over and the retired libraries the
+    // application no longer references
+    pub(crate) fn collect(&mut self) {
+        let epoch = self.epoch;
+        let mode = self.mode;
+
+        let (expired, parked): (Vec<Parked>, Vec<Parked>) = std::mem::take(&mut self.parked)
+            .into_iter()
+            .partition(|p| match mode {
+                DeferredUnload::Immediate => true,
+                DeferredUnload::Period(period) => p.since.elapsed() >= period,
+                DeferredUnload::Epoch => p.epoch < epoch,
+            });
+
+        self.parked = parked;
+        for parked in expired {
+            self.retire(parked.lib);
+        }
+
+        self.retired.retain(|lib| Arc::strong_count(lib) > 1);
+    }
+
+    // Released libraries that are still referenced by the application
+    pub(crate) fn take_referenced(&mut self) -> Vec<Arc<Lib>> {
+        self.released
+            .drain(..)
+            .filter_map(|lib| lib.upgrade())
+            .collect()
+    }
+
+    // True if a library loaded from the path has been let go of but is still loaded as it's
+    // never unloaded
+    pub(crate) fn is_leaked(&self, path: &Path) -> bool {
+        self.leaked.iter().any(|leaked| leaked == path)
+    }
+
+    pub(crate) fn retired_paths(&self) -> Vec<PathBuf> {
+        self.retired
+            .iter()
+            .map(|lib| lib.source_path().to_path_buf())
+            .collect()
+    }
+
+    pub(crate) fn advance_epoch(&mut self) -> u64 {
+        self.epoch += 1;
+        self.collect();
+        self.epoch
+    }
+
+    pub(crate) fn flush(&mut self) {
+        for parked in std::mem::take(&mut self.parked) {
+            self.retire(parked.lib);
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.parked.len()
+    }
+
+    // True if libraries are waiting for time to pass or the application to let go of them
+    pub(crate) fn is_waiting(&self) -> bool {
+        (matches!(self.mode, DeferredUnload::Period(_)) && !self.parked.is_empty())
+            || !self.retired.is_empty()
+            || !self.released.is_empty()
+    }
+}
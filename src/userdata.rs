@@ -0,0 +1,47 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+};
+
+type Value = Arc<dyn Any + Send + Sync>;
+
+// Values attached to a library with Lib::set_userdata, one per type. Shared by all versions of
+// the library so it survives reloads.
+#[derive(Clone, Default)]
+pub(crate) struct Userdata {
+    values: Arc<RwLock<HashMap<TypeId, Value>>>,
+    // Set in Before and taken in After, see Lib::set_reload_state
+    state: Arc<Mutex<Option<Box<dyn Any + Send>>>>,
+}
+
+impl Userdata {
+    pub(crate) fn insert<T: Any + Send + Sync>(&self, value: T) -> Option<Arc<T>> {
+        let mut values = self.values.write().unwrap_or_else(|e| e.into_inner());
+        values
+            .insert(TypeId::of::<T>(), Arc::new(value))
+            .and_then(|v| v.downcast().ok())
+    }
+
+    pub(crate) fn get<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        let values = self.values.read().unwrap_or_else(|e| e.into_inner());
+        values
+            .get(&TypeId::of::<T>())
+            .and_then(|v| v.clone().downcast().ok())
+    }
+
+    pub(crate) fn remove<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        let mut values = self.values.write().unwrap_or_else(|e| e.into_inner());
+        values
+            .remove(&TypeId::of::<T>())
+            .and_then(|v| v.downcast().ok())
+    }
+
+    pub(crate) fn set_state(&self, state: Box<dyn Any + Send>) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = Some(state);
+    }
+
+    pub(crate) fn take_state(&self) -> Option<Box<dyn Any + Send>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
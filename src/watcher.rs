@@ -0,0 +1,358 @@
+use crate::Error;
+use notify_debouncer_mini::{
+    new_debouncer, new_debouncer_opt,
+    notify::{
+        self,
+        event::{AccessKind, AccessMode, CreateKind, ModifyKind, RenameMode},
+        Config, Event, EventKind, PollWatcher, RecommendedWatcher, Watcher,
+    },
+    DebounceEventResult, DebouncedEvent, Debouncer,
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        mpsc::{channel, Sender},
+        Arc, Mutex,
+    },
+    task::Waker,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Name of the thread that delivers file events to [DynamicReload](struct.DynamicReload.html).
+pub const WATCHER_THREAD_NAME: &str = "dynamic_reload watcher";
+
+/// Messages sent from the file watcher to [DynamicReload](struct.DynamicReload.html).
+pub(crate) enum WatchMsg {
+    /// Events coming from the built-in time based debouncer.
+    Debounced(DebounceEventResult),
+    /// Events coming directly from notify, to be fed into a [DebounceStrategy].
+    Raw(notify::Result<Event>),
+}
+
+/// An event from the file watcher as it was received, before DynamicReload acts on it. See
+/// [set_raw_events](struct.DynamicReload.html#method.set_raw_events).
+#[derive(Debug)]
+pub enum RawEvent {
+    /// Changes reported by the built-in time based debouncer.
+    Debounced(Vec<DebouncedEvent>),
+    /// An event from notify, received when a [DebounceStrategy] is used.
+    Event(Event),
+    /// An error reported by the watcher.
+    Error(notify::Error),
+}
+
+/// Selects how changes to libraries are detected. Set when constructing with
+/// [watcher_backend](struct.DynamicReloadBuilder.html#method.watcher_backend) or later with
+/// [set_watcher_backend](struct.DynamicReload.html#method.set_watcher_backend).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatcherBackend {
+    /// The native file events of the platform (inotify, FSEvents, ReadDirectoryChangesW).
+    Recommended,
+    /// Check the files for changes with the given interval. Use this for network shares and
+    /// other file systems where native events never arrive. Changes are detected by looking at
+    /// the modification time which only has a resolution of one second.
+    Poll {
+        /// How often to check for changes
+        interval: Duration,
+    },
+    /// Don't watch for changes at all.
+    None,
+}
+
+/// State of the file watcher returned by
+/// [watcher_status](struct.DynamicReload.html#method.watcher_status).
+#[derive(Debug)]
+pub enum WatcherStatus<'a> {
+    /// Changes are watched with the backend.
+    Active(WatcherBackend),
+    /// Nothing is watched, either with ```WatcherBackend::None``` or after
+    /// [stop_watching](struct.DynamicReload.html#method.stop_watching).
+    Stopped,
+    /// The watcher couldn't be created when constructing with
+    /// [new](struct.DynamicReload.html#method.new) so changes aren't detected.
+    Failed(&'a Error),
+}
+
+enum Backend {
+    Debounced(Debouncer<RecommendedWatcher>),
+    DebouncedPoll(Debouncer<PollWatcher>),
+    Raw(Box<dyn Watcher + Send + Sync>),
+}
+
+/// The file watcher that is currently in use.
+///
+/// Events from notify are passed through a named thread owned by us before they end up in the
+/// receiver of DynamicReload. This gives the host a thread it can identify and a way to know
+/// that event delivery has fully stopped (the thread exits once notify drops its handler).
+pub(crate) struct FileWatcher {
+    backend: Backend,
+    active: WatcherBackend,
+    thread: Option<JoinHandle<()>>,
+    // Woken when an event has been delivered
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+/// Poll interval used when the native backend can't be used, see
+/// [set_poll_fallback](struct.DynamicReload.html#method.set_poll_fallback).
+pub const DEFAULT_POLL_FALLBACK: Duration = Duration::from_secs(1);
+
+impl FileWatcher {
+    /// Creates a watcher for the given backend. With a debounce duration the built-in time
+    /// based debouncer is used, otherwise raw events are delivered to be used with a
+    /// [DebounceStrategy]. Returns None for [WatcherBackend::None]. If the native backend can't
+    /// be created and a fallback interval is given a polling watcher is used instead.
+    pub(crate) fn new(
+        tx: Sender<WatchMsg>,
+        backend: WatcherBackend,
+        debounce_duration: Option<Duration>,
+        fallback: Option<Duration>,
+    ) -> notify::Result<Option<FileWatcher>> {
+        match (
+            Self::create(tx.clone(), backend, debounce_duration),
+            fallback,
+        ) {
+            (Err(_), Some(interval)) if backend == WatcherBackend::Recommended => {
+                Self::create(tx, WatcherBackend::Poll { interval }, debounce_duration)
+            }
+            (result, _) => result,
+        }
+    }
+
+    fn create(
+        tx: Sender<WatchMsg>,
+        backend: WatcherBackend,
+        debounce_duration: Option<Duration>,
+    ) -> notify::Result<Option<FileWatcher>> {
+        if backend == WatcherBackend::None {
+            return Ok(None);
+        }
+
+        let active = backend;
+
+        let waker = Arc::new(Mutex::new(None));
+        let (inner_tx, thread) = Self::spawn_thread(tx, waker.clone())?;
+
+        let backend = match debounce_duration {
+            Some(duration) => {
+                let handler = move |res| {
+                    let _ = inner_tx.send(WatchMsg::Debounced(res));
+                };
+
+                match backend {
+                    WatcherBackend::Poll { interval } => {
+                        let config = Config::default().with_poll_interval(interval);
+                        Backend::DebouncedPoll(new_debouncer_opt(duration, None, handler, config)?)
+                    }
+                    _ => Backend::Debounced(new_debouncer(duration, None, handler)?),
+                }
+            }
+            None => {
+                let handler = move |res| {
+                    let _ = inner_tx.send(WatchMsg::Raw(res));
+                };
+
+                match backend {
+                    WatcherBackend::Poll { interval } => {
+                        let config = Config::default().with_poll_interval(interval);
+                        Backend::Raw(Box::new(PollWatcher::new(handler, config)?))
+                    }
+                    _ => Backend::Raw(Box::new(RecommendedWatcher::new(
+                        handler,
+                        Config::default(),
+                    )?)),
+                }
+            }
+        };
+
+        Ok(Some(FileWatcher {
+            backend,
+            active,
+            thread: Some(thread),
+            waker,
+        }))
+    }
+
+    fn spawn_thread(
+        tx: Sender<WatchMsg>,
+        waker: Arc<Mutex<Option<Waker>>>,
+    ) -> notify::Result<(Sender<WatchMsg>, JoinHandle<()>)> {
+        let (inner_tx, inner_rx) = channel::<WatchMsg>();
+
+        let thread = thread::Builder::new()
+            .name(WATCHER_THREAD_NAME.to_string())
+            .spawn(move || {
+                for msg in inner_rx {
+                    if tx.send(msg).is_err() {
+                        break;
+                    }
+
+                    if let Some(waker) = waker.lock().unwrap().take() {
+                        waker.wake();
+                    }
+                }
+            })?;
+
+        Ok((inner_tx, thread))
+    }
+
+    pub(crate) fn watcher(&mut self) -> &mut dyn Watcher {
+        match &mut self.backend {
+            Backend::Debounced(d) => d.watcher(),
+            Backend::DebouncedPoll(d) => d.watcher(),
+            Backend::Raw(w) => w.as_mut(),
+        }
+    }
+
+    /// The backend actually in use, which differs from the requested one after a fallback.
+    pub(crate) fn backend(&self) -> WatcherBackend {
+        self.active
+    }
+
+    /// Wakes ```waker``` once the next event has been delivered.
+    pub(crate) fn set_waker(&self, waker: &Waker) {
+        *self.waker.lock().unwrap() = Some(waker.clone());
+    }
+
+    pub(crate) fn thread(&self) -> Option<&thread::Thread> {
+        self.thread.as_ref().map(|t| t.thread())
+    }
+
+    /// Tears down all watches and waits for the event threads to finish.
+    pub(crate) fn stop(mut self) {
+        match self.backend {
+            Backend::Debounced(d) => d.stop(),
+            Backend::DebouncedPoll(d) => d.stop(),
+            Backend::Raw(w) => drop(w),
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Returns true if a watch failed in a way that polling may get around, such as running out of
+/// inotify watches or a file system without native events. A path that doesn't exist fails
+/// with polling as well.
+pub(crate) fn needs_fallback(error: &notify::Error) -> bool {
+    match &error.kind {
+        notify::ErrorKind::PathNotFound | notify::ErrorKind::WatchNotFound => false,
+        notify::ErrorKind::Io(e) => e.kind() != std::io::ErrorKind::NotFound,
+        _ => true,
+    }
+}
+
+/// Decides when a file that has been changed on disk is ready to be reloaded.
+///
+/// By default DynamicReload waits for a fixed amount of time after the last change of a file
+/// before reloading it. That doesn't work well for all build setups so a custom strategy can be
+/// set with [set_debounce_strategy](struct.DynamicReload.html#method.set_debounce_strategy).
+/// The strategy is then given all raw notify events and is asked for the paths that are ready
+/// each time [update](struct.DynamicReload.html#method.update) is called.
+///
+/// Strategies have to be ```Send + Sync``` as [DynamicReload](struct.DynamicReload.html) may be
+/// shared between threads. They are only called through ```&mut``` so no locking is needed.
+pub trait DebounceStrategy: Send + Sync {
+    /// Called for every raw event received from the file watcher.
+    fn event(&mut self, event: &Event);
+    /// Returns the paths that have settled and should be reloaded now.
+    fn ready(&mut self) -> Vec<PathBuf>;
+    /// Returns all paths with pending changes, settled or not. Called when DynamicReload is
+    /// stopped and pending changes are flushed. Defaults to [ready](DebounceStrategy::ready).
+    fn flush(&mut self) -> Vec<PathBuf> {
+        self.ready()
+    }
+}
+
+/// Reloads a file once no new events has been seen for it during the given duration.
+/// This is the same behavior as the default debouncer but driven from `update`.
+pub struct QuietPeriod {
+    duration: Duration,
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl QuietPeriod {
+    pub fn new(duration: Duration) -> QuietPeriod {
+        QuietPeriod {
+            duration,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl DebounceStrategy for QuietPeriod {
+    fn event(&mut self, event: &Event) {
+        if let EventKind::Access(_) = event.kind {
+            return;
+        }
+
+        let now = Instant::now();
+        for path in &event.paths {
+            self.pending.insert(path.clone(), now);
+        }
+    }
+
+    fn ready(&mut self) -> Vec<PathBuf> {
+        let duration = self.duration;
+        let mut ready = Vec::new();
+
+        self.pending.retain(|path, last| {
+            if last.elapsed() >= duration {
+                ready.push(path.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        ready
+    }
+
+    fn flush(&mut self) -> Vec<PathBuf> {
+        self.pending.drain().map(|(path, _)| path).collect()
+    }
+}
+
+/// Reloads a file as soon as the writer has closed it (`IN_CLOSE_WRITE` on Linux) or when a
+/// file has been created or renamed into place (atomic saves). Useful when the build tool writes
+/// the library in several steps with pauses in between that a time based debouncer can't
+/// account for.
+#[derive(Default)]
+pub struct CloseWrite {
+    pending: Vec<PathBuf>,
+}
+
+impl CloseWrite {
+    pub fn new() -> CloseWrite {
+        CloseWrite::default()
+    }
+}
+
+impl DebounceStrategy for CloseWrite {
+    fn event(&mut self, event: &Event) {
+        // Renames are reported differently depending on the platform. Some only give the
+        // destination, some give both source and destination in the same event.
+        let done = matches!(
+            event.kind,
+            EventKind::Access(AccessKind::Close(AccessMode::Write))
+                | EventKind::Create(CreateKind::File | CreateKind::Any)
+                | EventKind::Modify(ModifyKind::Name(
+                    RenameMode::To | RenameMode::Both | RenameMode::Any
+                ))
+        );
+
+        if done {
+            for path in &event.paths {
+                if !self.pending.contains(path) {
+                    self.pending.push(path.clone());
+                }
+            }
+        }
+    }
+
+    fn ready(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.pending)
+    }
+}
@@ -0,0 +1,75 @@
+#[cfg(windows)]
+use std::{
+    ffi::OsString,
+    path::{Component, Prefix},
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Same as ```Path::canonicalize``` but falls back to the absolute path on Windows when the
+/// final path of a file that exists can't be resolved, which some network file systems (SMB
+/// shares, mapped network drives) don't support.
+pub(crate) fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    match path.canonicalize() {
+        Ok(canonical) => Ok(canonical),
+        #[cfg(windows)]
+        Err(_) if path.exists() => Ok(extended(path)),
+        Err(e) => Err(e),
+    }
+}
+
+/// The path in the extended form (```\\?\C:\dir``` and ```\\?\UNC\server\share\dir```), which
+/// isn't limited to ```MAX_PATH``` characters when loading libraries or watching directories.
+/// Paths that already are in the extended form are returned as they are. Other platforms don't
+/// have the limit so the path is returned unchanged.
+#[cfg(windows)]
+pub(crate) fn extended(path: &Path) -> PathBuf {
+    // The extended form is passed on as is, so ".." and "/" have to be resolved first
+    let path = match std::path::absolute(path) {
+        Ok(path) => path,
+        Err(_) => return path.to_path_buf(),
+    };
+
+    let mut components = path.components();
+    let mut extended = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(_) => {
+                let mut extended = OsString::from(r"\\?\");
+                extended.push(prefix.as_os_str());
+                extended
+            }
+            Prefix::UNC(server, share) => {
+                let mut extended = OsString::from(r"\\?\UNC\");
+                extended.push(server);
+                extended.push(r"\");
+                extended.push(share);
+                extended
+            }
+            // Already extended or a device
+            _ => return path,
+        },
+        _ => return path,
+    };
+
+    let mut root = true;
+    for component in components {
+        if let Component::Normal(name) = component {
+            extended.push(r"\");
+            extended.push(name);
+            root = false;
+        }
+    }
+
+    if root {
+        extended.push(r"\");
+    }
+
+    PathBuf::from(extended)
+}
+
+#[cfg(not(windows))]
+pub(crate) fn extended(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}